@@ -0,0 +1,52 @@
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use performance_enhance::cpu::Cpu;
+use performance_enhance::decode_cache::DecodeCache;
+use performance_enhance::interrupts::Interrupts;
+use performance_enhance::memory::Memory;
+use performance_enhance::ports::PortBus;
+use performance_enhance::sim::{is_running, step};
+use performance_enhance::timing::Processor;
+
+/// mov cx, 1000 / add ax, [bx+si+4] / adc dx, 0 / loop $-6 / hlt
+const LOOP: &[u8] = &[
+    0xB9, 0xE8, 0x03, 0x03, 0x40, 0x04, 0x83, 0xD2, 0x00, 0xE2, 0xF8, 0xF4,
+];
+
+/// Steps through [`LOOP`] fetching through `cache`, without the cost of writing a trace.
+fn run_loop(memory: &mut Memory, cache: &mut DecodeCache) -> Cpu {
+    let mut cpu = Cpu::new();
+    let mut interrupts = Interrupts::new();
+    let mut ports = PortBus::new();
+    let code = 0..LOOP.len();
+    while is_running(&cpu, &code) {
+        step(
+            &mut cpu,
+            memory,
+            &mut interrupts,
+            &mut ports,
+            cache,
+            Processor::I8086,
+        )
+        .unwrap();
+    }
+    cpu
+}
+
+fn bench_decode_cache(c: &mut Criterion) {
+    let mut memory = Memory::new();
+    memory.load(0, 0, LOOP);
+
+    let mut group = c.benchmark_group("decode_cache");
+    group.bench_function("decode_every_step", |b| {
+        b.iter(|| run_loop(black_box(&mut memory), &mut DecodeCache::disabled()))
+    });
+    // kept between runs, like the cache of a program that goes round its loop again
+    let mut cache = DecodeCache::new();
+    group.bench_function("cached", |b| {
+        b.iter(|| run_loop(black_box(&mut memory), &mut cache))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode_cache);
+criterion_main!(benches);
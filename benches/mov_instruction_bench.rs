@@ -25,11 +25,6 @@ mod approach1 {
                 _ => None,
             }
         }
-
-        pub fn find_instruction(byte: u8) -> Self {
-            Self::from_byte(byte)
-                .unwrap_or_else(|| panic!("Unable to determine instruction for byte {:08b}", byte))
-        }
     }
 }
 
@@ -86,11 +81,6 @@ mod approach2 {
                 _ => None,
             }
         }
-
-        pub fn find_instruction(byte: u8) -> Self {
-            Self::from_byte(byte)
-                .unwrap_or_else(|| panic!("Unable to determine instruction for byte {:08b}", byte))
-        }
     }
 }
 
@@ -138,11 +128,6 @@ mod approach3 {
 
             None
         }
-
-        pub fn find_instruction(byte: u8) -> Self {
-            Self::from_byte(byte)
-                .unwrap_or_else(|| panic!("Unable to determine instruction for byte {:08b}", byte))
-        }
     }
 }
 
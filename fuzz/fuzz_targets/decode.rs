@@ -0,0 +1,40 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use performance_enhance::decoder::Decoder;
+
+fuzz_target!(|data: &[u8]| {
+    let mut decoder = Decoder::new(data);
+    let mut consumed = 0;
+
+    for result in decoder.by_ref() {
+        match result {
+            Ok(instruction) => {
+                assert_eq!(
+                    instruction.offset, consumed,
+                    "instruction does not follow the previous one"
+                );
+                assert!(instruction.length > 0, "instruction consumed no bytes");
+                consumed += instruction.length;
+                assert!(
+                    consumed <= data.len(),
+                    "decoder read past the end of the input"
+                );
+
+                // formatting must not panic either
+                let _ = instruction.to_string();
+            }
+            Err(error) => assert_eq!(
+                error.offset(),
+                consumed,
+                "error reported at the wrong offset"
+            ),
+        }
+    }
+
+    assert_eq!(
+        decoder.position(),
+        consumed,
+        "instruction lengths do not sum to the consumed bytes"
+    );
+});
@@ -0,0 +1,126 @@
+use crate::decoder::{Mnemonic, Width};
+use crate::flags::{Flag, FlagSet};
+
+#[cfg(test)]
+mod alu_tests;
+
+/// Sets ZF, SF and PF from `result`. PF only looks at the low byte, even for word results.
+fn set_result_flags(flags: &mut FlagSet, width: Width, result: u16) {
+    flags.set(Flag::Zero, result & width.mask() == 0);
+    flags.set(Flag::Sign, result & width.sign_bit() != 0);
+    flags.set(Flag::Parity, (result as u8).count_ones().is_multiple_of(2));
+}
+
+/// `a + b + carry`, setting all six status flags.
+pub fn add(flags: &mut FlagSet, width: Width, a: u16, b: u16, carry: bool) -> u16 {
+    let wide = u32::from(a) + u32::from(b) + u32::from(carry);
+    let result = wide as u16 & width.mask();
+    let sign = width.sign_bit();
+
+    flags.set(Flag::Carry, wide > u32::from(width.mask()));
+    flags.set(
+        Flag::Auxiliary,
+        (a & 0xF) + (b & 0xF) + u16::from(carry) > 0xF,
+    );
+    // Both operands have the same sign and the result doesn't.
+    flags.set(Flag::Overflow, (a ^ result) & (b ^ result) & sign != 0);
+    set_result_flags(flags, width, result);
+    result
+}
+
+/// `a - b - borrow`, setting all six status flags. CF and AF are borrows out of the top bit and
+/// bit 3.
+pub fn sub(flags: &mut FlagSet, width: Width, a: u16, b: u16, borrow: bool) -> u16 {
+    let result = a.wrapping_sub(b).wrapping_sub(u16::from(borrow)) & width.mask();
+    let sign = width.sign_bit();
+
+    flags.set(Flag::Carry, u32::from(b) + u32::from(borrow) > u32::from(a));
+    flags.set(Flag::Auxiliary, (b & 0xF) + u16::from(borrow) > a & 0xF);
+    // The operands have different signs and the result took the sign of `b`.
+    flags.set(Flag::Overflow, (a ^ b) & (a ^ result) & sign != 0);
+    set_result_flags(flags, width, result);
+    result
+}
+
+/// Like [`add`] with one, but CF is left alone.
+pub fn inc(flags: &mut FlagSet, width: Width, value: u16) -> u16 {
+    let carry = flags.contains(Flag::Carry);
+    let result = add(flags, width, value, 1, false);
+    flags.set(Flag::Carry, carry);
+    result
+}
+
+/// Like [`sub`] of one, but CF is left alone.
+pub fn dec(flags: &mut FlagSet, width: Width, value: u16) -> u16 {
+    let carry = flags.contains(Flag::Carry);
+    let result = sub(flags, width, value, 1, false);
+    flags.set(Flag::Carry, carry);
+    result
+}
+
+/// `0 - value`, CF ends up set for anything but zero.
+pub fn neg(flags: &mut FlagSet, width: Width, value: u16) -> u16 {
+    sub(flags, width, 0, value, false)
+}
+
+/// Flags for the result of `and`, `or`, `xor` and `test`: CF, OF and AF are cleared.
+pub fn logic(flags: &mut FlagSet, width: Width, result: u16) -> u16 {
+    let result = result & width.mask();
+    flags.set(Flag::Carry, false);
+    flags.set(Flag::Overflow, false);
+    flags.set(Flag::Auxiliary, false);
+    set_result_flags(flags, width, result);
+    result
+}
+
+/// Shifts or rotates `value` by `count`, one bit at a time like the 8086 does, so counts past
+/// the width behave as they would on hardware. CF gets the last bit moved out and OF is set when
+/// the sign changed on the final step. Shifts also set ZF, SF and PF, rotates only touch CF and
+/// OF. A count of zero leaves the flags alone.
+///
+/// Returns `None` for anything that isn't a shift or rotate.
+pub fn shift(
+    flags: &mut FlagSet,
+    mnemonic: Mnemonic,
+    width: Width,
+    value: u16,
+    count: u8,
+) -> Option<u16> {
+    if !mnemonic.is_shift() {
+        return None;
+    }
+    let (mask, sign) = (width.mask(), width.sign_bit());
+    let mut value = value & mask;
+    let mut carry = flags.contains(Flag::Carry);
+    let top = |carry: bool| if carry { sign } else { 0 };
+
+    for _ in 0..count {
+        let (low_out, high_out) = (value & 1 != 0, value & sign != 0);
+        (value, carry) = match mnemonic {
+            Mnemonic::Shl => ((value << 1) & mask, high_out),
+            Mnemonic::Shr => (value >> 1, low_out),
+            Mnemonic::Sar => ((value >> 1) | (value & sign), low_out),
+            Mnemonic::Rol => (((value << 1) | u16::from(high_out)) & mask, high_out),
+            Mnemonic::Ror => ((value >> 1) | top(low_out), low_out),
+            Mnemonic::Rcl => (((value << 1) | u16::from(carry)) & mask, high_out),
+            Mnemonic::Rcr => ((value >> 1) | top(carry), low_out),
+            _ => return None,
+        };
+    }
+    if count == 0 {
+        return Some(value);
+    }
+
+    flags.set(Flag::Carry, carry);
+    let overflow = match mnemonic {
+        // The bit shifted into the sign differs from the one shifted out of it.
+        Mnemonic::Shl | Mnemonic::Rol | Mnemonic::Rcl => (value & sign != 0) != carry,
+        // The top two bits of the result differ.
+        _ => (value ^ (value << 1)) & sign != 0,
+    };
+    flags.set(Flag::Overflow, overflow);
+    if matches!(mnemonic, Mnemonic::Shl | Mnemonic::Shr | Mnemonic::Sar) {
+        set_result_flags(flags, width, value);
+    }
+    Some(value)
+}
@@ -0,0 +1,117 @@
+use super::*;
+use rstest::rstest;
+
+fn carry(set: bool) -> FlagSet {
+    let mut flags = FlagSet::EMPTY;
+    flags.set(Flag::Carry, set);
+    flags
+}
+
+#[rstest]
+#[case::signed_overflow_word(Width::Word, 0x7FFF, 0x0001, false, 0x8000, "PASO")]
+#[case::signed_overflow_byte(Width::Byte, 0x7F, 0x01, false, 0x80, "ASO")]
+#[case::carry_out_of_byte(Width::Byte, 0xFF, 0x01, false, 0x00, "CPAZ")]
+#[case::byte_carry_within_word(Width::Word, 0x00FF, 0x0001, false, 0x0100, "PA")]
+#[case::auxiliary_only(Width::Byte, 0x08, 0x08, false, 0x10, "A")]
+#[case::negative_overflow(Width::Word, 0x8000, 0x8000, false, 0x0000, "CPZO")]
+#[case::carry_in(Width::Byte, 0xFF, 0x00, true, 0x00, "CPAZ")]
+fn test_add(
+    #[case] width: Width,
+    #[case] a: u16,
+    #[case] b: u16,
+    #[case] carry_in: bool,
+    #[case] expected: u16,
+    #[case] flags: &str,
+) {
+    let mut actual = FlagSet::EMPTY;
+    assert_eq!(add(&mut actual, width, a, b, carry_in), expected);
+    assert_eq!(actual.to_string(), flags);
+}
+
+#[rstest]
+#[case::signed_overflow_byte(Width::Byte, 0x80, 0x01, false, 0x7F, "AO")]
+#[case::borrow_word(Width::Word, 0x0000, 0x0001, false, 0xFFFF, "CPAS")]
+#[case::auxiliary_borrow(Width::Byte, 0x10, 0x01, false, 0x0F, "PA")]
+#[case::equal(Width::Word, 5, 5, false, 0, "PZ")]
+#[case::minus_negative_overflows(Width::Byte, 0x7F, 0xFF, false, 0x80, "CSO")]
+#[case::borrow_in(Width::Byte, 0x00, 0x00, true, 0xFF, "CPAS")]
+fn test_sub(
+    #[case] width: Width,
+    #[case] a: u16,
+    #[case] b: u16,
+    #[case] borrow: bool,
+    #[case] expected: u16,
+    #[case] flags: &str,
+) {
+    let mut actual = FlagSet::EMPTY;
+    assert_eq!(sub(&mut actual, width, a, b, borrow), expected);
+    assert_eq!(actual.to_string(), flags);
+}
+
+#[test]
+fn test_inc_and_dec_keep_the_carry() {
+    let mut flags = carry(true);
+    assert_eq!(inc(&mut flags, Width::Byte, 0xFF), 0x00);
+    assert_eq!(flags.to_string(), "CPAZ");
+
+    let mut flags = carry(false);
+    assert_eq!(dec(&mut flags, Width::Word, 0x8000), 0x7FFF);
+    assert_eq!(flags.to_string(), "PAO");
+}
+
+#[rstest]
+#[case::most_negative_byte(Width::Byte, 0x80, 0x80, "CSO")]
+#[case::zero(Width::Word, 0x0000, 0x0000, "PZ")]
+#[case::one(Width::Word, 0x0001, 0xFFFF, "CPAS")]
+fn test_neg(#[case] width: Width, #[case] value: u16, #[case] expected: u16, #[case] flags: &str) {
+    let mut actual = FlagSet::EMPTY;
+    assert_eq!(neg(&mut actual, width, value), expected);
+    assert_eq!(actual.to_string(), flags);
+}
+
+#[test]
+fn test_logic_clears_carry_overflow_and_auxiliary() {
+    let mut flags = FlagSet::STATUS;
+    assert_eq!(logic(&mut flags, Width::Word, 0x8001), 0x8001);
+    assert_eq!(flags.to_string(), "S");
+
+    let mut flags = FlagSet::EMPTY;
+    assert_eq!(logic(&mut flags, Width::Byte, 0x0300), 0x00);
+    assert_eq!(flags.to_string(), "PZ");
+}
+
+#[rstest]
+#[case::shl(Mnemonic::Shl, Width::Byte, 0x81, 1, false, 0x02, "CO")]
+#[case::shr(Mnemonic::Shr, Width::Word, 0x8001, 1, false, 0x4000, "CPO")]
+#[case::sar_keeps_sign(Mnemonic::Sar, Width::Byte, 0x81, 1, false, 0xC0, "CPS")]
+#[case::rol(Mnemonic::Rol, Width::Byte, 0x81, 1, false, 0x03, "CO")]
+#[case::ror(Mnemonic::Ror, Width::Byte, 0x01, 1, false, 0x80, "CO")]
+#[case::rcl_through_carry(Mnemonic::Rcl, Width::Byte, 0x80, 1, false, 0x00, "CO")]
+#[case::rcr_through_carry(Mnemonic::Rcr, Width::Word, 0x0001, 1, true, 0x8000, "CO")]
+#[case::count_past_width(Mnemonic::Shl, Width::Byte, 0x01, 9, false, 0x00, "PZ")]
+#[case::multi_bit_shr(Mnemonic::Shr, Width::Word, 0x00F0, 4, false, 0x000F, "P")]
+#[case::zero_count(Mnemonic::Shl, Width::Word, 0x1234, 0, true, 0x1234, "C")]
+fn test_shift(
+    #[case] mnemonic: Mnemonic,
+    #[case] width: Width,
+    #[case] value: u16,
+    #[case] count: u8,
+    #[case] carry_in: bool,
+    #[case] expected: u16,
+    #[case] flags: &str,
+) {
+    let mut actual = carry(carry_in);
+    assert_eq!(
+        shift(&mut actual, mnemonic, width, value, count),
+        Some(expected)
+    );
+    assert_eq!(actual.to_string(), flags);
+}
+
+#[test]
+fn test_shift_rejects_other_mnemonics() {
+    assert_eq!(
+        shift(&mut FlagSet::default(), Mnemonic::Add, Width::Word, 1, 1),
+        None
+    );
+}
@@ -0,0 +1,182 @@
+use crate::decoder::{Flow, Instruction};
+use crate::syntax::InstructionFormatter;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Write};
+use std::ops::Range;
+
+#[cfg(test)]
+mod cfg_tests;
+
+/// A run of instructions that is only ever entered at the top and left at the bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// Offset of the first instruction.
+    pub start: usize,
+    /// Offset just past the last instruction.
+    pub end: usize,
+    /// Indices into the instructions the graph was built from.
+    pub instructions: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// Execution continues with the block right after this one.
+    FallThrough,
+    /// A jump (conditional or not) to its target.
+    Taken,
+    /// A call to a subroutine, which comes back through a fall-through edge.
+    Call,
+}
+
+/// Edge between two blocks, by their index in [`ControlFlowGraph::blocks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>,
+}
+
+impl ControlFlowGraph {
+    /// Splits `instructions` (sorted by offset, gaps allowed) into basic blocks and connects them.
+    ///
+    /// Indirect jumps and calls, and targets that don't land on the start of a decoded
+    /// instruction, have no edge.
+    pub fn build(instructions: &[Instruction]) -> Self {
+        let index_of: HashMap<usize, usize> = instructions
+            .iter()
+            .enumerate()
+            .map(|(index, instruction)| (instruction.offset, index))
+            .collect();
+
+        let mut leaders = BTreeSet::new();
+        if !instructions.is_empty() {
+            leaders.insert(0);
+        }
+        for (index, instruction) in instructions.iter().enumerate() {
+            if let Some(&target) = instruction
+                .branch_target()
+                .and_then(|target| index_of.get(&target))
+            {
+                leaders.insert(target);
+            }
+
+            let ends_block = !matches!(
+                instruction.mnemonic.flow(),
+                Flow::Sequential | Flow::Interrupt
+            );
+            let next_is_adjacent = instructions
+                .get(index + 1)
+                .is_some_and(|next| next.offset == instruction.offset + instruction.length);
+            if index + 1 < instructions.len() && (ends_block || !next_is_adjacent) {
+                leaders.insert(index + 1);
+            }
+        }
+
+        let leaders: Vec<usize> = leaders.into_iter().collect();
+        let blocks: Vec<BasicBlock> = leaders
+            .iter()
+            .enumerate()
+            .map(|(block, &first)| {
+                let last = leaders
+                    .get(block + 1)
+                    .map_or(instructions.len(), |&next| next);
+                let end = &instructions[last - 1];
+                BasicBlock {
+                    start: instructions[first].offset,
+                    end: end.offset + end.length,
+                    instructions: first..last,
+                }
+            })
+            .collect();
+
+        let block_at: HashMap<usize, usize> = blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (block.start, index))
+            .collect();
+
+        let mut edges = vec![];
+        for (from, block) in blocks.iter().enumerate() {
+            let last = &instructions[block.instructions.end - 1];
+            let target = last
+                .branch_target()
+                .and_then(|target| block_at.get(&target).copied());
+            let next = block_at.get(&block.end).copied();
+
+            let successors = match last.mnemonic.flow() {
+                Flow::Sequential | Flow::Interrupt => {
+                    [None, next.map(|to| (to, EdgeKind::FallThrough))]
+                }
+                Flow::ConditionalJump => [
+                    target.map(|to| (to, EdgeKind::Taken)),
+                    next.map(|to| (to, EdgeKind::FallThrough)),
+                ],
+                Flow::Jump => [target.map(|to| (to, EdgeKind::Taken)), None],
+                Flow::Call => [
+                    target.map(|to| (to, EdgeKind::Call)),
+                    next.map(|to| (to, EdgeKind::FallThrough)),
+                ],
+                Flow::Return | Flow::Halt => [None, None],
+            };
+
+            edges.extend(successors.into_iter().flatten().map(|(to, kind)| Edge {
+                from,
+                to,
+                kind,
+            }));
+        }
+
+        Self { blocks, edges }
+    }
+
+    /// Writes the graph in Graphviz DOT, one box per block listing its instructions.
+    /// `instructions` must be the ones the graph was built from.
+    pub fn write_dot(
+        &self,
+        out: &mut impl Write,
+        instructions: &[Instruction],
+        formatter: &dyn InstructionFormatter,
+    ) -> io::Result<()> {
+        let node = |block: &BasicBlock| format!("block_{:04x}", block.start);
+
+        writeln!(out, "digraph cfg {{")?;
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];")?;
+
+        for block in &self.blocks {
+            let label: String = instructions[block.instructions.clone()]
+                .iter()
+                .map(|instruction| {
+                    let text = formatter.format_instruction(instruction);
+                    format!("{:04x}  {}\\l", instruction.offset, escape(&text))
+                })
+                .collect();
+            writeln!(out, "    {} [label=\"{label}\"];", node(block))?;
+        }
+
+        for edge in &self.edges {
+            let attributes = match edge.kind {
+                EdgeKind::FallThrough => "",
+                EdgeKind::Taken => " [label=\"taken\", color=\"forestgreen\"]",
+                EdgeKind::Call => " [label=\"call\", style=\"dashed\"]",
+            };
+            writeln!(
+                out,
+                "    {} -> {}{attributes};",
+                node(&self.blocks[edge.from]),
+                node(&self.blocks[edge.to])
+            )?;
+        }
+
+        writeln!(out, "}}")
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
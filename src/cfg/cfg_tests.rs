@@ -0,0 +1,108 @@
+use super::*;
+use crate::decoder::decode_all;
+use crate::syntax::Nasm;
+
+// 0000  mov cx, 3
+// 0003  dec cx        <-+
+// 0005  jne $-2       --+
+// 0007  call $+4      --+
+// 000a  hlt             |
+// 000b  ret           <-+
+const PROGRAM: [u8; 12] = [
+    0xB9, 0x03, 0x00, 0xFF, 0xC9, 0x75, 0xFC, 0xE8, 0x01, 0x00, 0xF4, 0xC3,
+];
+
+fn edge(from: usize, to: usize, kind: EdgeKind) -> Edge {
+    Edge { from, to, kind }
+}
+
+#[test]
+fn test_splits_blocks_at_leaders() {
+    let instructions = decode_all(&PROGRAM).unwrap();
+    let graph = ControlFlowGraph::build(&instructions);
+
+    let ranges: Vec<_> = graph
+        .blocks
+        .iter()
+        .map(|block| (block.start, block.end, block.instructions.clone()))
+        .collect();
+    assert_eq!(
+        ranges,
+        [
+            (0x0, 0x3, 0..1),
+            (0x3, 0x7, 1..3),
+            (0x7, 0xA, 3..4),
+            (0xA, 0xB, 4..5),
+            (0xB, 0xC, 5..6),
+        ]
+    );
+}
+
+#[test]
+fn test_connects_blocks() {
+    let instructions = decode_all(&PROGRAM).unwrap();
+    let graph = ControlFlowGraph::build(&instructions);
+
+    assert_eq!(
+        graph.edges,
+        [
+            edge(0, 1, EdgeKind::FallThrough),
+            edge(1, 1, EdgeKind::Taken),
+            edge(1, 2, EdgeKind::FallThrough),
+            edge(2, 4, EdgeKind::Call),
+            edge(2, 3, EdgeKind::FallThrough),
+        ]
+    );
+}
+
+#[test]
+fn test_indirect_and_unresolved_jumps_have_no_edges() {
+    // jmp bx | jmp $+0x100 | mov cx, bx
+    let instructions = decode_all(&[0xFF, 0xE3, 0xE9, 0xFD, 0x00, 0x89, 0xD9]).unwrap();
+    let graph = ControlFlowGraph::build(&instructions);
+
+    assert_eq!(graph.blocks.len(), 3);
+    assert!(
+        graph.edges.is_empty(),
+        "Unexpected edges: {:?}",
+        graph.edges
+    );
+}
+
+#[test]
+fn test_gaps_split_blocks_without_fall_through() {
+    let data = [0x89, 0xD9, 0x89, 0xD9, 0x89, 0xD9];
+    let mut instructions = decode_all(&data).unwrap();
+    instructions.remove(1);
+    let graph = ControlFlowGraph::build(&instructions);
+
+    assert_eq!(graph.blocks.len(), 2);
+    assert!(graph.edges.is_empty());
+}
+
+#[test]
+fn test_empty_graph() {
+    assert_eq!(ControlFlowGraph::build(&[]), ControlFlowGraph::default());
+}
+
+#[test]
+fn test_writes_dot() {
+    let instructions = decode_all(&PROGRAM[..7]).unwrap();
+    let graph = ControlFlowGraph::build(&instructions);
+    let mut out = vec![];
+    graph
+        .write_dot(&mut out, &instructions, &Nasm::default())
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        r#"digraph cfg {
+    node [shape=box, fontname="monospace"];
+    block_0000 [label="0000  mov cx, 3\l"];
+    block_0003 [label="0003  dec cx\l0005  jne $-2\l"];
+    block_0000 -> block_0003;
+    block_0003 -> block_0003 [label="taken", color="forestgreen"];
+}
+"#
+    );
+}
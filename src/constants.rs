@@ -0,0 +1,244 @@
+use crate::alu;
+use crate::cfg::{ControlFlowGraph, EdgeKind};
+use crate::decoder::{
+    EffectiveAddress, Flow, Instruction, Mnemonic, Operand, Register, RegisterByteOp,
+    RegisterWordOp, Width,
+};
+use crate::effects::{Effects, Location};
+use crate::flags::FlagSet;
+
+#[cfg(test)]
+mod constants_tests;
+
+/// What's known, without running anything, about the general registers at one point of a
+/// program. Each byte is tracked on its own, so `mov al, 1` tells AL even while AH is unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownRegisters {
+    /// The low and high byte of each word register, in [`RegisterWordOp`] order.
+    bytes: [[Option<u8>; 2]; 8],
+}
+
+impl KnownRegisters {
+    /// Nothing known, as at the start of a program.
+    pub fn unknown() -> Self {
+        Self {
+            bytes: [[None; 2]; 8],
+        }
+    }
+
+    /// The value of a byte or word register, `None` unless all of it is known.
+    pub fn get(&self, register: Register) -> Option<u16> {
+        match register {
+            Register::Byte(byte) => self.byte(byte).map(u16::from),
+            Register::Word(word) => {
+                let [low, high] = self.bytes[word as usize];
+                Some(u16::from_le_bytes([low?, high?]))
+            }
+        }
+    }
+
+    /// Sets a register to `value`, or forgets it for `None`. Byte registers leave the other half
+    /// alone.
+    pub fn set(&mut self, register: Register, value: Option<u16>) {
+        match register {
+            Register::Byte(byte) => {
+                self.bytes[byte.word() as usize][usize::from(byte.is_high())] =
+                    value.map(|value| value as u8);
+            }
+            Register::Word(word) => {
+                self.bytes[word as usize] = match value {
+                    Some(value) => value.to_le_bytes().map(Some),
+                    None => [None; 2],
+                };
+            }
+        }
+    }
+
+    fn byte(&self, register: RegisterByteOp) -> Option<u8> {
+        self.bytes[register.word() as usize][usize::from(register.is_high())]
+    }
+
+    /// Offset of a memory operand within its segment, like [`crate::cpu::Cpu::offset_of`], when
+    /// the registers it's based on are known.
+    pub fn offset_of(&self, address: &EffectiveAddress) -> Option<u16> {
+        let base = match address.base.map(|base| base.registers()) {
+            None => 0,
+            Some((first, None)) => self.get(Register::Word(first))?,
+            Some((first, Some(second))) => self
+                .get(Register::Word(first))?
+                .wrapping_add(self.get(Register::Word(second))?),
+        };
+        Some(base.wrapping_add(address.displacement as u16))
+    }
+
+    /// The offset the memory operand of `instruction` resolves to, `None` when it has none, it's
+    /// a direct address that needs no resolving, or a register it's based on isn't known.
+    pub fn resolve(&self, instruction: &Instruction) -> Option<u16> {
+        instruction.operands().find_map(|operand| match operand {
+            Operand::Memory(address) if address.base.is_some() => self.offset_of(&address),
+            _ => None,
+        })
+    }
+
+    /// What's known after `instruction` runs. Results are only worked out for moves and
+    /// arithmetic that doesn't depend on the flags, anything else an instruction writes becomes
+    /// unknown. An `int` can come back with any register changed.
+    pub fn step(&mut self, instruction: &Instruction) {
+        use Mnemonic::*;
+
+        let value = |operand| self.value(instruction, operand);
+        let (destination, source) = (instruction.destination, instruction.source);
+        let width = instruction.width;
+        let mut flags = FlagSet::EMPTY;
+        let result = match instruction.mnemonic {
+            Mov => value(source),
+            Lea => match source {
+                Some(Operand::Memory(address)) => self.offset_of(&address),
+                _ => None,
+            },
+            // the same register on both sides is zero whatever it held
+            Xor | Sub if destination == source => Some(0),
+            Add | Sub | And | Or | Xor => {
+                value(destination)
+                    .zip(value(source))
+                    .map(|(a, b)| match instruction.mnemonic {
+                        Add => alu::add(&mut flags, width, a, b, false),
+                        Sub => alu::sub(&mut flags, width, a, b, false),
+                        And => a & b,
+                        Or => a | b,
+                        _ => a ^ b,
+                    })
+            }
+            Inc => value(destination).map(|a| alu::inc(&mut flags, width, a)),
+            Dec => value(destination).map(|a| alu::dec(&mut flags, width, a)),
+            Neg => value(destination).map(|a| alu::neg(&mut flags, width, a)),
+            Not => value(destination).map(|a| !a & width.mask()),
+            // rotates through the carry need the flags, which aren't tracked
+            Shl | Shr | Sar | Rol | Ror => {
+                value(destination)
+                    .zip(value(source))
+                    .and_then(|(a, count)| {
+                        alu::shift(&mut flags, instruction.mnemonic, width, a, count as u8)
+                    })
+            }
+            Xchg => {
+                if let (Some(Operand::Register(a)), Some(Operand::Register(b))) =
+                    (destination, source)
+                {
+                    let (first, second) = (self.get(a), self.get(b));
+                    self.set(a, second);
+                    self.set(b, first);
+                    return;
+                }
+                None
+            }
+            Cbw => {
+                let al = self.byte(RegisterByteOp::AL);
+                let ah = al.map(|al| if al & 0x80 != 0 { 0xFF } else { 0 });
+                self.set(Register::Byte(RegisterByteOp::AH), ah);
+                return;
+            }
+            Cwd => {
+                let ax = self.get(Register::Word(RegisterWordOp::AX));
+                let dx = ax.map(|ax| if ax & 0x8000 != 0 { 0xFFFF } else { 0 });
+                self.set(Register::Word(RegisterWordOp::DX), dx);
+                return;
+            }
+            Int | Int3 | Into => {
+                *self = Self::unknown();
+                return;
+            }
+            _ => None,
+        };
+
+        for location in Effects::of(instruction).writes {
+            if let Location::Register(register) = location {
+                self.set(register, None);
+            }
+        }
+        if let (Some(Operand::Register(register)), Some(result)) = (destination, result)
+            && !matches!(instruction.mnemonic, Cmp | Test)
+        {
+            self.set(register, Some(result));
+        }
+    }
+
+    /// An immediate, or a register that's known. Immediates of byte instructions are cut to
+    /// the byte.
+    fn value(&self, instruction: &Instruction, operand: Option<Operand>) -> Option<u16> {
+        match operand? {
+            Operand::Immediate(value) => Some(match instruction.width {
+                Width::Byte => value & 0xFF,
+                Width::Word => value,
+            }),
+            Operand::Register(register) => self.get(register),
+            _ => None,
+        }
+    }
+
+    /// What's known on both paths that meet, bytes only stay known if they agree.
+    fn meet(&self, other: &Self) -> Self {
+        let mut bytes = self.bytes;
+        for (mine, theirs) in bytes.iter_mut().flatten().zip(other.bytes.iter().flatten()) {
+            if mine != theirs {
+                *mine = None;
+            }
+        }
+        Self { bytes }
+    }
+}
+
+impl Default for KnownRegisters {
+    fn default() -> Self {
+        Self::unknown()
+    }
+}
+
+/// What's known about the registers before each of `instructions` (sorted by offset, as for
+/// [`ControlFlowGraph::build`]), following the control flow between blocks. Blocks nothing
+/// jumps or falls into start with nothing known, and so does the code after a `call`, since the
+/// subroutine may change anything.
+///
+/// Only the edges of the graph are followed, so a block that's also reached by an indirect jump
+/// can be said to know values the jump doesn't bring along.
+pub fn propagate(instructions: &[Instruction]) -> Vec<KnownRegisters> {
+    let graph = ControlFlowGraph::build(instructions);
+    let mut entries: Vec<Option<KnownRegisters>> = vec![None; graph.blocks.len()];
+    for (index, entry) in entries.iter_mut().enumerate() {
+        if !graph.edges.iter().any(|edge| edge.to == index) {
+            *entry = Some(KnownRegisters::unknown());
+        }
+    }
+
+    let mut before = vec![KnownRegisters::unknown(); instructions.len()];
+    let mut pending: Vec<usize> = (0..graph.blocks.len()).rev().collect();
+    while let Some(index) = pending.pop() {
+        let Some(mut state) = entries[index] else {
+            continue;
+        };
+        let block = &graph.blocks[index];
+        for (offset, instruction) in instructions[block.instructions.clone()].iter().enumerate() {
+            before[block.instructions.start + offset] = state;
+            state.step(instruction);
+        }
+        let calls = instructions[block.instructions.end - 1].mnemonic.flow() == Flow::Call;
+
+        for edge in graph.edges.iter().filter(|edge| edge.from == index) {
+            let incoming = match edge.kind {
+                EdgeKind::FallThrough if calls => KnownRegisters::unknown(),
+                _ => state,
+            };
+            let merged = match entries[edge.to] {
+                Some(entry) => entry.meet(&incoming),
+                None => incoming,
+            };
+            if entries[edge.to] != Some(merged) {
+                entries[edge.to] = Some(merged);
+                if !pending.contains(&edge.to) {
+                    pending.push(edge.to);
+                }
+            }
+        }
+    }
+    before
+}
@@ -0,0 +1,117 @@
+use super::*;
+use crate::decoder::decode_all;
+use RegisterByteOp::AL;
+use RegisterWordOp::{AX, BX, CX, DI, DX, SI};
+use rstest::rstest;
+
+/// What's known after the last instruction of `data`.
+fn after(data: &[u8]) -> KnownRegisters {
+    let instructions = decode_all(data).unwrap();
+    let mut known = *propagate(&instructions).last().unwrap();
+    known.step(instructions.last().unwrap());
+    known
+}
+
+#[rstest]
+// mov bx, 1000 / add bx, 10
+#[case::add(&[0xBB, 0xE8, 0x03, 0x83, 0xC3, 0x0A], Register::Word(BX), Some(1010))]
+// mov al, 0x12 / mov ah, 0x34
+#[case::halves(&[0xB0, 0x12, 0xB4, 0x34], Register::Word(AX), Some(0x3412))]
+// mov ax, 0x1234 / mov al, 0xff
+#[case::one_half(&[0xB8, 0x34, 0x12, 0xB0, 0xFF], Register::Word(AX), Some(0x12FF))]
+// mov al, 1
+#[case::other_half_unknown(&[0xB0, 0x01], Register::Word(AX), None)]
+// xor cx, cx
+#[case::xor_itself(&[0x31, 0xC9], Register::Word(CX), Some(0))]
+// sub al, al
+#[case::sub_itself(&[0x28, 0xC0], Register::Byte(AL), Some(0))]
+// mov si, 3 / shl si, 1
+#[case::shift(&[0xBE, 0x03, 0x00, 0xD1, 0xE6], Register::Word(SI), Some(6))]
+// mov al, 0x80 / cbw
+#[case::cbw(&[0xB0, 0x80, 0x98], Register::Word(AX), Some(0xFF80))]
+// mov ax, 0x8000 / cwd
+#[case::cwd(&[0xB8, 0x00, 0x80, 0x99], Register::Word(DX), Some(0xFFFF))]
+// mov bx, 5 / lea di, [bx + 2]
+#[case::lea(&[0xBB, 0x05, 0x00, 0x8D, 0x7F, 0x02], Register::Word(DI), Some(7))]
+// mov cx, 1 / mov dx, 2 / xchg cx, dx
+#[case::xchg(&[0xB9, 0x01, 0x00, 0xBA, 0x02, 0x00, 0x87, 0xD1], Register::Word(CX), Some(2))]
+// mov cx, 5 / cmp cx, 1
+#[case::cmp(&[0xB9, 0x05, 0x00, 0x83, 0xF9, 0x01], Register::Word(CX), Some(5))]
+// mov bx, 1 / mov bx, [0]
+#[case::loaded_from_memory(&[0xBB, 0x01, 0x00, 0x8B, 0x1E, 0x00, 0x00], Register::Word(BX), None)]
+// mov ax, 1 / mul cx
+#[case::not_worked_out(&[0xB8, 0x01, 0x00, 0xF7, 0xE1], Register::Word(AX), None)]
+// mov al, 1 / adc al, 1
+#[case::needs_the_carry(&[0xB0, 0x01, 0x14, 0x01], Register::Byte(AL), None)]
+// mov bx, 1 / int 21h
+#[case::interrupt(&[0xBB, 0x01, 0x00, 0xCD, 0x21], Register::Word(BX), None)]
+fn test_follows_values_through_instructions(
+    #[case] data: &[u8],
+    #[case] register: Register,
+    #[case] expected: Option<u16>,
+) {
+    assert_eq!(after(data).get(register), expected);
+}
+
+#[test]
+fn test_values_changed_in_a_loop_are_unknown_inside_it() {
+    // mov cx, 3 / mov bx, 0 / add bx, 2 / mov [bx], al / loop $-5 / hlt
+    let data = [
+        0xB9, 0x03, 0x00, 0xBB, 0x00, 0x00, 0x83, 0xC3, 0x02, 0x88, 0x07, 0xE2, 0xF9, 0xF4,
+    ];
+    let known = propagate(&decode_all(&data).unwrap());
+
+    assert_eq!(known[2].get(Register::Word(BX)), None);
+    assert_eq!(known[3].get(Register::Word(CX)), None);
+    assert_eq!(known[1].get(Register::Word(CX)), Some(3));
+}
+
+#[rstest]
+// mov bx, 4 / je $+5 / mov si, 1 / mov [bx + si], al
+#[case::set_on_one_path(&[0xBB, 0x04, 0x00, 0x74, 0x03, 0xBE, 0x01, 0x00, 0x88, 0x00], None)]
+// mov bx, 4 / mov si, 1 / je $+5 / mov si, 1 / mov [bx + si], al
+#[case::same_on_both_paths(
+    &[0xBB, 0x04, 0x00, 0xBE, 0x01, 0x00, 0x74, 0x03, 0xBE, 0x01, 0x00, 0x88, 0x00],
+    Some(5),
+)]
+fn test_paths_that_meet_keep_what_they_agree_on(
+    #[case] data: &[u8],
+    #[case] resolved: Option<u16>,
+) {
+    let instructions = decode_all(data).unwrap();
+    let known = propagate(&instructions);
+    let last = instructions.len() - 1;
+
+    assert_eq!(known[last].get(Register::Word(BX)), Some(4));
+    assert_eq!(known[last].resolve(&instructions[last]), resolved);
+}
+
+#[test]
+fn test_subroutines_see_the_caller_and_callers_forget_after() {
+    // mov bx, 4 / call $+6 / mov [bx], al / hlt / mov [bx + 1], al / ret
+    let data = [
+        0xBB, 0x04, 0x00, 0xE8, 0x03, 0x00, 0x88, 0x07, 0xF4, 0x88, 0x47, 0x01, 0xC3,
+    ];
+    let instructions = decode_all(&data).unwrap();
+    let known = propagate(&instructions);
+
+    assert_eq!(known[4].resolve(&instructions[4]), Some(5));
+    assert_eq!(known[2].resolve(&instructions[2]), None);
+}
+
+#[rstest]
+// mov [bx + 0xfffe], al with bx 4, wrapping round the segment
+#[case::wraps(&[0x88, 0x87, 0xFE, 0xFF], Some(2))]
+// mov [0x1000], al needs no resolving
+#[case::direct(&[0xA2, 0x00, 0x10], None)]
+// mov al, bl has no memory operand
+#[case::no_memory(&[0x88, 0xD8], None)]
+// mov [bp], al with bp unknown
+#[case::unknown_base(&[0x88, 0x46, 0x00], None)]
+fn test_resolves_memory_operands(#[case] data: &[u8], #[case] expected: Option<u16>) {
+    let instruction = decode_all(data).unwrap()[0];
+    let mut known = KnownRegisters::unknown();
+    known.set(Register::Word(BX), Some(4));
+
+    assert_eq!(known.resolve(&instruction), expected);
+}
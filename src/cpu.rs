@@ -0,0 +1,154 @@
+use crate::decoder::{EffectiveAddress, Register, RegisterWordOp, SegmentRegister};
+use crate::flags::FlagSet;
+use std::fmt;
+
+#[cfg(test)]
+mod cpu_tests;
+
+/// Order the reference traces list the general registers in, rather than encoding order.
+pub const TRACE_ORDER: [RegisterWordOp; 8] = [
+    RegisterWordOp::AX,
+    RegisterWordOp::BX,
+    RegisterWordOp::CX,
+    RegisterWordOp::DX,
+    RegisterWordOp::SP,
+    RegisterWordOp::BP,
+    RegisterWordOp::SI,
+    RegisterWordOp::DI,
+];
+
+/// The 8086 register file. Byte registers aren't stored separately, AL and AH are the low and
+/// high halves of AX and so on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cpu {
+    /// Indexed by [`RegisterWordOp`].
+    registers: [u16; 8],
+    /// Indexed by [`SegmentRegister`].
+    segments: [u16; 4],
+    pub ip: u16,
+    pub flags: FlagSet,
+    /// Clocks spent so far, on an 8086 unless a run estimates them for another processor.
+    pub cycles: u64,
+}
+
+impl Cpu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn word(&self, register: RegisterWordOp) -> u16 {
+        self.registers[register as usize]
+    }
+
+    pub fn set_word(&mut self, register: RegisterWordOp, value: u16) {
+        self.registers[register as usize] = value;
+    }
+
+    /// Reads a byte or word register, bytes are zero extended.
+    pub fn register(&self, register: Register) -> u16 {
+        match register {
+            Register::Byte(byte) => {
+                let word = self.word(byte.word());
+                if byte.is_high() {
+                    word >> 8
+                } else {
+                    word & 0xFF
+                }
+            }
+            Register::Word(word) => self.word(word),
+        }
+    }
+
+    /// Writes a byte or word register, only the low byte of `value` is used for byte registers
+    /// and the other half of the word is left alone.
+    pub fn set_register(&mut self, register: Register, value: u16) {
+        match register {
+            Register::Byte(byte) => {
+                let word = self.word(byte.word());
+                let value = value & 0xFF;
+                let merged = if byte.is_high() {
+                    (word & 0x00FF) | (value << 8)
+                } else {
+                    (word & 0xFF00) | value
+                };
+                self.set_word(byte.word(), merged);
+            }
+            Register::Word(word) => self.set_word(word, value),
+        }
+    }
+
+    pub fn segment(&self, segment: SegmentRegister) -> u16 {
+        self.segments[segment as usize]
+    }
+
+    pub fn set_segment(&mut self, segment: SegmentRegister, value: u16) {
+        self.segments[segment as usize] = value;
+    }
+
+    /// Offset of a memory operand within its segment: the base registers plus the displacement,
+    /// wrapping at 64 KB.
+    pub fn offset_of(&self, address: &EffectiveAddress) -> u16 {
+        let base = address.base.map_or(0, |base| match base.registers() {
+            (first, Some(second)) => self.word(first).wrapping_add(self.word(second)),
+            (first, None) => self.word(first),
+        });
+        base.wrapping_add(address.displacement as u16)
+    }
+
+    /// Every register that differs from `before`, as `name:0xold->0xnew`, with flags written as
+    /// `flags:old->new` letters. This is the comment the course's reference traces put after
+    /// each instruction.
+    pub fn changes_since(&self, before: &Self) -> Vec<String> {
+        let mut changes = vec![];
+        for register in TRACE_ORDER {
+            let (old, new) = (before.word(register), self.word(register));
+            if old != new {
+                changes.push(format!("{}:{old:#x}->{new:#x}", register.name()));
+            }
+        }
+        for segment in SegmentRegister::ALL {
+            let (old, new) = (before.segment(segment), self.segment(segment));
+            if old != new {
+                changes.push(format!("{}:{old:#x}->{new:#x}", segment.name()));
+            }
+        }
+        if before.ip != self.ip {
+            changes.push(format!("ip:{:#x}->{:#x}", before.ip, self.ip));
+        }
+        if before.flags != self.flags {
+            changes.push(format!("flags:{}->{}", before.flags, self.flags));
+        }
+        changes
+    }
+}
+
+/// The final register dump of the reference traces, registers that are still zero are skipped:
+///
+/// ```text
+/// Final registers:
+///       ax: 0x0001 (1)
+///    flags: PZ
+/// ```
+impl fmt::Display for Cpu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Final registers:")?;
+        let registers = TRACE_ORDER
+            .iter()
+            .map(|&register| (register.name(), self.word(register)));
+        let segments = SegmentRegister::ALL
+            .iter()
+            .map(|&segment| (segment.name(), self.segment(segment)));
+
+        for (name, value) in registers
+            .chain(segments)
+            .chain([("ip", self.ip)])
+            .filter(|&(_, value)| value != 0)
+        {
+            writeln!(f, "{name:>8}: {value:#06x} ({value})")?;
+        }
+        if !self.flags.is_empty() {
+            writeln!(f, "{:>8}: {}", "flags", self.flags)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use super::*;
+use crate::decoder::{AddressBase, RegisterByteOp};
+use crate::flags::Flag;
+use rstest::rstest;
+
+#[rstest]
+#[case::low(RegisterByteOp::AL, 0x1234, 0x34)]
+#[case::high(RegisterByteOp::AH, 0x1234, 0x12)]
+#[case::other_register(RegisterByteOp::DH, 0xABCD, 0xAB)]
+fn test_reads_byte_halves(
+    #[case] register: RegisterByteOp,
+    #[case] word: u16,
+    #[case] expected: u16,
+) {
+    let mut cpu = Cpu::new();
+    cpu.set_word(register.word(), word);
+    assert_eq!(cpu.register(Register::Byte(register)), expected);
+}
+
+#[rstest]
+#[case::low(RegisterByteOp::BL, 0x1234)]
+#[case::high(RegisterByteOp::BH, 0x3412)]
+fn test_byte_writes_keep_the_other_half(#[case] register: RegisterByteOp, #[case] expected: u16) {
+    let mut cpu = Cpu::new();
+    cpu.set_word(RegisterWordOp::BX, 0x1212);
+    cpu.set_register(Register::Byte(register), 0xFF34);
+    assert_eq!(cpu.word(RegisterWordOp::BX), expected);
+}
+
+#[test]
+fn test_changes_since() {
+    let before = Cpu::new();
+    let mut after = before.clone();
+    after.set_register(Register::Byte(RegisterByteOp::CH), 0x01);
+    after.set_word(RegisterWordOp::AX, 0xFFFF);
+    after.set_segment(SegmentRegister::ES, 0x10);
+    after.flags = FlagSet::of(&[Flag::Zero, Flag::Parity]);
+
+    assert_eq!(
+        after.changes_since(&before),
+        [
+            "ax:0x0->0xffff",
+            "cx:0x0->0x100",
+            "es:0x0->0x10",
+            "flags:->PZ"
+        ]
+    );
+    assert!(after.changes_since(&after).is_empty());
+}
+
+#[test]
+fn test_final_registers_skip_zeros() {
+    let mut cpu = Cpu::new();
+    cpu.set_word(RegisterWordOp::DI, 4);
+    cpu.set_word(RegisterWordOp::BX, 0xFFFE);
+    cpu.set_segment(SegmentRegister::SS, 0x2222);
+    cpu.flags = FlagSet::of(&[Flag::Sign]);
+
+    assert_eq!(
+        cpu.to_string(),
+        "\
+Final registers:
+      bx: 0xfffe (65534)
+      di: 0x0004 (4)
+      ss: 0x2222 (8738)
+   flags: S
+"
+    );
+}
+
+#[rstest]
+#[case::direct(None, 1000, 1000)]
+#[case::base_and_index(Some(AddressBase::BxSi), 4, 0x3004)]
+#[case::negative_displacement(Some(AddressBase::Bp), -2, 0xFFFE)]
+#[case::wraps_at_64k(Some(AddressBase::BpDi), 0x10, 0x000F)]
+fn test_offset_of(
+    #[case] base: Option<AddressBase>,
+    #[case] displacement: i16,
+    #[case] expected: u16,
+) {
+    let mut cpu = Cpu::new();
+    cpu.set_word(RegisterWordOp::BX, 0x1000);
+    cpu.set_word(RegisterWordOp::SI, 0x2000);
+    cpu.set_word(RegisterWordOp::DI, 0xFFFF);
+
+    let address = EffectiveAddress {
+        base,
+        displacement,
+        has_displacement: displacement != 0,
+    };
+    assert_eq!(cpu.offset_of(&address), expected);
+}
@@ -0,0 +1,709 @@
+use crate::cpu::{Cpu, TRACE_ORDER};
+use crate::decode_cache::DecodeCache;
+use crate::decoder::{Decoder, Instruction, Mnemonic, Register, RegisterByteOp, SegmentRegister};
+use crate::flags::{Flag, FlagSet};
+use crate::interrupts::Interrupts;
+use crate::memory::{self, Memory, physical_address};
+use crate::ports::PortBus;
+use crate::recording::{Delta, Recording, RecordingError, fingerprint, write_deltas};
+use crate::sim::{self, SimError};
+use crate::syntax::InstructionFormatter;
+use crate::timing::Processor;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::ops::Range;
+
+#[cfg(test)]
+mod debugger_tests;
+
+const HELP: &str = "\
+s, step [n]                   run the next n instructions (1 by default), printing what they changed
+c, continue                   run until a breakpoint, `hlt` or the end of the program
+bs, back [n]                  undo the last n instructions (1 by default)
+rc, reverse                   undo instructions until a breakpoint or the start
+record <file>                 save what every instruction so far changed, for --replay
+b, break [address|text]       stop before the instruction at an IP, or any whose text contains
+                              `text`, lists the breakpoints without an argument
+d, delete <n>                 remove breakpoint n
+r, regs                       print every register and the flags
+x, examine <address> [count]  print count bytes (16 by default) of memory
+w, write <address> <byte>..   store bytes in memory
+set <register> <value>        change a register, `set flags CZ` sets the flags by letter
+u, disasm [n]                 n instructions (5 by default) either side of IP
+h, help
+q, quit
+
+Addresses are physical, or segment:offset where the segment is a register or a number.
+Numbers are decimal or 0x-prefixed hex.";
+
+/// Where [`Debugger::continue_running`] stops before an instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// IP of the instruction.
+    Address(u16),
+    /// Part of the instruction's text, as the debugger prints it.
+    Pattern(String),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address(ip) => write!(f, "ip {ip:#06x}"),
+            Self::Pattern(text) => write!(f, "`{text}`"),
+        }
+    }
+}
+
+/// Whether the REPL should keep reading commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Quit,
+}
+
+/// Steps through a program one command at a time, the same way [`sim::run`] goes through it in
+/// one go.
+pub struct Debugger<'a> {
+    pub cpu: Cpu,
+    pub memory: Memory,
+    /// Physical addresses the program was loaded at, it's finished once IP leaves them.
+    code: Range<usize>,
+    /// IP `code` starts at, so offsets into it can be turned back into IPs.
+    origin: u16,
+    breakpoints: Vec<Breakpoint>,
+    /// What each instruction changed, the ones before `position` have run. Stepping forward
+    /// replays the rest before executing anything new.
+    history: Vec<Delta>,
+    position: usize,
+    /// How far into the history the program stopped, with `hlt`, by terminating or at the end
+    /// of a replayed recording.
+    finished_at: Option<usize>,
+    /// Where `int` instructions go, and the console and keys of the built-in services.
+    pub interrupts: Interrupts,
+    /// Where `in` and `out` go.
+    pub ports: PortBus,
+    /// What `cpu.cycles`, the time the devices on `ports` see, counts the clocks of.
+    pub processor: Processor,
+    cache: DecodeCache,
+    formatter: &'a dyn InstructionFormatter,
+}
+
+impl<'a> Debugger<'a> {
+    /// Loads `data` at `cs:ip` of `cpu`.
+    pub fn new(data: &[u8], cpu: Cpu, formatter: &'a dyn InstructionFormatter) -> Self {
+        let mut memory = Memory::new();
+        let code = sim::load(data, &cpu, &mut memory);
+        Self::loaded(cpu, memory, code, formatter)
+    }
+
+    /// Debugs a program that's already in memory at `code`, such as one from
+    /// [`crate::dos::load`].
+    pub fn loaded(
+        cpu: Cpu,
+        memory: Memory,
+        code: Range<usize>,
+        formatter: &'a dyn InstructionFormatter,
+    ) -> Self {
+        let entry = physical_address(cpu.segment(SegmentRegister::CS), cpu.ip);
+        Self {
+            origin: cpu.ip.wrapping_sub(entry.wrapping_sub(code.start) as u16),
+            cpu,
+            memory,
+            code,
+            breakpoints: vec![],
+            history: vec![],
+            position: 0,
+            finished_at: None,
+            interrupts: Interrupts::new(),
+            ports: PortBus::new(),
+            processor: Processor::default(),
+            cache: DecodeCache::new(),
+            formatter,
+        }
+    }
+
+    /// Steps forward through a `recording` (from [`crate::recording::read_deltas`]) instead of
+    /// executing, until it runs out. It has to start from the state the debugger is in.
+    pub fn replay(&mut self, recording: Recording) -> Result<(), RecordingError> {
+        if recording.start != fingerprint(&self.cpu, &self.memory) {
+            return Err(RecordingError::DifferentStart);
+        }
+        self.finished_at = Some(recording.deltas.len());
+        self.history = recording.deltas;
+        self.position = 0;
+        Ok(())
+    }
+
+    /// What each instruction run so far changed, followed by the ones stepped back over.
+    pub fn history(&self) -> &[Delta] {
+        &self.history
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished_at.is_some_and(|end| self.position >= end)
+            || !sim::is_running(&self.cpu, &self.code)
+    }
+
+    /// Reads commands from `input` until it runs out or one says to quit, with a prompt before
+    /// each.
+    pub fn repl(&mut self, input: impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "> ")?;
+        out.flush()?;
+        for line in input.lines() {
+            if self.command(&line?, out)? == Control::Quit {
+                return Ok(());
+            }
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+        writeln!(out)
+    }
+
+    /// Runs one command. Mistakes in it are reported to `out` rather than returned, only failing
+    /// to write is an error.
+    pub fn command(&mut self, line: &str, out: &mut impl Write) -> io::Result<Control> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(Control::Continue);
+        };
+        let arguments: Vec<&str> = words.collect();
+
+        let result = match command {
+            "s" | "step" => self.step_command(&arguments, out),
+            "c" | "continue" => self.continue_running(out),
+            "bs" | "back" => self.back_command(&arguments, out),
+            "rc" | "reverse" => self.reverse(out),
+            "record" => self.record(&arguments, out),
+            "b" | "break" => self.break_command(line, &arguments, out),
+            "d" | "delete" => self.delete(&arguments, out),
+            "r" | "regs" => self.print_registers(out).map_err(Error::Io),
+            "x" | "examine" => self.examine(&arguments, out),
+            "w" | "write" => self.write_memory(&arguments),
+            "set" => self.set(&arguments),
+            "u" | "disasm" => self.disassemble(&arguments, out),
+            "h" | "help" => writeln!(out, "{HELP}").map_err(Error::Io),
+            "q" | "quit" => return Ok(Control::Quit),
+            _ => Err(Error::Usage(format!(
+                "unknown command `{command}`, try `help`"
+            ))),
+        };
+        match result {
+            Ok(()) => {}
+            Err(Error::Io(error)) => return Err(error),
+            Err(Error::Usage(message)) => writeln!(out, "error: {message}")?,
+            Err(error @ (Error::Sim(_) | Error::File { .. })) => writeln!(out, "error: {error}")?,
+        }
+        Ok(Control::Continue)
+    }
+
+    /// Executes the instruction at `cs:ip`, printing it like a trace line. `None` once the
+    /// program has finished.
+    pub fn step(&mut self, out: &mut impl Write) -> Result<Option<Instruction>, Error> {
+        if self.is_finished() {
+            writeln!(out, "the program has finished")?;
+            return Ok(None);
+        }
+        let before = self.cpu.clone();
+        let instruction = self.advance()?;
+        let changes = self.cpu.changes_since(&before);
+        sim::write_trace_line(out, self.formatter, &instruction, &changes)?;
+        self.write_console(out)?;
+        Ok(Some(instruction))
+    }
+
+    /// Prints what the program printed since last time, escaped so control characters show.
+    fn write_console(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let text = self.interrupts.services_mut().take_console();
+        if text.is_empty() {
+            return Ok(());
+        }
+        writeln!(
+            out,
+            "console: {}",
+            String::from_utf8_lossy(&text).escape_debug()
+        )
+    }
+
+    /// Moves on one instruction, replaying it from the history if it's there.
+    fn advance(&mut self) -> Result<Instruction, Error> {
+        let instruction = match self.history.get(self.position) {
+            Some(delta) => {
+                let instruction = self.cache.fetch(&self.cpu, &self.memory)?;
+                delta.apply(&mut self.cpu, &mut self.memory);
+                instruction
+            }
+            None => {
+                let before = self.cpu.clone();
+                self.memory.start_journal();
+                let result = sim::step(
+                    &mut self.cpu,
+                    &mut self.memory,
+                    &mut self.interrupts,
+                    &mut self.ports,
+                    &mut self.cache,
+                    self.processor,
+                );
+                let writes = self.memory.take_journal();
+                self.memory.stop_journal();
+                let instruction = result?.instruction;
+                self.history
+                    .push(Delta::between(&before, &self.cpu, writes));
+                instruction
+            }
+        };
+        self.position += 1;
+        if instruction.mnemonic == Mnemonic::Hlt || self.interrupts.exit_code().is_some() {
+            self.finished_at = Some(self.position);
+            self.interrupts.clear_exit();
+        }
+        Ok(instruction)
+    }
+
+    /// Undoes the last instruction, printing it with what undoing it changed. `None` at the
+    /// start of the history.
+    pub fn step_back(&mut self, out: &mut impl Write) -> Result<Option<Instruction>, Error> {
+        if self.position == 0 {
+            writeln!(out, "at the start of the history")?;
+            return Ok(None);
+        }
+        self.position -= 1;
+        let before = self.cpu.clone();
+        self.history[self.position].revert(&mut self.cpu, &mut self.memory);
+        let instruction = self.cache.fetch(&self.cpu, &self.memory)?;
+        let changes = self.cpu.changes_since(&before);
+        sim::write_trace_line(out, self.formatter, &instruction, &changes)?;
+        Ok(Some(instruction))
+    }
+
+    fn back_command(&mut self, arguments: &[&str], out: &mut impl Write) -> Result<(), Error> {
+        let count = match arguments {
+            [] => 1,
+            [count] => parse_number(count)?,
+            _ => return Err(usage("back [n]")),
+        };
+        for _ in 0..count {
+            if self.step_back(out)?.is_none() || self.position == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Undoes instructions until the next one to run matches a breakpoint, or there's nothing
+    /// left to undo.
+    pub fn reverse(&mut self, out: &mut impl Write) -> Result<(), Error> {
+        while self.position > 0 {
+            self.position -= 1;
+            self.history[self.position].revert(&mut self.cpu, &mut self.memory);
+            let instruction = self.cache.fetch(&self.cpu, &self.memory)?;
+            if let Some(index) = self.breakpoint_at(&instruction) {
+                return self.report_breakpoint(out, index, &instruction);
+            }
+        }
+        writeln!(out, "at the start of the history")?;
+        Ok(())
+    }
+
+    /// The [`fingerprint`] of the state before the first instruction in the history.
+    fn start_fingerprint(&self) -> u64 {
+        let (mut cpu, mut memory) = (self.cpu.clone(), self.memory.clone());
+        for delta in self.history[..self.position].iter().rev() {
+            delta.revert(&mut cpu, &mut memory);
+        }
+        fingerprint(&cpu, &memory)
+    }
+
+    fn record(&self, arguments: &[&str], out: &mut impl Write) -> Result<(), Error> {
+        let [path] = arguments else {
+            return Err(usage("record <file>"));
+        };
+        let failed = |error| Error::File {
+            path: path.to_string(),
+            error,
+        };
+        let mut file = BufWriter::new(File::create(path).map_err(failed)?);
+        write_deltas(&mut file, self.start_fingerprint(), &self.history)
+            .and_then(|()| file.flush())
+            .map_err(failed)?;
+        writeln!(
+            out,
+            "recorded {} instructions to {path}",
+            self.history.len()
+        )?;
+        Ok(())
+    }
+
+    fn step_command(&mut self, arguments: &[&str], out: &mut impl Write) -> Result<(), Error> {
+        let count = match arguments {
+            [] => 1,
+            [count] => parse_number(count)?,
+            _ => return Err(usage("step [n]")),
+        };
+        for _ in 0..count {
+            if self.step(out)?.is_none() || self.is_finished() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs until the next instruction matches a breakpoint, or the program finishes. The
+    /// instruction IP starts on never stops it, so continuing from a breakpoint moves on.
+    pub fn continue_running(&mut self, out: &mut impl Write) -> Result<(), Error> {
+        let result = self.run_to_breakpoint(out);
+        // what the program printed on the way, even when something went wrong
+        self.write_console(out)?;
+        result
+    }
+
+    fn run_to_breakpoint(&mut self, out: &mut impl Write) -> Result<(), Error> {
+        let mut first = true;
+        while !self.is_finished() {
+            if !first && !self.breakpoints.is_empty() {
+                let instruction = self.cache.fetch(&self.cpu, &self.memory)?;
+                if let Some(index) = self.breakpoint_at(&instruction) {
+                    return self.report_breakpoint(out, index, &instruction);
+                }
+            }
+            first = false;
+            self.advance()?;
+        }
+        writeln!(out, "the program has finished at ip {:#06x}", self.cpu.ip)?;
+        Ok(())
+    }
+
+    fn report_breakpoint(
+        &self,
+        out: &mut impl Write,
+        index: usize,
+        instruction: &Instruction,
+    ) -> Result<(), Error> {
+        writeln!(
+            out,
+            "breakpoint {} ({}) at {:#06x}: {}",
+            index + 1,
+            self.breakpoints[index],
+            self.cpu.ip,
+            self.formatter.format_instruction(instruction)
+        )?;
+        Ok(())
+    }
+
+    fn breakpoint_at(&self, instruction: &Instruction) -> Option<usize> {
+        let text = self.formatter.format_instruction(instruction);
+        self.breakpoints
+            .iter()
+            .position(|breakpoint| match breakpoint {
+                Breakpoint::Address(ip) => *ip == self.cpu.ip,
+                Breakpoint::Pattern(pattern) => text.contains(pattern.as_str()),
+            })
+    }
+
+    fn break_command(
+        &mut self,
+        line: &str,
+        arguments: &[&str],
+        out: &mut impl Write,
+    ) -> Result<(), Error> {
+        if arguments.is_empty() {
+            if self.breakpoints.is_empty() {
+                writeln!(out, "no breakpoints")?;
+            }
+            for (index, breakpoint) in self.breakpoints.iter().enumerate() {
+                writeln!(out, "{}: {breakpoint}", index + 1)?;
+            }
+            return Ok(());
+        }
+
+        // everything after the command, so patterns can have spaces in them
+        let text = line
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap()
+            .1
+            .trim();
+        let breakpoint = match parse_number(text) {
+            Ok(ip) => Breakpoint::Address(
+                u16::try_from(ip).map_err(|_| Error::Usage(format!("{text} isn't a 16-bit IP")))?,
+            ),
+            Err(_) => Breakpoint::Pattern(text.to_string()),
+        };
+        writeln!(
+            out,
+            "breakpoint {}: {breakpoint}",
+            self.breakpoints.len() + 1
+        )?;
+        self.breakpoints.push(breakpoint);
+        Ok(())
+    }
+
+    fn delete(&mut self, arguments: &[&str], out: &mut impl Write) -> Result<(), Error> {
+        let [number] = arguments else {
+            return Err(usage("delete <n>"));
+        };
+        let number = parse_number(number)?;
+        if number == 0 || number > self.breakpoints.len() {
+            return Err(Error::Usage(format!("there is no breakpoint {number}")));
+        }
+        let breakpoint = self.breakpoints.remove(number - 1);
+        writeln!(out, "deleted breakpoint {number}: {breakpoint}")?;
+        Ok(())
+    }
+
+    /// Every register, unlike the final dump, which skips the ones that are zero.
+    fn print_registers(&self, out: &mut impl Write) -> io::Result<()> {
+        let registers = TRACE_ORDER
+            .iter()
+            .map(|&register| (register.name(), self.cpu.word(register)));
+        let segments = SegmentRegister::ALL
+            .iter()
+            .map(|&segment| (segment.name(), self.cpu.segment(segment)));
+        for (name, value) in registers.chain(segments).chain([("ip", self.cpu.ip)]) {
+            writeln!(out, "{name:>8}: {value:#06x} ({value})")?;
+        }
+        writeln!(out, "{:>8}: {}", "flags", self.cpu.flags)
+    }
+
+    fn examine(&self, arguments: &[&str], out: &mut impl Write) -> Result<(), Error> {
+        let (address, count) = match arguments {
+            [address] => (address, 16),
+            [address, count] => (address, parse_number(count)?),
+            _ => return Err(usage("x <address> [count]")),
+        };
+        let start = self.parse_address(address)?;
+        let end = start.saturating_add(count).min(memory::SIZE);
+        for (index, row) in self.memory.bytes()[start..end].chunks(16).enumerate() {
+            let bytes: Vec<String> = row.iter().map(|byte| format!("{byte:02x}")).collect();
+            writeln!(out, "{:05x}: {}", start + index * 16, bytes.join(" "))?;
+        }
+        Ok(())
+    }
+
+    fn write_memory(&mut self, arguments: &[&str]) -> Result<(), Error> {
+        let [address, bytes @ ..] = arguments else {
+            return Err(usage("write <address> <byte>.."));
+        };
+        if bytes.is_empty() {
+            return Err(usage("write <address> <byte>.."));
+        }
+        let start = self.parse_address(address)?;
+        let bytes = bytes
+            .iter()
+            .map(|byte| {
+                u8::try_from(parse_number(byte)?)
+                    .map_err(|_| Error::Usage(format!("{byte} doesn't fit in a byte")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // physical addresses wrap at 1 MB like everything else
+        for (index, byte) in bytes.into_iter().enumerate() {
+            self.memory.store((start + index) % memory::SIZE, byte);
+        }
+        self.forget_future();
+        Ok(())
+    }
+
+    /// After an edit the instructions stepped back over might not do the same thing again.
+    fn forget_future(&mut self) {
+        self.history.truncate(self.position);
+        self.finished_at = self.finished_at.filter(|&end| end <= self.position);
+    }
+
+    fn set(&mut self, arguments: &[&str]) -> Result<(), Error> {
+        let [name, value] = arguments else {
+            return Err(usage("set <register> <value>"));
+        };
+        if *name == "flags" {
+            self.cpu.flags = parse_flags(value)?;
+            self.forget_future();
+            return Ok(());
+        }
+        let value = parse_number(value)?;
+        let value = u16::try_from(value)
+            .map_err(|_| Error::Usage(format!("{value:#x} doesn't fit in 16 bits")))?;
+
+        if *name == "ip" {
+            self.cpu.ip = value;
+        } else if let Some(segment) = segment_named(name) {
+            self.cpu.set_segment(segment, value);
+        } else if let Some(register) = register_named(name) {
+            if matches!(register, Register::Byte(_)) && value > 0xFF {
+                return Err(Error::Usage(format!("{value:#x} doesn't fit in {name}")));
+            }
+            self.cpu.set_register(register, value);
+        } else {
+            return Err(Error::Usage(format!("unknown register `{name}`")));
+        }
+        self.forget_future();
+        Ok(())
+    }
+
+    /// Lists instructions either side of IP. The ones before are found by decoding the program
+    /// from the start, the ones after by decoding from IP, so a jump into the middle of an
+    /// instruction still shows what will run.
+    fn disassemble(&self, arguments: &[&str], out: &mut impl Write) -> Result<(), Error> {
+        let context = match arguments {
+            [] => 5,
+            [count] => parse_number(count)?,
+            _ => return Err(usage("disasm [n]")),
+        };
+        let code = &self.memory.bytes()[self.code.clone()];
+        let current = self.code_offset();
+
+        let before: Vec<Instruction> = Decoder::new(code)
+            .map_while(Result::ok)
+            .take_while(|instruction| current.is_some_and(|ip| instruction.offset < ip))
+            .filter(|instruction| {
+                current.is_some_and(|ip| instruction.offset + instruction.length <= ip)
+            })
+            .collect();
+        for instruction in &before[before.len().saturating_sub(context)..] {
+            self.write_disassembly_line(out, instruction, false)?;
+        }
+
+        match current {
+            Some(offset) => {
+                for (index, instruction) in Decoder::starting_at(code, offset)
+                    .take(context + 1)
+                    .enumerate()
+                {
+                    match instruction {
+                        Ok(instruction) => {
+                            self.write_disassembly_line(out, &instruction, index == 0)?
+                        }
+                        Err(error) => {
+                            writeln!(out, "error: {error}")?;
+                            break;
+                        }
+                    }
+                }
+            }
+            None => writeln!(out, "ip {:#06x} is outside the program", self.cpu.ip)?,
+        }
+        Ok(())
+    }
+
+    /// Where IP is within the loaded program, if it's in it at all.
+    fn code_offset(&self) -> Option<usize> {
+        let address = physical_address(self.cpu.segment(SegmentRegister::CS), self.cpu.ip);
+        self.code
+            .contains(&address)
+            .then(|| address - self.code.start)
+    }
+
+    fn write_disassembly_line(
+        &self,
+        out: &mut impl Write,
+        instruction: &Instruction,
+        current: bool,
+    ) -> io::Result<()> {
+        let marker = if current { "=>" } else { "  " };
+        let ip = self.origin.wrapping_add(instruction.offset as u16);
+        writeln!(
+            out,
+            "{marker} {ip:04x}: {}",
+            self.formatter.format_instruction(instruction)
+        )
+    }
+
+    /// A physical address, or `segment:offset` with the segment as a register name or number.
+    fn parse_address(&self, text: &str) -> Result<usize, Error> {
+        let Some((segment, offset)) = text.split_once(':') else {
+            let address = parse_number(text)?;
+            if address >= memory::SIZE {
+                return Err(Error::Usage(format!("{text} is past the end of memory")));
+            }
+            return Ok(address);
+        };
+        let segment = match segment_named(segment) {
+            Some(register) => self.cpu.segment(register),
+            None => parse_word(segment)?,
+        };
+        Ok(physical_address(segment, parse_word(offset)?))
+    }
+}
+
+/// Why a command didn't work.
+#[derive(Debug)]
+pub enum Error {
+    /// The command was typed wrong, the message says how.
+    Usage(String),
+    Sim(SimError),
+    /// A file a command names couldn't be written.
+    File {
+        path: String,
+        error: io::Error,
+    },
+    /// Writing to the output failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(message) => write!(f, "{message}"),
+            Self::Sim(error) => error.fmt(f),
+            Self::File { path, error } => write!(f, "failed to write {path}: {error}"),
+            Self::Io(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<SimError> for Error {
+    fn from(error: SimError) -> Self {
+        Self::Sim(error)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+fn usage(syntax: &str) -> Error {
+    Error::Usage(format!("usage: {syntax}"))
+}
+
+fn parse_number(text: &str) -> Result<usize, Error> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| Error::Usage(format!("`{text}` is not a valid number")))
+}
+
+fn parse_word(text: &str) -> Result<u16, Error> {
+    u16::try_from(parse_number(text)?)
+        .map_err(|_| Error::Usage(format!("{text} doesn't fit in 16 bits")))
+}
+
+fn parse_flags(letters: &str) -> Result<FlagSet, Error> {
+    // `-` clears them all
+    let letters = letters.trim_start_matches('-');
+    letters.chars().try_fold(FlagSet::EMPTY, |flags, letter| {
+        Flag::ALL
+            .into_iter()
+            .find(|flag| flag.letter() == letter.to_ascii_uppercase())
+            .map(|flag| flags.with(flag))
+            .ok_or_else(|| Error::Usage(format!("`{letter}` isn't a flag, use CPAZSTIDO")))
+    })
+}
+
+fn segment_named(name: &str) -> Option<SegmentRegister> {
+    SegmentRegister::ALL
+        .into_iter()
+        .find(|segment| segment.name() == name)
+}
+
+fn register_named(name: &str) -> Option<Register> {
+    let words = TRACE_ORDER.into_iter().map(Register::Word);
+    let bytes = RegisterByteOp::ALL.into_iter().map(Register::Byte);
+    words.chain(bytes).find(|register| register.name() == name)
+}
@@ -0,0 +1,405 @@
+use super::*;
+use crate::decoder::RegisterWordOp;
+use crate::ports::{PIT_PORTS, Pit, TIMER_VECTOR};
+use crate::recording::read_deltas;
+use crate::syntax::Nasm;
+use rstest::rstest;
+
+/// mov cx, 3 / dec cx / jnz $-1 / hlt
+const COUNTDOWN: &[u8] = &[0xB9, 0x03, 0x00, 0x49, 0x75, 0xFD, 0xF4];
+
+fn session(debugger: &mut Debugger, commands: &str) -> String {
+    let mut out = vec![];
+    debugger.repl(commands.as_bytes(), &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn run_commands(data: &[u8], commands: &str) -> (String, Cpu, Memory) {
+    let formatter = Nasm::default();
+    let mut debugger = Debugger::new(data, Cpu::new(), &formatter);
+    let output = session(&mut debugger, commands);
+    (output, debugger.cpu, debugger.memory)
+}
+
+#[test]
+fn test_steps_print_trace_lines() {
+    let (output, cpu, _) = run_commands(COUNTDOWN, "step\nstep 2\n");
+
+    assert_eq!(
+        output,
+        "\
+> mov cx, 3 ; cx:0x0->0x3 ip:0x0->0x3
+> dec cx ; cx:0x3->0x2 ip:0x3->0x4
+jne $-1 ; ip:0x4->0x3
+> \n"
+    );
+    assert_eq!(cpu.word(RegisterWordOp::CX), 2);
+}
+
+#[test]
+fn test_stepping_stops_after_hlt() {
+    let (output, _, _) = run_commands(COUNTDOWN, "s 100\ns\n");
+
+    assert!(output.ends_with("hlt ; ip:0x6->0x7\n> the program has finished\n> \n"));
+}
+
+#[rstest]
+#[case::address("b 4\nc\n", "breakpoint 1 (ip 0x0004) at 0x0004: jne $-1", 2)]
+#[case::pattern("b dec cx\nc\n", "breakpoint 1 (`dec cx`) at 0x0003: dec cx", 3)]
+#[case::moves_on_from_the_current_one(
+    "b 4\nc\nc\n",
+    "breakpoint 1 (ip 0x0004) at 0x0004: jne $-1",
+    1
+)]
+fn test_continue_stops_at_breakpoints(
+    #[case] commands: &str,
+    #[case] expected: &str,
+    #[case] cx: u16,
+) {
+    let (output, cpu, _) = run_commands(COUNTDOWN, commands);
+
+    let stops = commands.matches("c\n").count();
+    assert_eq!(output.matches(expected).count(), stops, "{output}");
+    assert_eq!(cpu.word(RegisterWordOp::CX), cx);
+}
+
+#[test]
+fn test_continue_without_breakpoints_runs_to_the_end() {
+    let (output, cpu, _) = run_commands(COUNTDOWN, "continue\n");
+
+    assert_eq!(output, "> the program has finished at ip 0x0007\n> \n");
+    assert_eq!(cpu.word(RegisterWordOp::CX), 0);
+}
+
+#[test]
+fn test_lists_and_deletes_breakpoints() {
+    let (output, _, _) = run_commands(COUNTDOWN, "b 0x4\nb hlt\nb\ndelete 1\nb\nd 5\n");
+
+    assert_eq!(
+        output,
+        "\
+> breakpoint 1: ip 0x0004
+> breakpoint 2: `hlt`
+> 1: ip 0x0004
+2: `hlt`
+> deleted breakpoint 1: ip 0x0004
+> 1: `hlt`
+> error: there is no breakpoint 5
+> \n"
+    );
+}
+
+#[test]
+fn test_prints_every_register() {
+    let (output, _, _) = run_commands(COUNTDOWN, "s\nset flags ZS\nregs\n");
+
+    assert!(output.contains("      ax: 0x0000 (0)\n"), "{output}");
+    assert!(output.contains("      cx: 0x0003 (3)\n"), "{output}");
+    assert!(output.contains("      cs: 0x0000 (0)\n"), "{output}");
+    assert!(output.contains("      ip: 0x0003 (3)\n"), "{output}");
+    assert!(output.contains("   flags: ZS\n"), "{output}");
+}
+
+#[test]
+fn test_examines_and_writes_memory() {
+    let (output, _, memory) = run_commands(
+        COUNTDOWN,
+        "set ds 0x100\nwrite ds:2 0xAB 1 2\nx 0x1000 4\nx 0 20\n",
+    );
+
+    assert_eq!(memory.bytes()[0x1002..0x1005], [0xAB, 1, 2]);
+    assert_eq!(
+        output,
+        "\
+> > > 01000: 00 00 ab 01
+> 00000: b9 03 00 49 75 fd f4 00 00 00 00 00 00 00 00 00
+00010: 00 00 00 00
+> \n"
+    );
+}
+
+#[test]
+fn test_set_changes_registers() {
+    let (output, cpu, _) = run_commands(
+        COUNTDOWN,
+        "set ah 0x12\nset al 0x34\nset bp 7\nset es 0xB800\nset ip 3\nset al 0x100\nset zz 1\n",
+    );
+
+    assert_eq!(cpu.word(RegisterWordOp::AX), 0x1234);
+    assert_eq!(cpu.word(RegisterWordOp::BP), 7);
+    assert_eq!(cpu.segment(SegmentRegister::ES), 0xB800);
+    assert_eq!(cpu.ip, 3);
+    assert!(
+        output.contains("error: 0x100 doesn't fit in al"),
+        "{output}"
+    );
+    assert!(output.contains("error: unknown register `zz`"), "{output}");
+}
+
+#[test]
+fn test_disassembles_around_ip() {
+    let (output, _, _) = run_commands(COUNTDOWN, "s 2\nu 1\nu\n");
+
+    assert!(
+        output.contains(
+            "\
+>    0003: dec cx
+=> 0004: jne $-1
+   0006: hlt
+"
+        ),
+        "{output}"
+    );
+    assert!(output.ends_with(
+        "\
+>    0000: mov cx, 3
+   0003: dec cx
+=> 0004: jne $-1
+   0006: hlt
+> \n"
+    ));
+}
+
+#[test]
+fn test_disassembles_from_ip_in_the_middle_of_an_instruction() {
+    // the 0x49 inside the first instruction's immediate decodes as `dec cx` from there
+    let (output, _, _) = run_commands(&[0xB8, 0x49, 0x00, 0xF4], "set ip 1\nu 1\n");
+
+    assert_eq!(output, "> > => 0001: dec cx\n   0002: add ah, dh\n> \n");
+}
+
+#[test]
+fn test_reports_mistakes_and_keeps_going() {
+    let (output, cpu, _) = run_commands(COUNTDOWN, "bogus\nstep x\nx\n\ns\nquit\ns\n");
+
+    assert_eq!(
+        output,
+        "\
+> error: unknown command `bogus`, try `help`
+> error: `x` is not a valid number
+> error: usage: x <address> [count]
+> > mov cx, 3 ; cx:0x0->0x3 ip:0x0->0x3
+> "
+    );
+    assert_eq!(cpu.ip, 3);
+}
+
+#[test]
+fn test_short_forms_in_the_help_do_what_their_commands_do() {
+    let pairs: Vec<(&str, &str)> = HELP
+        .lines()
+        .filter_map(|line| line.split_once(", "))
+        .filter_map(|(short, rest)| Some((short, rest.split_whitespace().next()?)))
+        .filter(|(short, _)| !short.contains(' '))
+        .collect();
+    assert_eq!(pairs.len(), 12, "{pairs:?}");
+
+    for (short, long) in pairs {
+        let (shortened, _, _) = run_commands(COUNTDOWN, &format!("s\n{short}\n"));
+        let (spelled_out, _, _) = run_commands(COUNTDOWN, &format!("s\n{long}\n"));
+        assert!(
+            !shortened.contains("unknown command"),
+            "{short}: {shortened}"
+        );
+        assert_eq!(shortened, spelled_out, "{short} and {long}");
+    }
+}
+
+#[test]
+fn test_reports_simulation_errors() {
+    // daa isn't simulated
+    let (output, cpu, _) = run_commands(&[0x27], "s\nc\n");
+
+    assert_eq!(output.matches("error: ").count(), 2, "{output}");
+    assert_eq!(cpu.ip, 0);
+}
+
+#[test]
+fn test_steps_back_and_forward_again() {
+    let (output, cpu, _) = run_commands(COUNTDOWN, "s 3\nback 2\ns\nbs 5\n");
+
+    assert_eq!(
+        output,
+        "\
+> mov cx, 3 ; cx:0x0->0x3 ip:0x0->0x3
+dec cx ; cx:0x3->0x2 ip:0x3->0x4
+jne $-1 ; ip:0x4->0x3
+> jne $-1 ; ip:0x3->0x4
+dec cx ; cx:0x2->0x3 ip:0x4->0x3
+> dec cx ; cx:0x3->0x2 ip:0x3->0x4
+> dec cx ; cx:0x2->0x3 ip:0x4->0x3
+mov cx, 3 ; cx:0x3->0x0 ip:0x3->0x0
+> \n"
+    );
+    assert_eq!(cpu, Cpu::new());
+}
+
+#[test]
+fn test_stepping_back_undoes_memory_writes_and_hlt() {
+    // mov byte [0x10], 7 / hlt
+    let (output, cpu, memory) = run_commands(
+        &[0xC6, 0x06, 0x10, 0x00, 0x07, 0xF4],
+        "c\nback 2\nback\nx 0x10 1\n",
+    );
+
+    assert!(
+        output.ends_with("> at the start of the history\n> 00010: 00\n> \n"),
+        "{output}"
+    );
+    assert_eq!(memory.bytes()[0x10], 0);
+    assert_eq!(cpu.ip, 0);
+}
+
+#[test]
+fn test_reverse_stops_at_breakpoints() {
+    let (output, cpu, _) = run_commands(COUNTDOWN, "c\nb hlt\nb 4\nrc\nrc\nb\nd 2\nrc\n");
+
+    assert!(
+        output.contains(
+            "\
+> breakpoint 1 (`hlt`) at 0x0006: hlt
+> breakpoint 2 (ip 0x0004) at 0x0004: jne $-1
+"
+        ),
+        "{output}"
+    );
+    assert!(
+        output.ends_with("> at the start of the history\n> \n"),
+        "{output}"
+    );
+    assert_eq!(cpu, Cpu::new());
+}
+
+#[test]
+fn test_edits_after_stepping_back_forget_what_came_after() {
+    let formatter = Nasm::default();
+    let mut debugger = Debugger::new(COUNTDOWN, Cpu::new(), &formatter);
+    session(&mut debugger, "s 3\nback 2\n");
+    assert_eq!(debugger.history().len(), 3);
+
+    let output = session(&mut debugger, "set cx 9\ns\n");
+
+    assert_eq!(debugger.history().len(), 2);
+    assert_eq!(output, "> > dec cx ; cx:0x9->0x8 ip:0x3->0x4\n> \n");
+}
+
+#[test]
+fn test_continuing_fetches_through_the_cache() {
+    let formatter = Nasm::default();
+    let mut debugger = Debugger::new(COUNTDOWN, Cpu::new(), &formatter);
+    session(&mut debugger, "b 0x99\nc\n");
+
+    // 8 instructions, each fetched to check for a breakpoint but the first and again to run
+    // it, of which only the 4 different ones are decoded
+    assert_eq!(debugger.history().len(), 8);
+    assert_eq!((debugger.cache.misses(), debugger.cache.hits()), (4, 11));
+}
+
+#[test]
+fn test_replays_a_recording() {
+    let formatter = Nasm::default();
+    let mut recorded = Debugger::new(COUNTDOWN, Cpu::new(), &formatter);
+    session(&mut recorded, "c\n");
+
+    // a recording that says cx went to 5 rather than 3 is replayed as it is
+    let mut deltas = recorded.history().to_vec();
+    deltas[0].changes[0].after = 5;
+    let mut debugger = Debugger::new(COUNTDOWN, Cpu::new(), &formatter);
+    let start = fingerprint(&debugger.cpu, &debugger.memory);
+    debugger.replay(Recording { start, deltas }).unwrap();
+    let output = session(&mut debugger, "s\nbs\ns 2\n");
+
+    assert_eq!(
+        output,
+        "\
+> mov cx, 3 ; cx:0x0->0x5 ip:0x0->0x3
+> mov cx, 3 ; cx:0x5->0x0 ip:0x3->0x0
+> mov cx, 3 ; cx:0x0->0x5 ip:0x0->0x3
+dec cx ; cx:0x5->0x2 ip:0x3->0x4
+> \n"
+    );
+}
+
+#[test]
+fn test_records_the_history_to_a_file() {
+    let path = std::env::temp_dir().join(format!("debugger-record-{}.bin", std::process::id()));
+    let (output, _, _) = run_commands(COUNTDOWN, &format!("s 2\nrecord {}\n", path.display()));
+
+    let recording = read_deltas(&mut std::fs::read(&path).unwrap().as_slice()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.contains("recorded 2 instructions to "), "{output}");
+    assert_eq!(recording.deltas.len(), 2);
+    // from where it started, not where it got to
+    let formatter = Nasm::default();
+    let start = Debugger::new(COUNTDOWN, Cpu::new(), &formatter);
+    assert_eq!(recording.start, fingerprint(&start.cpu, &start.memory));
+}
+
+#[test]
+fn test_replays_only_onto_the_state_it_was_recorded_from() {
+    let formatter = Nasm::default();
+    let mut recorded = Debugger::new(COUNTDOWN, Cpu::new(), &formatter);
+    session(&mut recorded, "c\n");
+    let recording = Recording {
+        start: fingerprint(&Cpu::new(), &Memory::new()),
+        deltas: recorded.history().to_vec(),
+    };
+
+    let mut debugger = Debugger::new(COUNTDOWN, Cpu::new(), &formatter);
+    assert!(matches!(
+        debugger.replay(recording),
+        Err(RecordingError::DifferentStart)
+    ));
+    assert!(debugger.history().is_empty());
+}
+
+#[test]
+fn test_shows_console_output_and_stops_at_exit() {
+    // mov ah, 2 / mov dl, 'A' / int 0x21 / mov ax, 0x4c00 / int 0x21 / nop
+    let program = [
+        0xB4, 0x02, 0xB2, 0x41, 0xCD, 0x21, 0xB8, 0x00, 0x4C, 0xCD, 0x21, 0x90,
+    ];
+    let (output, cpu, _) = run_commands(&program, "s 3\nc\ns\n");
+
+    assert_eq!(
+        output,
+        "\
+> mov ah, 2 ; ax:0x0->0x200 ip:0x0->0x2
+mov dl, 65 ; dx:0x0->0x41 ip:0x2->0x4
+int 33 ; ax:0x200->0x241 ip:0x4->0x6
+console: A
+> the program has finished at ip 0x000b
+> the program has finished
+> \n"
+    );
+    assert_eq!(cpu.ip, 0xB);
+}
+
+#[test]
+fn test_timer_interrupts_while_debugging() {
+    let formatter = Nasm::default();
+    let mut cpu = Cpu::new();
+    cpu.set_segment(SegmentRegister::CS, 0x100);
+    cpu.set_segment(SegmentRegister::SS, 0x200);
+    cpu.set_word(RegisterWordOp::SP, 0x100);
+    // mov al, 0x34 / out 0x43, al / mov al, 100 / out 0x40, al / xor al, al / out 0x40, al
+    // sti / mov cx, 100 / loop $ / hlt / nop / nop / inc bx / iret
+    let program = [
+        0xB0, 0x34, 0xE6, 0x43, 0xB0, 0x64, 0xE6, 0x40, 0x30, 0xC0, 0xE6, 0x40, 0xFB, 0xB9, 0x64,
+        0x00, 0xE2, 0xFE, 0xF4, 0x90, 0x90, 0x43, 0xCF,
+    ];
+    let mut debugger = Debugger::new(&program, cpu, &formatter);
+    debugger.ports.attach(PIT_PORTS, Pit::new());
+    debugger
+        .memory
+        .write_word(0, u16::from(TIMER_VECTOR) * 4, 0x15);
+    debugger
+        .memory
+        .write_word(0, u16::from(TIMER_VECTOR) * 4 + 2, 0x100);
+
+    session(&mut debugger, "c\n");
+
+    // every 400 clocks, as when simulating
+    assert_eq!(debugger.cpu.word(RegisterWordOp::BX), 5);
+    assert_ne!(debugger.cpu.cycles, 0);
+}
@@ -0,0 +1,112 @@
+use crate::cpu::Cpu;
+use crate::decoder::{Instruction, SegmentRegister};
+use crate::memory::{Memory, physical_address};
+use crate::sim::{self, SimError};
+
+#[cfg(test)]
+mod decode_cache_tests;
+
+/// Slots in a [`DecodeCache::new`] cache, a few times more than the hot loops of the programs
+/// this runs.
+pub const SLOTS: usize = 4096;
+
+/// Instructions already decoded from memory, by the physical address of their first byte, so
+/// code that runs again isn't decoded again. Each slot holds one instruction, the one at an
+/// address that's a multiple of the slot count away replaces it.
+///
+/// An instruction is only used again while there have been no stores to the
+/// [lines](crate::memory::LINE) of memory its bytes are in, so code that patches itself runs
+/// what it wrote.
+#[derive(Debug, Clone)]
+pub struct DecodeCache {
+    slots: Vec<Option<Entry>>,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    address: usize,
+    instruction: Instruction,
+    /// [`Memory::generation`] of the first and last byte's lines when it was decoded.
+    generations: [u64; 2],
+}
+
+impl DecodeCache {
+    pub fn new() -> Self {
+        Self::with_slots(SLOTS)
+    }
+
+    pub fn with_slots(slots: usize) -> Self {
+        Self {
+            slots: vec![None; slots],
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Keeps nothing, so every fetch decodes.
+    pub fn disabled() -> Self {
+        Self::with_slots(0)
+    }
+
+    /// Like [`sim::fetch`], from the cache when it holds the instruction at `cs:ip`.
+    pub fn fetch(&mut self, cpu: &Cpu, memory: &Memory) -> Result<Instruction, SimError> {
+        if self.slots.is_empty() {
+            return sim::fetch(cpu, memory);
+        }
+        let cs = cpu.segment(SegmentRegister::CS);
+        let address = physical_address(cs, cpu.ip);
+        let slot = address % self.slots.len();
+        if let Some(entry) = &self.slots[slot]
+            && entry.address == address
+            && entry.generations == generations(memory, address, entry.instruction.length)
+        {
+            self.hits += 1;
+            return Ok(Instruction {
+                offset: usize::from(cpu.ip),
+                ..entry.instruction
+            });
+        }
+
+        self.misses += 1;
+        let instruction = sim::fetch(cpu, memory)?;
+        let length = instruction.length;
+        let last = physical_address(cs, cpu.ip.wrapping_add(length as u16 - 1));
+        // the lines of the first and last byte only cover instructions that don't wrap round
+        // the end of the segment or of memory
+        self.slots[slot] = (last == address + length - 1).then(|| Entry {
+            address,
+            instruction,
+            generations: generations(memory, address, length),
+        });
+        Ok(instruction)
+    }
+
+    /// Fetches that were answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Fetches that had to decode.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.fill(None);
+    }
+}
+
+impl Default for DecodeCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn generations(memory: &Memory, address: usize, length: usize) -> [u64; 2] {
+    [
+        memory.generation(address),
+        memory.generation(address + length - 1),
+    ]
+}
@@ -0,0 +1,157 @@
+use super::*;
+use crate::decoder::{Mnemonic, Operand, RegisterWordOp};
+use crate::memory::LINE;
+use crate::sim::{RunOptions, run};
+use crate::syntax::Nasm;
+use rstest::rstest;
+
+/// mov cx, 2 / mov ax, 1 / mov byte [4], 7 / loop $-8 / hlt, the second time round the `mov ax`
+/// moves 7.
+const PATCHES_IMMEDIATE: &[u8] = &[
+    0xB9, 0x02, 0x00, 0xB8, 0x01, 0x00, 0xC6, 0x06, 0x04, 0x00, 0x07, 0xE2, 0xF6, 0xF4,
+];
+
+/// mov cx, 2 / inc ax / mov byte [3], 0x48 / loop $-6 / hlt, the second time round the `inc ax`
+/// is `dec ax`.
+const PATCHES_OPCODE: &[u8] = &[
+    0xB9, 0x02, 0x00, 0x40, 0xC6, 0x06, 0x03, 0x00, 0x48, 0xE2, 0xF8, 0xF4,
+];
+
+fn run_with(data: &[u8], cache: &mut DecodeCache) -> (String, Cpu) {
+    let mut cpu = Cpu::new();
+    let mut trace = vec![];
+    run(
+        data,
+        &mut cpu,
+        &mut Memory::new(),
+        RunOptions {
+            cache: Some(cache),
+            ..RunOptions::default()
+        },
+        &Nasm::default(),
+        &mut trace,
+    )
+    .unwrap();
+    (String::from_utf8(trace).unwrap(), cpu)
+}
+
+#[test]
+fn test_code_that_runs_again_comes_from_the_cache() {
+    // mov cx, 3 / dec cx / jnz $-1 / hlt
+    let mut cache = DecodeCache::new();
+    run_with(&[0xB9, 0x03, 0x00, 0x49, 0x75, 0xFD, 0xF4], &mut cache);
+
+    assert_eq!(cache.misses(), 4);
+    assert_eq!(cache.hits(), 4);
+}
+
+#[rstest]
+#[case::immediate(PATCHES_IMMEDIATE, 7)]
+#[case::opcode(PATCHES_OPCODE, 0)]
+fn test_runs_the_code_a_program_patched_in(#[case] data: &[u8], #[case] ax: u16) {
+    let (expected, _) = run_with(data, &mut DecodeCache::disabled());
+
+    for mut cache in [
+        DecodeCache::new(),
+        DecodeCache::with_slots(1),
+        DecodeCache::with_slots(3),
+    ] {
+        let (trace, cpu) = run_with(data, &mut cache);
+        assert_eq!(trace, expected);
+        assert_eq!(cpu.word(RegisterWordOp::AX), ax);
+    }
+}
+
+#[test]
+fn test_patched_instruction_is_decoded_again() {
+    let (trace, _) = run_with(PATCHES_OPCODE, &mut DecodeCache::new());
+
+    let lines: Vec<_> = trace.lines().collect();
+    assert!(lines[1].starts_with("inc ax ;"), "{trace}");
+    assert!(lines[4].starts_with("dec ax ;"), "{trace}");
+}
+
+#[test]
+fn test_only_stores_to_its_lines_throw_an_instruction_out() {
+    let mut memory = Memory::new();
+    memory.load(0, 0x10, &[0xB8, 0x34, 0x12]);
+    let mut cpu = Cpu::new();
+    cpu.ip = 0x10;
+    let mut cache = DecodeCache::new();
+
+    cache.fetch(&cpu, &memory).unwrap();
+    memory.write_byte(0, LINE as u16, 0xFF);
+    cache.fetch(&cpu, &memory).unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+    // the same value still counts as a store
+    memory.write_byte(0, 0x3F, 0);
+    cache.fetch(&cpu, &memory).unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (1, 2));
+
+    cache.clear();
+    cache.fetch(&cpu, &memory).unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (1, 3));
+}
+
+#[test]
+fn test_instructions_across_a_line_boundary_watch_both_lines() {
+    let mut memory = Memory::new();
+    let start = LINE as u16 - 1;
+    memory.load(0, start, &[0xB8, 0x34, 0x12]);
+    let mut cpu = Cpu::new();
+    cpu.ip = start;
+    let mut cache = DecodeCache::new();
+
+    cache.fetch(&cpu, &memory).unwrap();
+    memory.write_byte(0, start + 2, 0x56);
+    let instruction = cache.fetch(&cpu, &memory).unwrap();
+
+    assert_eq!(cache.misses(), 2);
+    assert_eq!(instruction.source, Some(Operand::Immediate(0x5634)));
+}
+
+#[test]
+fn test_same_address_through_another_segment_has_its_own_offset() {
+    let mut memory = Memory::new();
+    memory.load(0, 0x110, &[0x90]);
+    let mut cache = DecodeCache::new();
+    let mut cpu = Cpu::new();
+    cpu.ip = 0x110;
+    cache.fetch(&cpu, &memory).unwrap();
+
+    cpu.set_segment(SegmentRegister::CS, 0x10);
+    cpu.ip = 0x10;
+    let instruction = cache.fetch(&cpu, &memory).unwrap();
+
+    assert_eq!(cache.hits(), 1);
+    assert_eq!(instruction.mnemonic, Mnemonic::Nop);
+    assert_eq!(instruction.offset, 0x10);
+}
+
+#[test]
+fn test_instructions_wrapping_round_the_segment_are_not_kept() {
+    let mut memory = Memory::new();
+    memory.load(0x100, 0xFFFF, &[0xB8, 0x34, 0x12]);
+    let mut cpu = Cpu::new();
+    cpu.set_segment(SegmentRegister::CS, 0x100);
+    cpu.ip = 0xFFFF;
+    let mut cache = DecodeCache::new();
+
+    for _ in 0..2 {
+        let instruction = cache.fetch(&cpu, &memory).unwrap();
+        assert_eq!(instruction.source, Some(Operand::Immediate(0x1234)));
+    }
+    assert_eq!(cache.misses(), 2);
+}
+
+#[test]
+fn test_decode_errors_are_not_kept() {
+    let mut memory = Memory::new();
+    memory.load(0, 0, &[0x0F]);
+    let mut cache = DecodeCache::new();
+
+    assert!(cache.fetch(&Cpu::new(), &memory).is_err());
+    memory.load(0, 0, &[0x90]);
+    assert!(cache.fetch(&Cpu::new(), &memory).is_ok());
+}
@@ -0,0 +1,1477 @@
+use std::fmt;
+
+#[cfg(test)]
+mod decoder_tests;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovInstructionType {
+    RegisterOrMemoryToOrFromRegister,  // opcode -> 0b100010xx
+    ImmediateToRegisterOrMemory,       // opcode -> 0b1100011x
+    ImmediateToRegister,               // opcode -> 0b1011xxxx
+    MemoryToAccumulator,               // opcode -> 0b1010000x
+    AccumulatorToMemory,               // opcode -> 0b1010001x
+    RegisterOrMemoryToSegmentRegister, // opcode -> 0b10001110
+    SegmentRegisterToRegisterOrMemory, // opcode -> 0b10001100
+}
+
+impl MovInstructionType {
+    // masks
+    const REG_MEM_MASK: u8 = 0xFC; // 11111100
+    const ACC_MEM_MASK: u8 = 0xFE; // 11111110
+    const IMM_REG_MASK: u8 = 0xF0; // 11110000
+
+    /*
+        TODO: add extra benchmark to see if putting the opcode directly in the MovInstructionType
+        enum has any performance benefits in lieu of using constants here
+    */
+    // opcode patterns
+    const REG_MEM_PATTERN: u8 = 0x88; // 10001000
+    const MEM_ACC_PATTERN: u8 = 0xA0; // 10100000
+    const ACC_MEM_PATTERN: u8 = 0xA2; // 10100010
+    const IMM_REG_PATTERN: u8 = 0xB0; // 10110000
+    const IMM_MEM_PATTERN: u8 = 0xC6; // 11000110
+    const MEM_SEG_PATTERN: u8 = 0x8E; // 10001110
+    const SEG_MEM_PATTERN: u8 = 0x8C; // 10001100
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            b if (b & Self::REG_MEM_MASK) == Self::REG_MEM_PATTERN => {
+                Some(Self::RegisterOrMemoryToOrFromRegister)
+            }
+
+            b if (b & Self::ACC_MEM_MASK) == Self::MEM_ACC_PATTERN => {
+                Some(Self::MemoryToAccumulator)
+            }
+
+            b if (b & Self::ACC_MEM_MASK) == Self::ACC_MEM_PATTERN => {
+                Some(Self::AccumulatorToMemory)
+            }
+
+            b if (b & Self::IMM_REG_MASK) == Self::IMM_REG_PATTERN => {
+                Some(Self::ImmediateToRegister)
+            }
+
+            b if (b & Self::ACC_MEM_MASK) == Self::IMM_MEM_PATTERN => {
+                Some(Self::ImmediateToRegisterOrMemory)
+            }
+
+            b if (b & Self::ACC_MEM_MASK) == Self::MEM_SEG_PATTERN => {
+                Some(Self::RegisterOrMemoryToSegmentRegister)
+            }
+
+            b if (b & Self::ACC_MEM_MASK) == Self::SEG_MEM_PATTERN => {
+                Some(Self::SegmentRegisterToRegisterOrMemory)
+            }
+
+            _ => None,
+        }
+    }
+
+    pub fn find_instruction(byte: u8) -> Self {
+        Self::from_byte(byte)
+            .unwrap_or_else(|| panic!("Unable to determine instruction for byte {:08b}", byte))
+    }
+}
+
+// 6 bits are opcode (mov), 2 bits (d, w)
+// second byte 2 bits (mod), 3 (reg) 3 (R/M)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModEncoding {
+    /// no displacement (except for the direct address r/m = 110)
+    MemMode = 0b00,
+    /// 8-bit displacement, sign extended to 16 bits
+    MemMode8B = 0b01,
+    /// 16-bit displacement
+    MemMode16B = 0b10,
+    RegisterMode = 0b11,
+}
+
+impl ModEncoding {
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            x if x == ModEncoding::MemMode as u8 => Some(Self::MemMode),
+            x if x == ModEncoding::MemMode8B as u8 => Some(Self::MemMode8B),
+            x if x == ModEncoding::MemMode16B as u8 => Some(Self::MemMode16B),
+            x if x == ModEncoding::RegisterMode as u8 => Some(Self::RegisterMode),
+            _ => None,
+        }
+    }
+}
+
+// w0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegisterByteOp {
+    AL = 0b000,
+    CL = 0b001,
+    DL = 0b010,
+    BL = 0b011,
+    AH = 0b100,
+    CH = 0b101,
+    DH = 0b110,
+    BH = 0b111,
+}
+
+impl RegisterByteOp {
+    pub const ALL: [Self; 8] = [
+        Self::AL,
+        Self::CL,
+        Self::DL,
+        Self::BL,
+        Self::AH,
+        Self::CH,
+        Self::DH,
+        Self::BH,
+    ];
+
+    /// Only the lower 3 bits are considered.
+    pub fn from_bits(bits: u8) -> Self {
+        Self::ALL[(bits & 0b111) as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::AL => "al",
+            Self::CL => "cl",
+            Self::DL => "dl",
+            Self::BL => "bl",
+            Self::AH => "ah",
+            Self::CH => "ch",
+            Self::DH => "dh",
+            Self::BH => "bh",
+        }
+    }
+
+    /// The word register this is one half of, AL and AH are both part of AX.
+    pub fn word(self) -> RegisterWordOp {
+        RegisterWordOp::from_bits(self as u8 & 0b11)
+    }
+
+    /// Whether this is the upper byte (AH, CH, DH, BH) of its word register.
+    pub fn is_high(self) -> bool {
+        self as u8 & 0b100 != 0
+    }
+}
+
+// w1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegisterWordOp {
+    AX = 0b000,
+    CX = 0b001,
+    DX = 0b010,
+    BX = 0b011,
+    SP = 0b100,
+    BP = 0b101,
+    SI = 0b110,
+    DI = 0b111,
+}
+
+impl RegisterWordOp {
+    pub const ALL: [Self; 8] = [
+        Self::AX,
+        Self::CX,
+        Self::DX,
+        Self::BX,
+        Self::SP,
+        Self::BP,
+        Self::SI,
+        Self::DI,
+    ];
+
+    /// Only the lower 3 bits are considered.
+    pub fn from_bits(bits: u8) -> Self {
+        Self::ALL[(bits & 0b111) as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::AX => "ax",
+            Self::CX => "cx",
+            Self::DX => "dx",
+            Self::BX => "bx",
+            Self::SP => "sp",
+            Self::BP => "bp",
+            Self::SI => "si",
+            Self::DI => "di",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SegmentRegister {
+    ES = 0b00,
+    CS = 0b01,
+    SS = 0b10,
+    DS = 0b11,
+}
+
+impl SegmentRegister {
+    pub const ALL: [Self; 4] = [Self::ES, Self::CS, Self::SS, Self::DS];
+
+    /// Only the lower 2 bits are considered.
+    pub fn from_bits(bits: u8) -> Self {
+        Self::ALL[(bits & 0b11) as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ES => "es",
+            Self::CS => "cs",
+            Self::SS => "ss",
+            Self::DS => "ds",
+        }
+    }
+}
+
+/// A general purpose register, sized by the `w` bit of the instruction that names it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Register {
+    Byte(RegisterByteOp),
+    Word(RegisterWordOp),
+}
+
+impl Register {
+    pub fn from_bits(w: u8, bits: u8) -> Self {
+        match w {
+            0 => Self::Byte(RegisterByteOp::from_bits(bits)),
+            _ => Self::Word(RegisterWordOp::from_bits(bits)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Byte(register) => register.name(),
+            Self::Word(register) => register.name(),
+        }
+    }
+
+    pub fn width(self) -> Width {
+        match self {
+            Self::Byte(_) => Width::Byte,
+            Self::Word(_) => Width::Word,
+        }
+    }
+
+    /// The full 16-bit register holding this one.
+    pub fn word(self) -> RegisterWordOp {
+        match self {
+            Self::Byte(register) => register.word(),
+            Self::Word(register) => register,
+        }
+    }
+
+    /// Whether the two share any bits, AX overlaps AL and AH but AL and AH don't overlap.
+    pub fn overlaps(self, other: Self) -> bool {
+        match (self, other) {
+            (Self::Byte(a), Self::Byte(b)) => a == b,
+            _ => self.word() == other.word(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+    Byte,
+    Word,
+}
+
+impl Width {
+    pub fn from_w(w: u8) -> Self {
+        match w {
+            0 => Self::Byte,
+            _ => Self::Word,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Byte => "byte",
+            Self::Word => "word",
+        }
+    }
+
+    /// All the bits a value of this width can hold.
+    pub fn mask(self) -> u16 {
+        match self {
+            Self::Byte => 0x00FF,
+            Self::Word => 0xFFFF,
+        }
+    }
+
+    /// The top bit, which holds the sign of a value of this width.
+    pub fn sign_bit(self) -> u16 {
+        match self {
+            Self::Byte => 0x0080,
+            Self::Word => 0x8000,
+        }
+    }
+}
+
+/// The register(s) an r/m field adds together when addressing memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressBase {
+    BxSi = 0b000,
+    BxDi = 0b001,
+    BpSi = 0b010,
+    BpDi = 0b011,
+    Si = 0b100,
+    Di = 0b101,
+    Bp = 0b110,
+    Bx = 0b111,
+}
+
+impl AddressBase {
+    const ALL: [Self; 8] = [
+        Self::BxSi,
+        Self::BxDi,
+        Self::BpSi,
+        Self::BpDi,
+        Self::Si,
+        Self::Di,
+        Self::Bp,
+        Self::Bx,
+    ];
+
+    /// Only the lower 3 bits are considered.
+    pub fn from_bits(bits: u8) -> Self {
+        Self::ALL[(bits & 0b111) as usize]
+    }
+
+    pub fn registers(self) -> (RegisterWordOp, Option<RegisterWordOp>) {
+        match self {
+            Self::BxSi => (RegisterWordOp::BX, Some(RegisterWordOp::SI)),
+            Self::BxDi => (RegisterWordOp::BX, Some(RegisterWordOp::DI)),
+            Self::BpSi => (RegisterWordOp::BP, Some(RegisterWordOp::SI)),
+            Self::BpDi => (RegisterWordOp::BP, Some(RegisterWordOp::DI)),
+            Self::Si => (RegisterWordOp::SI, None),
+            Self::Di => (RegisterWordOp::DI, None),
+            Self::Bp => (RegisterWordOp::BP, None),
+            Self::Bx => (RegisterWordOp::BX, None),
+        }
+    }
+}
+
+/// A memory operand, `base` is `None` for a direct address held in `displacement`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EffectiveAddress {
+    pub base: Option<AddressBase>,
+    pub displacement: i16,
+    /// Whether the encoding has a displacement, which it can do even when it's zero. Direct
+    /// addresses always have one.
+    pub has_displacement: bool,
+}
+
+impl EffectiveAddress {
+    /// Addresses built on BP point into the stack and use SS, everything else uses DS.
+    pub fn default_segment(&self) -> SegmentRegister {
+        match self.base {
+            Some(AddressBase::BpSi | AddressBase::BpDi | AddressBase::Bp) => SegmentRegister::SS,
+            _ => SegmentRegister::DS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register(Register),
+    Segment(SegmentRegister),
+    Memory(EffectiveAddress),
+    Immediate(u16),
+    /// Signed displacement from the end of the instruction, for relative jumps and calls.
+    Relative(i16),
+    /// `segment:offset` pointer of a direct far jump or call.
+    Far {
+        segment: u16,
+        offset: u16,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mnemonic {
+    Mov,
+    Push,
+    Pop,
+    Xchg,
+    In,
+    Out,
+    Xlat,
+    Lea,
+    Lds,
+    Les,
+    Lahf,
+    Sahf,
+    Pushf,
+    Popf,
+    Add,
+    Adc,
+    Inc,
+    Aaa,
+    Daa,
+    Sub,
+    Sbb,
+    Dec,
+    Neg,
+    Cmp,
+    Aas,
+    Das,
+    Mul,
+    Imul,
+    Aam,
+    Div,
+    Idiv,
+    Aad,
+    Cbw,
+    Cwd,
+    Not,
+    Shl,
+    Shr,
+    Sar,
+    Rol,
+    Ror,
+    Rcl,
+    Rcr,
+    And,
+    Test,
+    Or,
+    Xor,
+    Movs,
+    Cmps,
+    Scas,
+    Lods,
+    Stos,
+    Jo,
+    Jno,
+    Jb,
+    Jnb,
+    Je,
+    Jne,
+    Jbe,
+    Ja,
+    Js,
+    Jns,
+    Jp,
+    Jnp,
+    Jl,
+    Jnl,
+    Jle,
+    Jg,
+    Loopnz,
+    Loopz,
+    Loop,
+    Jcxz,
+    Call,
+    CallFar,
+    Jmp,
+    JmpFar,
+    Ret,
+    Retf,
+    Int,
+    Int3,
+    Into,
+    Iret,
+    Clc,
+    Cmc,
+    Stc,
+    Cld,
+    Std,
+    Cli,
+    Sti,
+    Hlt,
+    Wait,
+    Nop,
+}
+
+/// How an instruction hands control to the one that runs after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Always continues with the next instruction.
+    Sequential,
+    Jump,
+    /// Either jumps or continues with the next instruction, includes the `loop` family.
+    ConditionalJump,
+    Call,
+    Return,
+    /// Runs an interrupt handler that returns to the next instruction.
+    Interrupt,
+    Halt,
+}
+
+impl Mnemonic {
+    // indexed by the low nibble of 0b0111cccc
+    const CONDITIONAL_JUMPS: [Self; 16] = [
+        Self::Jo,
+        Self::Jno,
+        Self::Jb,
+        Self::Jnb,
+        Self::Je,
+        Self::Jne,
+        Self::Jbe,
+        Self::Ja,
+        Self::Js,
+        Self::Jns,
+        Self::Jp,
+        Self::Jnp,
+        Self::Jl,
+        Self::Jnl,
+        Self::Jle,
+        Self::Jg,
+    ];
+
+    // indexed by the low 2 bits of 0b111000xx
+    const LOOPS: [Self; 4] = [Self::Loopnz, Self::Loopz, Self::Loop, Self::Jcxz];
+
+    // indexed by bits 3-5 of 0b00ooo0dw and the reg field of the 0x80-0x83 group
+    const ARITHMETIC: [Self; 8] = [
+        Self::Add,
+        Self::Or,
+        Self::Adc,
+        Self::Sbb,
+        Self::And,
+        Self::Sub,
+        Self::Xor,
+        Self::Cmp,
+    ];
+
+    // indexed by the reg field of the 0xD0-0xD3 group, 110 is undefined
+    const SHIFTS: [Option<Self>; 8] = [
+        Some(Self::Rol),
+        Some(Self::Ror),
+        Some(Self::Rcl),
+        Some(Self::Rcr),
+        Some(Self::Shl),
+        Some(Self::Shr),
+        None,
+        Some(Self::Sar),
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mov => "mov",
+            Self::Push => "push",
+            Self::Pop => "pop",
+            Self::Xchg => "xchg",
+            Self::In => "in",
+            Self::Out => "out",
+            Self::Xlat => "xlat",
+            Self::Lea => "lea",
+            Self::Lds => "lds",
+            Self::Les => "les",
+            Self::Lahf => "lahf",
+            Self::Sahf => "sahf",
+            Self::Pushf => "pushf",
+            Self::Popf => "popf",
+            Self::Add => "add",
+            Self::Adc => "adc",
+            Self::Inc => "inc",
+            Self::Aaa => "aaa",
+            Self::Daa => "daa",
+            Self::Sub => "sub",
+            Self::Sbb => "sbb",
+            Self::Dec => "dec",
+            Self::Neg => "neg",
+            Self::Cmp => "cmp",
+            Self::Aas => "aas",
+            Self::Das => "das",
+            Self::Mul => "mul",
+            Self::Imul => "imul",
+            Self::Aam => "aam",
+            Self::Div => "div",
+            Self::Idiv => "idiv",
+            Self::Aad => "aad",
+            Self::Cbw => "cbw",
+            Self::Cwd => "cwd",
+            Self::Not => "not",
+            Self::Shl => "shl",
+            Self::Shr => "shr",
+            Self::Sar => "sar",
+            Self::Rol => "rol",
+            Self::Ror => "ror",
+            Self::Rcl => "rcl",
+            Self::Rcr => "rcr",
+            Self::And => "and",
+            Self::Test => "test",
+            Self::Or => "or",
+            Self::Xor => "xor",
+            Self::Movs => "movs",
+            Self::Cmps => "cmps",
+            Self::Scas => "scas",
+            Self::Lods => "lods",
+            Self::Stos => "stos",
+            Self::Jo => "jo",
+            Self::Jno => "jno",
+            Self::Jb => "jb",
+            Self::Jnb => "jnb",
+            Self::Je => "je",
+            Self::Jne => "jne",
+            Self::Jbe => "jbe",
+            Self::Ja => "ja",
+            Self::Js => "js",
+            Self::Jns => "jns",
+            Self::Jp => "jp",
+            Self::Jnp => "jnp",
+            Self::Jl => "jl",
+            Self::Jnl => "jnl",
+            Self::Jle => "jle",
+            Self::Jg => "jg",
+            Self::Loopnz => "loopnz",
+            Self::Loopz => "loopz",
+            Self::Loop => "loop",
+            Self::Jcxz => "jcxz",
+            Self::Call | Self::CallFar => "call",
+            Self::Jmp | Self::JmpFar => "jmp",
+            Self::Ret => "ret",
+            Self::Retf => "retf",
+            Self::Int => "int",
+            Self::Int3 => "int3",
+            Self::Into => "into",
+            Self::Iret => "iret",
+            Self::Clc => "clc",
+            Self::Cmc => "cmc",
+            Self::Stc => "stc",
+            Self::Cld => "cld",
+            Self::Std => "std",
+            Self::Cli => "cli",
+            Self::Sti => "sti",
+            Self::Hlt => "hlt",
+            Self::Wait => "wait",
+            Self::Nop => "nop",
+        }
+    }
+
+    /// Far (intersegment) forms load CS as well as IP.
+    pub fn is_far(self) -> bool {
+        matches!(self, Self::CallFar | Self::JmpFar | Self::Retf)
+    }
+
+    pub fn flow(self) -> Flow {
+        match self {
+            Self::Jmp | Self::JmpFar => Flow::Jump,
+            Self::Call | Self::CallFar => Flow::Call,
+            Self::Ret | Self::Retf | Self::Iret => Flow::Return,
+            Self::Int | Self::Int3 | Self::Into => Flow::Interrupt,
+            Self::Hlt => Flow::Halt,
+            m if Self::CONDITIONAL_JUMPS.contains(&m) || Self::LOOPS.contains(&m) => {
+                Flow::ConditionalJump
+            }
+            _ => Flow::Sequential,
+        }
+    }
+
+    /// Whether an immediate operand is a count, a vector or a port rather than a signed value.
+    pub fn has_unsigned_immediate(self) -> bool {
+        matches!(
+            self,
+            Self::Ret | Self::Retf | Self::Int | Self::In | Self::Out | Self::Aam | Self::Aad
+        )
+    }
+
+    /// String instructions work on `[si]`/`[di]` implicitly, their size goes in the name
+    /// (`movsb`, `stosw`).
+    pub fn is_string(self) -> bool {
+        matches!(
+            self,
+            Self::Movs | Self::Cmps | Self::Scas | Self::Lods | Self::Stos
+        )
+    }
+
+    /// Shifts and rotates, whose source is a count that says nothing about the operand size.
+    pub fn is_shift(self) -> bool {
+        Self::SHIFTS.contains(&Some(self))
+    }
+}
+
+/// The `rep` prefixes, which repeat a string instruction CX times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// `0xF3`, also stops `cmps`/`scas` once ZF is clear.
+    Rep,
+    /// `0xF2`, stops `cmps`/`scas` once ZF is set.
+    Repne,
+}
+
+impl Repeat {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rep => "rep",
+            Self::Repne => "repne",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Prefixes {
+    pub segment: Option<SegmentRegister>,
+    pub lock: bool,
+    pub repeat: Option<Repeat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    /// Position of the first byte (including prefixes) in the decoded image.
+    pub offset: usize,
+    /// Number of bytes the instruction occupies, including prefixes.
+    pub length: usize,
+    pub mnemonic: Mnemonic,
+    pub width: Width,
+    pub destination: Option<Operand>,
+    pub source: Option<Operand>,
+    pub prefixes: Prefixes,
+}
+
+impl Instruction {
+    pub fn operands(&self) -> impl Iterator<Item = Operand> {
+        self.destination.into_iter().chain(self.source)
+    }
+
+    /// Offset of the instruction a relative jump or call transfers to, `None` for anything else
+    /// (including far and indirect transfers) or a target before the start of the image.
+    pub fn branch_target(&self) -> Option<usize> {
+        self.operands().find_map(|operand| match operand {
+            Operand::Relative(displacement) => {
+                (self.offset + self.length).checked_add_signed(isize::from(displacement))
+            }
+            _ => None,
+        })
+    }
+
+    /// Whether the operand size can't be inferred from a register operand and has to be spelled out.
+    pub fn needs_width_hint(&self) -> bool {
+        // `shl byte [bx], cl` is still a byte shift, the count doesn't size it
+        let sizing = if self.mnemonic.is_shift() { 1 } else { 2 };
+        !self
+            .operands()
+            .take(sizing)
+            .any(|operand| matches!(operand, Operand::Register(_) | Operand::Segment(_)))
+            && self
+                .operands()
+                .any(|operand| matches!(operand, Operand::Memory(_)))
+    }
+
+    /// Segment a memory operand is relative to, the override prefix or the default for its base.
+    pub fn segment_of(&self, address: &EffectiveAddress) -> SegmentRegister {
+        self.prefixes
+            .segment
+            .unwrap_or_else(|| address.default_segment())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The instruction starting at `offset` needs more bytes than are left in the input.
+    UnexpectedEnd {
+        offset: usize,
+    },
+    UnknownOpcode {
+        offset: usize,
+        opcode: u8,
+    },
+    InvalidEncoding {
+        offset: usize,
+        opcode: u8,
+        reason: &'static str,
+    },
+}
+
+impl DecodeError {
+    /// Position of the first byte of the instruction that failed to decode.
+    pub fn offset(&self) -> usize {
+        match *self {
+            Self::UnexpectedEnd { offset }
+            | Self::UnknownOpcode { offset, .. }
+            | Self::InvalidEncoding { offset, .. } => offset,
+        }
+    }
+
+    /// The same error for an instruction that was decoded out of a copy of its bytes, placing it
+    /// back at `offset`.
+    pub fn at(self, offset: usize) -> Self {
+        match self {
+            Self::UnexpectedEnd { .. } => Self::UnexpectedEnd { offset },
+            Self::UnknownOpcode { opcode, .. } => Self::UnknownOpcode { offset, opcode },
+            Self::InvalidEncoding { opcode, reason, .. } => Self::InvalidEncoding {
+                offset,
+                opcode,
+                reason,
+            },
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEnd { offset } => {
+                write!(f, "instruction at offset {offset:#06x} is truncated")
+            }
+            Self::UnknownOpcode { offset, opcode } => write!(
+                f,
+                "unable to determine instruction for byte {opcode:08b} at offset {offset:#06x}"
+            ),
+            Self::InvalidEncoding {
+                offset,
+                opcode,
+                reason,
+            } => write!(
+                f,
+                "invalid encoding for opcode {opcode:08b} at offset {offset:#06x}: {reason}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+pub fn extract_bits(byte: u8, start: u8, end: u8) -> u8 {
+    // Validate inputs
+    assert!(start < end, "Start must be less than the end");
+    assert!(end <= 8, "The End cannot be greater than 8");
+
+    // Calculate the number of bits to extract
+    let num_bits = end - start;
+
+    // Shift left to align desired bits, then shift right
+    (byte << start) >> (8 - num_bits)
+}
+
+/// Bounds checked cursor over the bytes of a single instruction.
+struct ByteReader<'a> {
+    data: &'a [u8],
+    start: usize,
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8], start: usize) -> Self {
+        Self {
+            data,
+            start,
+            position: start,
+        }
+    }
+
+    fn next_u8(&mut self) -> Result<u8, DecodeError> {
+        let byte = *self
+            .data
+            .get(self.position)
+            .ok_or(DecodeError::UnexpectedEnd { offset: self.start })?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn next_u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_le_bytes([self.next_u8()?, self.next_u8()?]))
+    }
+
+    /// Immediate data, 1 or 2 bytes depending on the `w` bit.
+    fn next_data(&mut self, w: u8) -> Result<u16, DecodeError> {
+        match w {
+            0 => self.next_u8().map(u16::from),
+            _ => self.next_u16(),
+        }
+    }
+
+    fn invalid(&self, opcode: u8, reason: &'static str) -> DecodeError {
+        DecodeError::InvalidEncoding {
+            offset: self.start,
+            opcode,
+            reason,
+        }
+    }
+}
+
+/// The fields of a `mod reg r/m` byte, with the r/m side already resolved to an operand.
+struct ModRegRm {
+    reg: u8,
+    r_m: Operand,
+}
+
+fn decode_mod_reg_rm(reader: &mut ByteReader, w: u8) -> Result<ModRegRm, DecodeError> {
+    let byte = reader.next_u8()?;
+    let mode = extract_bits(byte, 0, 2);
+    let reg = extract_bits(byte, 2, 5);
+    let r_m = extract_bits(byte, 5, 8);
+
+    let displacement = match ModEncoding::from_bits(mode) {
+        Some(ModEncoding::RegisterMode) => {
+            return Ok(ModRegRm {
+                reg,
+                r_m: Operand::Register(Register::from_bits(w, r_m)),
+            });
+        }
+        // direct address, there is no base register
+        Some(ModEncoding::MemMode) if r_m == AddressBase::Bp as u8 => {
+            let address = reader.next_u16()? as i16;
+            return Ok(ModRegRm {
+                reg,
+                r_m: Operand::Memory(EffectiveAddress {
+                    base: None,
+                    displacement: address,
+                    has_displacement: true,
+                }),
+            });
+        }
+        Some(ModEncoding::MemMode) => None,
+        Some(ModEncoding::MemMode8B) => Some(i16::from(reader.next_u8()? as i8)),
+        Some(ModEncoding::MemMode16B) => Some(reader.next_u16()? as i16),
+        None => unreachable!("mod is always 2 bits"),
+    };
+
+    Ok(ModRegRm {
+        reg,
+        r_m: Operand::Memory(EffectiveAddress {
+            base: Some(AddressBase::from_bits(r_m)),
+            displacement: displacement.unwrap_or(0),
+            has_displacement: displacement.is_some(),
+        }),
+    })
+}
+
+/// Everything about an instruction that depends on its opcode.
+struct Operation {
+    mnemonic: Mnemonic,
+    width: Width,
+    destination: Option<Operand>,
+    source: Option<Operand>,
+}
+
+impl Operation {
+    fn new(mnemonic: Mnemonic, width: Width) -> Self {
+        Self {
+            mnemonic,
+            width,
+            destination: None,
+            source: None,
+        }
+    }
+
+    fn unary(mnemonic: Mnemonic, width: Width, operand: Operand) -> Self {
+        Self {
+            destination: Some(operand),
+            ..Self::new(mnemonic, width)
+        }
+    }
+
+    fn binary(mnemonic: Mnemonic, width: Width, destination: Operand, source: Operand) -> Self {
+        Self {
+            destination: Some(destination),
+            source: Some(source),
+            ..Self::new(mnemonic, width)
+        }
+    }
+}
+
+/// The `d` and `w` bits of `0bxxxxxxdw | mod reg r/m`, shared by `mov` and the arithmetic
+/// instructions.
+fn decode_register_direction(
+    reader: &mut ByteReader,
+    opcode: u8,
+) -> Result<(Width, Operand, Operand), DecodeError> {
+    let d = extract_bits(opcode, 6, 7);
+    let w = extract_bits(opcode, 7, 8);
+    let ModRegRm { reg, r_m } = decode_mod_reg_rm(reader, w)?;
+    let reg = Operand::Register(Register::from_bits(w, reg));
+
+    match d {
+        // direction is from register (i.e. the data source is from a register)
+        0 => Ok((Width::from_w(w), r_m, reg)),
+        // direction is to register (i.e. the data destination is to a register)
+        _ => Ok((Width::from_w(w), reg, r_m)),
+    }
+}
+
+fn decode_mov(
+    reader: &mut ByteReader,
+    opcode: u8,
+    instruction: MovInstructionType,
+) -> Result<Operation, DecodeError> {
+    let (width, destination, source) = decode_mov_operands(reader, opcode, instruction)?;
+    Ok(Operation::binary(Mnemonic::Mov, width, destination, source))
+}
+
+fn decode_mov_operands(
+    reader: &mut ByteReader,
+    opcode: u8,
+    instruction: MovInstructionType,
+) -> Result<(Width, Operand, Operand), DecodeError> {
+    let w = extract_bits(opcode, 7, 8);
+
+    match instruction {
+        // 0b100010dw | mod reg r/m | disp-lo | disp-hi
+        MovInstructionType::RegisterOrMemoryToOrFromRegister => {
+            decode_register_direction(reader, opcode)
+        }
+
+        // 0b1100011w | mod 000 r/m | disp-lo | disp-hi | data | data if w = 1
+        MovInstructionType::ImmediateToRegisterOrMemory => {
+            let ModRegRm { reg, r_m } = decode_mod_reg_rm(reader, w)?;
+            if reg != 0b000 {
+                return Err(reader.invalid(opcode, "reg field must be 000"));
+            }
+            let data = reader.next_data(w)?;
+            Ok((Width::from_w(w), r_m, Operand::Immediate(data)))
+        }
+
+        // 0b1011wreg | data | data if w = 1
+        MovInstructionType::ImmediateToRegister => {
+            let w = extract_bits(opcode, 4, 5);
+            let reg = extract_bits(opcode, 5, 8);
+            let data = reader.next_data(w)?;
+            Ok((
+                Width::from_w(w),
+                Operand::Register(Register::from_bits(w, reg)),
+                Operand::Immediate(data),
+            ))
+        }
+
+        // 0b1010000w | addr-lo | addr-hi
+        MovInstructionType::MemoryToAccumulator | MovInstructionType::AccumulatorToMemory => {
+            let memory = Operand::Memory(EffectiveAddress {
+                base: None,
+                displacement: reader.next_u16()? as i16,
+                has_displacement: true,
+            });
+            let accumulator = Operand::Register(Register::from_bits(w, 0b000));
+
+            match instruction {
+                MovInstructionType::MemoryToAccumulator => {
+                    Ok((Width::from_w(w), accumulator, memory))
+                }
+                _ => Ok((Width::from_w(w), memory, accumulator)),
+            }
+        }
+
+        // 0b10001110 | mod 0 sr r/m | disp-lo | disp-hi
+        // 0b10001100 | mod 0 sr r/m | disp-lo | disp-hi
+        MovInstructionType::RegisterOrMemoryToSegmentRegister
+        | MovInstructionType::SegmentRegisterToRegisterOrMemory => {
+            // the patterns only mask off the lowest bit, 0x8D and 0x8F are not segment moves
+            if w != 0 {
+                return Err(DecodeError::UnknownOpcode {
+                    offset: reader.start,
+                    opcode,
+                });
+            }
+            let ModRegRm { reg, r_m } = decode_mod_reg_rm(reader, 1)?;
+            if reg & 0b100 != 0 {
+                return Err(reader.invalid(opcode, "segment register field must be 0sr"));
+            }
+            let segment = Operand::Segment(SegmentRegister::from_bits(reg));
+
+            match instruction {
+                MovInstructionType::RegisterOrMemoryToSegmentRegister => {
+                    Ok((Width::Word, segment, r_m))
+                }
+                _ => Ok((Width::Word, r_m, segment)),
+            }
+        }
+    }
+}
+
+/// Decodes the single instruction starting at `offset`.
+pub fn decode_instruction(data: &[u8], offset: usize) -> Result<Instruction, DecodeError> {
+    let mut reader = ByteReader::new(data, offset);
+    let mut prefixes = Prefixes::default();
+
+    let opcode = loop {
+        let byte = reader.next_u8()?;
+        match byte {
+            // 0b001sr110 segment override
+            0x26 | 0x2E | 0x36 | 0x3E => {
+                prefixes.segment = Some(SegmentRegister::from_bits(extract_bits(byte, 3, 5)))
+            }
+            0xF0 => prefixes.lock = true,
+            0xF2 => prefixes.repeat = Some(Repeat::Repne),
+            0xF3 => prefixes.repeat = Some(Repeat::Rep),
+            _ => break byte,
+        }
+    };
+
+    let operation = decode_operation(&mut reader, opcode)?;
+
+    Ok(Instruction {
+        offset,
+        length: reader.position - offset,
+        mnemonic: operation.mnemonic,
+        width: operation.width,
+        destination: operation.destination,
+        source: operation.source,
+        prefixes,
+    })
+}
+
+fn relative8(reader: &mut ByteReader) -> Result<Operand, DecodeError> {
+    Ok(Operand::Relative(i16::from(reader.next_u8()? as i8)))
+}
+
+fn relative16(reader: &mut ByteReader) -> Result<Operand, DecodeError> {
+    Ok(Operand::Relative(reader.next_u16()? as i16))
+}
+
+fn far_pointer(reader: &mut ByteReader) -> Result<Operand, DecodeError> {
+    let offset = reader.next_u16()?;
+    let segment = reader.next_u16()?;
+    Ok(Operand::Far { segment, offset })
+}
+
+// 0b1111111w | mod op r/m, the reg field picks the operation
+fn decode_group_ff(reader: &mut ByteReader, opcode: u8) -> Result<Operation, DecodeError> {
+    let w = extract_bits(opcode, 7, 8);
+    let ModRegRm { reg, r_m } = decode_mod_reg_rm(reader, w)?;
+    let is_memory = matches!(r_m, Operand::Memory(_));
+
+    let mnemonic = match (w, reg) {
+        (_, 0b000) => Mnemonic::Inc,
+        (_, 0b001) => Mnemonic::Dec,
+        (1, 0b010) => Mnemonic::Call,
+        (1, 0b011) if is_memory => Mnemonic::CallFar,
+        (1, 0b100) => Mnemonic::Jmp,
+        (1, 0b101) if is_memory => Mnemonic::JmpFar,
+        (1, 0b110) => Mnemonic::Push,
+        (1, 0b011 | 0b101) => {
+            return Err(reader.invalid(opcode, "far pointer must be in memory"));
+        }
+        _ => return Err(reader.invalid(opcode, "undefined operation in reg field")),
+    };
+
+    Ok(Operation::unary(mnemonic, Width::from_w(w), r_m))
+}
+
+// 0b00ooo0dw | mod reg r/m | disp-lo | disp-hi
+// 0b00ooo10w | data | data if w = 1
+fn decode_arithmetic(reader: &mut ByteReader, opcode: u8) -> Result<Operation, DecodeError> {
+    let mnemonic = Mnemonic::ARITHMETIC[extract_bits(opcode, 2, 5) as usize];
+
+    if opcode & 0b100 == 0 {
+        let (width, destination, source) = decode_register_direction(reader, opcode)?;
+        return Ok(Operation::binary(mnemonic, width, destination, source));
+    }
+
+    let w = extract_bits(opcode, 7, 8);
+    let data = reader.next_data(w)?;
+    Ok(Operation::binary(
+        mnemonic,
+        Width::from_w(w),
+        Operand::Register(Register::from_bits(w, 0b000)),
+        Operand::Immediate(data),
+    ))
+}
+
+// 0b100000sw | mod op r/m | disp-lo | disp-hi | data | data if sw = 01
+fn decode_group_80(reader: &mut ByteReader, opcode: u8) -> Result<Operation, DecodeError> {
+    let s = extract_bits(opcode, 6, 7);
+    let w = extract_bits(opcode, 7, 8);
+    let ModRegRm { reg, r_m } = decode_mod_reg_rm(reader, w)?;
+
+    let data = match (s, w) {
+        // a byte of data sign extended to the word operand
+        (1, 1) => i16::from(reader.next_u8()? as i8) as u16,
+        _ => reader.next_data(w)?,
+    };
+
+    Ok(Operation::binary(
+        Mnemonic::ARITHMETIC[reg as usize],
+        Width::from_w(w),
+        r_m,
+        Operand::Immediate(data),
+    ))
+}
+
+// 0b1111011w | mod op r/m | disp-lo | disp-hi | data for test
+fn decode_group_f6(reader: &mut ByteReader, opcode: u8) -> Result<Operation, DecodeError> {
+    let w = extract_bits(opcode, 7, 8);
+    let ModRegRm { reg, r_m } = decode_mod_reg_rm(reader, w)?;
+
+    let mnemonic = match reg {
+        0b000 => {
+            let data = reader.next_data(w)?;
+            return Ok(Operation::binary(
+                Mnemonic::Test,
+                Width::from_w(w),
+                r_m,
+                Operand::Immediate(data),
+            ));
+        }
+        0b010 => Mnemonic::Not,
+        0b011 => Mnemonic::Neg,
+        0b100 => Mnemonic::Mul,
+        0b101 => Mnemonic::Imul,
+        0b110 => Mnemonic::Div,
+        0b111 => Mnemonic::Idiv,
+        _ => return Err(reader.invalid(opcode, "undefined operation in reg field")),
+    };
+
+    Ok(Operation::unary(mnemonic, Width::from_w(w), r_m))
+}
+
+// 0b110100vw | mod op r/m | disp-lo | disp-hi, v = 1 shifts by CL instead of 1
+fn decode_shift(reader: &mut ByteReader, opcode: u8) -> Result<Operation, DecodeError> {
+    let v = extract_bits(opcode, 6, 7);
+    let w = extract_bits(opcode, 7, 8);
+    let ModRegRm { reg, r_m } = decode_mod_reg_rm(reader, w)?;
+
+    let mnemonic = Mnemonic::SHIFTS[reg as usize]
+        .ok_or_else(|| reader.invalid(opcode, "undefined operation in reg field"))?;
+    let count = match v {
+        0 => Operand::Immediate(1),
+        _ => Operand::Register(Register::Byte(RegisterByteOp::CL)),
+    };
+
+    Ok(Operation::binary(mnemonic, Width::from_w(w), r_m, count))
+}
+
+// 0b1000010w test and 0b1000011w xchg | mod reg r/m | disp-lo | disp-hi
+fn decode_test_xchg(reader: &mut ByteReader, opcode: u8) -> Result<Operation, DecodeError> {
+    let w = extract_bits(opcode, 7, 8);
+    let ModRegRm { reg, r_m } = decode_mod_reg_rm(reader, w)?;
+    let reg = Operand::Register(Register::from_bits(w, reg));
+
+    Ok(match opcode & 0b10 {
+        0 => Operation::binary(Mnemonic::Test, Width::from_w(w), r_m, reg),
+        _ => Operation::binary(Mnemonic::Xchg, Width::from_w(w), reg, r_m),
+    })
+}
+
+// 0b10001101 lea, 0b11000100 les, 0b11000101 lds | mod reg r/m | disp-lo | disp-hi
+fn decode_load_address(
+    reader: &mut ByteReader,
+    opcode: u8,
+    mnemonic: Mnemonic,
+) -> Result<Operation, DecodeError> {
+    let ModRegRm { reg, r_m } = decode_mod_reg_rm(reader, 1)?;
+    if !matches!(r_m, Operand::Memory(_)) {
+        return Err(reader.invalid(opcode, "source must be in memory"));
+    }
+
+    Ok(Operation::binary(
+        mnemonic,
+        Width::Word,
+        Operand::Register(Register::Word(RegisterWordOp::from_bits(reg))),
+        r_m,
+    ))
+}
+
+// 0b10001111 | mod 000 r/m | disp-lo | disp-hi
+fn decode_pop(reader: &mut ByteReader, opcode: u8) -> Result<Operation, DecodeError> {
+    let ModRegRm { reg, r_m } = decode_mod_reg_rm(reader, 1)?;
+    if reg != 0b000 {
+        return Err(reader.invalid(opcode, "reg field must be 000"));
+    }
+    Ok(Operation::unary(Mnemonic::Pop, Width::Word, r_m))
+}
+
+// 0b1110010w in / 0b1110011w out | data-8, or 0b1110110w / 0b1110111w with the port in DX
+fn decode_in_out(reader: &mut ByteReader, opcode: u8) -> Result<Operation, DecodeError> {
+    let w = extract_bits(opcode, 7, 8);
+    let accumulator = Operand::Register(Register::from_bits(w, 0b000));
+    let port = match opcode & 0b1000 {
+        0 => Operand::Immediate(u16::from(reader.next_u8()?)),
+        _ => Operand::Register(Register::Word(RegisterWordOp::DX)),
+    };
+
+    Ok(match opcode & 0b10 {
+        0 => Operation::binary(Mnemonic::In, Width::from_w(w), accumulator, port),
+        _ => Operation::binary(Mnemonic::Out, Width::from_w(w), port, accumulator),
+    })
+}
+
+// 0b1101010x | 0b00001010, other bases work on the 8086 but have no mnemonic of their own
+fn decode_ascii_adjust(
+    reader: &mut ByteReader,
+    mnemonic: Mnemonic,
+) -> Result<Operation, DecodeError> {
+    Ok(match reader.next_u8()? {
+        10 => Operation::new(mnemonic, Width::Byte),
+        base => Operation::unary(mnemonic, Width::Byte, Operand::Immediate(u16::from(base))),
+    })
+}
+
+fn register_word(opcode: u8) -> Operand {
+    Operand::Register(Register::Word(RegisterWordOp::from_bits(opcode)))
+}
+
+fn segment(opcode: u8) -> Operand {
+    Operand::Segment(SegmentRegister::from_bits(extract_bits(opcode, 3, 5)))
+}
+
+fn decode_operation(reader: &mut ByteReader, opcode: u8) -> Result<Operation, DecodeError> {
+    let operation = match opcode {
+        // 0b0111cccc | ip-inc8
+        0x70..=0x7F => Operation::unary(
+            Mnemonic::CONDITIONAL_JUMPS[(opcode & 0x0F) as usize],
+            Width::Word,
+            relative8(reader)?,
+        ),
+        // 0b111000cc | ip-inc8
+        0xE0..=0xE3 => Operation::unary(
+            Mnemonic::LOOPS[(opcode & 0b11) as usize],
+            Width::Word,
+            relative8(reader)?,
+        ),
+        0xE8 => Operation::unary(Mnemonic::Call, Width::Word, relative16(reader)?),
+        0xE9 => Operation::unary(Mnemonic::Jmp, Width::Word, relative16(reader)?),
+        0xEB => Operation::unary(Mnemonic::Jmp, Width::Word, relative8(reader)?),
+        0x9A => Operation::unary(Mnemonic::CallFar, Width::Word, far_pointer(reader)?),
+        0xEA => Operation::unary(Mnemonic::JmpFar, Width::Word, far_pointer(reader)?),
+        0xC2 => Operation::unary(
+            Mnemonic::Ret,
+            Width::Word,
+            Operand::Immediate(reader.next_u16()?),
+        ),
+        0xC3 => Operation::new(Mnemonic::Ret, Width::Word),
+        0xCA => Operation::unary(
+            Mnemonic::Retf,
+            Width::Word,
+            Operand::Immediate(reader.next_u16()?),
+        ),
+        0xCB => Operation::new(Mnemonic::Retf, Width::Word),
+        0xCC => Operation::new(Mnemonic::Int3, Width::Byte),
+        0xCD => Operation::unary(
+            Mnemonic::Int,
+            Width::Byte,
+            Operand::Immediate(u16::from(reader.next_u8()?)),
+        ),
+        0xCE => Operation::new(Mnemonic::Into, Width::Byte),
+        0xCF => Operation::new(Mnemonic::Iret, Width::Word),
+        0xF4 => Operation::new(Mnemonic::Hlt, Width::Byte),
+        0xFE | 0xFF => decode_group_ff(reader, opcode)?,
+        0x00..=0x3F if opcode & 0b111 < 0b110 => decode_arithmetic(reader, opcode)?,
+        // 0b000sr110 push, 0b000sr111 pop, `pop cs` (0x0F) isn't a usable instruction
+        0x06 | 0x0E | 0x16 | 0x1E => Operation::unary(Mnemonic::Push, Width::Word, segment(opcode)),
+        0x07 | 0x17 | 0x1F => Operation::unary(Mnemonic::Pop, Width::Word, segment(opcode)),
+        0x27 => Operation::new(Mnemonic::Daa, Width::Byte),
+        0x2F => Operation::new(Mnemonic::Das, Width::Byte),
+        0x37 => Operation::new(Mnemonic::Aaa, Width::Byte),
+        0x3F => Operation::new(Mnemonic::Aas, Width::Byte),
+        // 0b01000reg inc, 0b01001reg dec, 0b01010reg push, 0b01011reg pop
+        0x40..=0x47 => Operation::unary(Mnemonic::Inc, Width::Word, register_word(opcode)),
+        0x48..=0x4F => Operation::unary(Mnemonic::Dec, Width::Word, register_word(opcode)),
+        0x50..=0x57 => Operation::unary(Mnemonic::Push, Width::Word, register_word(opcode)),
+        0x58..=0x5F => Operation::unary(Mnemonic::Pop, Width::Word, register_word(opcode)),
+        0x80..=0x83 => decode_group_80(reader, opcode)?,
+        0x84..=0x87 => decode_test_xchg(reader, opcode)?,
+        0x8D => decode_load_address(reader, opcode, Mnemonic::Lea)?,
+        0x8F => decode_pop(reader, opcode)?,
+        0x90 => Operation::new(Mnemonic::Nop, Width::Byte),
+        // 0b10010reg
+        0x91..=0x97 => Operation::binary(
+            Mnemonic::Xchg,
+            Width::Word,
+            Operand::Register(Register::Word(RegisterWordOp::AX)),
+            register_word(opcode),
+        ),
+        0x98 => Operation::new(Mnemonic::Cbw, Width::Byte),
+        0x99 => Operation::new(Mnemonic::Cwd, Width::Word),
+        0x9B => Operation::new(Mnemonic::Wait, Width::Byte),
+        0x9C => Operation::new(Mnemonic::Pushf, Width::Word),
+        0x9D => Operation::new(Mnemonic::Popf, Width::Word),
+        0x9E => Operation::new(Mnemonic::Sahf, Width::Byte),
+        0x9F => Operation::new(Mnemonic::Lahf, Width::Byte),
+        // 0b1010100w | data | data if w = 1
+        0xA8 | 0xA9 => {
+            let w = extract_bits(opcode, 7, 8);
+            Operation::binary(
+                Mnemonic::Test,
+                Width::from_w(w),
+                Operand::Register(Register::from_bits(w, 0b000)),
+                Operand::Immediate(reader.next_data(w)?),
+            )
+        }
+        // 0b1010xxxw string instructions
+        0xA4..=0xAF => {
+            let mnemonic = match opcode & 0xFE {
+                0xA4 => Mnemonic::Movs,
+                0xA6 => Mnemonic::Cmps,
+                0xAA => Mnemonic::Stos,
+                0xAC => Mnemonic::Lods,
+                _ => Mnemonic::Scas,
+            };
+            Operation::new(mnemonic, Width::from_w(opcode & 1))
+        }
+        0xC4 => decode_load_address(reader, opcode, Mnemonic::Les)?,
+        0xC5 => decode_load_address(reader, opcode, Mnemonic::Lds)?,
+        0xD0..=0xD3 => decode_shift(reader, opcode)?,
+        0xD4 => decode_ascii_adjust(reader, Mnemonic::Aam)?,
+        0xD5 => decode_ascii_adjust(reader, Mnemonic::Aad)?,
+        0xD7 => Operation::new(Mnemonic::Xlat, Width::Byte),
+        0xE4..=0xE7 | 0xEC..=0xEF => decode_in_out(reader, opcode)?,
+        0xF5 => Operation::new(Mnemonic::Cmc, Width::Byte),
+        0xF6 | 0xF7 => decode_group_f6(reader, opcode)?,
+        0xF8 => Operation::new(Mnemonic::Clc, Width::Byte),
+        0xF9 => Operation::new(Mnemonic::Stc, Width::Byte),
+        0xFA => Operation::new(Mnemonic::Cli, Width::Byte),
+        0xFB => Operation::new(Mnemonic::Sti, Width::Byte),
+        0xFC => Operation::new(Mnemonic::Cld, Width::Byte),
+        0xFD => Operation::new(Mnemonic::Std, Width::Byte),
+        _ => {
+            let instruction =
+                MovInstructionType::from_byte(opcode).ok_or(DecodeError::UnknownOpcode {
+                    offset: reader.start,
+                    opcode,
+                })?;
+            decode_mov(reader, opcode, instruction)?
+        }
+    };
+
+    Ok(operation)
+}
+
+/// Linear sweep over a byte stream, yielding instructions until the data runs out or an
+/// instruction fails to decode.
+pub struct Decoder<'a> {
+    data: &'a [u8],
+    position: usize,
+    failed: bool,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            failed: false,
+        }
+    }
+
+    /// Starts decoding at `position` instead of the beginning of `data`, offsets stay relative to
+    /// the start of `data`.
+    pub fn starting_at(data: &'a [u8], position: usize) -> Self {
+        Self {
+            data,
+            position,
+            failed: false,
+        }
+    }
+
+    /// Offset just past the last instruction decoded so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl Iterator for Decoder<'_> {
+    type Item = Result<Instruction, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.position >= self.data.len() {
+            return None;
+        }
+
+        let result = decode_instruction(self.data, self.position);
+        match &result {
+            Ok(instruction) => self.position += instruction.length,
+            Err(_) => self.failed = true,
+        }
+        Some(result)
+    }
+}
+
+pub fn decode_all(data: &[u8]) -> Result<Vec<Instruction>, DecodeError> {
+    Decoder::new(data).collect()
+}
+
+pub fn disassemble_binary(data: &[u8]) -> Result<String, DecodeError> {
+    let result: Vec<String> = Decoder::new(data)
+        .map(|instruction| instruction.map(|instruction| instruction.to_string()))
+        .collect::<Result<_, _>>()?;
+
+    Ok(result.join("\n"))
+}
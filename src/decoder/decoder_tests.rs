@@ -0,0 +1,780 @@
+use super::*;
+use anyhow::Context;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn listing_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(name)
+}
+
+mod move_instruction_tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::base(0x88)]
+    #[case::d_bit_set(0x89)]
+    #[case::w_bit_set(0x8A)]
+    #[case::both_bits_set(0x8B)]
+    fn test_register_or_memory_to_or_from_register(#[case] opcode: u8) {
+        assert_eq!(
+            MovInstructionType::find_instruction(opcode),
+            MovInstructionType::RegisterOrMemoryToOrFromRegister,
+            "Failed for opcode: {:#010b}",
+            opcode
+        );
+    }
+
+    #[rstest]
+    #[case::byte_operation(0xA0)]
+    #[case::word_operation(0xA1)]
+    fn test_memory_to_accumulator(#[case] opcode: u8) {
+        assert_eq!(
+            MovInstructionType::find_instruction(opcode),
+            MovInstructionType::MemoryToAccumulator,
+            "Failed for opcode: {:#010b}",
+            opcode
+        );
+    }
+
+    #[rstest]
+    #[case::byte_operation(0xA2)]
+    #[case::word_operation(0xA3)]
+    fn test_accumulator_to_memory(#[case] opcode: u8) {
+        assert_eq!(
+            MovInstructionType::find_instruction(opcode),
+            MovInstructionType::AccumulatorToMemory,
+            "Failed for opcode: {:#010b}",
+            opcode
+        );
+    }
+
+    #[rstest]
+    #[case::al(0xB0)]
+    #[case::cl(0xB1)]
+    #[case::dl(0xB2)]
+    #[case::bl(0xB3)]
+    #[case::ah(0xB4)]
+    #[case::ch(0xB5)]
+    #[case::dh(0xB6)]
+    #[case::bh(0xB7)]
+    #[case::ax(0xB8)]
+    #[case::cx(0xB9)]
+    #[case::dx(0xBA)]
+    #[case::bx(0xBB)]
+    #[case::sp(0xBC)]
+    #[case::bp(0xBD)]
+    #[case::si(0xBE)]
+    #[case::di(0xBF)]
+    fn test_immediate_to_register(#[case] opcode: u8) {
+        assert_eq!(
+            MovInstructionType::find_instruction(opcode),
+            MovInstructionType::ImmediateToRegister,
+            "Failed for opcode: {:#010b}",
+            opcode
+        );
+    }
+
+    #[rstest]
+    #[case::byte_operation(0xC6)]
+    #[case::word_operation(0xC7)]
+    fn test_immediate_to_register_or_memory(#[case] opcode: u8) {
+        assert_eq!(
+            MovInstructionType::find_instruction(opcode),
+            MovInstructionType::ImmediateToRegisterOrMemory,
+            "Failed for opcode: {:#010b}",
+            opcode
+        );
+    }
+
+    #[rstest]
+    #[case::byte_operation(0x8E)]
+    #[case::word_operation(0x8F)]
+    fn test_register_or_memory_to_segment_register(#[case] opcode: u8) {
+        assert_eq!(
+            MovInstructionType::find_instruction(opcode),
+            MovInstructionType::RegisterOrMemoryToSegmentRegister,
+            "Failed for opcode: {:#010b}",
+            opcode
+        );
+    }
+
+    #[rstest]
+    #[case::byte_operation(0x8C)]
+    #[case::word_operation(0x8D)]
+    fn test_segment_register_to_register_or_memory(#[case] opcode: u8) {
+        assert_eq!(
+            MovInstructionType::find_instruction(opcode),
+            MovInstructionType::SegmentRegisterToRegisterOrMemory,
+            "Failed for opcode: {:#010b}",
+            opcode
+        );
+    }
+
+    #[rstest]
+    #[case::zero(0x00, "00000000")]
+    #[case::one(0x01, "00000001")]
+    #[case::arbitrary1(0x10, "00010000")]
+    #[case::arbitrary2(0x42, "01000010")]
+    #[case::arbitrary3(0xD0, "11010000")]
+    #[case::arbitrary4(0xFF, "11111111")]
+    #[should_panic(expected = "Unable to determine instruction for byte")]
+    fn test_invalid_opcodes(#[case] opcode: u8, #[case] _binary_repr: &str) {
+        MovInstructionType::find_instruction(opcode);
+    }
+
+    #[test]
+    fn test_all_valid_opcodes_return_some() {
+        for opcode in 0..=255u8 {
+            let expected = match opcode {
+                0x88..=0x8B => Some(MovInstructionType::RegisterOrMemoryToOrFromRegister),
+                0xA0..=0xA1 => Some(MovInstructionType::MemoryToAccumulator),
+                0xA2..=0xA3 => Some(MovInstructionType::AccumulatorToMemory),
+                0xB0..=0xBF => Some(MovInstructionType::ImmediateToRegister),
+                0xC6..=0xC7 => Some(MovInstructionType::ImmediateToRegisterOrMemory),
+                0x8E..=0x8F => Some(MovInstructionType::RegisterOrMemoryToSegmentRegister),
+                0x8C..=0x8D => Some(MovInstructionType::SegmentRegisterToRegisterOrMemory),
+                _ => None,
+            };
+
+            assert_eq!(
+                MovInstructionType::from_byte(opcode),
+                expected,
+                "Incorrect classification for opcode: {:#04X} ({:#010b})",
+                opcode,
+                opcode
+            );
+        }
+    }
+}
+
+#[test]
+fn test_disassemble_single_register_binary() {
+    let bin_file: Vec<u8> = fs::read(listing_path("listing_0037_single_register_mov"))
+        .context("Failed to open listing_0037_single_register_mov")
+        .unwrap();
+
+    let expected_result = r"mov cx, bx";
+    assert_eq!(disassemble_binary(&bin_file).unwrap(), expected_result);
+}
+
+#[test]
+fn test_disassemble_many_register_binary() {
+    let bin_file: Vec<u8> = fs::read(listing_path("listing_0038_many_register_mov"))
+        .context("Failed to open listing_0038_many_register_mov")
+        .unwrap();
+
+    let expected_result = r"mov cx, bx
+mov ch, ah
+mov dx, bx
+mov si, bx
+mov bx, di
+mov al, cl
+mov ch, ch
+mov bx, ax
+mov bx, si
+mov sp, di
+mov bp, ax";
+    assert_eq!(disassemble_binary(&bin_file).unwrap(), expected_result);
+}
+
+#[test]
+fn test_disassemble_immediate_to_register_binary() {
+    let bin_file: Vec<u8> = fs::read(listing_path("listing_0037_single_register_mov"))
+        .context("Failed to open listing_0037_single_register_mov")
+        .unwrap();
+
+    bin_file.iter().for_each(|byte| {
+        print!("{:08b}", byte);
+    });
+
+    let expected_result = r"mov cx, bx";
+    assert_eq!(disassemble_binary(&bin_file).unwrap(), expected_result);
+}
+
+mod decode_instruction_tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::register_to_register(&[0x89, 0xD9], "mov cx, bx")]
+    #[case::register_from_register(&[0x8B, 0xCB], "mov cx, bx")]
+    #[case::byte_immediate(&[0xB1, 0x0C], "mov cl, 12")]
+    #[case::negative_byte_immediate(&[0xB5, 0xF4], "mov ch, -12")]
+    #[case::word_immediate(&[0xBA, 0x6C, 0x0F], "mov dx, 3948")]
+    #[case::negative_word_immediate(&[0xBA, 0x94, 0xF0], "mov dx, -3948")]
+    #[case::source_address(&[0x8A, 0x00], "mov al, [bx + si]")]
+    #[case::source_address_bp_di(&[0x8B, 0x1B], "mov bx, [bp + di]")]
+    #[case::bp_needs_displacement(&[0x8B, 0x56, 0x00], "mov dx, [bp]")]
+    #[case::displacement_8(&[0x8A, 0x60, 0x04], "mov ah, [bx + si + 4]")]
+    #[case::displacement_16(&[0x8A, 0x80, 0x87, 0x13], "mov al, [bx + si + 4999]")]
+    #[case::destination_address(&[0x89, 0x09], "mov [bx + di], cx")]
+    #[case::signed_displacement_8(&[0x8B, 0x41, 0xDB], "mov ax, [bx + di - 37]")]
+    #[case::signed_displacement_16(&[0x89, 0x8C, 0xD4, 0xFE], "mov [si - 300], cx")]
+    #[case::byte_immediate_to_memory(&[0xC6, 0x03, 0x07], "mov byte [bp + di], 7")]
+    #[case::word_immediate_to_memory(
+        &[0xC7, 0x85, 0x85, 0x03, 0x5B, 0x01],
+        "mov word [di + 901], 347"
+    )]
+    #[case::direct_address(&[0x8B, 0x2E, 0x05, 0x00], "mov bp, [5]")]
+    #[case::memory_to_accumulator(&[0xA1, 0xFB, 0x09], "mov ax, [2555]")]
+    #[case::accumulator_to_memory(&[0xA3, 0x0F, 0x00], "mov [15], ax")]
+    #[case::register_to_segment(&[0x8E, 0xD0], "mov ss, ax")]
+    #[case::segment_to_register(&[0x8C, 0xDB], "mov bx, ds")]
+    #[case::segment_override(&[0x26, 0x8A, 0x07], "mov al, [es:bx]")]
+    fn test_decodes_mov(#[case] bytes: &[u8], #[case] expected: &str) {
+        let instruction = decode_instruction(bytes, 0).unwrap();
+
+        assert_eq!(
+            instruction.to_string(),
+            expected,
+            "Failed for bytes: {:02X?}",
+            bytes
+        );
+        assert_eq!(
+            instruction.length,
+            bytes.len(),
+            "Wrong length for: {}",
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::conditional_jump(&[0x75, 0xFC], "jne $-2")]
+    #[case::jump_to_self(&[0xEB, 0xFE], "jmp $+0")]
+    #[case::loop_(&[0xE2, 0x10], "loop $+18")]
+    #[case::loopnz(&[0xE0, 0xFA], "loopnz $-4")]
+    #[case::jcxz(&[0xE3, 0x00], "jcxz $+2")]
+    #[case::near_call(&[0xE8, 0x01, 0x00], "call $+4")]
+    #[case::near_jump(&[0xE9, 0x00, 0x80], "jmp $-32765")]
+    #[case::far_call(&[0x9A, 0x78, 0x56, 0x34, 0x12], "call 4660:22136")]
+    #[case::far_jump(&[0xEA, 0x00, 0x00, 0xFF, 0xFF], "jmp 65535:0")]
+    #[case::indirect_call(&[0xFF, 0xD3], "call bx")]
+    #[case::indirect_jump(&[0xFF, 0x27], "jmp word [bx]")]
+    #[case::indirect_far_jump(&[0xFF, 0x2F], "jmp far [bx]")]
+    #[case::indirect_far_call(&[0xFF, 0x5E, 0x04], "call far [bp + 4]")]
+    #[case::ret(&[0xC3], "ret")]
+    #[case::ret_pop(&[0xC2, 0x04, 0x00], "ret 4")]
+    #[case::retf(&[0xCB], "retf")]
+    #[case::retf_pop(&[0xCA, 0xFF, 0xFF], "retf 65535")]
+    #[case::int(&[0xCD, 0xFF], "int 255")]
+    #[case::int3(&[0xCC], "int3")]
+    #[case::into(&[0xCE], "into")]
+    #[case::iret(&[0xCF], "iret")]
+    #[case::hlt(&[0xF4], "hlt")]
+    #[case::inc_byte(&[0xFE, 0x07], "inc byte [bx]")]
+    #[case::dec_register(&[0xFF, 0xC9], "dec cx")]
+    #[case::push_memory(&[0xFF, 0x32], "push word [bp + si]")]
+    fn test_decodes_control_flow_and_group(#[case] bytes: &[u8], #[case] expected: &str) {
+        let instruction = decode_instruction(bytes, 0).unwrap();
+
+        assert_eq!(
+            instruction.to_string(),
+            expected,
+            "Failed for bytes: {:02X?}",
+            bytes
+        );
+        assert_eq!(
+            instruction.length,
+            bytes.len(),
+            "Wrong length for: {}",
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::add_from_memory(&[0x03, 0x18], "add bx, [bx + si]")]
+    #[case::add_registers(&[0x01, 0xD8], "add ax, bx")]
+    #[case::add_to_accumulator(&[0x05, 0xE8, 0x03], "add ax, 1000")]
+    #[case::add_byte_to_memory(&[0x80, 0x07, 0x22], "add byte [bx], 34")]
+    #[case::sign_extended_immediate(&[0x83, 0xEE, 0xFE], "sub si, -2")]
+    #[case::word_immediate(&[0x81, 0xC6, 0xE8, 0x03], "add si, 1000")]
+    #[case::byte_alias_of_0x80(&[0x82, 0x07, 0x22], "add byte [bx], 34")]
+    #[case::adc(&[0x13, 0x4E, 0x00], "adc cx, [bp]")]
+    #[case::sbb_accumulator(&[0x1C, 0x05], "sbb al, 5")]
+    #[case::cmp_direct_address(&[0x80, 0x3E, 0xE2, 0x12, 0x14], "cmp byte [4834], 20")]
+    #[case::and(&[0x24, 0x0F], "and al, 15")]
+    #[case::or(&[0x08, 0xC3], "or bl, al")]
+    #[case::xor(&[0x34, 0xFF], "xor al, -1")]
+    #[case::test_register(&[0x84, 0x07], "test [bx], al")]
+    #[case::test_accumulator(&[0xA9, 0x34, 0x12], "test ax, 4660")]
+    #[case::test_immediate(&[0xF7, 0x06, 0x00, 0x10, 0x01, 0x00], "test word [4096], 1")]
+    #[case::inc_register(&[0x40], "inc ax")]
+    #[case::dec_register_short(&[0x4F], "dec di")]
+    #[case::not(&[0xF6, 0xD0], "not al")]
+    #[case::neg(&[0xF7, 0x5E, 0x02], "neg word [bp + 2]")]
+    #[case::mul(&[0xF6, 0xE3], "mul bl")]
+    #[case::imul(&[0xF7, 0x2F], "imul word [bx]")]
+    #[case::div(&[0xF6, 0x76, 0x02], "div byte [bp + 2]")]
+    #[case::idiv(&[0xF7, 0xF9], "idiv cx")]
+    #[case::shl_by_one(&[0xD0, 0xE0], "shl al, 1")]
+    #[case::shr_by_one(&[0xD1, 0xE8], "shr ax, 1")]
+    #[case::sar_by_cl(&[0xD2, 0xF8], "sar al, cl")]
+    #[case::rol_memory_by_cl(&[0xD3, 0x07], "rol word [bx], cl")]
+    #[case::rcr(&[0xD2, 0xDB], "rcr bl, cl")]
+    #[case::daa(&[0x27], "daa")]
+    #[case::aas(&[0x3F], "aas")]
+    #[case::aam(&[0xD4, 0x0A], "aam")]
+    #[case::aad_other_base(&[0xD5, 0x10], "aad 16")]
+    #[case::cbw(&[0x98], "cbw")]
+    #[case::cwd(&[0x99], "cwd")]
+    fn test_decodes_arithmetic_and_logic(#[case] bytes: &[u8], #[case] expected: &str) {
+        let instruction = decode_instruction(bytes, 0).unwrap();
+
+        assert_eq!(instruction.to_string(), expected);
+        assert_eq!(
+            instruction.length,
+            bytes.len(),
+            "Wrong length for: {expected}"
+        );
+    }
+
+    #[rstest]
+    #[case::push_register(&[0x50], "push ax")]
+    #[case::pop_register(&[0x5B], "pop bx")]
+    #[case::pop_memory(&[0x8F, 0x06, 0x00, 0x10], "pop word [4096]")]
+    #[case::push_segment(&[0x0E], "push cs")]
+    #[case::pop_segment(&[0x1F], "pop ds")]
+    #[case::pushf(&[0x9C], "pushf")]
+    #[case::popf(&[0x9D], "popf")]
+    #[case::xchg_memory(&[0x86, 0x07], "xchg al, [bx]")]
+    #[case::xchg_accumulator(&[0x91], "xchg ax, cx")]
+    #[case::nop(&[0x90], "nop")]
+    #[case::lea(&[0x8D, 0x81, 0x8C, 0x05], "lea ax, [bx + di + 1420]")]
+    #[case::les(&[0xC4, 0x5E, 0xF9], "les bx, [bp - 7]")]
+    #[case::lds(&[0xC5, 0x1C], "lds bx, [si]")]
+    #[case::in_fixed_port(&[0xE4, 0xC8], "in al, 200")]
+    #[case::in_variable_port(&[0xED], "in ax, dx")]
+    #[case::out_fixed_port(&[0xE7, 0x43], "out 67, ax")]
+    #[case::out_variable_port(&[0xEE], "out dx, al")]
+    #[case::xlat(&[0xD7], "xlat")]
+    #[case::lahf(&[0x9F], "lahf")]
+    #[case::sahf(&[0x9E], "sahf")]
+    #[case::movsb(&[0xA4], "movsb")]
+    #[case::cmpsw(&[0xA7], "cmpsw")]
+    #[case::scasb(&[0xAE], "scasb")]
+    #[case::lodsw(&[0xAD], "lodsw")]
+    #[case::stosb(&[0xAA], "stosb")]
+    #[case::rep(&[0xF3, 0xA5], "rep movsw")]
+    #[case::repne(&[0xF2, 0xAE], "repne scasb")]
+    #[case::lock(&[0xF0, 0x87, 0x07], "lock xchg ax, [bx]")]
+    #[case::string_segment_override(&[0x26, 0xA4], "es movsb")]
+    #[case::clc(&[0xF8], "clc")]
+    #[case::cmc(&[0xF5], "cmc")]
+    #[case::stc(&[0xF9], "stc")]
+    #[case::cld(&[0xFC], "cld")]
+    #[case::std(&[0xFD], "std")]
+    #[case::cli(&[0xFA], "cli")]
+    #[case::sti(&[0xFB], "sti")]
+    #[case::wait(&[0x9B], "wait")]
+    fn test_decodes_stack_io_string_and_flag_instructions(
+        #[case] bytes: &[u8],
+        #[case] expected: &str,
+    ) {
+        let instruction = decode_instruction(bytes, 0).unwrap();
+
+        assert_eq!(instruction.to_string(), expected);
+        assert_eq!(
+            instruction.length,
+            bytes.len(),
+            "Wrong length for: {expected}"
+        );
+    }
+
+    #[test]
+    fn test_decodes_prefixes() {
+        let instruction = decode_instruction(&[0xF0, 0xF3, 0x2E, 0xA6], 0).unwrap();
+
+        assert_eq!(instruction.mnemonic, Mnemonic::Cmps);
+        assert_eq!(
+            instruction.prefixes,
+            Prefixes {
+                segment: Some(SegmentRegister::CS),
+                lock: true,
+                repeat: Some(Repeat::Rep),
+            }
+        );
+    }
+
+    #[rstest]
+    #[case::bx(AddressBase::Bx, SegmentRegister::DS)]
+    #[case::bp(AddressBase::Bp, SegmentRegister::SS)]
+    #[case::bp_si(AddressBase::BpSi, SegmentRegister::SS)]
+    #[case::bx_di(AddressBase::BxDi, SegmentRegister::DS)]
+    fn test_default_segment(#[case] base: AddressBase, #[case] expected: SegmentRegister) {
+        let address = EffectiveAddress {
+            base: Some(base),
+            displacement: 0,
+            has_displacement: false,
+        };
+        assert_eq!(address.default_segment(), expected);
+    }
+
+    #[rstest]
+    #[case::same_byte(
+        Register::Byte(RegisterByteOp::AL),
+        Register::Byte(RegisterByteOp::AL),
+        true
+    )]
+    #[case::halves(
+        Register::Byte(RegisterByteOp::AL),
+        Register::Byte(RegisterByteOp::AH),
+        false
+    )]
+    #[case::low_half(
+        Register::Byte(RegisterByteOp::AL),
+        Register::Word(RegisterWordOp::AX),
+        true
+    )]
+    #[case::high_half(
+        Register::Word(RegisterWordOp::DX),
+        Register::Byte(RegisterByteOp::DH),
+        true
+    )]
+    #[case::other_word(
+        Register::Byte(RegisterByteOp::BH),
+        Register::Word(RegisterWordOp::CX),
+        false
+    )]
+    #[case::not_a_half(
+        Register::Byte(RegisterByteOp::AH),
+        Register::Word(RegisterWordOp::SP),
+        false
+    )]
+    fn test_register_overlaps(#[case] a: Register, #[case] b: Register, #[case] expected: bool) {
+        assert_eq!(a.overlaps(b), expected);
+        assert_eq!(b.overlaps(a), expected);
+    }
+
+    #[rstest]
+    #[case::sequential(&[0x89, 0xD9], Flow::Sequential)]
+    #[case::conditional_jump(&[0x74, 0x00], Flow::ConditionalJump)]
+    #[case::loop_(&[0xE2, 0x00], Flow::ConditionalJump)]
+    #[case::jump(&[0xFF, 0xE3], Flow::Jump)]
+    #[case::far_call(&[0x9A, 0x00, 0x00, 0x00, 0x00], Flow::Call)]
+    #[case::iret(&[0xCF], Flow::Return)]
+    #[case::int(&[0xCD, 0x21], Flow::Interrupt)]
+    #[case::hlt(&[0xF4], Flow::Halt)]
+    fn test_flow(#[case] bytes: &[u8], #[case] expected: Flow) {
+        assert_eq!(
+            decode_instruction(bytes, 0).unwrap().mnemonic.flow(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::forward(&[0x00, 0x00, 0x74, 0x02], 2, Some(6))]
+    #[case::backward(&[0x00, 0x00, 0x74, 0xFC], 2, Some(0))]
+    #[case::before_the_image(&[0x00, 0x00, 0x74, 0xFB], 2, None)]
+    #[case::near_call(&[0xE8, 0x10, 0x00], 0, Some(0x13))]
+    #[case::indirect(&[0xFF, 0xE3], 0, None)]
+    #[case::far(&[0xEA, 0x00, 0x00, 0x00, 0x00], 0, None)]
+    fn test_branch_target(
+        #[case] bytes: &[u8],
+        #[case] offset: usize,
+        #[case] expected: Option<usize>,
+    ) {
+        let instruction = decode_instruction(bytes, offset).unwrap();
+        assert_eq!(instruction.branch_target(), expected);
+    }
+
+    #[test]
+    fn test_decodes_structured_operands() {
+        let instruction = decode_instruction(&[0x89, 0x8C, 0xD4, 0xFE], 0).unwrap();
+
+        assert_eq!(instruction.mnemonic, Mnemonic::Mov);
+        assert_eq!(instruction.width, Width::Word);
+        assert_eq!(
+            instruction.destination,
+            Some(Operand::Memory(EffectiveAddress {
+                base: Some(AddressBase::Si),
+                displacement: -300,
+                has_displacement: true,
+            }))
+        );
+        assert_eq!(
+            instruction.source,
+            Some(Operand::Register(Register::Word(RegisterWordOp::CX)))
+        );
+    }
+
+    #[rstest]
+    #[case::missing_mod_reg_rm(&[0x89], DecodeError::UnexpectedEnd { offset: 0 })]
+    #[case::missing_displacement(&[0x8A, 0x80, 0x87], DecodeError::UnexpectedEnd { offset: 0 })]
+    #[case::missing_data(&[0xB9, 0x0C], DecodeError::UnexpectedEnd { offset: 0 })]
+    #[case::dangling_prefix(&[0x26], DecodeError::UnexpectedEnd { offset: 0 })]
+    #[case::unknown_opcode(&[0x0F], DecodeError::UnknownOpcode { offset: 0, opcode: 0x0F })]
+    #[case::undefined_opcode(&[0x60], DecodeError::UnknownOpcode { offset: 0, opcode: 0x60 })]
+    #[case::pop_reg_field(
+        &[0x8F, 0xC8],
+        DecodeError::InvalidEncoding { offset: 0, opcode: 0x8F, reason: "reg field must be 000" }
+    )]
+    #[case::lea_from_register(
+        &[0x8D, 0xC3],
+        DecodeError::InvalidEncoding { offset: 0, opcode: 0x8D, reason: "source must be in memory" }
+    )]
+    #[case::undefined_shift(
+        &[0xD0, 0xF0],
+        DecodeError::InvalidEncoding { offset: 0, opcode: 0xD0, reason: "undefined operation in reg field" }
+    )]
+    #[case::undefined_group_f6(
+        &[0xF6, 0xC8],
+        DecodeError::InvalidEncoding { offset: 0, opcode: 0xF6, reason: "undefined operation in reg field" }
+    )]
+    #[case::missing_sign_extended_data(&[0x83, 0xC6], DecodeError::UnexpectedEnd { offset: 0 })]
+    #[case::missing_far_pointer(&[0xEA, 0x00, 0x00, 0x00], DecodeError::UnexpectedEnd { offset: 0 })]
+    #[case::undefined_group_operation(
+        &[0xFF, 0x3F],
+        DecodeError::InvalidEncoding { offset: 0, opcode: 0xFF, reason: "undefined operation in reg field" }
+    )]
+    #[case::byte_call(
+        &[0xFE, 0xD3],
+        DecodeError::InvalidEncoding { offset: 0, opcode: 0xFE, reason: "undefined operation in reg field" }
+    )]
+    #[case::far_call_through_register(
+        &[0xFF, 0xDB],
+        DecodeError::InvalidEncoding { offset: 0, opcode: 0xFF, reason: "far pointer must be in memory" }
+    )]
+    #[case::immediate_reg_field(
+        &[0xC6, 0x08, 0x07],
+        DecodeError::InvalidEncoding { offset: 0, opcode: 0xC6, reason: "reg field must be 000" }
+    )]
+    fn test_rejects_invalid_input(#[case] bytes: &[u8], #[case] expected: DecodeError) {
+        assert_eq!(decode_instruction(bytes, 0), Err(expected));
+    }
+
+    #[test]
+    fn test_decoder_stops_at_first_error() {
+        let mut decoder = Decoder::new(&[0x89, 0xD9, 0xB1, 0x0C, 0x89]);
+
+        assert_eq!(decoder.next().unwrap().unwrap().offset, 0);
+        assert_eq!(decoder.next().unwrap().unwrap().offset, 2);
+        assert_eq!(
+            decoder.next(),
+            Some(Err(DecodeError::UnexpectedEnd { offset: 4 }))
+        );
+        assert_eq!(decoder.next(), None);
+        assert_eq!(decoder.position(), 4);
+    }
+
+    /// Same invariants as the `decode` fuzz target, checked exhaustively over short inputs.
+    #[test]
+    fn test_lengths_account_for_consumed_bytes() {
+        let tails: [&[u8]; 3] = [&[], &[0x00, 0x00, 0x00, 0x00], &[0xFF, 0xFF, 0xFF, 0xFF]];
+
+        for first in 0..=255u8 {
+            for second in 0..=255u8 {
+                for tail in tails {
+                    let data = [&[first, second], tail].concat();
+                    let mut decoder = Decoder::new(&data);
+                    let mut consumed = 0;
+
+                    for result in decoder.by_ref() {
+                        match result {
+                            Ok(instruction) => {
+                                assert_eq!(instruction.offset, consumed);
+                                assert!(instruction.length > 0);
+                                consumed += instruction.length;
+                                assert!(consumed <= data.len(), "Read past the end of {data:02X?}");
+                            }
+                            Err(error) => assert_eq!(error.offset(), consumed),
+                        }
+                    }
+
+                    assert_eq!(decoder.position(), consumed, "Mismatch for {data:02X?}");
+                }
+            }
+        }
+    }
+}
+
+mod golden_listing_tests {
+    use super::*;
+    use similar::TextDiff;
+    use std::collections::HashMap;
+
+    /// Names an operand can have that mean a register, which tells the operand size.
+    const REGISTERS: [&str; 20] = [
+        "al", "cl", "dl", "bl", "ah", "ch", "dh", "bh", "ax", "cx", "dx", "bx", "sp", "bp", "si",
+        "di", "es", "cs", "ss", "ds",
+    ];
+
+    /// Every `listing_*` binary in the crate root that has a sibling `.asm` source.
+    fn discover_listings() -> Vec<(PathBuf, PathBuf)> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut listings: Vec<(PathBuf, PathBuf)> = fs::read_dir(root)
+            .expect("Failed to read crate root")
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().is_none())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("listing_"))
+            })
+            .filter_map(|binary| {
+                let source = binary.with_extension("asm");
+                source.is_file().then_some((binary, source))
+            })
+            .collect();
+
+        listings.sort();
+        listings
+    }
+
+    /// Strips comments, blank lines and the `bits 16` directive, and collapses whitespace so
+    /// the assembler source and the decoder output can be compared line by line.
+    fn normalize(text: &str) -> String {
+        text.lines()
+            .map(|line| line.split(';').next().unwrap_or_default())
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .map(|line| line.to_lowercase())
+            .filter(|line| !line.is_empty() && line != "bits 16")
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Rewrites what the course sources spell differently from the decoder into the decoder's
+    /// spelling, given the `instructions` decoded from the assembled listing: labels become
+    /// `$`-relative targets, `jz` and `jnz` become `je` and `jne`, products of constants are
+    /// worked out, a zero displacement goes, sizes go where a register gives the size and byte
+    /// immediates are signed. Takes the output of [`normalize`].
+    fn course_spelling(source: &str, instructions: &[Instruction]) -> String {
+        let mut labels = HashMap::new();
+        let mut lines = vec![];
+        for line in source.lines() {
+            match line.strip_suffix(':') {
+                Some(label) => {
+                    labels.insert(label, lines.len());
+                }
+                None => lines.push(line),
+            }
+        }
+
+        lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| {
+                let (mnemonic, operands) = line.split_once(' ').unwrap_or((line, ""));
+                let mnemonic = match mnemonic {
+                    "jz" => "je",
+                    "jnz" => "jne",
+                    mnemonic => mnemonic,
+                };
+                let mut operands: Vec<String> = operands
+                    .split(", ")
+                    .filter(|operand| !operand.is_empty())
+                    .map(|operand| {
+                        let target = labels
+                            .get(operand)
+                            .and_then(|&target| instructions.get(target));
+                        if let (Some(target), Some(from)) = (target, instructions.get(index)) {
+                            return format!("${:+}", target.offset as i64 - from.offset as i64);
+                        }
+                        let product = operand
+                            .split('*')
+                            .map(|factor| factor.parse::<i64>())
+                            .collect::<Result<Vec<_>, _>>();
+                        match product {
+                            Ok(factors) if factors.len() > 1 => {
+                                factors.iter().product::<i64>().to_string()
+                            }
+                            _ => operand.replace(" + 0]", "]"),
+                        }
+                    })
+                    .collect();
+
+                if operands
+                    .iter()
+                    .any(|operand| REGISTERS.contains(&operand.as_str()))
+                {
+                    for operand in &mut operands {
+                        for size in ["byte ", "word "] {
+                            *operand = operand.replacen(size, "", 1);
+                        }
+                    }
+                } else if operands
+                    .first()
+                    .is_some_and(|operand| operand.starts_with("byte "))
+                    && let Some(Ok(value @ 128..=255)) =
+                        operands.last().map(|operand| operand.parse::<i32>())
+                {
+                    *operands.last_mut().unwrap() = (value - 256).to_string();
+                }
+
+                match operands.is_empty() {
+                    true => mnemonic.to_string(),
+                    false => format!("{mnemonic} {}", operands.join(", ")),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_course_spelling_matches_the_decoder() {
+        // mov cx, 0 / add cx, 1 / jnz $-3 / mov [bp], cx / mov byte [bp + 3], -1
+        let instructions = decode_all(&[
+            0xB9, 0x00, 0x00, 0x83, 0xC1, 0x01, 0x75, 0xFB, 0x89, 0x4E, 0x00, 0xC6, 0x46, 0x03,
+            0xFF,
+        ])
+        .unwrap();
+        let source = "mov cx, 16*0\nagain:\nadd cx, 1\njnz again\nmov word [bp + 0], cx\n\
+                      mov byte [bp + 3], 255";
+
+        assert_eq!(
+            course_spelling(source, &instructions),
+            "mov cx, 0\nadd cx, 1\njne $-3\nmov [bp], cx\nmov byte [bp + 3], -1"
+        );
+    }
+
+    #[test]
+    fn test_normalize_strips_comments_and_directives() {
+        let source = "; header\n\nbits 16\n\nMOV  cx,   bx ; trailing\nmov ch, ah";
+        assert_eq!(normalize(source), "mov cx, bx\nmov ch, ah");
+    }
+
+    #[test]
+    fn test_discovers_listings_with_sources() {
+        let listings = discover_listings();
+        assert!(!listings.is_empty(), "No listings found in the crate root");
+        assert!(
+            listings
+                .iter()
+                .all(|(binary, source)| binary.with_extension("asm") == *source),
+            "Listings were paired with the wrong sources: {:?}",
+            listings
+        );
+    }
+
+    #[test]
+    fn test_disassembly_matches_listing_sources() {
+        let mut failures = vec![];
+
+        for (binary, source) in discover_listings() {
+            let bin_file = fs::read(&binary)
+                .with_context(|| format!("Failed to open {}", binary.display()))
+                .unwrap();
+            let asm_file = fs::read_to_string(&source)
+                .with_context(|| format!("Failed to open {}", source.display()))
+                .unwrap();
+
+            let instructions = decode_all(&bin_file).unwrap();
+            let expected = course_spelling(&normalize(&asm_file), &instructions);
+            let actual = normalize(&disassemble_binary(&bin_file).unwrap());
+
+            if expected != actual {
+                let diff = TextDiff::from_lines(&expected, &actual)
+                    .unified_diff()
+                    .header(&source.display().to_string(), &binary.display().to_string())
+                    .to_string();
+                failures.push(diff);
+            }
+        }
+
+        assert!(
+            failures.is_empty(),
+            "Disassembly differs from the listing sources:\n{}",
+            failures.join("\n")
+        );
+    }
+}
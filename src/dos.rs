@@ -0,0 +1,262 @@
+use crate::cpu::Cpu;
+use crate::decoder::{RegisterWordOp, SegmentRegister};
+use crate::memory::{Memory, physical_address};
+use std::fmt;
+use std::ops::Range;
+
+#[cfg(test)]
+mod dos_tests;
+
+/// Segment of the program segment prefix, programs are loaded straight after it.
+pub const PSP_SEGMENT: u16 = 0x1000;
+/// First segment past conventional memory, where the video buffers start.
+pub const MEMORY_TOP: u16 = 0xA000;
+/// Where a `.COM` image starts in its segment, after the PSP.
+pub const COM_OFFSET: u16 = 0x100;
+/// Largest `.COM` image, one segment less the PSP and a word of stack.
+pub const COM_LIMIT: usize = 0x10000 - COM_OFFSET as usize - 2;
+
+/// The two signatures DOS accepts at the start of an executable.
+const EXE_SIGNATURES: [&[u8; 2]; 2] = [b"MZ", b"ZM"];
+const EXE_HEADER_SIZE: usize = 0x1C;
+const PARAGRAPH: usize = 16;
+const PAGE: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgramKind {
+    Com,
+    Exe,
+}
+
+impl ProgramKind {
+    /// What DOS runs a file as, which goes by its first two bytes rather than its extension.
+    pub fn detect(data: &[u8]) -> Self {
+        match data.first_chunk::<2>() {
+            Some(signature) if EXE_SIGNATURES.contains(&signature) => Self::Exe,
+            _ => Self::Com,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// The program doesn't fit where DOS would put it.
+    TooLarge { size: usize, limit: usize },
+    /// The MZ header is cut short or doesn't describe the file.
+    BadHeader(&'static str),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge { size, limit } => write!(
+                f,
+                "the {size}-byte program is larger than the {limit} bytes available"
+            ),
+            Self::BadHeader(reason) => write!(f, "invalid MZ header: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// The fields of an MZ header the loader uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExeHeader {
+    /// Where the load image starts in the file.
+    pub header_size: usize,
+    /// Where the load image ends in the file, from the page counts.
+    pub file_size: usize,
+    pub ss: u16,
+    pub sp: u16,
+    pub ip: u16,
+    pub cs: u16,
+    /// `(offset, segment)` of every word that holds a segment, relative to the start of the
+    /// image, that gets the load segment added to it.
+    pub relocations: Vec<(u16, u16)>,
+}
+
+impl ExeHeader {
+    pub fn parse(data: &[u8]) -> Result<Self, LoadError> {
+        if data.len() < EXE_HEADER_SIZE {
+            return Err(LoadError::BadHeader("the file is shorter than the header"));
+        }
+        if ProgramKind::detect(data) != ProgramKind::Exe {
+            return Err(LoadError::BadHeader("the file doesn't start with MZ"));
+        }
+        let word = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+
+        let (last_page, pages) = (usize::from(word(0x02)), usize::from(word(0x04)));
+        let file_size = match last_page {
+            0 => pages * PAGE,
+            _ => pages.saturating_sub(1) * PAGE + last_page,
+        };
+        let header_size = usize::from(word(0x08)) * PARAGRAPH;
+        if header_size < EXE_HEADER_SIZE || header_size > file_size {
+            return Err(LoadError::BadHeader(
+                "the header size is outside the file size",
+            ));
+        }
+        if file_size > data.len() {
+            return Err(LoadError::BadHeader("the file is shorter than its pages"));
+        }
+
+        let (count, table) = (usize::from(word(0x06)), usize::from(word(0x18)));
+        let relocations = data
+            .get(table..table + count * 4)
+            .ok_or(LoadError::BadHeader("the relocation table is cut short"))?
+            .chunks_exact(4)
+            .map(|entry| {
+                (
+                    u16::from_le_bytes([entry[0], entry[1]]),
+                    u16::from_le_bytes([entry[2], entry[3]]),
+                )
+            })
+            .collect();
+
+        Ok(Self {
+            header_size,
+            file_size,
+            ss: word(0x0E),
+            sp: word(0x10),
+            ip: word(0x14),
+            cs: word(0x16),
+            relocations,
+        })
+    }
+
+    /// The bytes DOS copies into memory.
+    pub fn image<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[self.header_size..self.file_size]
+    }
+
+    /// Where execution starts, as an offset into the image.
+    pub fn entry(&self) -> usize {
+        usize::from(self.cs) * PARAGRAPH + usize::from(self.ip)
+    }
+}
+
+/// The code of a program without any file header, the offset in its segment it's loaded at and
+/// the offset execution starts at, counted the same way. Disassembling this from its origin
+/// instead of the file lines offsets up with the image DOS loads.
+pub fn image(data: &[u8]) -> Result<(&[u8], usize, usize), LoadError> {
+    match ProgramKind::detect(data) {
+        ProgramKind::Com => Ok((data, usize::from(COM_OFFSET), usize::from(COM_OFFSET))),
+        ProgramKind::Exe => {
+            let header = ExeHeader::parse(data)?;
+            Ok((header.image(data), 0, header.entry()))
+        }
+    }
+}
+
+/// A program in memory, ready to run from `cs:ip`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub kind: ProgramKind,
+    pub cpu: Cpu,
+    pub memory: Memory,
+    /// Physical addresses of the load image.
+    pub image: Range<usize>,
+}
+
+impl Program {
+    /// Physical addresses the program runs in: the load image, and for a `.COM` the PSP before
+    /// it too, so a `ret` from the program reaches the `int 20h` there.
+    pub fn running(&self) -> Range<usize> {
+        match self.kind {
+            ProgramKind::Com => physical_address(PSP_SEGMENT, 0)..self.image.end,
+            ProgramKind::Exe => self.image.clone(),
+        }
+    }
+}
+
+/// Loads `data` the way DOS would, as an MZ executable if it starts with one's signature and
+/// as a `.COM` image otherwise.
+pub fn load(data: &[u8]) -> Result<Program, LoadError> {
+    match ProgramKind::detect(data) {
+        ProgramKind::Com => load_com(data),
+        ProgramKind::Exe => load_exe(data),
+    }
+}
+
+/// Copies `data` to offset 0x100 of the PSP's segment, which every segment register points at.
+/// SP starts at the top of the segment with a zero word on the stack, so a `ret` from the
+/// program lands on the `int 20h` at the start of the PSP when it runs over
+/// [`Program::running`].
+pub fn load_com(data: &[u8]) -> Result<Program, LoadError> {
+    if data.len() > COM_LIMIT {
+        return Err(LoadError::TooLarge {
+            size: data.len(),
+            limit: COM_LIMIT,
+        });
+    }
+    let mut memory = Memory::new();
+    write_psp(&mut memory);
+    memory.load(PSP_SEGMENT, COM_OFFSET, data);
+
+    let mut cpu = Cpu::new();
+    for segment in SegmentRegister::ALL {
+        cpu.set_segment(segment, PSP_SEGMENT);
+    }
+    cpu.ip = COM_OFFSET;
+    cpu.set_word(RegisterWordOp::SP, 0xFFFE);
+    memory.write_word(PSP_SEGMENT, 0xFFFE, 0);
+
+    let start = physical_address(PSP_SEGMENT, COM_OFFSET);
+    Ok(Program {
+        kind: ProgramKind::Com,
+        cpu,
+        memory,
+        image: start..start + data.len(),
+    })
+}
+
+/// Copies the load image to the paragraph after the PSP, adds that segment to every word the
+/// relocation table lists and to CS and SS. DS and ES point at the PSP.
+pub fn load_exe(data: &[u8]) -> Result<Program, LoadError> {
+    let header = ExeHeader::parse(data)?;
+    let image = header.image(data);
+    let load_segment = PSP_SEGMENT + (COM_OFFSET / PARAGRAPH as u16);
+    let start = physical_address(load_segment, 0);
+    let limit = physical_address(MEMORY_TOP, 0) - start;
+    if image.len() > limit {
+        return Err(LoadError::TooLarge {
+            size: image.len(),
+            limit,
+        });
+    }
+
+    let mut memory = Memory::new();
+    write_psp(&mut memory);
+    for (address, &byte) in (start..).zip(image) {
+        memory.store(address, byte);
+    }
+    for &(offset, segment) in &header.relocations {
+        let segment = load_segment.wrapping_add(segment);
+        let value = memory.read_word(segment, offset);
+        memory.write_word(segment, offset, value.wrapping_add(load_segment));
+    }
+
+    let mut cpu = Cpu::new();
+    cpu.set_segment(SegmentRegister::CS, load_segment.wrapping_add(header.cs));
+    cpu.set_segment(SegmentRegister::SS, load_segment.wrapping_add(header.ss));
+    cpu.set_segment(SegmentRegister::DS, PSP_SEGMENT);
+    cpu.set_segment(SegmentRegister::ES, PSP_SEGMENT);
+    cpu.ip = header.ip;
+    cpu.set_word(RegisterWordOp::SP, header.sp);
+
+    Ok(Program {
+        kind: ProgramKind::Exe,
+        cpu,
+        memory,
+        image: start..start + image.len(),
+    })
+}
+
+/// The parts of the program segment prefix programs look at: `int 20h` at the start, the top
+/// of memory, and an empty command line.
+fn write_psp(memory: &mut Memory) {
+    memory.load(PSP_SEGMENT, 0x00, &[0xCD, 0x20]);
+    memory.write_word(PSP_SEGMENT, 0x02, MEMORY_TOP);
+    memory.load(PSP_SEGMENT, 0x80, &[0, b'\r']);
+}
@@ -0,0 +1,217 @@
+use super::*;
+use crate::interrupts::Interrupts;
+use crate::sim::{RunOptions, run_loaded};
+use crate::syntax::Nasm;
+use rstest::rstest;
+
+/// An MZ file with a 2-paragraph header and one relocation, whose image is
+///
+/// ```text
+/// mov ax, seg data    ; b8 00 00, relocated
+/// mov ds, ax          ; 8e d8
+/// mov bx, [0]         ; 8b 1e 00 00
+/// hlt                 ; f4
+/// ```
+///
+/// padded to a paragraph, then a data paragraph holding 0x1234. CS:IP is 0000:0000 and SS:SP is
+/// 0002:0100.
+fn exe() -> Vec<u8> {
+    let mut file = vec![0; 0x20];
+    file[0..2].copy_from_slice(b"MZ");
+    let words: [(usize, u16); 8] = [
+        (0x02, 0x20 + 0x20), // bytes in the last page
+        (0x04, 1),           // pages
+        (0x06, 1),           // relocations
+        (0x08, 2),           // header paragraphs
+        (0x0E, 0x0002),      // ss
+        (0x10, 0x0100),      // sp
+        (0x14, 0x0000),      // ip
+        (0x18, 0x001C),      // relocation table
+    ];
+    for (offset, value) in words {
+        file[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+    // patch the immediate of the first mov at 0000:0001 with 0x0001 + the load segment
+    file[0x1C..0x20].copy_from_slice(&[0x01, 0x00, 0x00, 0x00]);
+
+    let code = [0xB8, 0x01, 0x00, 0x8E, 0xD8, 0x8B, 0x1E, 0x00, 0x00, 0xF4];
+    file.extend(code);
+    file.resize(0x30, 0);
+    file.extend([0x34, 0x12]);
+    file.resize(0x40, 0);
+    file
+}
+
+#[rstest]
+#[case::mz(b"MZ\x00", ProgramKind::Exe)]
+#[case::zm(b"ZM\x00", ProgramKind::Exe)]
+#[case::flat(b"\xB8\x01\x00", ProgramKind::Com)]
+#[case::one_byte(b"M", ProgramKind::Com)]
+#[case::empty(b"", ProgramKind::Com)]
+fn test_detects_the_kind_by_signature(#[case] data: &[u8], #[case] expected: ProgramKind) {
+    assert_eq!(ProgramKind::detect(data), expected);
+}
+
+#[test]
+fn test_com_goes_after_the_psp() {
+    let program = load_com(&[0xB8, 0x01, 0x00]).unwrap();
+
+    assert_eq!(program.kind, ProgramKind::Com);
+    for segment in SegmentRegister::ALL {
+        assert_eq!(program.cpu.segment(segment), PSP_SEGMENT);
+    }
+    assert_eq!(program.cpu.ip, 0x100);
+    assert_eq!(program.cpu.word(RegisterWordOp::SP), 0xFFFE);
+    assert_eq!(program.image, 0x10100..0x10103);
+    assert_eq!(program.memory.bytes()[0x10100..0x10103], [0xB8, 0x01, 0x00]);
+    // int 20h, the top of memory and an empty command line
+    assert_eq!(
+        program.memory.bytes()[0x10000..0x10004],
+        [0xCD, 0x20, 0x00, 0xA0]
+    );
+    assert_eq!(program.memory.bytes()[0x10080..0x10082], [0, b'\r']);
+    assert_eq!(program.memory.read_word(PSP_SEGMENT, 0xFFFE), 0);
+}
+
+#[test]
+fn test_com_returning_exits_through_the_psp() {
+    // mov ax, 1 / ret
+    let mut program = load_com(&[0xB8, 0x01, 0x00, 0xC3]).unwrap();
+    let mut interrupts = Interrupts::new();
+    let mut trace = vec![];
+    run_loaded(
+        &program.running(),
+        &mut program.cpu,
+        &mut program.memory,
+        RunOptions {
+            interrupts: Some(&mut interrupts),
+            ..RunOptions::default()
+        },
+        &Nasm::default(),
+        &mut trace,
+    )
+    .unwrap();
+
+    assert_eq!(
+        String::from_utf8(trace).unwrap(),
+        "\
+mov ax, 1 ; ax:0x0->0x1 ip:0x100->0x103
+ret ; sp:0xfffe->0x0 ip:0x103->0x0
+int 32 ; ip:0x0->0x2
+"
+    );
+    assert_eq!(interrupts.exit_code(), Some(0));
+}
+
+#[test]
+fn test_exe_runs_in_its_image() {
+    let program = load_exe(&exe()).unwrap();
+
+    assert_eq!(program.running(), program.image);
+}
+
+#[test]
+fn test_com_size_limit() {
+    assert!(load_com(&vec![0x90; COM_LIMIT]).is_ok());
+    assert_eq!(
+        load_com(&vec![0x90; COM_LIMIT + 1]).unwrap_err(),
+        LoadError::TooLarge {
+            size: COM_LIMIT + 1,
+            limit: COM_LIMIT,
+        }
+    );
+}
+
+#[test]
+fn test_parses_the_exe_header() {
+    let header = ExeHeader::parse(&exe()).unwrap();
+
+    assert_eq!(
+        header,
+        ExeHeader {
+            header_size: 0x20,
+            file_size: 0x40,
+            ss: 2,
+            sp: 0x100,
+            ip: 0,
+            cs: 0,
+            relocations: vec![(1, 0)],
+        }
+    );
+    assert_eq!(header.image(&exe())[..3], [0xB8, 0x01, 0x00]);
+    assert_eq!(header.entry(), 0);
+}
+
+#[test]
+fn test_exe_is_relocated_after_the_psp() {
+    let program = load_exe(&exe()).unwrap();
+    let load_segment = PSP_SEGMENT + 0x10;
+
+    assert_eq!(program.kind, ProgramKind::Exe);
+    assert_eq!(program.image, 0x10100..0x10120);
+    assert_eq!(program.cpu.segment(SegmentRegister::CS), load_segment);
+    assert_eq!(program.cpu.segment(SegmentRegister::SS), load_segment + 2);
+    assert_eq!(program.cpu.segment(SegmentRegister::DS), PSP_SEGMENT);
+    assert_eq!(program.cpu.segment(SegmentRegister::ES), PSP_SEGMENT);
+    assert_eq!(program.cpu.word(RegisterWordOp::SP), 0x100);
+    assert_eq!(program.memory.read_word(load_segment, 1), load_segment + 1);
+}
+
+#[test]
+fn test_exe_runs_from_its_entry_point() {
+    let mut program = load(&exe()).unwrap();
+    run_loaded(
+        &program.image,
+        &mut program.cpu,
+        &mut program.memory,
+        RunOptions::default(),
+        &Nasm::default(),
+        &mut vec![],
+    )
+    .unwrap();
+
+    assert_eq!(program.cpu.segment(SegmentRegister::DS), 0x1011);
+    assert_eq!(program.cpu.word(RegisterWordOp::BX), 0x1234);
+}
+
+#[test]
+fn test_com_image_starts_after_the_psp() {
+    let (image, origin, entry) = image(&[0xB8, 0x01, 0x00]).unwrap();
+    assert_eq!(image, [0xB8, 0x01, 0x00]);
+    assert_eq!((origin, entry), (0x100, 0x100));
+}
+
+#[test]
+fn test_exe_entry_point_is_relative_to_the_image() {
+    let mut file = exe();
+    // cs:ip 0001:0002
+    file[0x14..0x18].copy_from_slice(&[2, 0, 1, 0]);
+
+    let (image, origin, entry) = image(&file).unwrap();
+    assert_eq!(image.len(), 0x20);
+    assert_eq!((origin, entry), (0, 0x12));
+    let program = load(&file).unwrap();
+    assert_eq!(program.cpu.segment(SegmentRegister::CS), PSP_SEGMENT + 0x11);
+    assert_eq!(program.cpu.ip, 2);
+}
+
+#[rstest]
+#[case::short(0x1B, None, "the file is shorter than the header")]
+#[case::header_bigger_than_the_file(0x08, Some(5), "the header size is outside the file size")]
+#[case::header_too_small(0x08, Some(1), "the header size is outside the file size")]
+#[case::more_pages_than_the_file(0x04, Some(2), "the file is shorter than its pages")]
+#[case::relocations_past_the_end(0x06, Some(100), "the relocation table is cut short")]
+fn test_rejects_bad_headers(
+    #[case] offset: usize,
+    #[case] value: Option<u16>,
+    #[case] expected: &str,
+) {
+    let mut file = exe();
+    match value {
+        Some(value) => file[offset..offset + 2].copy_from_slice(&value.to_le_bytes()),
+        None => file.truncate(offset),
+    }
+
+    let error = load(&file).unwrap_err();
+    assert_eq!(error.to_string(), format!("invalid MZ header: {expected}"));
+}
@@ -0,0 +1,501 @@
+use crate::decoder::{
+    EffectiveAddress, Instruction, Mnemonic, Operand, Register, RegisterByteOp, RegisterWordOp,
+    SegmentRegister, Width,
+};
+use crate::flags::{Flag, FlagSet};
+use std::fmt;
+
+#[cfg(test)]
+mod effects_tests;
+
+/// Memory an instruction touches, either through an operand or implicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Memory {
+    /// A memory operand, relative to `segment` (the override or the default for its base).
+    Operand {
+        segment: SegmentRegister,
+        address: EffectiveAddress,
+    },
+    /// The top of the stack at SS:SP, pushed to or popped from.
+    Stack,
+    /// `[si]` as read by string instructions, DS unless overridden.
+    StringSource(SegmentRegister),
+    /// ES:DI as used by string instructions, which can't be overridden.
+    StringDestination,
+    /// The `[bx + al]` entry `xlat` looks up.
+    Table(SegmentRegister),
+    /// An entry of the interrupt vector table at 0000:0000.
+    Vector(u8),
+}
+
+/// Somewhere an instruction reads from or writes to, other than the flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Register(Register),
+    Segment(SegmentRegister),
+    Memory(Memory),
+    /// An I/O port, `None` when DX picks it at run time.
+    Port(Option<u8>),
+}
+
+impl Location {
+    /// Whether writing one can change what reading the other sees. Registers account for AL and
+    /// AH being halves of AX, memory and ports compare conservatively since most addresses are
+    /// only known at run time.
+    pub fn overlaps(self, other: Self) -> bool {
+        match (self, other) {
+            (Self::Register(a), Self::Register(b)) => a.overlaps(b),
+            (Self::Segment(a), Self::Segment(b)) => a == b,
+            (Self::Memory(_), Self::Memory(_)) => true,
+            (Self::Port(Some(a)), Self::Port(Some(b))) => a == b,
+            (Self::Port(_), Self::Port(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// NASM-like names, memory always spells out its segment, e.g. `[ss:bp + 4]`.
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Register(register) => f.write_str(register.name()),
+            Self::Segment(segment) => f.write_str(segment.name()),
+            Self::Memory(Memory::Operand { segment, address }) => {
+                write!(f, "[{}:", segment.name())?;
+                match address.base {
+                    None => write!(f, "{}", address.displacement as u16)?,
+                    Some(base) => {
+                        let (first, second) = base.registers();
+                        f.write_str(first.name())?;
+                        if let Some(second) = second {
+                            write!(f, " + {}", second.name())?;
+                        }
+                        match address.displacement {
+                            0 => {}
+                            d if d < 0 => write!(f, " - {}", d.unsigned_abs())?,
+                            d => write!(f, " + {d}")?,
+                        }
+                    }
+                }
+                f.write_str("]")
+            }
+            Self::Memory(Memory::Stack) => f.write_str("[ss:sp]"),
+            Self::Memory(Memory::StringSource(segment)) => write!(f, "[{}:si]", segment.name()),
+            Self::Memory(Memory::StringDestination) => f.write_str("[es:di]"),
+            Self::Memory(Memory::Table(segment)) => write!(f, "[{}:bx + al]", segment.name()),
+            Self::Memory(Memory::Vector(vector)) => write!(f, "[0:{}]", u16::from(vector) * 4),
+            Self::Port(Some(port)) => write!(f, "port {port}"),
+            Self::Port(None) => f.write_str("port dx"),
+        }
+    }
+}
+
+/// Everything an instruction reads and writes, including implicit operands such as DX:AX of
+/// `mul`, CX/SI/DI of `rep movsb` or the stack of `push`. IP isn't tracked, every instruction
+/// changes it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Effects {
+    pub reads: Vec<Location>,
+    pub writes: Vec<Location>,
+    pub flags_read: FlagSet,
+    pub flags_written: FlagSet,
+}
+
+fn word(register: RegisterWordOp) -> Location {
+    Location::Register(Register::Word(register))
+}
+
+fn byte(register: RegisterByteOp) -> Location {
+    Location::Register(Register::Byte(register))
+}
+
+fn accumulator(width: Width) -> Location {
+    match width {
+        Width::Byte => byte(RegisterByteOp::AL),
+        Width::Word => word(RegisterWordOp::AX),
+    }
+}
+
+/// Flags a conditional jump tests.
+fn condition_flags(mnemonic: Mnemonic) -> FlagSet {
+    use Flag::*;
+    match mnemonic {
+        Mnemonic::Jo | Mnemonic::Jno => FlagSet::of(&[Overflow]),
+        Mnemonic::Jb | Mnemonic::Jnb => FlagSet::of(&[Carry]),
+        Mnemonic::Je | Mnemonic::Jne | Mnemonic::Loopz | Mnemonic::Loopnz => FlagSet::of(&[Zero]),
+        Mnemonic::Jbe | Mnemonic::Ja => FlagSet::of(&[Carry, Zero]),
+        Mnemonic::Js | Mnemonic::Jns => FlagSet::of(&[Sign]),
+        Mnemonic::Jp | Mnemonic::Jnp => FlagSet::of(&[Parity]),
+        Mnemonic::Jl | Mnemonic::Jnl => FlagSet::of(&[Sign, Overflow]),
+        Mnemonic::Jle | Mnemonic::Jg => FlagSet::of(&[Sign, Overflow, Zero]),
+        _ => FlagSet::EMPTY,
+    }
+}
+
+impl Effects {
+    pub fn of(instruction: &Instruction) -> Self {
+        use Mnemonic::*;
+        use RegisterWordOp::{AX, BX, CX, DX};
+
+        let mut effects = Self::default();
+        let destination = instruction.destination;
+        let source = instruction.source;
+        let width = instruction.width;
+        let status_without_carry = FlagSet::STATUS.without(Flag::Carry);
+
+        match instruction.mnemonic {
+            Mov => {
+                effects.read_operand(instruction, source);
+                effects.write_operand(instruction, destination);
+            }
+            // only the address is computed, memory isn't accessed
+            Lea => {
+                if let Some(Operand::Memory(address)) = source {
+                    effects.read_base(&address);
+                }
+                effects.write_operand(instruction, destination);
+            }
+            Lds | Les => {
+                effects.read_operand(instruction, source);
+                effects.write_operand(instruction, destination);
+                effects.write(Location::Segment(match instruction.mnemonic {
+                    Lds => SegmentRegister::DS,
+                    _ => SegmentRegister::ES,
+                }));
+            }
+            Push => {
+                effects.read_operand(instruction, destination);
+                effects.push();
+            }
+            Pop => {
+                effects.pop();
+                effects.write_operand(instruction, destination);
+            }
+            Xchg => {
+                effects.read_operand(instruction, destination);
+                effects.read_operand(instruction, source);
+                effects.write_operand(instruction, destination);
+                effects.write_operand(instruction, source);
+            }
+            In => {
+                effects.read_operand(instruction, source);
+                effects.read(Location::Port(port(source)));
+                effects.write_operand(instruction, destination);
+            }
+            Out => {
+                effects.read_operand(instruction, source);
+                effects.read_operand(instruction, destination);
+                effects.write(Location::Port(port(destination)));
+            }
+            Xlat => {
+                let segment = instruction.prefixes.segment.unwrap_or(SegmentRegister::DS);
+                effects.read(word(BX));
+                effects.read(byte(RegisterByteOp::AL));
+                effects.read(Location::Segment(segment));
+                effects.read(Location::Memory(Memory::Table(segment)));
+                effects.write(byte(RegisterByteOp::AL));
+            }
+            Lahf => {
+                effects.flags_read = FlagSet::STATUS.without(Flag::Overflow);
+                effects.write(byte(RegisterByteOp::AH));
+            }
+            Sahf => {
+                effects.read(byte(RegisterByteOp::AH));
+                effects.flags_written = FlagSet::STATUS.without(Flag::Overflow);
+            }
+            Pushf => {
+                effects.flags_read = FlagSet::ALL;
+                effects.push();
+            }
+            Popf => {
+                effects.pop();
+                effects.flags_written = FlagSet::ALL;
+            }
+            Add | Adc | Sub | Sbb | And | Or | Xor | Cmp | Test => {
+                effects.read_operand(instruction, destination);
+                effects.read_operand(instruction, source);
+                if !matches!(instruction.mnemonic, Cmp | Test) {
+                    effects.write_operand(instruction, destination);
+                }
+                if matches!(instruction.mnemonic, Adc | Sbb) {
+                    effects.flags_read = FlagSet::of(&[Flag::Carry]);
+                }
+                effects.flags_written = FlagSet::STATUS;
+            }
+            Inc | Dec | Neg | Not => {
+                effects.read_operand(instruction, destination);
+                effects.write_operand(instruction, destination);
+                effects.flags_written = match instruction.mnemonic {
+                    Inc | Dec => status_without_carry,
+                    Neg => FlagSet::STATUS,
+                    _ => FlagSet::EMPTY,
+                };
+            }
+            Shl | Shr | Sar | Rol | Ror | Rcl | Rcr => {
+                effects.read_operand(instruction, destination);
+                effects.read_operand(instruction, source);
+                effects.write_operand(instruction, destination);
+                if matches!(instruction.mnemonic, Rcl | Rcr) {
+                    effects.flags_read = FlagSet::of(&[Flag::Carry]);
+                }
+                effects.flags_written = match instruction.mnemonic {
+                    Rol | Ror | Rcl | Rcr => FlagSet::of(&[Flag::Carry, Flag::Overflow]),
+                    _ => FlagSet::STATUS,
+                };
+            }
+            // AX = AL * src, DX:AX = AX * src, and the reverse for division
+            Mul | Imul | Div | Idiv => {
+                let divides = matches!(instruction.mnemonic, Div | Idiv);
+                effects.read_operand(instruction, destination);
+                match (width, divides) {
+                    (Width::Byte, false) => effects.read(byte(RegisterByteOp::AL)),
+                    (Width::Byte, true) => effects.read(word(AX)),
+                    (Width::Word, false) => effects.read(word(AX)),
+                    (Width::Word, true) => {
+                        effects.read(word(DX));
+                        effects.read(word(AX));
+                    }
+                }
+                effects.write(word(AX));
+                if width == Width::Word {
+                    effects.write(word(DX));
+                }
+                effects.flags_written = FlagSet::STATUS;
+            }
+            Aam | Aad | Daa | Das | Aaa | Aas => {
+                match instruction.mnemonic {
+                    Aam | Daa | Das => effects.read(byte(RegisterByteOp::AL)),
+                    _ => effects.read(word(AX)),
+                }
+                match instruction.mnemonic {
+                    Daa | Das => effects.write(byte(RegisterByteOp::AL)),
+                    _ => effects.write(word(AX)),
+                }
+                effects.flags_read = match instruction.mnemonic {
+                    Daa | Das => FlagSet::of(&[Flag::Carry, Flag::Auxiliary]),
+                    Aaa | Aas => FlagSet::of(&[Flag::Auxiliary]),
+                    _ => FlagSet::EMPTY,
+                };
+                effects.flags_written = FlagSet::STATUS;
+            }
+            Cbw => {
+                effects.read(byte(RegisterByteOp::AL));
+                effects.write(byte(RegisterByteOp::AH));
+            }
+            Cwd => {
+                effects.read(word(AX));
+                effects.write(word(DX));
+            }
+            Movs | Cmps | Scas | Lods | Stos => effects.string(instruction),
+            Jo | Jno | Jb | Jnb | Je | Jne | Jbe | Ja | Js | Jns | Jp | Jnp | Jl | Jnl | Jle
+            | Jg => effects.flags_read = condition_flags(instruction.mnemonic),
+            Loopnz | Loopz | Loop => {
+                effects.read(word(CX));
+                effects.write(word(CX));
+                effects.flags_read = condition_flags(instruction.mnemonic);
+            }
+            Jcxz => effects.read(word(CX)),
+            Call | CallFar | Jmp | JmpFar => {
+                effects.read_operand(instruction, destination);
+                if instruction.mnemonic.is_far() {
+                    effects.write(Location::Segment(SegmentRegister::CS));
+                }
+                if matches!(instruction.mnemonic, Call | CallFar) {
+                    if instruction.mnemonic == CallFar {
+                        effects.read(Location::Segment(SegmentRegister::CS));
+                    }
+                    effects.push();
+                }
+            }
+            Ret | Retf => {
+                effects.pop();
+                if instruction.mnemonic == Retf {
+                    effects.write(Location::Segment(SegmentRegister::CS));
+                }
+            }
+            Int | Int3 | Into => {
+                let vector = match (instruction.mnemonic, destination) {
+                    (Int, Some(Operand::Immediate(vector))) => vector as u8,
+                    (Into, _) => 4,
+                    _ => 3,
+                };
+                effects.flags_read = FlagSet::ALL;
+                effects.read(Location::Segment(SegmentRegister::CS));
+                effects.push();
+                effects.read(Location::Memory(Memory::Vector(vector)));
+                effects.write(Location::Segment(SegmentRegister::CS));
+                effects.flags_written = FlagSet::of(&[Flag::Interrupt, Flag::Trap]);
+            }
+            Iret => {
+                effects.pop();
+                effects.write(Location::Segment(SegmentRegister::CS));
+                effects.flags_written = FlagSet::ALL;
+            }
+            Clc | Stc | Cmc => {
+                if instruction.mnemonic == Cmc {
+                    effects.flags_read = FlagSet::of(&[Flag::Carry]);
+                }
+                effects.flags_written = FlagSet::of(&[Flag::Carry]);
+            }
+            Cld | Std => effects.flags_written = FlagSet::of(&[Flag::Direction]),
+            Cli | Sti => effects.flags_written = FlagSet::of(&[Flag::Interrupt]),
+            Hlt | Wait | Nop => {}
+        }
+
+        effects
+    }
+
+    fn read(&mut self, location: Location) {
+        if !self.reads.contains(&location) {
+            self.reads.push(location);
+        }
+    }
+
+    fn write(&mut self, location: Location) {
+        if !self.writes.contains(&location) {
+            self.writes.push(location);
+        }
+    }
+
+    /// Registers that form an address are read whether the memory is read or written.
+    fn read_base(&mut self, address: &EffectiveAddress) {
+        if let Some(base) = address.base {
+            let (first, second) = base.registers();
+            self.read(word(first));
+            if let Some(second) = second {
+                self.read(word(second));
+            }
+        }
+    }
+
+    /// Reads the registers and the segment that locate `address`.
+    fn memory(&mut self, instruction: &Instruction, address: &EffectiveAddress) -> Location {
+        let segment = instruction.segment_of(address);
+        self.read_base(address);
+        self.read(Location::Segment(segment));
+        Location::Memory(Memory::Operand {
+            segment,
+            address: *address,
+        })
+    }
+
+    fn read_operand(&mut self, instruction: &Instruction, operand: Option<Operand>) {
+        match operand {
+            Some(Operand::Register(register)) => self.read(Location::Register(register)),
+            Some(Operand::Segment(segment)) => self.read(Location::Segment(segment)),
+            Some(Operand::Memory(address)) => {
+                let memory = self.memory(instruction, &address);
+                self.read(memory);
+            }
+            Some(Operand::Immediate(_) | Operand::Relative(_) | Operand::Far { .. }) | None => {}
+        }
+    }
+
+    fn write_operand(&mut self, instruction: &Instruction, operand: Option<Operand>) {
+        match operand {
+            Some(Operand::Register(register)) => self.write(Location::Register(register)),
+            Some(Operand::Segment(segment)) => self.write(Location::Segment(segment)),
+            Some(Operand::Memory(address)) => {
+                let memory = self.memory(instruction, &address);
+                self.write(memory);
+            }
+            Some(Operand::Immediate(_) | Operand::Relative(_) | Operand::Far { .. }) | None => {}
+        }
+    }
+
+    fn push(&mut self) {
+        self.read(word(RegisterWordOp::SP));
+        self.read(Location::Segment(SegmentRegister::SS));
+        self.write(word(RegisterWordOp::SP));
+        self.write(Location::Memory(Memory::Stack));
+    }
+
+    fn pop(&mut self) {
+        self.read(word(RegisterWordOp::SP));
+        self.read(Location::Segment(SegmentRegister::SS));
+        self.read(Location::Memory(Memory::Stack));
+        self.write(word(RegisterWordOp::SP));
+    }
+
+    fn string(&mut self, instruction: &Instruction) {
+        use Mnemonic::*;
+        let mnemonic = instruction.mnemonic;
+
+        if instruction.prefixes.repeat.is_some() {
+            self.read(word(RegisterWordOp::CX));
+            self.write(word(RegisterWordOp::CX));
+        }
+        if matches!(mnemonic, Stos | Scas) {
+            self.read(accumulator(instruction.width));
+        }
+        if matches!(mnemonic, Movs | Cmps | Lods) {
+            let segment = instruction.prefixes.segment.unwrap_or(SegmentRegister::DS);
+            self.read(word(RegisterWordOp::SI));
+            self.read(Location::Segment(segment));
+            self.read(Location::Memory(Memory::StringSource(segment)));
+            self.write(word(RegisterWordOp::SI));
+        }
+        if matches!(mnemonic, Movs | Cmps | Stos | Scas) {
+            self.read(word(RegisterWordOp::DI));
+            self.read(Location::Segment(SegmentRegister::ES));
+            if matches!(mnemonic, Cmps | Scas) {
+                self.read(Location::Memory(Memory::StringDestination));
+            } else {
+                self.write(Location::Memory(Memory::StringDestination));
+            }
+            self.write(word(RegisterWordOp::DI));
+        }
+        if mnemonic == Lods {
+            self.write(accumulator(instruction.width));
+        }
+
+        self.flags_read = FlagSet::of(&[Flag::Direction]);
+        if matches!(mnemonic, Cmps | Scas) {
+            self.flags_written = FlagSet::STATUS;
+        }
+    }
+
+    /// Whether this reads anything `earlier` writes, i.e. has to wait for its result.
+    pub fn depends_on(&self, earlier: &Self) -> bool {
+        self.flags_read.intersects(earlier.flags_written)
+            || self
+                .reads
+                .iter()
+                .any(|read| earlier.writes.iter().any(|written| read.overlaps(*written)))
+    }
+}
+
+fn port(operand: Option<Operand>) -> Option<u8> {
+    match operand {
+        Some(Operand::Immediate(port)) => Some(port as u8),
+        _ => None,
+    }
+}
+
+/// For every instruction, the indices of the closest earlier instructions that wrote (part of)
+/// something it reads. This follows the order of `effects`, not control flow, so it's exact
+/// within a basic block and an approximation across jumps.
+pub fn dependencies(effects: &[Effects]) -> Vec<Vec<usize>> {
+    effects
+        .iter()
+        .enumerate()
+        .map(|(index, current)| {
+            let earlier = &effects[..index];
+            let location_writers = current.reads.iter().filter_map(|read| {
+                earlier.iter().rposition(|effects| {
+                    effects.writes.iter().any(|written| read.overlaps(*written))
+                })
+            });
+            let flag_writers = current.flags_read.iter().filter_map(|flag| {
+                earlier
+                    .iter()
+                    .rposition(|effects| effects.flags_written.contains(flag))
+            });
+
+            let mut writers: Vec<usize> = location_writers.chain(flag_writers).collect();
+            writers.sort_unstable();
+            writers.dedup();
+            writers
+        })
+        .collect()
+}
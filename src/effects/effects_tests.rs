@@ -0,0 +1,127 @@
+use super::*;
+use crate::decoder::{decode_all, decode_instruction};
+use rstest::rstest;
+
+fn names(locations: &[Location]) -> Vec<String> {
+    locations.iter().map(Location::to_string).collect()
+}
+
+fn effects(bytes: &[u8]) -> Effects {
+    Effects::of(&decode_instruction(bytes, 0).unwrap())
+}
+
+#[rstest]
+#[case::mov_registers(&[0x89, 0xD9], &["bx"], &["cx"])]
+#[case::mov_to_memory(&[0x89, 0x8C, 0xD4, 0xFE], &["cx", "si", "ds"], &["[ds:si - 300]"])]
+#[case::bp_defaults_to_ss(&[0x8B, 0x46, 0x04], &["bp", "ss", "[ss:bp + 4]"], &["ax"])]
+#[case::segment_override(&[0x26, 0x8A, 0x07], &["bx", "es", "[es:bx]"], &["al"])]
+#[case::direct_address(&[0xA1, 0xFB, 0x09], &["ds", "[ds:2555]"], &["ax"])]
+#[case::lea_reads_no_memory(&[0x8D, 0x01], &["bx", "di"], &["ax"])]
+#[case::lds(&[0xC5, 0x1C], &["si", "ds", "[ds:si]"], &["bx", "ds"])]
+#[case::add(&[0x00, 0xE0], &["al", "ah"], &["al"])]
+#[case::cmp_writes_no_register(&[0x39, 0xD8], &["ax", "bx"], &[])]
+#[case::mul_byte(&[0xF6, 0xE3], &["bl", "al"], &["ax"])]
+#[case::mul_word(&[0xF7, 0xE3], &["bx", "ax"], &["ax", "dx"])]
+#[case::div_word(&[0xF7, 0xF1], &["cx", "dx", "ax"], &["ax", "dx"])]
+#[case::cbw(&[0x98], &["al"], &["ah"])]
+#[case::cwd(&[0x99], &["ax"], &["dx"])]
+#[case::shift_by_cl(&[0xD3, 0xE0], &["ax", "cl"], &["ax"])]
+#[case::push(&[0x53], &["bx", "sp", "ss"], &["sp", "[ss:sp]"])]
+#[case::pop_memory(&[0x8F, 0x07], &["sp", "ss", "[ss:sp]", "bx", "ds"], &["sp", "[ds:bx]"])]
+#[case::xchg(&[0x87, 0xD8], &["bx", "ax"], &["bx", "ax"])]
+#[case::in_fixed_port(&[0xE4, 0x60], &["port 96"], &["al"])]
+#[case::out_variable_port(&[0xEF], &["ax", "dx"], &["port dx"])]
+#[case::xlat(&[0xD7], &["bx", "al", "ds", "[ds:bx + al]"], &["al"])]
+#[case::movsb(&[0xA4], &["si", "ds", "[ds:si]", "di", "es"], &["si", "[es:di]", "di"])]
+#[case::rep_stosw(&[0xF3, 0xAB], &["cx", "ax", "di", "es"], &["cx", "[es:di]", "di"])]
+#[case::scasb(&[0xAE], &["al", "di", "es", "[es:di]"], &["di"])]
+#[case::lodsb_override(&[0x2E, 0xAC], &["si", "cs", "[cs:si]"], &["si", "al"])]
+#[case::loop_(&[0xE2, 0xFE], &["cx"], &["cx"])]
+#[case::near_call(&[0xE8, 0x00, 0x00], &["sp", "ss"], &["sp", "[ss:sp]"])]
+#[case::indirect_far_jump(&[0xFF, 0x2F], &["bx", "ds", "[ds:bx]"], &["cs"])]
+#[case::retf(&[0xCB], &["sp", "ss", "[ss:sp]"], &["sp", "cs"])]
+#[case::int(&[0xCD, 0x21], &["cs", "sp", "ss", "[0:132]"], &["sp", "[ss:sp]", "cs"])]
+#[case::nop(&[0x90], &[], &[])]
+fn test_reads_and_writes(#[case] bytes: &[u8], #[case] reads: &[&str], #[case] writes: &[&str]) {
+    let effects = effects(bytes);
+    assert_eq!(names(&effects.reads), reads, "reads");
+    assert_eq!(names(&effects.writes), writes, "writes");
+}
+
+#[rstest]
+#[case::add(&[0x01, 0xD8], "", "CPAZSO")]
+#[case::adc(&[0x11, 0xD8], "C", "CPAZSO")]
+#[case::inc_keeps_carry(&[0x40], "", "PAZSO")]
+#[case::not_changes_nothing(&[0xF7, 0xD0], "", "")]
+#[case::rotate(&[0xD1, 0xC0], "", "CO")]
+#[case::rotate_through_carry(&[0xD1, 0xD0], "C", "CO")]
+#[case::jle(&[0x7E, 0x00], "ZSO", "")]
+#[case::loopnz(&[0xE0, 0x00], "Z", "")]
+#[case::cmpsb(&[0xA6], "D", "CPAZSO")]
+#[case::sahf(&[0x9E], "", "CPAZS")]
+#[case::pushf(&[0x9C], "CPAZSTIDO", "")]
+#[case::popf(&[0x9D], "", "CPAZSTIDO")]
+#[case::cmc(&[0xF5], "C", "C")]
+#[case::cld(&[0xFC], "", "D")]
+#[case::int(&[0xCC], "CPAZSTIDO", "TI")]
+fn test_flags(#[case] bytes: &[u8], #[case] read: &str, #[case] written: &str) {
+    let effects = effects(bytes);
+    assert_eq!(effects.flags_read.to_string(), read, "read");
+    assert_eq!(effects.flags_written.to_string(), written, "written");
+}
+
+#[rstest]
+#[case::full_register(
+    Location::Register(Register::Word(RegisterWordOp::AX)),
+    Location::Register(Register::Byte(RegisterByteOp::AH)),
+    true
+)]
+#[case::separate_halves(
+    Location::Register(Register::Byte(RegisterByteOp::AL)),
+    Location::Register(Register::Byte(RegisterByteOp::AH)),
+    false
+)]
+#[case::memory_may_alias(
+    Location::Memory(Memory::Stack),
+    Location::Memory(Memory::StringDestination),
+    true
+)]
+#[case::different_ports(Location::Port(Some(1)), Location::Port(Some(2)), false)]
+#[case::port_in_dx(Location::Port(None), Location::Port(Some(2)), true)]
+#[case::segment_and_register(
+    Location::Segment(SegmentRegister::ES),
+    Location::Register(Register::Word(RegisterWordOp::AX)),
+    false
+)]
+fn test_overlaps(#[case] a: Location, #[case] b: Location, #[case] expected: bool) {
+    assert_eq!(a.overlaps(b), expected);
+}
+
+#[test]
+fn test_depends_on_a_half_of_the_register() {
+    // mov ah, 1 / mov bx, ax
+    let write = effects(&[0xB4, 0x01]);
+    let read = effects(&[0x89, 0xC3]);
+
+    assert!(read.depends_on(&write));
+    assert!(!write.depends_on(&read));
+}
+
+#[test]
+fn test_dependencies_use_the_closest_writer() {
+    // 0: mov al, 1
+    // 1: mov ah, 2
+    // 2: mov al, 3
+    // 3: add bx, ax    <- ah from 1, al from 2, but only the closest writer of ax counts
+    // 4: jne $+0       <- flags from 3
+    // 5: mov cl, ah    <- 1
+    let data = [
+        0xB0, 0x01, 0xB4, 0x02, 0xB0, 0x03, 0x01, 0xC3, 0x75, 0xFE, 0x88, 0xE1,
+    ];
+    let effects: Vec<_> = decode_all(&data).unwrap().iter().map(Effects::of).collect();
+
+    assert_eq!(
+        dependencies(&effects),
+        [vec![], vec![], vec![], vec![2], vec![3], vec![1]]
+    );
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+
+#[cfg(test)]
+mod flags_tests;
+
+/// A bit of the flags register, the discriminant is its position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Flag {
+    Carry = 0,
+    Parity = 2,
+    Auxiliary = 4,
+    Zero = 6,
+    Sign = 7,
+    Trap = 8,
+    Interrupt = 9,
+    Direction = 10,
+    Overflow = 11,
+}
+
+impl Flag {
+    /// In the order the course reference traces print them.
+    pub const ALL: [Self; 9] = [
+        Self::Carry,
+        Self::Parity,
+        Self::Auxiliary,
+        Self::Zero,
+        Self::Sign,
+        Self::Trap,
+        Self::Interrupt,
+        Self::Direction,
+        Self::Overflow,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Carry => "cf",
+            Self::Parity => "pf",
+            Self::Auxiliary => "af",
+            Self::Zero => "zf",
+            Self::Sign => "sf",
+            Self::Trap => "tf",
+            Self::Interrupt => "if",
+            Self::Direction => "df",
+            Self::Overflow => "of",
+        }
+    }
+
+    pub fn letter(self) -> char {
+        match self {
+            Self::Carry => 'C',
+            Self::Parity => 'P',
+            Self::Auxiliary => 'A',
+            Self::Zero => 'Z',
+            Self::Sign => 'S',
+            Self::Trap => 'T',
+            Self::Interrupt => 'I',
+            Self::Direction => 'D',
+            Self::Overflow => 'O',
+        }
+    }
+
+    pub const fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+/// A set of flags laid out like the flags register, so it doubles as the register's value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FlagSet(u16);
+
+impl FlagSet {
+    pub const EMPTY: Self = Self(0);
+    /// The six flags arithmetic instructions set from their result.
+    pub const STATUS: Self = Self(
+        Flag::Carry.bit()
+            | Flag::Parity.bit()
+            | Flag::Auxiliary.bit()
+            | Flag::Zero.bit()
+            | Flag::Sign.bit()
+            | Flag::Overflow.bit(),
+    );
+    pub const ALL: Self = Self(Self::STATUS.0 | 0b111 << 8);
+
+    /// Keeps only the bits that are flags.
+    pub fn from_bits(bits: u16) -> Self {
+        Self(bits & Self::ALL.0)
+    }
+
+    pub fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// The flags register as the 8086 pushes it, where the bits that aren't flags (1 and 12-15)
+    /// read as set.
+    pub fn pushed(self) -> u16 {
+        self.0 | 0xF002
+    }
+
+    pub fn of(flags: &[Flag]) -> Self {
+        flags.iter().fold(Self::EMPTY, |set, &flag| set.with(flag))
+    }
+
+    pub fn with(self, flag: Flag) -> Self {
+        Self(self.0 | flag.bit())
+    }
+
+    pub fn without(self, flag: Flag) -> Self {
+        Self(self.0 & !flag.bit())
+    }
+
+    pub fn set(&mut self, flag: Flag, value: bool) {
+        *self = if value {
+            self.with(flag)
+        } else {
+            self.without(flag)
+        };
+    }
+
+    pub fn contains(self, flag: Flag) -> bool {
+        self.0 & flag.bit() != 0
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Flag> {
+        Flag::ALL
+            .into_iter()
+            .filter(move |&flag| self.contains(flag))
+    }
+}
+
+/// The letters of the flags in the set, e.g. `CZ`.
+impl fmt::Display for FlagSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.iter()
+            .try_for_each(|flag| write!(f, "{}", flag.letter()))
+    }
+}
@@ -0,0 +1,33 @@
+use super::*;
+use rstest::rstest;
+
+#[rstest]
+#[case::empty(FlagSet::EMPTY, "")]
+#[case::single(FlagSet::of(&[Flag::Zero]), "Z")]
+#[case::reference_order(FlagSet::of(&[Flag::Overflow, Flag::Zero, Flag::Carry]), "CZO")]
+#[case::status(FlagSet::STATUS, "CPAZSO")]
+#[case::all(FlagSet::ALL, "CPAZSTIDO")]
+fn test_display(#[case] flags: FlagSet, #[case] expected: &str) {
+    assert_eq!(flags.to_string(), expected);
+}
+
+#[test]
+fn test_bits_match_the_flags_register() {
+    assert_eq!(FlagSet::of(&[Flag::Carry, Flag::Zero]).bits(), 0x0041);
+    assert_eq!(FlagSet::ALL.bits(), 0x0FD5);
+    assert_eq!(FlagSet::from_bits(0xFFFF), FlagSet::ALL);
+    assert_eq!(FlagSet::of(&[Flag::Carry]).pushed(), 0xF003);
+}
+
+#[test]
+fn test_set_and_clear() {
+    let mut flags = FlagSet::EMPTY;
+    flags.set(Flag::Sign, true);
+    flags.set(Flag::Parity, true);
+    flags.set(Flag::Sign, false);
+
+    assert!(flags.contains(Flag::Parity));
+    assert!(!flags.contains(Flag::Sign));
+    assert!(flags.intersects(FlagSet::STATUS));
+    assert!(!flags.intersects(FlagSet::of(&[Flag::Direction])));
+}
@@ -0,0 +1,151 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+#[cfg(test)]
+mod image_tests;
+
+/// Size of the image the course's drawing listings fill in.
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Binary `P6` portable pixmap, which has no alpha channel.
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    /// Picks the format from the file extension, `None` when it isn't one we can write.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "ppm" => Some(Self::Ppm),
+            "png" => Some(Self::Png),
+            _ => None,
+        }
+    }
+}
+
+/// Rows of RGBA pixels, top to bottom, four bytes each in that order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framebuffer<'a> {
+    rgba: &'a [u8],
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Framebuffer<'a> {
+    /// `None` if `rgba` is too short to hold `width * height` pixels or either is zero, anything
+    /// past the pixels is ignored.
+    pub fn new(rgba: &'a [u8], width: usize, height: usize) -> Option<Self> {
+        let size = width.checked_mul(height)?.checked_mul(4)?;
+        if size == 0 {
+            return None;
+        }
+        Some(Self {
+            rgba: rgba.get(..size)?,
+            width,
+            height,
+        })
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let start = (y * self.width + x) * 4;
+        self.rgba[start..start + 4].try_into().unwrap()
+    }
+
+    fn rows(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.rgba.chunks_exact(self.width * 4)
+    }
+
+    pub fn write(&self, format: ImageFormat, out: &mut impl Write) -> io::Result<()> {
+        match format {
+            ImageFormat::Ppm => self.write_ppm(out),
+            ImageFormat::Png => self.write_png(out),
+        }
+    }
+
+    /// Drops the alpha channel, PPM only has RGB.
+    pub fn write_ppm(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in self.rgba.chunks_exact(4) {
+            out.write_all(&pixel[..3])?;
+        }
+        Ok(())
+    }
+
+    /// An 8-bit RGBA PNG. The pixels are stored rather than compressed, which keeps the encoder
+    /// small and is plenty for 64x64 images.
+    pub fn write_png(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+        let mut header = vec![];
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGBA, deflate, adaptive filtering, not interlaced
+        header.extend([8, 6, 0, 0, 0]);
+        write_chunk(out, b"IHDR", &header)?;
+
+        // every row starts with its filter type, 0 is none
+        let mut scanlines = Vec::with_capacity(self.rgba.len() + self.height);
+        for row in self.rows() {
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+        write_chunk(out, b"IDAT", &zlib_stored(&scanlines))?;
+        write_chunk(out, b"IEND", &[])
+    }
+}
+
+fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data).copied());
+    out.write_all(&crc.to_be_bytes())
+}
+
+/// A zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // deflate with a 32K window, no preset dictionary, fastest compression
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        stream.extend([1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let length = block.len() as u16;
+        stream.push(u8::from(last));
+        stream.extend(length.to_le_bytes());
+        stream.extend((!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend(adler32(data).to_be_bytes());
+    stream
+}
+
+/// The CRC-32 PNG chunks end with (the same one zip and Ethernet use).
+fn crc32(bytes: impl IntoIterator<Item = u8>) -> u32 {
+    let crc = bytes.into_iter().fold(u32::MAX, |crc, byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    });
+    !crc
+}
+
+/// The checksum at the end of a zlib stream.
+fn adler32(bytes: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+    let (a, b) = bytes.iter().fold((1, 0), |(a, b), &byte| {
+        let a = (a + u32::from(byte)) % MODULUS;
+        (a, (b + a) % MODULUS)
+    });
+    (b << 16) | a
+}
@@ -0,0 +1,104 @@
+use super::*;
+use rstest::rstest;
+
+#[rstest]
+#[case::ppm("out.ppm", Some(ImageFormat::Ppm))]
+#[case::png("dir/out.PNG", Some(ImageFormat::Png))]
+#[case::unknown("out.bmp", None)]
+#[case::no_extension("out", None)]
+fn test_format_from_path(#[case] path: &str, #[case] expected: Option<ImageFormat>) {
+    assert_eq!(ImageFormat::from_path(Path::new(path)), expected);
+}
+
+#[rstest]
+#[case::too_short(7, 1, 2)]
+#[case::no_width(8, 0, 2)]
+#[case::no_height(8, 2, 0)]
+fn test_rejects_sizes_that_dont_fit(
+    #[case] length: usize,
+    #[case] width: usize,
+    #[case] height: usize,
+) {
+    assert_eq!(Framebuffer::new(&vec![0; length], width, height), None);
+}
+
+#[test]
+fn test_pixels_are_rgba_rows() {
+    let rgba = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 99];
+    let framebuffer = Framebuffer::new(&rgba, 2, 2).unwrap();
+
+    assert_eq!(framebuffer.pixel(1, 0), [5, 6, 7, 8]);
+    assert_eq!(framebuffer.pixel(0, 1), [9, 10, 11, 12]);
+}
+
+#[test]
+fn test_ppm_drops_alpha() {
+    let rgba = [255, 0, 0, 255, 0, 0, 255, 128];
+    let mut out = vec![];
+    Framebuffer::new(&rgba, 2, 1)
+        .unwrap()
+        .write(ImageFormat::Ppm, &mut out)
+        .unwrap();
+
+    assert_eq!(out, b"P6\n2 1\n255\n\xff\x00\x00\x00\x00\xff");
+}
+
+#[test]
+fn test_png_layout() {
+    let rgba = [10, 20, 30, 40, 50, 60, 70, 80];
+    let mut out = vec![];
+    Framebuffer::new(&rgba, 1, 2)
+        .unwrap()
+        .write(ImageFormat::Png, &mut out)
+        .unwrap();
+
+    let mut expected = b"\x89PNG\r\n\x1a\n".to_vec();
+    let header = [0, 0, 0, 1, 0, 0, 0, 2, 8, 6, 0, 0, 0];
+    expected.extend([0, 0, 0, 13]);
+    expected.extend(b"IHDR");
+    expected.extend(header);
+    expected.extend(crc32(b"IHDR".iter().chain(&header).copied()).to_be_bytes());
+
+    let scanlines = [0, 10, 20, 30, 40, 0, 50, 60, 70, 80];
+    let mut data = vec![0x78, 0x01, 1, 10, 0, 0xF5, 0xFF];
+    data.extend(scanlines);
+    data.extend(adler32(&scanlines).to_be_bytes());
+    expected.extend((data.len() as u32).to_be_bytes());
+    expected.extend(b"IDAT");
+    expected.extend(&data);
+    expected.extend(crc32(b"IDAT".iter().chain(&data).copied()).to_be_bytes());
+
+    expected.extend([0, 0, 0, 0]);
+    expected.extend(b"IEND");
+    expected.extend([0xAE, 0x42, 0x60, 0x82]);
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn test_large_images_split_into_blocks() {
+    let data = vec![7; 70_000];
+    let stream = zlib_stored(&data);
+
+    // header, two block headers, the data and the checksum
+    assert_eq!(stream.len(), 2 + 5 + 5 + 70_000 + 4);
+    assert_eq!(stream[2..7], [0, 0xFF, 0xFF, 0, 0]);
+    assert_eq!(
+        stream[7 + 65_535..7 + 65_535 + 5],
+        [1, 0x71, 0x11, 0x8E, 0xEE]
+    );
+}
+
+#[rstest]
+#[case::empty(b"", 0)]
+#[case::check_value(b"123456789", 0xCBF4_3926)]
+#[case::png_end_chunk(b"IEND", 0xAE42_6082)]
+fn test_crc32(#[case] bytes: &[u8], #[case] expected: u32) {
+    assert_eq!(crc32(bytes.iter().copied()), expected);
+}
+
+#[rstest]
+#[case::empty(b"", 1)]
+#[case::wikipedia(b"Wikipedia", 0x11E6_0398)]
+fn test_adler32(#[case] bytes: &[u8], #[case] expected: u32) {
+    assert_eq!(adler32(bytes), expected);
+}
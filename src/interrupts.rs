@@ -0,0 +1,409 @@
+use crate::cpu::Cpu;
+use crate::decoder::{Register, RegisterByteOp, RegisterWordOp, SegmentRegister};
+use crate::flags::Flag;
+use crate::memory::Memory;
+use crate::sim::push;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+#[cfg(test)]
+mod interrupts_tests;
+
+/// How a serviced interrupt left the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Carry on with the instruction after the `int`.
+    Return,
+    /// The program terminated with this exit code.
+    Exit(u8),
+}
+
+/// Replaces the handler for one interrupt, an error stops the simulation with the message.
+pub type Hook = Box<dyn FnMut(&mut Cpu, &mut Memory) -> Result<Outcome, String>>;
+
+/// Where `int` instructions go. Hooks come first, then the built-in [`Services`].
+#[derive(Default)]
+pub struct Interrupts {
+    hooks: HashMap<u8, Hook>,
+    services: Services,
+    exit_code: Option<u8>,
+}
+
+impl Interrupts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_services(services: Services) -> Self {
+        Self {
+            services,
+            ..Self::default()
+        }
+    }
+
+    /// Handles `int number` with `hook` instead of whatever did before.
+    pub fn hook(
+        &mut self,
+        number: u8,
+        hook: impl FnMut(&mut Cpu, &mut Memory) -> Result<Outcome, String> + 'static,
+    ) {
+        self.hooks.insert(number, Box::new(hook));
+    }
+
+    pub fn unhook(&mut self, number: u8) {
+        self.hooks.remove(&number);
+    }
+
+    pub fn services(&self) -> &Services {
+        &self.services
+    }
+
+    pub fn services_mut(&mut self) -> &mut Services {
+        &mut self.services
+    }
+
+    /// Set once the program has terminated through a DOS service or a hook.
+    pub fn exit_code(&self) -> Option<u8> {
+        self.exit_code
+    }
+
+    /// Forgets that the program terminated, for carrying on anyway.
+    pub fn clear_exit(&mut self) {
+        self.exit_code = None;
+    }
+
+    /// Services `int number`, `Ok(false)` when nothing handles it. A hook comes first, then a
+    /// handler the program installed in the interrupt vector table, which is entered the way
+    /// the 8086 does, then the built-in services.
+    pub fn call(&mut self, number: u8, cpu: &mut Cpu, memory: &mut Memory) -> Result<bool, String> {
+        let outcome = match self.hooks.get_mut(&number) {
+            Some(hook) => hook(cpu, memory)?,
+            None if let Some((segment, offset)) = vector(memory, number) => {
+                enter(cpu, memory, segment, offset);
+                Outcome::Return
+            }
+            None => match self.services.call(number, cpu, memory) {
+                Some(outcome) => outcome?,
+                None => return Ok(false),
+            },
+        };
+        if let Outcome::Exit(code) = outcome {
+            self.exit_code = Some(code);
+        }
+        Ok(true)
+    }
+}
+
+impl fmt::Debug for Interrupts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut hooked: Vec<_> = self.hooks.keys().collect();
+        hooked.sort();
+        f.debug_struct("Interrupts")
+            .field("hooked", &hooked)
+            .field("services", &self.services)
+            .field("exit_code", &self.exit_code)
+            .finish()
+    }
+}
+
+/// Where the handler for `number` is, from the interrupt vector table of `offset:segment`
+/// pairs at 0000:0000. `None` while it's 0000:0000, which is how memory starts out.
+pub fn vector(memory: &Memory, number: u8) -> Option<(u16, u16)> {
+    let entry = u16::from(number) * 4;
+    let (offset, segment) = (memory.read_word(0, entry), memory.read_word(0, entry + 2));
+    (segment != 0 || offset != 0).then_some((segment, offset))
+}
+
+/// Calls the handler at `segment:offset` like an interrupt: pushes the flags, clears IF and TF
+/// so the handler runs without being interrupted or trapped, then pushes CS and IP. `iret` undoes
+/// it.
+pub fn enter(cpu: &mut Cpu, memory: &mut Memory, segment: u16, offset: u16) {
+    push(cpu, memory, cpu.flags.pushed());
+    cpu.flags.set(Flag::Interrupt, false);
+    cpu.flags.set(Flag::Trap, false);
+    push(cpu, memory, cpu.segment(SegmentRegister::CS));
+    push(cpu, memory, cpu.ip);
+    cpu.set_segment(SegmentRegister::CS, segment);
+    cpu.ip = offset;
+}
+
+/// DOS error codes returned in AX with the carry flag set.
+const FILE_NOT_FOUND: u16 = 2;
+const ACCESS_DENIED: u16 = 5;
+const INVALID_HANDLE: u16 = 6;
+
+/// The handles DOS opens for every program, files get the ones after.
+const STANDARD_HANDLES: u16 = 5;
+/// Longest file name DOS accepts, with its terminating zero.
+const MAX_PATH: u16 = 128;
+
+/// Stubs for the DOS and BIOS services simple programs use:
+///
+/// - `int 20h` and `int 21h` functions 00h and 4Ch terminate.
+/// - `int 21h` functions 02h and 09h print a character or a `$`-terminated string, 01h and 08h
+///   read a key, 3Dh, 3Fh and 3Eh open, read and close files in the sandbox directory, and 40h
+///   writes to the standard output or error handles.
+/// - `int 10h` function 0Eh prints a character as a teletype.
+/// - `int 16h` functions 00h and 01h read and peek at keys.
+///
+/// Printed text collects in [`Services::console`] and keys come from a queue filled up front,
+/// so runs are repeatable.
+#[derive(Debug, Default)]
+pub struct Services {
+    console: Vec<u8>,
+    input: VecDeque<u8>,
+    sandbox: Option<PathBuf>,
+    files: BTreeMap<u16, File>,
+}
+
+impl Services {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything printed so far.
+    pub fn console(&self) -> &[u8] {
+        &self.console
+    }
+
+    /// What's been printed since the last call.
+    pub fn take_console(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.console)
+    }
+
+    /// Adds keys for the program to read, a line feed is the Enter key's carriage return.
+    pub fn queue_input(&mut self, keys: &[u8]) {
+        let keys = keys
+            .iter()
+            .map(|&key| if key == b'\n' { b'\r' } else { key });
+        self.input.extend(keys);
+    }
+
+    /// Lets programs open files in `directory`, and nowhere else. Without one every open fails.
+    pub fn set_sandbox(&mut self, directory: impl Into<PathBuf>) {
+        self.sandbox = Some(directory.into());
+    }
+
+    /// `None` for interrupts these stubs don't cover at all.
+    fn call(
+        &mut self,
+        number: u8,
+        cpu: &mut Cpu,
+        memory: &mut Memory,
+    ) -> Option<Result<Outcome, String>> {
+        match number {
+            0x10 => Some(self.video(cpu)),
+            0x16 => Some(self.keyboard(cpu)),
+            0x20 => Some(Ok(Outcome::Exit(0))),
+            0x21 => Some(self.dos(cpu, memory)),
+            _ => None,
+        }
+    }
+
+    fn video(&mut self, cpu: &mut Cpu) -> Result<Outcome, String> {
+        match byte(cpu, RegisterByteOp::AH) {
+            0x0E => self.console.push(byte(cpu, RegisterByteOp::AL)),
+            function => return Err(unsupported(0x10, function)),
+        }
+        Ok(Outcome::Return)
+    }
+
+    /// Keys have no scan code, AH is always zero.
+    fn keyboard(&mut self, cpu: &mut Cpu) -> Result<Outcome, String> {
+        match byte(cpu, RegisterByteOp::AH) {
+            0x00 => {
+                let key = self.read_key()?;
+                cpu.set_word(RegisterWordOp::AX, u16::from(key));
+            }
+            0x01 => match self.input.front() {
+                Some(&key) => {
+                    cpu.set_word(RegisterWordOp::AX, u16::from(key));
+                    cpu.flags.set(Flag::Zero, false);
+                }
+                None => cpu.flags.set(Flag::Zero, true),
+            },
+            function => return Err(unsupported(0x16, function)),
+        }
+        Ok(Outcome::Return)
+    }
+
+    fn read_key(&mut self) -> Result<u8, String> {
+        // a real program would wait forever
+        self.input
+            .pop_front()
+            .ok_or_else(|| "the program is waiting for a key but the input is empty".to_string())
+    }
+
+    fn dos(&mut self, cpu: &mut Cpu, memory: &mut Memory) -> Result<Outcome, String> {
+        let ds = cpu.segment(SegmentRegister::DS);
+        let dx = cpu.word(RegisterWordOp::DX);
+        match byte(cpu, RegisterByteOp::AH) {
+            0x00 => return Ok(Outcome::Exit(0)),
+            0x01 | 0x08 => {
+                let key = self.read_key()?;
+                if byte(cpu, RegisterByteOp::AH) == 0x01 {
+                    self.console.push(key);
+                }
+                set_byte(cpu, RegisterByteOp::AL, key);
+            }
+            0x02 => {
+                let character = byte(cpu, RegisterByteOp::DL);
+                self.console.push(character);
+                set_byte(cpu, RegisterByteOp::AL, character);
+            }
+            0x09 => {
+                let mut offset = dx;
+                loop {
+                    match memory.read_byte(ds, offset) {
+                        b'$' => break,
+                        character => self.console.push(character),
+                    }
+                    offset = offset.wrapping_add(1);
+                    if offset == dx {
+                        return Err("the string to print has no `$` at the end".to_string());
+                    }
+                }
+                set_byte(cpu, RegisterByteOp::AL, b'$');
+            }
+            0x3D => {
+                let result = self.open(memory, ds, dx, byte(cpu, RegisterByteOp::AL));
+                finish(cpu, result);
+            }
+            0x3E => {
+                let handle = cpu.word(RegisterWordOp::BX);
+                let result = match self.files.remove(&handle) {
+                    Some(_) => Ok(0),
+                    None => Err(INVALID_HANDLE),
+                };
+                finish(cpu, result);
+            }
+            0x3F => {
+                let result = self.read(cpu, memory, ds, dx);
+                finish(cpu, result);
+            }
+            0x40 => {
+                let result = self.write(cpu, memory, ds, dx);
+                finish(cpu, result);
+            }
+            0x4C => return Ok(Outcome::Exit(byte(cpu, RegisterByteOp::AL))),
+            function => return Err(unsupported(0x21, function)),
+        }
+        Ok(Outcome::Return)
+    }
+
+    /// Opens the file named by the zero-terminated string at `segment:offset` for reading.
+    fn open(&mut self, memory: &Memory, segment: u16, offset: u16, mode: u8) -> Result<u16, u16> {
+        // only read access, in any of the sharing modes
+        if mode & 0b111 != 0 {
+            return Err(ACCESS_DENIED);
+        }
+        let name: Vec<u8> = (0..MAX_PATH)
+            .map(|index| memory.read_byte(segment, offset.wrapping_add(index)))
+            .take_while(|&byte| byte != 0)
+            .collect();
+        let path = sandboxed(self.sandbox.as_deref(), &name)?;
+        let file = File::open(path).map_err(|_| FILE_NOT_FOUND)?;
+
+        let handle = (STANDARD_HANDLES..)
+            .find(|handle| !self.files.contains_key(handle))
+            .ok_or(ACCESS_DENIED)?;
+        self.files.insert(handle, file);
+        Ok(handle)
+    }
+
+    /// Reads CX bytes to `segment:offset` from the file in BX, or from the input for standard
+    /// input.
+    fn read(
+        &mut self,
+        cpu: &Cpu,
+        memory: &mut Memory,
+        segment: u16,
+        offset: u16,
+    ) -> Result<u16, u16> {
+        let (handle, count) = (cpu.word(RegisterWordOp::BX), cpu.word(RegisterWordOp::CX));
+        let bytes = match handle {
+            0 => {
+                let available = self.input.len().min(usize::from(count));
+                self.input.drain(..available).collect()
+            }
+            _ => {
+                let file = self.files.get_mut(&handle).ok_or(INVALID_HANDLE)?;
+                let mut bytes = vec![];
+                file.take(u64::from(count))
+                    .read_to_end(&mut bytes)
+                    .map_err(|_| ACCESS_DENIED)?;
+                bytes
+            }
+        };
+        memory.load(segment, offset, &bytes);
+        Ok(bytes.len() as u16)
+    }
+
+    /// Only standard output and error can be written, to the console.
+    fn write(&mut self, cpu: &Cpu, memory: &Memory, segment: u16, offset: u16) -> Result<u16, u16> {
+        let (handle, count) = (cpu.word(RegisterWordOp::BX), cpu.word(RegisterWordOp::CX));
+        match handle {
+            1 | 2 => {
+                let bytes =
+                    (0..count).map(|index| memory.read_byte(segment, offset.wrapping_add(index)));
+                self.console.extend(bytes);
+                Ok(count)
+            }
+            _ if self.files.contains_key(&handle) => Err(ACCESS_DENIED),
+            _ => Err(INVALID_HANDLE),
+        }
+    }
+}
+
+/// Where a DOS file name points inside `sandbox`. Names that could reach outside it are denied:
+/// drive letters, absolute paths, `..` and symbolic links that lead out of it. The path is
+/// resolved, so opening it doesn't follow links again.
+fn sandboxed(sandbox: Option<&Path>, name: &[u8]) -> Result<PathBuf, u16> {
+    let name = std::str::from_utf8(name)
+        .map_err(|_| ACCESS_DENIED)?
+        .replace('\\', "/");
+    let relative = Path::new(&name);
+    if name.is_empty() || name.contains(':') {
+        return Err(ACCESS_DENIED);
+    }
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(ACCESS_DENIED);
+    }
+    let root = sandbox
+        .ok_or(ACCESS_DENIED)?
+        .canonicalize()
+        .map_err(|_| ACCESS_DENIED)?;
+    let path = root
+        .join(relative)
+        .canonicalize()
+        .map_err(|_| FILE_NOT_FOUND)?;
+    if !path.starts_with(&root) {
+        return Err(ACCESS_DENIED);
+    }
+    Ok(path)
+}
+
+/// DOS reports success with the carry flag clear and the result in AX, failure with it set and
+/// an error code in AX.
+fn finish(cpu: &mut Cpu, result: Result<u16, u16>) {
+    cpu.flags.set(Flag::Carry, result.is_err());
+    cpu.set_word(RegisterWordOp::AX, result.unwrap_or_else(|code| code));
+}
+
+fn unsupported(number: u8, function: u8) -> String {
+    format!("function {function:02X}h of interrupt {number:02X}h isn't supported")
+}
+
+fn byte(cpu: &Cpu, register: RegisterByteOp) -> u8 {
+    cpu.register(Register::Byte(register)) as u8
+}
+
+fn set_byte(cpu: &mut Cpu, register: RegisterByteOp, value: u8) {
+    cpu.set_register(Register::Byte(register), u16::from(value));
+}
@@ -0,0 +1,439 @@
+use super::*;
+use crate::dos;
+use crate::flags::FlagSet;
+use crate::sim::{RunOptions, SimError, run_loaded};
+use crate::syntax::Nasm;
+use rstest::rstest;
+use std::fs;
+
+/// Where test programs keep their strings and buffers, in DS.
+const DATA: u16 = 0x200;
+
+/// Runs `code` as a `.COM` program, returning the trace and the result.
+fn run_com(code: &[u8], interrupts: &mut Interrupts) -> (String, Result<(), SimError>) {
+    let mut program = dos::load_com(code).unwrap();
+    let mut trace = vec![];
+    let result = run_loaded(
+        &program.image,
+        &mut program.cpu,
+        &mut program.memory,
+        RunOptions {
+            interrupts: Some(interrupts),
+            ..RunOptions::default()
+        },
+        &Nasm::default(),
+        &mut trace,
+    );
+    (String::from_utf8(trace).unwrap(), result)
+}
+
+/// Calls `int number` with `ah` and whatever else `setup` puts in the machine.
+fn call(
+    interrupts: &mut Interrupts,
+    number: u8,
+    ah: u8,
+    setup: impl FnOnce(&mut Cpu, &mut Memory),
+) -> (Cpu, Memory, Result<bool, String>) {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    set_byte(&mut cpu, RegisterByteOp::AH, ah);
+    setup(&mut cpu, &mut memory);
+    let result = interrupts.call(number, &mut cpu, &mut memory);
+    (cpu, memory, result)
+}
+
+#[test]
+fn test_program_prints_a_string_and_exits_with_a_code() {
+    let code = [
+        0xB4, 0x09, // mov ah, 9
+        0xBA, 0x0C, 0x01, // mov dx, 0x10c
+        0xCD, 0x21, // int 0x21
+        0xB8, 0x2A, 0x4C, // mov ax, 0x4c2a
+        0xCD, 0x21, // int 0x21
+        b'h', b'i', b'\r', b'\n', b'$',
+    ];
+    let mut interrupts = Interrupts::new();
+    let (trace, result) = run_com(&code, &mut interrupts);
+
+    result.unwrap();
+    assert_eq!(interrupts.services().console(), b"hi\r\n");
+    assert_eq!(interrupts.exit_code(), Some(42));
+    // stopped at the exit rather than running into the string
+    assert_eq!(trace.lines().count(), 5);
+    assert!(trace.lines().last().unwrap().starts_with("int 33 ;"));
+}
+
+#[rstest]
+#[case::dos_character(0x21, 0x02)]
+#[case::bios_teletype(0x10, 0x0E)]
+fn test_prints_characters(#[case] number: u8, #[case] ah: u8) {
+    let mut interrupts = Interrupts::new();
+    for character in *b"ok" {
+        let (_, _, result) = call(&mut interrupts, number, ah, |cpu, _| {
+            set_byte(cpu, RegisterByteOp::DL, character);
+            set_byte(cpu, RegisterByteOp::AL, character);
+        });
+        assert_eq!(result, Ok(true));
+    }
+
+    assert_eq!(interrupts.services().console(), b"ok");
+    assert_eq!(interrupts.services_mut().take_console(), b"ok");
+    assert_eq!(interrupts.services().console(), b"");
+}
+
+#[test]
+fn test_print_string_needs_a_terminator() {
+    let mut interrupts = Interrupts::new();
+    let (_, _, result) = call(&mut interrupts, 0x21, 0x09, |_, _| {});
+
+    assert_eq!(
+        result,
+        Err("the string to print has no `$` at the end".to_string())
+    );
+}
+
+#[test]
+fn test_reads_keys_from_the_queue() {
+    let mut services = Services::new();
+    services.queue_input(b"ab\n");
+    let mut interrupts = Interrupts::with_services(services);
+
+    // read with echo, without echo, then through the BIOS
+    let (cpu, _, _) = call(&mut interrupts, 0x21, 0x01, |_, _| {});
+    assert_eq!(byte(&cpu, RegisterByteOp::AL), b'a');
+    let (cpu, _, _) = call(&mut interrupts, 0x21, 0x08, |_, _| {});
+    assert_eq!(byte(&cpu, RegisterByteOp::AL), b'b');
+    let (cpu, _, _) = call(&mut interrupts, 0x16, 0x00, |_, _| {});
+    assert_eq!(cpu.word(RegisterWordOp::AX), u16::from(b'\r'));
+    assert_eq!(interrupts.services().console(), b"a");
+
+    let (_, _, result) = call(&mut interrupts, 0x21, 0x01, |_, _| {});
+    assert_eq!(
+        result,
+        Err("the program is waiting for a key but the input is empty".to_string())
+    );
+}
+
+#[test]
+fn test_peeking_leaves_the_key_queued() {
+    let mut interrupts = Interrupts::new();
+    let (cpu, _, _) = call(&mut interrupts, 0x16, 0x01, |cpu, _| {
+        cpu.flags.set(Flag::Zero, false);
+    });
+    assert!(cpu.flags.contains(Flag::Zero));
+
+    interrupts.services_mut().queue_input(b"x");
+    for _ in 0..2 {
+        let (cpu, _, _) = call(&mut interrupts, 0x16, 0x01, |_, _| {});
+        assert!(!cpu.flags.contains(Flag::Zero));
+        assert_eq!(cpu.word(RegisterWordOp::AX), u16::from(b'x'));
+    }
+    let (cpu, _, _) = call(&mut interrupts, 0x16, 0x00, |_, _| {});
+    assert_eq!(cpu.word(RegisterWordOp::AX), u16::from(b'x'));
+}
+
+#[rstest]
+#[case::int_20h(0x20, 0x00, 0)]
+#[case::function_00h(0x21, 0x00, 0)]
+#[case::function_4ch(0x21, 0x4C, 7)]
+fn test_terminates(#[case] number: u8, #[case] ah: u8, #[case] expected: u8) {
+    let mut interrupts = Interrupts::new();
+    let (_, _, result) = call(&mut interrupts, number, ah, |cpu, _| {
+        set_byte(cpu, RegisterByteOp::AL, 7);
+    });
+
+    assert_eq!(result, Ok(true));
+    assert_eq!(interrupts.exit_code(), Some(expected));
+    interrupts.clear_exit();
+    assert_eq!(interrupts.exit_code(), None);
+}
+
+#[test]
+fn test_writes_to_standard_output() {
+    let mut interrupts = Interrupts::new();
+    let (cpu, _, _) = call(&mut interrupts, 0x21, 0x40, |cpu, memory| {
+        cpu.set_word(RegisterWordOp::BX, 1);
+        cpu.set_word(RegisterWordOp::CX, 3);
+        cpu.set_word(RegisterWordOp::DX, DATA);
+        memory.load(0, DATA, b"yes$");
+    });
+
+    assert_eq!(interrupts.services().console(), b"yes");
+    assert_eq!(cpu.word(RegisterWordOp::AX), 3);
+    assert!(!cpu.flags.contains(Flag::Carry));
+}
+
+#[rstest]
+#[case::unsupported_function(0x21, 0x30, "function 30h of interrupt 21h isn't supported")]
+#[case::unsupported_video(0x10, 0x00, "function 00h of interrupt 10h isn't supported")]
+fn test_rejects_unsupported_functions(#[case] number: u8, #[case] ah: u8, #[case] expected: &str) {
+    let (_, _, result) = call(&mut Interrupts::new(), number, ah, |_, _| {});
+    assert_eq!(result, Err(expected.to_string()));
+}
+
+#[test]
+fn test_unknown_interrupts_are_not_handled() {
+    let (_, _, result) = call(&mut Interrupts::new(), 0x33, 0x00, |_, _| {});
+    assert_eq!(result, Ok(false));
+}
+
+#[test]
+fn test_service_errors_stop_the_program() {
+    // mov ah, 1 / int 0x21
+    let (trace, result) = run_com(&[0xB4, 0x01, 0xCD, 0x21], &mut Interrupts::new());
+
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "interrupt 21h at offset 0x0102 failed: the program is waiting for a key but the input \
+         is empty"
+    );
+    assert_eq!(trace, "mov ah, 1 ; ax:0x0->0x100 ip:0x100->0x102\n");
+}
+
+#[test]
+fn test_hooks_replace_the_services() {
+    let mut interrupts = Interrupts::new();
+    interrupts.hook(0x21, |cpu, _| {
+        cpu.set_word(RegisterWordOp::BX, 0xBEEF);
+        Ok(Outcome::Return)
+    });
+    interrupts.hook(0x33, |_, _| Ok(Outcome::Exit(3)));
+
+    let (cpu, _, result) = call(&mut interrupts, 0x21, 0x02, |cpu, _| {
+        set_byte(cpu, RegisterByteOp::DL, b'!');
+    });
+    assert_eq!(result, Ok(true));
+    assert_eq!(cpu.word(RegisterWordOp::BX), 0xBEEF);
+    assert_eq!(interrupts.services().console(), b"");
+
+    let (_, _, result) = call(&mut interrupts, 0x33, 0x00, |_, _| {});
+    assert_eq!(result, Ok(true));
+    assert_eq!(interrupts.exit_code(), Some(3));
+
+    interrupts.unhook(0x21);
+    let (_, _, result) = call(&mut interrupts, 0x21, 0x02, |cpu, _| {
+        set_byte(cpu, RegisterByteOp::DL, b'!');
+    });
+    assert_eq!(result, Ok(true));
+    assert_eq!(interrupts.services().console(), b"!");
+}
+
+#[test]
+fn test_hook_errors_stop_the_program() {
+    let mut interrupts = Interrupts::new();
+    interrupts.hook(0x60, |_, _| Err("not today".to_string()));
+    // int 0x60
+    let (_, result) = run_com(&[0xCD, 0x60], &mut interrupts);
+
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "interrupt 60h at offset 0x0100 failed: not today"
+    );
+}
+
+/// A sandbox directory holding `data.txt`, unique to the test.
+fn sandbox(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("interrupts-{name}-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("data.txt"), b"hello").unwrap();
+    directory
+}
+
+/// Opens the zero-terminated `name` for reading, returning AX and the carry flag.
+fn open(interrupts: &mut Interrupts, name: &[u8]) -> (u16, bool) {
+    let (cpu, _, _) = call(interrupts, 0x21, 0x3D, |cpu, memory| {
+        cpu.set_word(RegisterWordOp::DX, DATA);
+        memory.load(0, DATA, name);
+    });
+    (
+        cpu.word(RegisterWordOp::AX),
+        cpu.flags.contains(Flag::Carry),
+    )
+}
+
+#[test]
+fn test_reads_files_in_the_sandbox() {
+    let directory = sandbox("read");
+    let mut interrupts = Interrupts::new();
+    interrupts.services_mut().set_sandbox(&directory);
+
+    let (handle, failed) = open(&mut interrupts, b"data.txt\0");
+    assert_eq!((handle, failed), (5, false));
+
+    let read = |interrupts: &mut Interrupts, count: u16| {
+        let (cpu, memory, _) = call(interrupts, 0x21, 0x3F, |cpu, _| {
+            cpu.set_word(RegisterWordOp::BX, handle);
+            cpu.set_word(RegisterWordOp::CX, count);
+            cpu.set_word(RegisterWordOp::DX, DATA);
+        });
+        let length = usize::from(cpu.word(RegisterWordOp::AX));
+        memory.bytes()[usize::from(DATA)..usize::from(DATA) + length].to_vec()
+    };
+    assert_eq!(read(&mut interrupts, 3), b"hel");
+    assert_eq!(read(&mut interrupts, 10), b"lo");
+    assert_eq!(read(&mut interrupts, 10), b"");
+
+    let close = |interrupts: &mut Interrupts| {
+        let (cpu, _, _) = call(interrupts, 0x21, 0x3E, |cpu, _| {
+            cpu.set_word(RegisterWordOp::BX, handle);
+        });
+        (
+            cpu.word(RegisterWordOp::AX),
+            cpu.flags.contains(Flag::Carry),
+        )
+    };
+    assert_eq!(close(&mut interrupts), (0, false));
+    assert_eq!(close(&mut interrupts), (INVALID_HANDLE, true));
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_files_are_read_only() {
+    let directory = sandbox("read-only");
+    let mut interrupts = Interrupts::new();
+    interrupts.services_mut().set_sandbox(&directory);
+    let (handle, _) = open(&mut interrupts, b"data.txt\0");
+
+    let (cpu, _, _) = call(&mut interrupts, 0x21, 0x40, |cpu, _| {
+        cpu.set_word(RegisterWordOp::BX, handle);
+        cpu.set_word(RegisterWordOp::CX, 1);
+    });
+    assert_eq!(cpu.word(RegisterWordOp::AX), ACCESS_DENIED);
+    assert!(cpu.flags.contains(Flag::Carry));
+
+    // open for writing
+    let (cpu, _, _) = call(&mut interrupts, 0x21, 0x3D, |cpu, memory| {
+        set_byte(cpu, RegisterByteOp::AL, 1);
+        cpu.set_word(RegisterWordOp::DX, DATA);
+        memory.load(0, DATA, b"data.txt\0");
+    });
+    assert_eq!(cpu.word(RegisterWordOp::AX), ACCESS_DENIED);
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[rstest]
+#[case::missing(b"nothing.txt\0", FILE_NOT_FOUND)]
+#[case::parent(b"../data.txt\0", ACCESS_DENIED)]
+#[case::nested_parent(b"sub\\..\\..\\data.txt\0", ACCESS_DENIED)]
+#[case::absolute(b"/etc/passwd\0", ACCESS_DENIED)]
+#[case::drive(b"C:\\data.txt\0", ACCESS_DENIED)]
+#[case::empty(b"\0", ACCESS_DENIED)]
+fn test_opening_outside_the_sandbox_fails(#[case] name: &[u8], #[case] expected: u16) {
+    let directory = sandbox(&format!("escape-{expected}-{}", name.len()));
+    let mut interrupts = Interrupts::new();
+    interrupts.services_mut().set_sandbox(&directory);
+
+    assert_eq!(open(&mut interrupts, name), (expected, true));
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_links_out_of_the_sandbox_are_denied() {
+    let directory = sandbox("links");
+    let outside = sandbox("links-outside");
+    std::os::unix::fs::symlink(&outside, directory.join("out")).unwrap();
+    std::os::unix::fs::symlink(outside.join("data.txt"), directory.join("leak.txt")).unwrap();
+    std::os::unix::fs::symlink("data.txt", directory.join("inside.txt")).unwrap();
+    let mut interrupts = Interrupts::new();
+    interrupts.services_mut().set_sandbox(&directory);
+
+    assert_eq!(
+        open(&mut interrupts, b"out\\data.txt\0"),
+        (ACCESS_DENIED, true)
+    );
+    assert_eq!(open(&mut interrupts, b"leak.txt\0"), (ACCESS_DENIED, true));
+    // links that stay inside are fine
+    assert_eq!(open(&mut interrupts, b"inside.txt\0"), (5, false));
+    fs::remove_dir_all(directory).unwrap();
+    fs::remove_dir_all(outside).unwrap();
+}
+
+#[test]
+fn test_opening_without_a_sandbox_fails() {
+    assert_eq!(
+        open(&mut Interrupts::new(), b"data.txt\0"),
+        (ACCESS_DENIED, true)
+    );
+}
+
+#[test]
+fn test_program_echoes_a_file() {
+    let directory = sandbox("echo");
+    let mut interrupts = Interrupts::new();
+    interrupts.services_mut().set_sandbox(&directory);
+    let code = [
+        0xB8, 0x00, 0x3D, // mov ax, 0x3d00
+        0xBA, 0x1F, 0x01, // mov dx, 0x11f
+        0xCD, 0x21, // int 0x21
+        0x89, 0xC3, // mov bx, ax
+        0xB4, 0x3F, // mov ah, 0x3f
+        0xB9, 0x10, 0x00, // mov cx, 16
+        0xBA, 0x00, 0x02, // mov dx, 0x200
+        0xCD, 0x21, // int 0x21
+        0x89, 0xC1, // mov cx, ax
+        0xBB, 0x01, 0x00, // mov bx, 1
+        0xB4, 0x40, // mov ah, 0x40
+        0xCD, 0x21, // int 0x21
+        0xCD, 0x20, // int 0x20
+        b'd', b'a', b't', b'a', b'.', b't', b'x', b't', 0,
+    ];
+    let (_, result) = run_com(&code, &mut interrupts);
+
+    result.unwrap();
+    assert_eq!(interrupts.services().console(), b"hello");
+    assert_eq!(interrupts.exit_code(), Some(0));
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_vectors_come_from_the_table_at_zero() {
+    let mut memory = Memory::new();
+    assert_eq!(vector(&memory, 0x21), None);
+
+    memory.write_word(0, 0x21 * 4, 0x1234);
+    memory.write_word(0, 0x21 * 4 + 2, 0x5678);
+    assert_eq!(vector(&memory, 0x21), Some((0x5678, 0x1234)));
+    assert_eq!(vector(&memory, 0x20), None);
+}
+
+#[test]
+fn test_entering_a_handler_pushes_flags_cs_and_ip() {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    cpu.set_segment(SegmentRegister::CS, 0x1111);
+    cpu.set_segment(SegmentRegister::SS, 0x2000);
+    cpu.set_word(RegisterWordOp::SP, 0x100);
+    cpu.ip = 0x2222;
+    cpu.flags = FlagSet::of(&[Flag::Interrupt, Flag::Trap, Flag::Carry]);
+
+    enter(&mut cpu, &mut memory, 0x3000, 0x40);
+
+    assert_eq!(cpu.segment(SegmentRegister::CS), 0x3000);
+    assert_eq!(cpu.ip, 0x40);
+    assert_eq!(cpu.flags, FlagSet::of(&[Flag::Carry]));
+    assert_eq!(cpu.word(RegisterWordOp::SP), 0xFA);
+    assert_eq!(memory.read_word(0x2000, 0xFA), 0x2222);
+    assert_eq!(memory.read_word(0x2000, 0xFC), 0x1111);
+    // with the bits that aren't flags set
+    assert_eq!(memory.read_word(0x2000, 0xFE), 0xF303);
+}
+
+#[test]
+fn test_installed_handlers_come_between_hooks_and_services() {
+    let mut interrupts = Interrupts::new();
+    let install = |_: &mut Cpu, memory: &mut Memory| {
+        memory.write_word(0, 0x21 * 4, 0x40);
+        memory.write_word(0, 0x21 * 4 + 2, 0x3000);
+    };
+
+    let (cpu, _, result) = call(&mut interrupts, 0x21, 0x02, install);
+    assert_eq!(result, Ok(true));
+    assert_eq!((cpu.segment(SegmentRegister::CS), cpu.ip), (0x3000, 0x40));
+    assert_eq!(interrupts.services().console(), b"");
+
+    interrupts.hook(0x21, |_, _| Ok(Outcome::Exit(1)));
+    let (cpu, _, _) = call(&mut interrupts, 0x21, 0x02, install);
+    assert_eq!(cpu.ip, 0);
+    assert_eq!(interrupts.exit_code(), Some(1));
+}
@@ -0,0 +1,184 @@
+use crate::decoder::{EffectiveAddress, Instruction, Operand};
+use crate::effects::{Effects, Location, dependencies};
+use crate::flags::FlagSet;
+use crate::syntax::InstructionFormatter;
+use serde::Serialize;
+use std::io::{self, Write};
+
+#[cfg(test)]
+mod json_tests;
+
+/// Machine-readable view of a decoded instruction, built from the structured operands rather
+/// than the NASM text.
+#[derive(Debug, Serialize)]
+pub struct JsonInstruction<'a> {
+    pub offset: usize,
+    pub length: usize,
+    pub bytes: &'a [u8],
+    pub mnemonic: &'static str,
+    pub width: &'static str,
+    pub prefixes: JsonPrefixes,
+    pub operands: Vec<JsonOperand>,
+    /// Offset a relative jump or call transfers to.
+    pub target: Option<usize>,
+    pub text: String,
+    /// Registers, segments, memory, ports and flags (`cf`, `zf`, ...) read, implicit ones
+    /// included.
+    pub reads: Vec<String>,
+    pub writes: Vec<String>,
+    /// Offsets of the closest earlier instructions that wrote something this one reads.
+    pub depends_on: Vec<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonPrefixes {
+    pub segment: Option<&'static str>,
+    pub lock: bool,
+    pub repeat: Option<&'static str>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JsonOperand {
+    Register {
+        register: &'static str,
+        width: &'static str,
+    },
+    Segment {
+        register: &'static str,
+    },
+    Memory {
+        /// Registers added together to form the address, empty for a direct address.
+        base: Vec<&'static str>,
+        /// Signed displacement, or the unsigned address when `base` is empty.
+        displacement: i32,
+    },
+    Immediate {
+        value: u16,
+    },
+    /// Relative jump or call, the displacement is from the end of the instruction.
+    Relative {
+        displacement: i16,
+    },
+    Far {
+        segment: u16,
+        offset: u16,
+    },
+}
+
+impl From<Operand> for JsonOperand {
+    fn from(operand: Operand) -> Self {
+        match operand {
+            Operand::Register(register) => Self::Register {
+                register: register.name(),
+                width: register.width().name(),
+            },
+            Operand::Segment(segment) => Self::Segment {
+                register: segment.name(),
+            },
+            Operand::Memory(EffectiveAddress {
+                base, displacement, ..
+            }) => match base {
+                None => Self::Memory {
+                    base: vec![],
+                    displacement: i32::from(displacement as u16),
+                },
+                Some(base) => {
+                    let (first, second) = base.registers();
+                    Self::Memory {
+                        base: [Some(first), second]
+                            .into_iter()
+                            .flatten()
+                            .map(|register| register.name())
+                            .collect(),
+                        displacement: i32::from(displacement),
+                    }
+                }
+            },
+            Operand::Immediate(value) => Self::Immediate { value },
+            Operand::Relative(displacement) => Self::Relative { displacement },
+            Operand::Far { segment, offset } => Self::Far { segment, offset },
+        }
+    }
+}
+
+impl<'a> JsonInstruction<'a> {
+    /// `data` is the image the instruction was decoded from, `text` is rendered with `formatter`.
+    pub fn new(
+        data: &'a [u8],
+        instruction: &Instruction,
+        formatter: &dyn InstructionFormatter,
+    ) -> Self {
+        let effects = Effects::of(instruction);
+        Self {
+            offset: instruction.offset,
+            length: instruction.length,
+            bytes: &data[instruction.offset..instruction.offset + instruction.length],
+            mnemonic: instruction.mnemonic.name(),
+            width: instruction.width.name(),
+            prefixes: JsonPrefixes {
+                segment: instruction.prefixes.segment.map(|segment| segment.name()),
+                lock: instruction.prefixes.lock,
+                repeat: instruction.prefixes.repeat.map(|repeat| repeat.name()),
+            },
+            operands: instruction.operands().map(JsonOperand::from).collect(),
+            target: instruction.branch_target(),
+            text: formatter.format_instruction(instruction),
+            reads: names(&effects.reads, effects.flags_read),
+            writes: names(&effects.writes, effects.flags_written),
+            depends_on: vec![],
+        }
+    }
+}
+
+fn names(locations: &[Location], flags: FlagSet) -> Vec<String> {
+    locations
+        .iter()
+        .map(Location::to_string)
+        .chain(flags.iter().map(|flag| flag.name().to_owned()))
+        .collect()
+}
+
+fn records<'a>(
+    data: &'a [u8],
+    instructions: &'a [Instruction],
+    formatter: &'a dyn InstructionFormatter,
+) -> impl Iterator<Item = JsonInstruction<'a>> {
+    let effects: Vec<_> = instructions.iter().map(Effects::of).collect();
+    instructions
+        .iter()
+        .zip(dependencies(&effects))
+        .map(move |(instruction, writers)| JsonInstruction {
+            depends_on: writers
+                .into_iter()
+                .map(|index| instructions[index].offset)
+                .collect(),
+            ..JsonInstruction::new(data, instruction, formatter)
+        })
+}
+
+/// Writes all instructions as a single pretty-printed JSON array.
+pub fn write_json(
+    out: &mut impl Write,
+    data: &[u8],
+    instructions: &[Instruction],
+    formatter: &dyn InstructionFormatter,
+) -> io::Result<()> {
+    let records: Vec<_> = records(data, instructions, formatter).collect();
+    serde_json::to_writer_pretty(&mut *out, &records)?;
+    writeln!(out)
+}
+
+/// Writes one compact JSON object per instruction per line.
+pub fn write_json_lines(
+    out: &mut impl Write,
+    data: &[u8],
+    instructions: &[Instruction],
+    formatter: &dyn InstructionFormatter,
+) -> io::Result<()> {
+    for record in records(data, instructions, formatter) {
+        serde_json::to_writer(&mut *out, &record)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use super::*;
+use crate::decoder::decode_all;
+use crate::syntax::Nasm;
+use rstest::rstest;
+use serde_json::{Value, json};
+
+fn json_lines(data: &[u8]) -> Vec<Value> {
+    let instructions = decode_all(data).unwrap();
+    let mut out = vec![];
+    write_json_lines(&mut out, data, &instructions, &Nasm::default()).unwrap();
+
+    String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_serialises_every_field() {
+    assert_eq!(
+        json_lines(&[0x26, 0x89, 0x8C, 0xD4, 0xFE]),
+        [json!({
+            "offset": 0,
+            "length": 5,
+            "bytes": [0x26, 0x89, 0x8C, 0xD4, 0xFE],
+            "mnemonic": "mov",
+            "width": "word",
+            "prefixes": { "segment": "es", "lock": false, "repeat": null },
+            "operands": [
+                { "kind": "memory", "base": ["si"], "displacement": -300 },
+                { "kind": "register", "register": "cx", "width": "word" },
+            ],
+            "target": null,
+            "text": "mov [es:si - 300], cx",
+            "reads": ["cx", "si", "es"],
+            "writes": ["[es:si - 300]"],
+            "depends_on": [],
+        })]
+    );
+}
+
+#[rstest]
+#[case::register(&[0x88, 0xE5], json!({ "kind": "register", "register": "ch", "width": "byte" }))]
+#[case::segment(&[0x8E, 0xD8], json!({ "kind": "segment", "register": "ds" }))]
+#[case::register_loaded_from_memory(&[0x8A, 0x00], json!({ "kind": "register", "register": "al", "width": "byte" }))]
+#[case::direct_address(&[0xA3, 0xFF, 0xFF], json!({ "kind": "memory", "base": [], "displacement": 65535 }))]
+#[case::memory(&[0xC6, 0x03, 0xF4], json!({ "kind": "memory", "base": ["bp", "di"], "displacement": 0 }))]
+fn test_serialises_destination(#[case] bytes: &[u8], #[case] expected: Value) {
+    assert_eq!(json_lines(bytes)[0]["operands"][0], expected);
+}
+
+#[rstest]
+#[case::memory(&[0x8A, 0x00], json!({ "kind": "memory", "base": ["bx", "si"], "displacement": 0 }))]
+#[case::byte_immediate(&[0xC6, 0x03, 0xF4], json!({ "kind": "immediate", "value": 0xF4 }))]
+#[case::word_immediate(&[0xBA, 0x94, 0xF0], json!({ "kind": "immediate", "value": 0xF094 }))]
+fn test_serialises_source(#[case] bytes: &[u8], #[case] expected: Value) {
+    assert_eq!(json_lines(bytes)[0]["operands"][1], expected);
+}
+
+#[test]
+fn test_json_array_matches_json_lines() {
+    let data = [0x89, 0xD9, 0xB1, 0x0C];
+    let instructions = decode_all(&data).unwrap();
+    let mut out = vec![];
+    write_json(&mut out, &data, &instructions, &Nasm::default()).unwrap();
+
+    let array: Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(array, Value::Array(json_lines(&data)));
+}
+
+#[rstest]
+#[case::relative(&[0x75, 0x02], json!({ "kind": "relative", "displacement": 2 }), json!(4))]
+#[case::before_the_image(&[0x75, 0xFC], json!({ "kind": "relative", "displacement": -4 }), Value::Null)]
+#[case::far(
+    &[0xEA, 0x78, 0x56, 0x34, 0x12],
+    json!({ "kind": "far", "segment": 0x1234, "offset": 0x5678 }),
+    Value::Null
+)]
+fn test_serialises_jumps(#[case] bytes: &[u8], #[case] operand: Value, #[case] target: Value) {
+    let record = &json_lines(bytes)[0];
+    assert_eq!(record["operands"][0], operand);
+    assert_eq!(record["target"], target);
+}
+
+#[test]
+fn test_serialises_effects_and_dependencies() {
+    // mov cx, 3 / rep movsb / jne $+0
+    let records = json_lines(&[0xB9, 0x03, 0x00, 0xF3, 0xA4, 0x75, 0xFE]);
+
+    assert_eq!(records[1]["prefixes"]["repeat"], "rep");
+    assert_eq!(
+        records[1]["reads"],
+        json!(["cx", "si", "ds", "[ds:si]", "di", "es", "df"])
+    );
+    assert_eq!(records[1]["writes"], json!(["cx", "si", "[es:di]", "di"]));
+    assert_eq!(records[1]["depends_on"], json!([0]));
+    assert_eq!(records[2]["reads"], json!(["zf"]));
+    assert_eq!(records[2]["depends_on"], json!([]));
+}
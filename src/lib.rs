@@ -0,0 +1,25 @@
+//! Following along with the performance-aware programming series: decoding 8086 machine code.
+
+pub mod alu;
+pub mod cfg;
+pub mod constants;
+pub mod cpu;
+pub mod debugger;
+pub mod decode_cache;
+pub mod decoder;
+pub mod dos;
+pub mod effects;
+pub mod flags;
+pub mod image;
+pub mod interrupts;
+pub mod json;
+pub mod listing;
+pub mod memory;
+pub mod ports;
+pub mod recording;
+pub mod reference;
+pub mod sim;
+pub mod snapshot;
+pub mod syntax;
+pub mod timing;
+pub mod traversal;
@@ -0,0 +1,152 @@
+use crate::constants::propagate;
+use crate::decoder::Instruction;
+use crate::effects::{Effects, Location, dependencies};
+use crate::flags::FlagSet;
+use crate::syntax::InstructionFormatter;
+use crate::traversal::Region;
+use std::io::{self, Write};
+
+#[cfg(test)]
+mod listing_tests;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ListingOptions {
+    /// Adds a column with every byte of the instruction in binary, e.g. `10001001 11011001`.
+    pub show_bits: bool,
+    /// Ends each instruction with a comment listing what it reads and writes and which earlier
+    /// lines it depends on, e.g. `; reads ax, bx; writes ax, flags CPAZSO; depends on 0000`.
+    pub show_effects: bool,
+    /// Ends instructions with a memory operand based on registers whose values are known without
+    /// running, from constants moved and computed into them earlier, with the offset it
+    /// resolves to, e.g. `; = [0x1004]`.
+    pub show_addresses: bool,
+}
+
+/// Writes an `objdump` style listing, one instruction per line:
+///
+/// ```text
+/// 0000  89 d9     2  mov cx, bx
+/// 0002  b1 0c     2  mov cl, 12
+/// ```
+///
+/// The columns are the offset, the raw bytes, the instruction length and the assembly text.
+/// `data` is the image the instructions were decoded from, offsets index into it.
+pub fn write_listing(
+    out: &mut impl Write,
+    data: &[u8],
+    instructions: &[Instruction],
+    formatter: &dyn InstructionFormatter,
+    options: ListingOptions,
+) -> io::Result<()> {
+    let regions: Vec<_> = instructions.iter().map(Region::Code).collect();
+    write_region_listing(out, data, &regions, formatter, options)
+}
+
+/// Same as [`write_listing`], with data regions shown as `db` lines.
+pub fn write_region_listing(
+    out: &mut impl Write,
+    data: &[u8],
+    regions: &[Region],
+    formatter: &dyn InstructionFormatter,
+    options: ListingOptions,
+) -> io::Result<()> {
+    let last_offset = regions.last().map_or(0, |region| region.offset());
+    let offset_width = format!("{last_offset:x}").len().max(4);
+    let longest = regions.iter().map(|region| region.len()).max().unwrap_or(0);
+    // every byte takes two hex digits (or eight bits) plus a separating space
+    let hex_width = (longest * 3).saturating_sub(1);
+    let bits_width = (longest * 9).saturating_sub(1);
+
+    let code: Vec<_> = regions
+        .iter()
+        .filter_map(|region| match region {
+            Region::Code(instruction) => Some(*instruction),
+            Region::Data { .. } => None,
+        })
+        .collect();
+    let effects: Vec<_> = code
+        .iter()
+        .map(|instruction| Effects::of(instruction))
+        .collect();
+    let writers = dependencies(&effects);
+    let known = if options.show_addresses {
+        let instructions: Vec<Instruction> = code.iter().map(|&instruction| *instruction).collect();
+        propagate(&instructions)
+    } else {
+        vec![]
+    };
+    let mut code_index = 0;
+
+    for region in regions {
+        let bytes = &data[region.offset()..region.offset() + region.len()];
+        let hex = bytes
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        write!(
+            out,
+            "{:0offset_width$x}  {hex:hex_width$}  ",
+            region.offset()
+        )?;
+
+        if options.show_bits {
+            let bits = bytes
+                .iter()
+                .map(|byte| format!("{byte:08b}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            write!(out, "{bits:bits_width$}  ")?;
+        }
+
+        write!(out, "{}  {}", region.len(), region.format(formatter))?;
+
+        if let Region::Code(instruction) = region {
+            let mut parts = vec![];
+            if let Some(offset) = known
+                .get(code_index)
+                .and_then(|known| known.resolve(instruction))
+            {
+                parts.push(format!("= [{offset:#06x}]"));
+            }
+            if options.show_effects {
+                let writers: Vec<_> = writers[code_index]
+                    .iter()
+                    .map(|&index| format!("{:0offset_width$x}", code[index].offset))
+                    .collect();
+                parts.push(annotation(&effects[code_index], &writers));
+            }
+            parts.retain(|part| !part.is_empty());
+            if !parts.is_empty() {
+                write!(out, "  {} {}", formatter.comment(), parts.join("; "))?;
+            }
+            code_index += 1;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+fn describe(locations: &[Location], flags: FlagSet) -> String {
+    let mut names: Vec<_> = locations.iter().map(Location::to_string).collect();
+    if !flags.is_empty() {
+        names.push(format!("flags {flags}"));
+    }
+    names.join(", ")
+}
+
+fn annotation(effects: &Effects, writers: &[String]) -> String {
+    let parts = [
+        ("reads", describe(&effects.reads, effects.flags_read)),
+        ("writes", describe(&effects.writes, effects.flags_written)),
+        ("depends on", writers.join(", ")),
+    ];
+    parts
+        .iter()
+        .filter(|(_, text)| !text.is_empty())
+        .map(|(label, text)| format!("{label} {text}"))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
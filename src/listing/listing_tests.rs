@@ -0,0 +1,166 @@
+use super::*;
+use crate::decoder::decode_all;
+use crate::syntax::{Att, Nasm};
+
+fn listing(data: &[u8], options: ListingOptions) -> String {
+    let instructions = decode_all(data).unwrap();
+    let mut out = vec![];
+    write_listing(&mut out, data, &instructions, &Nasm::default(), options).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_aligns_columns_to_the_longest_instruction() {
+    let data = [0x89, 0xD9, 0xC7, 0x85, 0x85, 0x03, 0x5B, 0x01, 0xB1, 0x0C];
+
+    assert_eq!(
+        listing(&data, ListingOptions::default()),
+        "\
+0000  89 d9              2  mov cx, bx
+0002  c7 85 85 03 5b 01  6  mov word [di + 901], 347
+0008  b1 0c              2  mov cl, 12
+"
+    );
+}
+
+#[test]
+fn test_shows_bits_column() {
+    let data = [0x89, 0xD9, 0xBA, 0x6C, 0x0F];
+
+    assert_eq!(
+        listing(
+            &data,
+            ListingOptions {
+                show_bits: true,
+                ..ListingOptions::default()
+            }
+        ),
+        "\
+0000  89 d9     10001001 11011001           2  mov cx, bx
+0002  ba 6c 0f  10111010 01101100 00001111  3  mov dx, 3948
+"
+    );
+}
+
+#[test]
+fn test_offsets_are_relative_to_the_image() {
+    let data = [0x00, 0x00, 0x89, 0xD9];
+    let instructions: Vec<_> = crate::decoder::Decoder::starting_at(&data, 2)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let mut out = vec![];
+    write_listing(
+        &mut out,
+        &data,
+        &instructions,
+        &Nasm::default(),
+        ListingOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "0002  89 d9  2  mov cx, bx\n"
+    );
+}
+
+#[test]
+fn test_empty_listing() {
+    assert_eq!(
+        listing(
+            &[],
+            ListingOptions {
+                show_bits: true,
+                show_effects: true,
+                show_addresses: true,
+            }
+        ),
+        ""
+    );
+}
+
+#[test]
+fn test_uses_the_given_syntax() {
+    let data = [0x89, 0xD9];
+    let instructions = decode_all(&data).unwrap();
+    let mut out = vec![];
+    write_listing(
+        &mut out,
+        &data,
+        &instructions,
+        &Att::default(),
+        ListingOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "0000  89 d9  2  movw %bx, %cx\n"
+    );
+}
+
+#[test]
+fn test_shows_effects_and_dependencies() {
+    // mov ah, 1 / add bx, ax / jne $+0 / hlt
+    let data = [0xB4, 0x01, 0x01, 0xC3, 0x75, 0xFE, 0xF4];
+
+    assert_eq!(
+        listing(
+            &data,
+            ListingOptions {
+                show_effects: true,
+                ..ListingOptions::default()
+            }
+        ),
+        "\
+0000  b4 01  2  mov ah, 1  ; writes ah
+0002  01 c3  2  add bx, ax  ; reads bx, ax; writes bx, flags CPAZSO; depends on 0000
+0004  75 fe  2  jne $+0  ; reads flags Z; depends on 0002
+0006  f4     1  hlt
+"
+    );
+}
+
+#[test]
+fn test_shows_resolved_addresses() {
+    // mov bp, 0x1000 / mov di, 4 / mov [bp + di], al / mov [si], al
+    let data = [0xBD, 0x00, 0x10, 0xBF, 0x04, 0x00, 0x88, 0x03, 0x88, 0x04];
+
+    assert_eq!(
+        listing(
+            &data,
+            ListingOptions {
+                show_addresses: true,
+                ..ListingOptions::default()
+            }
+        ),
+        "\
+0000  bd 00 10  3  mov bp, 4096
+0003  bf 04 00  3  mov di, 4
+0006  88 03     2  mov [bp + di], al  ; = [0x1004]
+0008  88 04     2  mov [si], al
+"
+    );
+}
+
+#[test]
+fn test_resolved_address_comes_before_effects() {
+    // mov bx, 2 / inc word [bx + 8]
+    let data = [0xBB, 0x02, 0x00, 0xFF, 0x47, 0x08];
+
+    let text = listing(
+        &data,
+        ListingOptions {
+            show_effects: true,
+            show_addresses: true,
+            ..ListingOptions::default()
+        },
+    );
+    assert!(
+        text.lines()
+            .nth(1)
+            .unwrap()
+            .ends_with("inc word [bx + 8]  ; = [0x000a]; reads bx, ds, [ds:bx + 8]; writes [ds:bx + 8], flags PAZSO; depends on 0000"),
+        "{text}"
+    );
+}
@@ -1,239 +1,597 @@
-use anyhow::Context;
-use std::fs;
+use anyhow::{Context, bail};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use performance_enhance::cfg::ControlFlowGraph;
+use performance_enhance::cpu::Cpu;
+use performance_enhance::debugger::Debugger;
+use performance_enhance::decoder::{DecodeError, Decoder, Instruction};
+use performance_enhance::dos;
+use performance_enhance::image::{self, Framebuffer, ImageFormat};
+use performance_enhance::interrupts::{Interrupts, Services};
+use performance_enhance::json::{write_json, write_json_lines};
+use performance_enhance::listing::{ListingOptions, write_region_listing};
+use performance_enhance::memory::{self, Memory};
+use performance_enhance::ports::{CONSOLE_PORT, ConsolePort, PIT_PORTS, Pit, PortBus};
+use performance_enhance::recording::{fingerprint, read_deltas, write_deltas};
+use performance_enhance::sim::{RunOptions, load, run_loaded};
+use performance_enhance::snapshot::{read_snapshot, write_snapshot};
+use performance_enhance::syntax::{
+    FormatOptions, InstructionFormatter, LetterCase, NumberBase, Syntax,
+};
+use performance_enhance::timing::Processor;
+use performance_enhance::traversal::{Region, Traversal, traverse};
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 #[cfg(test)]
 mod main_tests;
 
-#[derive(Debug, PartialEq)]
-enum MovInstructionType {
-    RegisterOrMemoryToOrFromRegister,  // opcode -> 0b100010xx
-    ImmediateToRegisterOrMemory,       // opcode -> 0b1100011x
-    ImmediateToRegister,               // opcode -> 0b1011xxxx
-    MemoryToAccumulator,               // opcode -> 0b1010000x
-    AccumulatorToMemory,               // opcode -> 0b1010001x
-    RegisterOrMemoryToSegmentRegister, // opcode -> 0b10001110
-    SegmentRegisterToRegisterOrMemory, // opcode -> 0b10001100
-}
-
-impl MovInstructionType {
-    // masks
-    const REG_MEM_MASK: u8 = 0xFC; // 11111100
-    const ACC_MEM_MASK: u8 = 0xFE; // 11111110
-    const IMM_REG_MASK: u8 = 0xF0; // 11110000
-
-    /*
-        TODO: add extra benchmark to see if putting the opcode directly in the MovInstructionType
-        enum has any performance benefits in lieu of using constants here 
-    */
-    // opcode patterns
-    const REG_MEM_PATTERN: u8 = 0x88; // 10001000
-    const MEM_ACC_PATTERN: u8 = 0xA0; // 10100000
-    const ACC_MEM_PATTERN: u8 = 0xA2; // 10100010
-    const IMM_REG_PATTERN: u8 = 0xB0; // 10110000
-    const IMM_MEM_PATTERN: u8 = 0xC6; // 11000110
-    const MEM_SEG_PATTERN: u8 = 0x8E; // 10001110
-    const SEG_MEM_PATTERN: u8 = 0x8C; // 10001100
-
-    fn from_byte(byte: u8) -> Option<Self> {
-        match byte {
-            b if (b & Self::REG_MEM_MASK) == Self::REG_MEM_PATTERN => {
-                Some(Self::RegisterOrMemoryToOrFromRegister)
-            }
+/// Disassembles 8086 machine code into NASM syntax, or simulates it.
+#[derive(Debug, Parser)]
+#[command(version, about, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Binary files to disassemble, `-` reads from stdin
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    /// Write the disassembly to this file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Skip this many bytes of each input before decoding (decimal or 0x-prefixed hex)
+    #[arg(long, value_parser = parse_number, default_value = "0")]
+    start_offset: usize,
+
+    /// Decode at most this many bytes after the start offset (decimal or 0x-prefixed hex)
+    #[arg(long, value_parser = parse_number)]
+    length: Option<usize>,
+
+    /// Follow jumps and calls from the entry points instead of decoding every byte, bytes
+    /// nothing reaches are printed as data
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// Where --recursive starts decoding, defaults to the start offset (repeatable)
+    #[arg(long = "entry", value_parser = parse_number, requires = "recursive")]
+    entry_points: Vec<usize>,
+
+    /// Treat inputs as DOS programs: MZ executables are loaded from their header and relocated,
+    /// anything else is a .COM image at offset 0x100 after a PSP. Disassembly covers the load
+    /// image at the offsets it's loaded at and --recursive starts from the program's entry point
+    #[arg(long, conflicts_with_all = ["start_offset", "length", "load_state"])]
+    dos: bool,
+
+    /// Simulate the program instead, printing each instruction with the registers it changed
+    /// and the final register values
+    #[arg(short, long, conflicts_with_all = ["recursive", "format", "bits", "effects", "addresses"])]
+    exec: bool,
+
+    /// Estimate the clocks each simulated instruction takes on this processor, with a running
+    /// total
+    #[arg(long, value_enum, value_name = "PROCESSOR", requires = "exec")]
+    cycles: Option<ProcessorArg>,
+
+    /// Start simulating (or debugging) from the registers, flags, memory and cycle count saved in
+    /// this snapshot instead of from reset, the input is still loaded at its cs:ip
+    #[arg(long, value_name = "FILE", requires = "exec")]
+    load_state: Option<PathBuf>,
+
+    /// After simulating, save the registers, flags, memory and cycle count to this snapshot
+    #[arg(long, value_name = "FILE", requires = "exec")]
+    save_state: Option<PathBuf>,
+
+    /// Record what each simulated instruction changed to this file, for `debug --replay`
+    #[arg(long, value_name = "FILE", requires = "exec")]
+    record: Option<PathBuf>,
+
+    /// Keys the simulated program reads through the DOS and BIOS keyboard services, a newline
+    /// is the Enter key
+    #[arg(long, value_name = "TEXT")]
+    input: Option<String>,
+
+    /// Let the simulated program open files for reading under this directory
+    #[arg(long, value_name = "DIR")]
+    sandbox: Option<PathBuf>,
+
+    /// After simulating, write the 1 MB of memory (or the --dump-start/--dump-length part of
+    /// it) to this file
+    #[arg(long, value_name = "FILE", requires = "exec")]
+    dump: Option<PathBuf>,
+
+    /// Physical address the memory dump starts at (decimal or 0x-prefixed hex)
+    #[arg(long, value_parser = parse_number, default_value = "0", requires = "dump")]
+    dump_start: usize,
+
+    /// Number of bytes of memory to dump (decimal or 0x-prefixed hex)
+    #[arg(long, value_parser = parse_number, requires = "dump")]
+    dump_length: Option<usize>,
+
+    /// After simulating, save the 64x64 RGBA pixels at --image-start as a .ppm or .png
+    #[arg(long, value_name = "FILE", requires = "exec")]
+    image: Option<PathBuf>,
+
+    /// Physical address of the first pixel (decimal or 0x-prefixed hex)
+    #[arg(long, value_parser = parse_number, default_value = "0x100", requires = "image")]
+    image_start: usize,
+
+    /// How to print the decoded instructions
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Asm)]
+    format: OutputFormat,
+
+    /// Assembly syntax of the instruction text
+    #[arg(short, long, value_enum, default_value_t = SyntaxArg::Nasm)]
+    syntax: SyntaxArg,
+
+    /// Print mnemonics, registers and hex digits in upper case
+    #[arg(long)]
+    uppercase: bool,
+
+    /// Print immediates as raw hex instead of signed decimal
+    #[arg(long)]
+    hex_immediates: bool,
+
+    /// Print displacements and direct addresses in hex
+    #[arg(long)]
+    hex_displacements: bool,
+
+    /// Add a column with the instruction bytes in binary to the listing
+    #[arg(long)]
+    bits: bool,
+
+    /// End each listing line with the registers, memory and flags it reads and writes
+    #[arg(long)]
+    effects: bool,
+
+    /// End listing lines whose memory operand is based on registers holding constants known
+    /// without running with the offset it resolves to, e.g. `; = [0x1004]`
+    #[arg(long)]
+    addresses: bool,
+}
 
-            b if (b & Self::ACC_MEM_MASK) == Self::MEM_ACC_PATTERN => {
-                Some(Self::MemoryToAccumulator)
-            }
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Step through a program interactively, reading commands from stdin (`help` lists them)
+    Debug {
+        /// Binary file to load at 0000:0000
+        input: PathBuf,
+
+        /// Step through a recording made with --exec --record instead of executing, until it runs
+        /// out. The program has to be loaded the way the recorded run loaded it, with the same
+        /// --dos or --load-state
+        #[arg(long, value_name = "FILE")]
+        replay: Option<PathBuf>,
+    },
+}
 
-            b if (b & Self::ACC_MEM_MASK) == Self::ACC_MEM_PATTERN => {
-                Some(Self::AccumulatorToMemory)
-            }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Assembly source that reassembles to the input
+    Asm,
+    /// Offsets, raw bytes and instruction lengths next to the NASM text
+    Listing,
+    /// A JSON array of structured instructions per input
+    Json,
+    /// One JSON object per instruction per line
+    Jsonl,
+    /// Graphviz control-flow graph of the basic blocks
+    Dot,
+}
 
-            b if (b & Self::IMM_REG_MASK) == Self::IMM_REG_PATTERN => {
-                Some(Self::ImmediateToRegister)
-            }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ProcessorArg {
+    /// 16-bit bus, words at odd addresses take an extra 4 clocks
+    #[value(name = "8086")]
+    I8086,
+    /// 8-bit bus, every word takes an extra 4 clocks
+    #[value(name = "8088")]
+    I8088,
+}
 
-            b if (b & Self::ACC_MEM_MASK) == Self::IMM_MEM_PATTERN => {
-                Some(Self::ImmediateToRegisterOrMemory)
-            }
+impl From<ProcessorArg> for Processor {
+    fn from(processor: ProcessorArg) -> Self {
+        match processor {
+            ProcessorArg::I8086 => Self::I8086,
+            ProcessorArg::I8088 => Self::I8088,
+        }
+    }
+}
 
-            b if (b & Self::ACC_MEM_MASK) == Self::MEM_SEG_PATTERN => {
-                Some(Self::RegisterOrMemoryToSegmentRegister)
-            }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SyntaxArg {
+    Nasm,
+    Masm,
+    Att,
+}
 
-            b if (b & Self::ACC_MEM_MASK) == Self::SEG_MEM_PATTERN => {
-                Some(Self::SegmentRegisterToRegisterOrMemory)
-            }
+impl Cli {
+    fn formatter(&self) -> Box<dyn InstructionFormatter> {
+        let syntax = match self.syntax {
+            SyntaxArg::Nasm => Syntax::Nasm,
+            SyntaxArg::Masm => Syntax::Masm,
+            SyntaxArg::Att => Syntax::Att,
+        };
+        let base = |hex| match hex {
+            true => NumberBase::Hex,
+            false => NumberBase::Decimal,
+        };
+
+        syntax.formatter(FormatOptions {
+            case: match self.uppercase {
+                true => LetterCase::Upper,
+                false => LetterCase::Lower,
+            },
+            immediates: base(self.hex_immediates),
+            displacements: base(self.hex_displacements),
+        })
+    }
 
-            _ => None,
+    /// The DOS and BIOS services with the --input keys queued and the --sandbox directory.
+    fn interrupts(&self) -> Interrupts {
+        let mut services = Services::new();
+        if let Some(input) = &self.input {
+            services.queue_input(input.as_bytes());
+        }
+        if let Some(directory) = &self.sandbox {
+            services.set_sandbox(directory);
         }
+        Interrupts::with_services(services)
     }
+}
+
+/// A bus with the PC's timer and the console port, which is returned to read back what was
+/// written to it.
+fn standard_ports() -> (PortBus, ConsolePort) {
+    let console = ConsolePort::new();
+    let mut ports = PortBus::new();
+    ports.attach(PIT_PORTS, Pit::new());
+    ports.attach(CONSOLE_PORT..=CONSOLE_PORT, console.clone());
+    (ports, console)
+}
 
-    fn find_instruction(byte: u8) -> Self {
-        Self::from_byte(byte)
-            .unwrap_or_else(|| panic!("Unable to determine instruction for byte {:08b}", byte))
+/// Prints text the program wrote under a heading, if it wrote any.
+fn write_output(out: &mut impl Write, heading: &str, text: &[u8]) -> io::Result<()> {
+    if text.is_empty() {
+        return Ok(());
     }
+    write!(out, "\n{heading}:\n{}", String::from_utf8_lossy(text))?;
+    if !text.ends_with(b"\n") {
+        writeln!(out)?;
+    }
+    Ok(())
 }
 
-// 6 bits are opcode (mov), 2 bits (d, w)
-// second byte 2 bits (mod), 3 (reg) 3 (R/M)
-enum ModEncoding {
-    /// no displacement
-    MemMode = 0b00,
-    MemMode8B = 0b01,
-    MemMode16B = 0b10,
-    RegisterMode = 0b11,
+fn parse_number(value: &str) -> Result<usize, String> {
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|error| format!("`{value}` is not a valid number: {error}"))
 }
 
-impl ModEncoding {
-    fn from_bits(bits: u8) -> Option<Self> {
-        match bits {
-            x if x == ModEncoding::MemMode as u8 => Some(Self::MemMode),
-            x if x == ModEncoding::MemMode8B as u8 => Some(Self::MemMode8B),
-            x if x == ModEncoding::MemMode16B as u8 => Some(Self::MemMode16B),
-            x if x == ModEncoding::RegisterMode as u8 => Some(Self::RegisterMode),
-            _ => None,
-        }
+fn read_input(path: &Path) -> anyhow::Result<Vec<u8>> {
+    if path == Path::new("-") {
+        let mut data = vec![];
+        io::stdin()
+            .read_to_end(&mut data)
+            .context("Failed to read from stdin")?;
+        return Ok(data);
     }
+
+    fs::read(path).with_context(|| format!("Failed to open {}", path.display()))
 }
 
-// w0
-enum RegisterByteOp {
-    AL = 0b000,
-    CL = 0b001,
-    DL = 0b010,
-    BL = 0b011,
-    AH = 0b100,
-    Ch = 0b101,
-    DH = 0b110,
-    BH = 0b111,
-}
-
-// w1
-enum RegisterWordOp {
-    // RegisterWordOp
-    AX = 0b000,
-    CX = 0b001,
-    DX = 0b010,
-    BX = 0b011,
-    SP = 0b100,
-    BP = 0b101,
-    SI = 0b110,
-    DI = 0b111,
-}
-
-enum RegisterOp {
-    // RegisterByteOp=0b0,
-    // RegisterWordOp=0b1,
-}
-
-impl RegisterOp {
-    fn from_bits(w: u8, bits: u8) -> Option<&'static str> {
-        match (w, bits) {
-            (0, x) if x == RegisterByteOp::AL as u8 => Some("AL"),
-            (0, x) if x == RegisterByteOp::CL as u8 => Some("CL"),
-            (0, x) if x == RegisterByteOp::DL as u8 => Some("DL"),
-            (0, x) if x == RegisterByteOp::BL as u8 => Some("BL"),
-            (0, x) if x == RegisterByteOp::AH as u8 => Some("AH"),
-            (0, x) if x == RegisterByteOp::Ch as u8 => Some("Ch"),
-            (0, x) if x == RegisterByteOp::DH as u8 => Some("DH"),
-            (0, x) if x == RegisterByteOp::BH as u8 => Some("BH"),
-
-            (1, x) if x == RegisterWordOp::AX as u8 => Some("AX"),
-            (1, x) if x == RegisterWordOp::CX as u8 => Some("CX"),
-            (1, x) if x == RegisterWordOp::DX as u8 => Some("DX"),
-            (1, x) if x == RegisterWordOp::BX as u8 => Some("BX"),
-            (1, x) if x == RegisterWordOp::SP as u8 => Some("SP"),
-            (1, x) if x == RegisterWordOp::BP as u8 => Some("BP"),
-            (1, x) if x == RegisterWordOp::SI as u8 => Some("SI"),
-            (1, x) if x == RegisterWordOp::DI as u8 => Some("DI"),
-            _ => None,
+/// End of the byte range selected by `--start-offset` and `--length`.
+fn range_end(data_len: usize, start: usize, length: Option<usize>) -> anyhow::Result<usize> {
+    if start > data_len {
+        bail!("start offset {start:#x} is past the end of the input ({data_len:#x} bytes)");
+    }
+
+    match length {
+        None => Ok(data_len),
+        Some(length) => match start.checked_add(length) {
+            Some(end) if end <= data_len => Ok(end),
+            _ => bail!(
+                "{length:#x} bytes from offset {start:#x} runs past the end of the input ({data_len:#x} bytes)"
+            ),
+        },
+    }
+}
+
+/// Decodes up to the first instruction that fails, keeping everything before it.
+fn decode(data: &[u8], start: usize) -> (Vec<Instruction>, Option<DecodeError>) {
+    let mut instructions = vec![];
+
+    for instruction in Decoder::starting_at(data, start) {
+        match instruction {
+            Ok(instruction) => instructions.push(instruction),
+            Err(error) => return (instructions, Some(error)),
         }
     }
+
+    (instructions, None)
 }
 
-fn extract_bits(byte: u8, start: u8, end: u8) -> u8 {
-    // Validate inputs
-    assert!(start < end, "Start must be less than the end");
-    assert!(end <= 8, "The End cannot be greater than 8");
-
-    // Calculate the number of bits to extract
-    let num_bits = end - start;
-
-    // Shift left to align desired bits, then shift right
-    (byte << start) >> (8 - num_bits)
-}
-fn disassemble_binary(data: &[u8]) -> String {
-    /*
-    TODO: if program doesn't panic, the number of ops should be roughly <= size of data slice passed
-    into the function, consider checking if creating a vector with with_capacity is simply a better approach
-    */
-    let mut result: Vec<String> = vec![];
-    let mut i = 0;
-    // TODO: fix chunk logic, depending on the opcode, you will need to pull in more bytes down the line;
-    let chunk_size = 2;
-    // println!("first chunk size: {:08b}", data[0]);
-
-    while i < data.len() {
-        // TODO: fix chunk logic, depending on the opcode, you will need to pull in more bytes down the line;
-        let chunk = &data[i..i + chunk_size];
-        if chunk.len() == 2 {
-            // Combine 2 bytes into a 16-bit value
-            // let value = u16::from_be_bytes([chunk[0], chunk[1]]);
-            // println!("16-bit value: {:016b}", value);
-
-            let op_code = extract_bits(chunk[0], 0, 6);
-            let d = extract_bits(chunk[0], 6, 7);
-            let w = extract_bits(chunk[0], 7, 8);
-            let mode = extract_bits(chunk[1], 0, 2);
-            let reg = extract_bits(chunk[1], 2, 5);
-            let r_m = extract_bits(chunk[1], 5, 8);
-            // println!("first byte {:08b}", chunk[0]);
-            // println!("second byte {:08b}", chunk[1]);
-
-            // TODO: use the instruction to determine how to handle following bytes
-            let _instruction = MovInstructionType::find_instruction(chunk[0]);
-
-            // TODO: improve this as we progress in the course
-            let (source, mut destination) = match d {
-                // direction is from register (i.e. the data source is from a register)
-                0 => (RegisterOp::from_bits(w, reg), None),
-                // direction is to register (i.e. the data destination is to a register)
-                1 => (None, RegisterOp::from_bits(w, reg)),
-                _ => panic!("Direction is not supported"),
-            };
+fn disassemble(cli: &Cli, input: &Path, out: &mut impl Write) -> anyhow::Result<()> {
+    let file = read_input(input)?;
+    let (data, origin, entry) = match cli.dos {
+        true => {
+            let (image, origin, entry) =
+                dos::image(&file).with_context(|| format!("Failed to load {}", input.display()))?;
+            // placed at its origin, so offsets are the IPs it runs at
+            let mut segment = vec![0; origin];
+            segment.extend_from_slice(image);
+            (Cow::Owned(segment), origin, entry)
+        }
+        false => (Cow::Borrowed(file.as_slice()), 0, cli.start_offset),
+    };
+    let start = origin + cli.start_offset;
+    let end = origin
+        + range_end(data.len() - origin, cli.start_offset, cli.length)
+            .with_context(|| format!("Invalid range for {}", input.display()))?;
+    let data = &data[..end];
+
+    let (traversal, failure) = if cli.recursive {
+        let entry_points = if cli.entry_points.is_empty() {
+            vec![entry]
+        } else {
+            cli.entry_points.clone()
+        };
+        (traverse(data, start, &entry_points), None)
+    } else {
+        let (instructions, failure) = decode(data, start);
+        let traversal = Traversal {
+            start,
+            instructions,
+            ..Traversal::default()
+        };
+        (traversal, failure)
+    };
+    let instructions = &traversal.instructions;
+    // a linear sweep stops at the first failure, there is no data after it to show
+    let regions = if cli.recursive {
+        traversal.regions(data)
+    } else {
+        instructions.iter().map(Region::Code).collect()
+    };
+
+    for error in &traversal.errors {
+        eprintln!("warning: {}: {error}, treating it as data", input.display());
+    }
 
-            match ModEncoding::from_bits(mode) {
-                Some(m) => match m {
-                    ModEncoding::RegisterMode => {
-                        destination = RegisterOp::from_bits(w, r_m);
-                    }
-                    _ => panic!("not supported yet"),
-                },
-                _ => panic!("Invalid mode"),
-            }
+    let formatter = cli.formatter();
+    if cli.inputs.len() > 1 && matches!(cli.format, OutputFormat::Asm | OutputFormat::Listing) {
+        writeln!(out, "{} {}", formatter.comment(), input.display())?;
+    }
 
-            match (destination, source) {
-                (Some(destination), Some(source)) => {
-                    let operation = format!(
-                        "mov {}, {}",
-                        destination.to_lowercase(),
-                        source.to_lowercase()
-                    );
-                    result.push(operation);
+    match cli.format {
+        OutputFormat::Asm => {
+            writeln!(out, "{}", formatter.header())?;
+            for region in &regions {
+                write!(out, "{}", region.format(formatter.as_ref()))?;
+                if traversal.unresolved.contains(&region.offset()) {
+                    write!(out, " {} target not followed", formatter.comment())?;
                 }
-                _ => panic!("oh shit"),
+                writeln!(out)?;
             }
         }
-        i += chunk_size;
+        OutputFormat::Listing => {
+            let options = ListingOptions {
+                show_bits: cli.bits,
+                show_effects: cli.effects,
+                show_addresses: cli.addresses,
+            };
+            write_region_listing(out, data, &regions, formatter.as_ref(), options)?;
+        }
+        OutputFormat::Json => write_json(out, data, instructions, formatter.as_ref())?,
+        OutputFormat::Jsonl => write_json_lines(out, data, instructions, formatter.as_ref())?,
+        OutputFormat::Dot => ControlFlowGraph::build(instructions).write_dot(
+            out,
+            instructions,
+            formatter.as_ref(),
+        )?,
+    }
+
+    match failure {
+        Some(error) => {
+            out.flush()?;
+            Err(error).with_context(|| format!("Failed to decode {}", input.display()))
+        }
+        None => Ok(()),
+    }
+}
+
+fn simulate(cli: &Cli, input: &Path, out: &mut impl Write) -> anyhow::Result<()> {
+    let data = read_input(input)?;
+    let end = range_end(data.len(), cli.start_offset, cli.length)
+        .with_context(|| format!("Invalid range for {}", input.display()))?;
+    let formatter = cli.formatter();
+    let (mut cpu, mut memory, _, code) = load_program(cli, input, &data[cli.start_offset..end])?;
+    let mut recording = vec![];
+    let start = cli.record.is_some().then(|| fingerprint(&cpu, &memory));
+    let mut interrupts = cli.interrupts();
+    let (mut ports, console_port) = standard_ports();
+
+    writeln!(out, "--- {} execution ---", input.display())?;
+    let options = RunOptions {
+        timing: cli.cycles.map(Processor::from),
+        recording: cli.record.is_some().then_some(&mut recording),
+        interrupts: Some(&mut interrupts),
+        ports: Some(&mut ports),
+        cache: None,
+    };
+    let result = run_loaded(
+        &code,
+        &mut cpu,
+        &mut memory,
+        options,
+        formatter.as_ref(),
+        out,
+    );
+    // show how far it got even if it stopped early
+    write!(out, "\n{cpu}")?;
+    write_output(out, "Console output", interrupts.services().console())?;
+    write_output(
+        out,
+        &format!("Port {CONSOLE_PORT:#x} output"),
+        &console_port.output(),
+    )?;
+    if let Some(code) = interrupts.exit_code() {
+        writeln!(out, "Exit code: {code}")?;
+    }
+
+    // a recording or snapshot of a run that failed is still useful for seeing how it got there
+    if let Some(path) = &cli.record {
+        let start = start.unwrap_or_default();
+        write_file(path, |file| write_deltas(file, start, &recording))?;
+    }
+    if let Some(path) = &cli.save_state {
+        write_file(path, |file| write_snapshot(file, &cpu, &memory))?;
+    }
+    result.with_context(|| format!("Failed to simulate {}", input.display()))?;
+    save_memory(cli, &memory)
+}
+
+/// Puts `data` in memory as a DOS program with --dos, otherwise at cs:ip of the reset or
+/// --load-state machine. Returns the machine, where the program went and where it runs, which
+/// for a .COM also takes in the PSP its `ret` returns to.
+fn load_program(
+    cli: &Cli,
+    input: &Path,
+    data: &[u8],
+) -> anyhow::Result<(Cpu, Memory, Range<usize>, Range<usize>)> {
+    if cli.dos {
+        let program =
+            dos::load(data).with_context(|| format!("Failed to load {}", input.display()))?;
+        let running = program.running();
+        return Ok((program.cpu, program.memory, program.image, running));
+    }
+    let (cpu, mut memory) = match &cli.load_state {
+        Some(path) => {
+            let file =
+                File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+            read_snapshot(&mut BufReader::new(file))
+                .with_context(|| format!("Failed to read {}", path.display()))?
+        }
+        None => (Cpu::new(), Memory::new()),
+    };
+    let code = load(data, &cpu, &mut memory);
+    Ok((cpu, memory, code.clone(), code))
+}
+
+/// Writes out the parts of memory --dump and --image ask for.
+fn save_memory(cli: &Cli, memory: &Memory) -> anyhow::Result<()> {
+    if let Some(path) = &cli.dump {
+        let end = range_end(memory::SIZE, cli.dump_start, cli.dump_length)
+            .context("Invalid memory dump range")?;
+        fs::write(path, &memory.bytes()[cli.dump_start..end])
+            .with_context(|| format!("Failed to write {}", path.display()))?;
     }
 
-    result.join("\n")
+    if let Some(path) = &cli.image {
+        let Some(format) = ImageFormat::from_path(path) else {
+            bail!("{} should end in .ppm or .png", path.display());
+        };
+        let pixels = memory.bytes().get(cli.image_start..).unwrap_or_default();
+        let Some(framebuffer) = Framebuffer::new(pixels, image::WIDTH, image::HEIGHT) else {
+            bail!(
+                "a {}x{} image at {:#x} runs past the end of memory",
+                image::WIDTH,
+                image::HEIGHT,
+                cli.image_start
+            );
+        };
+        write_file(path, |file| framebuffer.write(format, file))?;
+    }
+    Ok(())
+}
+
+/// Creates `path` and has `write` fill it in through a buffer.
+fn write_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> anyhow::Result<()> {
+    let mut file = BufWriter::new(
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?,
+    );
+    write(&mut file)
+        .and_then(|()| file.flush())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn debug(cli: &Cli, input: &Path, replay: Option<&Path>) -> anyhow::Result<()> {
+    let data = read_input(input)?;
+    let formatter = cli.formatter();
+    let (cpu, memory, code, _) = load_program(cli, input, &data)?;
+    let mut debugger = Debugger::loaded(cpu, memory, code, formatter.as_ref());
+    debugger.interrupts = cli.interrupts();
+    let console_port;
+    (debugger.ports, console_port) = standard_ports();
+    if let Some(path) = replay {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let recording = read_deltas(&mut BufReader::new(file))
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        debugger
+            .replay(recording)
+            .with_context(|| format!("Failed to replay {}", path.display()))?;
+    }
+    let mut out = io::stdout().lock();
+    debugger
+        .repl(io::stdin().lock(), &mut out)
+        .context("Failed to run the debugger")?;
+    let heading = format!("Port {CONSOLE_PORT:#x} output");
+    Ok(write_output(&mut out, &heading, &console_port.output())?)
 }
 
 fn main() -> anyhow::Result<()> {
-    let bin_file: Vec<u8> =
-        fs::read("listing_0039_more_mov").context("Failed to open listing_0039_more_mov.asm")?;
+    let cli = Cli::parse();
+    if let Some(Command::Debug { input, replay }) = &cli.command {
+        return debug(&cli, input, replay.as_deref());
+    }
+    let listing_flags = [
+        (cli.bits, "--bits"),
+        (cli.effects, "--effects"),
+        (cli.addresses, "--addresses"),
+    ];
+    for (used, flag) in listing_flags {
+        if used && cli.format != OutputFormat::Listing {
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("{flag} only applies to --format listing"),
+                )
+                .exit();
+        }
+    }
+
+    let mut out: Box<dyn Write> = match &cli.output {
+        Some(path) => Box::new(
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?,
+        ),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut out = BufWriter::new(&mut out);
+
+    for (index, input) in cli.inputs.iter().enumerate() {
+        if index > 0 && cli.format != OutputFormat::Jsonl {
+            writeln!(out)?;
+        }
+        if cli.exec {
+            simulate(&cli, input, &mut out)?;
+        } else {
+            disassemble(&cli, input, &mut out)?;
+        }
+    }
 
-    let _result = disassemble_binary(&bin_file);
+    out.flush()?;
     Ok(())
 }
@@ -1,186 +1,530 @@
 use super::*;
+use rstest::rstest;
+use std::ffi::OsStr;
 
-mod move_instruction_tests {
-    use super::*;
-    use rstest::rstest;
-
-    #[rstest]
-    #[case::base(0x88)]
-    #[case::d_bit_set(0x89)]
-    #[case::w_bit_set(0x8A)]
-    #[case::both_bits_set(0x8B)]
-    fn test_register_or_memory_to_or_from_register(#[case] opcode: u8) {
-        assert_eq!(
-            MovInstructionType::find_instruction(opcode),
-            MovInstructionType::RegisterOrMemoryToOrFromRegister,
-            "Failed for opcode: {:#010b}",
-            opcode
-        );
-    }
+#[rstest]
+#[case::decimal("16", 16)]
+#[case::hex("0x10", 16)]
+#[case::upper_hex("0X1f", 31)]
+#[case::zero("0", 0)]
+fn test_parse_number(#[case] value: &str, #[case] expected: usize) {
+    assert_eq!(parse_number(value), Ok(expected));
+}
 
-    #[rstest]
-    #[case::byte_operation(0xA0)]
-    #[case::word_operation(0xA1)]
-    fn test_memory_to_accumulator(#[case] opcode: u8) {
-        assert_eq!(
-            MovInstructionType::find_instruction(opcode),
-            MovInstructionType::MemoryToAccumulator,
-            "Failed for opcode: {:#010b}",
-            opcode
-        );
-    }
+#[rstest]
+#[case::empty("")]
+#[case::negative("-1")]
+#[case::bad_hex("0xZZ")]
+fn test_parse_number_rejects_garbage(#[case] value: &str) {
+    assert!(parse_number(value).is_err(), "Accepted {value:?}");
+}
 
-    #[rstest]
-    #[case::byte_operation(0xA2)]
-    #[case::word_operation(0xA3)]
-    fn test_accumulator_to_memory(#[case] opcode: u8) {
-        assert_eq!(
-            MovInstructionType::find_instruction(opcode),
-            MovInstructionType::AccumulatorToMemory,
-            "Failed for opcode: {:#010b}",
-            opcode
-        );
-    }
+#[rstest]
+#[case::whole_input(10, 0, None, 10)]
+#[case::from_offset(10, 4, None, 10)]
+#[case::slice(10, 4, Some(2), 6)]
+#[case::up_to_the_end(10, 4, Some(6), 10)]
+#[case::empty_at_the_end(10, 10, None, 10)]
+fn test_range_end(
+    #[case] data_len: usize,
+    #[case] start: usize,
+    #[case] length: Option<usize>,
+    #[case] expected: usize,
+) {
+    assert_eq!(range_end(data_len, start, length).unwrap(), expected);
+}
 
-    #[rstest]
-    #[case::al(0xB0)]
-    #[case::cl(0xB1)]
-    #[case::dl(0xB2)]
-    #[case::bl(0xB3)]
-    #[case::ah(0xB4)]
-    #[case::ch(0xB5)]
-    #[case::dh(0xB6)]
-    #[case::bh(0xB7)]
-    #[case::ax(0xB8)]
-    #[case::cx(0xB9)]
-    #[case::dx(0xBA)]
-    #[case::bx(0xBB)]
-    #[case::sp(0xBC)]
-    #[case::bp(0xBD)]
-    #[case::si(0xBE)]
-    #[case::di(0xBF)]
-    fn test_immediate_to_register(#[case] opcode: u8) {
-        assert_eq!(
-            MovInstructionType::find_instruction(opcode),
-            MovInstructionType::ImmediateToRegister,
-            "Failed for opcode: {:#010b}",
-            opcode
-        );
-    }
+#[rstest]
+#[case::start_past_end(10, 11, None)]
+#[case::length_past_end(10, 4, Some(7))]
+#[case::overflow(10, 4, Some(usize::MAX))]
+fn test_range_end_rejects_out_of_bounds(
+    #[case] data_len: usize,
+    #[case] start: usize,
+    #[case] length: Option<usize>,
+) {
+    assert!(range_end(data_len, start, length).is_err());
+}
 
-    #[rstest]
-    #[case::byte_operation(0xC6)]
-    #[case::word_operation(0xC7)]
-    fn test_immediate_to_register_or_memory(#[case] opcode: u8) {
-        assert_eq!(
-            MovInstructionType::find_instruction(opcode),
-            MovInstructionType::ImmediateToRegisterOrMemory,
-            "Failed for opcode: {:#010b}",
-            opcode
-        );
-    }
+#[test]
+fn test_cli_parses_options() {
+    let cli = Cli::try_parse_from([
+        "performance_enhance",
+        "-o",
+        "out.asm",
+        "--start-offset",
+        "0x2",
+        "--length",
+        "4",
+        "listing_0038_many_register_mov",
+        "-",
+    ])
+    .unwrap();
 
-    #[rstest]
-    #[case::byte_operation(0x8E)]
-    #[case::word_operation(0x8F)]
-    fn test_register_or_memory_to_segment_register(#[case] opcode: u8) {
-        assert_eq!(
-            MovInstructionType::find_instruction(opcode),
-            MovInstructionType::RegisterOrMemoryToSegmentRegister,
-            "Failed for opcode: {:#010b}",
-            opcode
-        );
-    }
+    assert_eq!(
+        cli.inputs,
+        [
+            PathBuf::from("listing_0038_many_register_mov"),
+            PathBuf::from("-")
+        ]
+    );
+    assert_eq!(cli.output, Some(PathBuf::from("out.asm")));
+    assert_eq!(cli.start_offset, 2);
+    assert_eq!(cli.length, Some(4));
+}
 
-    #[rstest]
-    #[case::byte_operation(0x8C)]
-    #[case::word_operation(0x8D)]
-    fn test_segment_register_to_register_or_memory(#[case] opcode: u8) {
-        assert_eq!(
-            MovInstructionType::find_instruction(opcode),
-            MovInstructionType::SegmentRegisterToRegisterOrMemory,
-            "Failed for opcode: {:#010b}",
-            opcode
-        );
-    }
-    
-    #[rstest]
-    #[case::zero(0x00, "00000000")]
-    #[case::one(0x01, "00000001")]
-    #[case::arbitrary1(0x10, "00010000")]
-    #[case::arbitrary2(0x42, "01000010")]
-    #[case::arbitrary3(0xD0, "11010000")]
-    #[case::arbitrary4(0xFF, "11111111")]
-    #[should_panic(expected = "Unable to determine instruction for byte")]
-    fn test_invalid_opcodes(#[case] opcode: u8, #[case] _binary_repr: &str) {
-        MovInstructionType::find_instruction(opcode);
-    }
+#[test]
+fn test_cli_requires_an_input() {
+    assert!(Cli::try_parse_from(["performance_enhance"]).is_err());
+}
 
-    #[test]
-    fn test_all_valid_opcodes_return_some() {
-        for opcode in 0..=255u8 {
-            let expected = match opcode {
-                0x88..=0x8B => Some(MovInstructionType::RegisterOrMemoryToOrFromRegister),
-                0xA0..=0xA1 => Some(MovInstructionType::MemoryToAccumulator),
-                0xA2..=0xA3 => Some(MovInstructionType::AccumulatorToMemory),
-                0xB0..=0xBF => Some(MovInstructionType::ImmediateToRegister),
-                0xC6..=0xC7 => Some(MovInstructionType::ImmediateToRegisterOrMemory),
-                0x8E..=0x8F => Some(MovInstructionType::RegisterOrMemoryToSegmentRegister),
-                0x8C..=0x8D => Some(MovInstructionType::SegmentRegisterToRegisterOrMemory),
-                _ => None,
-            };
-
-            assert_eq!(
-                MovInstructionType::from_byte(opcode),
-                expected,
-                "Incorrect classification for opcode: {:#04X} ({:#010b})",
-                opcode,
-                opcode
-            );
-        }
-    }
+#[test]
+fn test_disassembles_a_slice_of_the_input() {
+    let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0038_many_register_mov");
+    let cli = Cli::try_parse_from([
+        "performance_enhance".as_ref(),
+        "--start-offset".as_ref(),
+        "2".as_ref(),
+        "--length".as_ref(),
+        "4".as_ref(),
+        input.as_os_str(),
+    ])
+    .unwrap();
+
+    let mut out = vec![];
+    disassemble(&cli, &input, &mut out).unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "bits 16\nmov ch, ah\nmov dx, bx\n"
+    );
 }
 
 #[test]
-fn test_disassemble_single_register_binary() {
-    let bin_file: Vec<u8> = fs::read("listing_0037_single_register_mov")
-        .context("Failed to open listing_0037_single_register_mov")
-        .unwrap();
+fn test_writes_listing_format() {
+    let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0039_more_mov");
+    let cli = Cli::try_parse_from([
+        "performance_enhance".as_ref(),
+        "--format".as_ref(),
+        "listing".as_ref(),
+        "--bits".as_ref(),
+        input.as_os_str(),
+    ])
+    .unwrap();
 
-    let expected_result = r"mov cx, bx";
-    assert_eq!(disassemble_binary(&bin_file), expected_result);
+    let mut out = vec![];
+    disassemble(&cli, &input, &mut out).unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "0000  b1 0c  10110001 00001100  2  mov cl, 12\n"
+    );
 }
 
 #[test]
-fn test_disassemble_many_register_binary() {
-    let bin_file: Vec<u8> = fs::read("listing_0038_many_register_mov")
-        .context("Failed to open listing_0038_many_register_mov")
-        .unwrap();
+fn test_listing_resolves_addresses() {
+    let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0051_memory_mov");
+    let cli = Cli::try_parse_from([
+        "performance_enhance".as_ref(),
+        "--format".as_ref(),
+        "listing".as_ref(),
+        "--addresses".as_ref(),
+        "--start-offset".as_ref(),
+        "0x18".as_ref(),
+        "--length".as_ref(),
+        "8".as_ref(),
+        input.as_os_str(),
+    ])
+    .unwrap();
+
+    let mut out = vec![];
+    disassemble(&cli, &input, &mut out).unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "\
+0018  bb e8 03        3  mov bx, 1000
+001b  c7 47 04 0a 00  5  mov word [bx + 4], 10  ; = [0x03ec]
+"
+    );
+}
+
+#[test]
+fn test_entry_requires_recursive() {
+    assert!(Cli::try_parse_from(["performance_enhance", "--entry", "0x10", "in.bin"]).is_err());
+    assert!(
+        Cli::try_parse_from(["performance_enhance", "-r", "--entry", "0x10", "in.bin"]).is_ok()
+    );
+}
+
+#[test]
+fn test_exec_prints_trace_and_final_registers() {
+    let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0043_immediate_movs");
+    let cli = Cli::try_parse_from([
+        "performance_enhance".as_ref(),
+        "--exec".as_ref(),
+        "--length".as_ref(),
+        "6".as_ref(),
+        input.as_os_str(),
+    ])
+    .unwrap();
+
+    let mut out = vec![];
+    simulate(&cli, &input, &mut out).unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        format!(
+            "\
+--- {} execution ---
+mov ax, 1 ; ax:0x0->0x1 ip:0x0->0x3
+mov bx, 2 ; bx:0x0->0x2 ip:0x3->0x6
+
+Final registers:
+      ax: 0x0001 (1)
+      bx: 0x0002 (2)
+      ip: 0x0006 (6)
+",
+            input.display()
+        )
+    );
+}
+
+#[test]
+fn test_exec_conflicts_with_output_formats() {
+    assert!(
+        Cli::try_parse_from([
+            "performance_enhance",
+            "--exec",
+            "--format",
+            "json",
+            "in.bin"
+        ])
+        .is_err()
+    );
+}
+
+#[test]
+fn test_cycles_needs_exec() {
+    assert!(Cli::try_parse_from(["performance_enhance", "--cycles", "8086", "in.bin"]).is_err());
+
+    let cli =
+        Cli::try_parse_from(["performance_enhance", "-e", "--cycles", "8088", "in.bin"]).unwrap();
+    assert_eq!(cli.cycles, Some(ProcessorArg::I8088));
+}
+
+#[test]
+fn test_exec_saves_memory_and_image() {
+    let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0054_draw_rectangle");
+    let directory =
+        std::env::temp_dir().join(format!("performance_enhance_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let (dump, image) = (directory.join("memory.bin"), directory.join("image.ppm"));
+    let cli = Cli::try_parse_from([
+        "performance_enhance".as_ref(),
+        "--exec".as_ref(),
+        "--dump".as_ref(),
+        dump.as_os_str(),
+        "--dump-start".as_ref(),
+        "0x100".as_ref(),
+        "--dump-length".as_ref(),
+        "0x4000".as_ref(),
+        "--image".as_ref(),
+        image.as_os_str(),
+        input.as_os_str(),
+    ])
+    .unwrap();
+
+    simulate(&cli, &input, &mut vec![]).unwrap();
+    let (dump, image) = (fs::read(&dump).unwrap(), fs::read(&image).unwrap());
+    fs::remove_dir_all(&directory).unwrap();
 
-    let expected_result = r"mov cx, bx
-mov ch, ah
-mov dx, bx
-mov si, bx
-mov bx, di
-mov al, cl
-mov ch, ch
-mov bx, ax
-mov bx, si
-mov sp, di
-mov bp, ax";
-    assert_eq!(disassemble_binary(&bin_file), expected_result);
+    // red counts up along x and blue down the rows
+    assert_eq!(dump.len(), 64 * 64 * 4);
+    assert_eq!(dump[..8], [0, 0, 0, 255, 1, 0, 0, 255]);
+    assert_eq!(dump[(64 * 5 + 3) * 4..][..4], [3, 0, 5, 255]);
+    let header = b"P6\n64 64\n255\n";
+    assert_eq!(image[..header.len()], header[..]);
+    assert_eq!(image[header.len() + (64 * 5 + 3) * 3..][..3], [3, 0, 5]);
+}
+
+#[rstest]
+#[case::dump_needs_exec(&["--dump", "out.bin", "in.bin"])]
+#[case::image_needs_exec(&["--image", "out.png", "in.bin"])]
+#[case::dump_range_needs_dump(&["--exec", "--dump-start", "16", "in.bin"])]
+fn test_memory_output_flags_need_exec(#[case] arguments: &[&str]) {
+    let arguments = ["performance_enhance"].iter().chain(arguments);
+    assert!(Cli::try_parse_from(arguments).is_err());
+}
+
+#[test]
+fn test_image_needs_a_known_extension() {
+    let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0043_immediate_movs");
+    let cli = Cli::try_parse_from([
+        "performance_enhance".as_ref(),
+        "--exec".as_ref(),
+        "--image".as_ref(),
+        "out.bmp".as_ref(),
+        input.as_os_str(),
+    ])
+    .unwrap();
+
+    let error = simulate(&cli, &input, &mut vec![]).unwrap_err();
+    assert_eq!(error.to_string(), "out.bmp should end in .ppm or .png");
 }
 
 #[test]
-fn test_disassemble_immediate_to_register_binary() {
-    let bin_file: Vec<u8> = fs::read("listing_0037_single_register_mov")
-        .context("Failed to open listing_0037_single_register_mov")
+fn test_debug_subcommand_takes_one_input() {
+    let cli = Cli::try_parse_from(["performance_enhance", "--syntax", "masm", "debug", "in.bin"])
         .unwrap();
 
-    bin_file.iter().for_each(|byte| {
-        print!("{:08b}", byte);
-    });
+    assert!(cli.inputs.is_empty());
+    assert_eq!(cli.syntax, SyntaxArg::Masm);
+    match cli.command {
+        Some(Command::Debug { input, .. }) => assert_eq!(input, Path::new("in.bin")),
+        None => panic!("debug wasn't parsed as a subcommand"),
+    }
+    assert!(Cli::try_parse_from(["performance_enhance", "debug"]).is_err());
+}
+
+#[test]
+fn test_exec_records_for_replay() {
+    let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0049_conditional_jumps");
+    let recording = std::env::temp_dir().join(format!("exec-record-{}.bin", std::process::id()));
+    let cli = Cli::try_parse_from([
+        "performance_enhance".as_ref(),
+        "--exec".as_ref(),
+        "--record".as_ref(),
+        recording.as_os_str(),
+        input.as_os_str(),
+    ])
+    .unwrap();
+
+    simulate(&cli, &input, &mut vec![]).unwrap();
+    let file = fs::read(&recording).unwrap();
+    fs::remove_file(&recording).unwrap();
+    let recording = read_deltas(&mut file.as_slice()).unwrap();
+    // 2 movs, then 3 times round the add, sub and jne
+    assert_eq!(recording.deltas.len(), 11);
+    assert!(Cli::try_parse_from(["performance_enhance", "--record", "out.bin", "in.bin"]).is_err());
+}
+
+#[test]
+fn test_debug_refuses_a_recording_from_another_state() {
+    let directory = std::env::temp_dir().join(format!("replay-state-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let (state, recording) = (directory.join("state.bin"), directory.join("run.bin"));
+    let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0049_conditional_jumps");
+    let parse = |arguments: &[&OsStr]| {
+        Cli::try_parse_from([OsStr::new("performance_enhance")].iter().chain(arguments)).unwrap()
+    };
+    let exec = OsStr::new("--exec");
+    let save = parse(&[
+        exec,
+        "--save-state".as_ref(),
+        state.as_os_str(),
+        input.as_os_str(),
+    ]);
+    simulate(&save, &input, &mut vec![]).unwrap();
+    let record = parse(&[
+        exec,
+        "--load-state".as_ref(),
+        state.as_os_str(),
+        "--record".as_ref(),
+        recording.as_os_str(),
+        input.as_os_str(),
+    ]);
+    simulate(&record, &input, &mut vec![]).unwrap();
+
+    let replay = parse(&["debug".as_ref(), input.as_os_str()]);
+    let error = debug(&replay, &input, Some(&recording)).unwrap_err();
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert!(
+        format!("{error:#}").contains("starts from a different machine state"),
+        "{error:#}"
+    );
+}
+
+#[test]
+fn test_exec_continues_from_a_saved_state() {
+    let directory = std::env::temp_dir().join(format!("exec-state-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let state = directory.join("state.bin");
+    let first = Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0049_conditional_jumps");
+    // add bx, 10
+    let second = directory.join("add");
+    fs::write(&second, [0x83, 0xC3, 0x0A]).unwrap();
+
+    let parse = |arguments: &[&OsStr]| {
+        Cli::try_parse_from(
+            [OsStr::new("performance_enhance"), OsStr::new("--exec")]
+                .iter()
+                .chain(arguments),
+        )
+        .unwrap()
+    };
+    let save = parse(&[
+        OsStr::new("--save-state"),
+        state.as_os_str(),
+        first.as_os_str(),
+    ]);
+    simulate(&save, &first, &mut vec![]).unwrap();
+    let load = parse(&[
+        OsStr::new("--load-state"),
+        state.as_os_str(),
+        second.as_os_str(),
+    ]);
+    let mut out = vec![];
+    simulate(&load, &second, &mut out).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+
+    // it carries on from ip 0xe with bx where the first program left it
+    let out = String::from_utf8(out).unwrap();
+    assert!(
+        out.contains("add bx, 10 ; bx:0x406->0x410 ip:0xe->0x11 flags:PZ->A"),
+        "{out}"
+    );
+}
+
+#[test]
+fn test_dos_programs_start_after_the_psp() {
+    let directory = std::env::temp_dir().join(format!("dos-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let input = directory.join("one.com");
+    // mov ax, 1 / ret
+    fs::write(&input, [0xB8, 0x01, 0x00, 0xC3]).unwrap();
+
+    let cli = Cli::try_parse_from([
+        "performance_enhance".as_ref(),
+        "--exec".as_ref(),
+        "--dos".as_ref(),
+        input.as_os_str(),
+    ])
+    .unwrap();
+    let mut out = vec![];
+    simulate(&cli, &input, &mut out).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    assert!(
+        out.contains("mov ax, 1 ; ax:0x0->0x1 ip:0x100->0x103\n"),
+        "{out}"
+    );
+    assert!(out.contains("      cs: 0x1000 (4096)\n"), "{out}");
+    // the ret lands on the PSP's int 20h
+    assert!(out.contains("int 32 ; ip:0x0->0x2\n"), "{out}");
+    assert!(out.ends_with("Exit code: 0\n"), "{out}");
+}
+
+#[test]
+fn test_dos_com_listing_counts_from_0x100() {
+    let directory = std::env::temp_dir().join(format!("dos-listing-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let input = directory.join("one.com");
+    // mov ax, 1 / ret
+    fs::write(&input, [0xB8, 0x01, 0x00, 0xC3]).unwrap();
+
+    let cli = Cli::try_parse_from([
+        "performance_enhance".as_ref(),
+        "--dos".as_ref(),
+        "--recursive".as_ref(),
+        "--format".as_ref(),
+        "listing".as_ref(),
+        input.as_os_str(),
+    ])
+    .unwrap();
+    let mut out = vec![];
+    disassemble(&cli, &input, &mut out).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("0100"), "{out}");
+    assert!(out.contains("\n0103"), "{out}");
+}
+
+#[test]
+fn test_dos_disassembly_skips_the_exe_header() {
+    let directory = std::env::temp_dir().join(format!("dos-exe-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let input = directory.join("program.exe");
+    // a 2-paragraph header with cs:ip 0000:0001, then `int3 / mov ax, 1`
+    let mut file = vec![0; 0x20];
+    file[..10].copy_from_slice(&[b'M', b'Z', 0x24, 0, 1, 0, 0, 0, 2, 0]);
+    file[0x14] = 1;
+    file.extend([0xCC, 0xB8, 0x01, 0x00]);
+    fs::write(&input, file).unwrap();
+
+    let cli = Cli::try_parse_from([
+        "performance_enhance".as_ref(),
+        "--dos".as_ref(),
+        "--recursive".as_ref(),
+        input.as_os_str(),
+    ])
+    .unwrap();
+    let mut out = vec![];
+    disassemble(&cli, &input, &mut out).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+
+    // the byte before the entry point isn't reached, so it's data
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "bits 16\ndb 204\nmov ax, 1\n"
+    );
+}
+
+#[test]
+fn test_exec_prints_the_console_and_exit_code() {
+    let directory = std::env::temp_dir().join(format!("dos-console-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let input = directory.join("echo.com");
+    // mov ah, 1 / int 0x21 / mov ax, 0x4c03 / int 0x21
+    fs::write(
+        &input,
+        [0xB4, 0x01, 0xCD, 0x21, 0xB8, 0x03, 0x4C, 0xCD, 0x21],
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "performance_enhance".as_ref(),
+        "--exec".as_ref(),
+        "--dos".as_ref(),
+        "--input".as_ref(),
+        "y".as_ref(),
+        "--sandbox".as_ref(),
+        directory.as_os_str(),
+        input.as_os_str(),
+    ])
+    .unwrap();
+    let mut out = vec![];
+    simulate(&cli, &input, &mut out).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    assert!(
+        out.ends_with("\nConsole output:\ny\nExit code: 3\n"),
+        "{out}"
+    );
+}
+
+#[test]
+fn test_exec_prints_what_went_to_the_console_port() {
+    let directory = std::env::temp_dir().join(format!("port-console-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let input = directory.join("port.bin");
+    // mov al, 'k' / out 0xe9, al
+    fs::write(&input, [0xB0, b'k', 0xE6, 0xE9]).unwrap();
+
+    let cli = Cli::try_parse_from([
+        "performance_enhance".as_ref(),
+        "--exec".as_ref(),
+        input.as_os_str(),
+    ])
+    .unwrap();
+    let mut out = vec![];
+    simulate(&cli, &input, &mut out).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
 
-    let expected_result = r"mov cx, bx";
-    assert_eq!(disassemble_binary(&bin_file), expected_result);
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.ends_with("\nPort 0xe9 output:\nk\n"), "{out}");
 }
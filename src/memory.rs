@@ -0,0 +1,161 @@
+use crate::decoder::Width;
+use std::fmt;
+
+#[cfg(test)]
+mod memory_tests;
+
+/// The 8086 has 20 address lines.
+pub const SIZE: usize = 1 << 20;
+
+/// Bytes in each of the lines [`Memory::generation`] tracks stores in.
+pub const LINE: usize = 64;
+
+/// `segment * 16 + offset`, wrapped to 20 bits the way the 8086 drops the carry out of the top
+/// address line.
+pub fn physical_address(segment: u16, offset: u16) -> usize {
+    ((usize::from(segment) << 4) + usize::from(offset)) & (SIZE - 1)
+}
+
+/// A byte store, with what it overwrote so it can be undone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteWrite {
+    pub address: usize,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// The full megabyte the 8086 can address, zeroed to start with.
+#[derive(Clone)]
+pub struct Memory {
+    bytes: Box<[u8]>,
+    /// Every store since the journal was last taken, while journaling is on.
+    journal: Option<Vec<ByteWrite>>,
+    /// Stores to each line so far.
+    generations: Box<[u64]>,
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn read_byte(&self, segment: u16, offset: u16) -> u8 {
+        self.bytes[physical_address(segment, offset)]
+    }
+
+    pub fn write_byte(&mut self, segment: u16, offset: u16, value: u8) {
+        self.store(physical_address(segment, offset), value);
+    }
+
+    /// Writes a byte at a physical address.
+    pub fn store(&mut self, address: usize, value: u8) {
+        let byte = &mut self.bytes[address];
+        if let Some(journal) = &mut self.journal {
+            journal.push(ByteWrite {
+                address,
+                old: *byte,
+                new: value,
+            });
+        }
+        *byte = value;
+        self.generations[address / LINE] += 1;
+    }
+
+    /// How many stores there have been to the [`LINE`] of memory holding `address`, so anything
+    /// worked out from those bytes can tell when it's out of date.
+    pub fn generation(&self, address: usize) -> u64 {
+        self.generations[address / LINE]
+    }
+
+    /// Little endian. The high byte comes from `offset + 1` within the same segment, so a word at
+    /// offset 0xFFFF takes its high byte from offset 0.
+    pub fn read_word(&self, segment: u16, offset: u16) -> u16 {
+        let low = self.read_byte(segment, offset);
+        let high = self.read_byte(segment, offset.wrapping_add(1));
+        u16::from_le_bytes([low, high])
+    }
+
+    /// Little endian, wrapping within the segment like [`Memory::read_word`].
+    pub fn write_word(&mut self, segment: u16, offset: u16, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        self.write_byte(segment, offset, low);
+        self.write_byte(segment, offset.wrapping_add(1), high);
+    }
+
+    /// Reads a byte or word, bytes are zero extended.
+    pub fn read(&self, width: Width, segment: u16, offset: u16) -> u16 {
+        match width {
+            Width::Byte => u16::from(self.read_byte(segment, offset)),
+            Width::Word => self.read_word(segment, offset),
+        }
+    }
+
+    /// Writes a byte or word, only the low byte of `value` is used for bytes.
+    pub fn write(&mut self, width: Width, segment: u16, offset: u16, value: u16) {
+        match width {
+            Width::Byte => self.write_byte(segment, offset, value as u8),
+            Width::Word => self.write_word(segment, offset, value),
+        }
+    }
+
+    /// Copies `data` in starting at `segment:offset`. Like word accesses, offsets wrap within
+    /// the segment.
+    pub fn load(&mut self, segment: u16, offset: u16, data: &[u8]) {
+        let mut offset = offset;
+        for &byte in data {
+            self.write_byte(segment, offset, byte);
+            offset = offset.wrapping_add(1);
+        }
+    }
+
+    /// Starts keeping a [`ByteWrite`] for every store, including ones that don't change the byte.
+    pub fn start_journal(&mut self) {
+        self.journal.get_or_insert_with(Vec::new);
+    }
+
+    pub fn stop_journal(&mut self) {
+        self.journal = None;
+    }
+
+    /// The stores since the journal was started or last taken, in the order they happened.
+    /// Journaling carries on.
+    pub fn take_journal(&mut self) -> Vec<ByteWrite> {
+        self.journal
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self {
+            bytes: vec![0; SIZE].into_boxed_slice(),
+            journal: None,
+            generations: vec![0; SIZE / LINE].into_boxed_slice(),
+        }
+    }
+}
+
+/// Only the contents count, not whether stores are being journaled or how many there were.
+impl PartialEq for Memory {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for Memory {}
+
+/// A megabyte of hex isn't useful in test failures, only say how many bytes are in use.
+impl fmt::Debug for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let used = self.bytes.iter().filter(|&&byte| byte != 0).count();
+        f.debug_struct("Memory")
+            .field("non_zero_bytes", &used)
+            .finish_non_exhaustive()
+    }
+}
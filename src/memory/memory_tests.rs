@@ -0,0 +1,116 @@
+use super::*;
+use rstest::rstest;
+
+#[rstest]
+#[case::offset_only(0x0000, 0x1234, 0x01234)]
+#[case::segment_only(0x1234, 0x0000, 0x12340)]
+#[case::overlapping_segments(0x1000, 0x0010, 0x10010)]
+#[case::same_place_another_way(0x1001, 0x0000, 0x10010)]
+#[case::wraps_at_one_megabyte(0xFFFF, 0x0010, 0x00000)]
+#[case::highest_address(0xF000, 0xFFFF, 0xFFFFF)]
+fn test_physical_address(#[case] segment: u16, #[case] offset: u16, #[case] expected: usize) {
+    assert_eq!(physical_address(segment, offset), expected);
+}
+
+#[test]
+fn test_words_are_little_endian() {
+    let mut memory = Memory::new();
+    memory.write_word(0x0010, 0x0004, 0xBEEF);
+
+    assert_eq!(memory.bytes()[0x104..0x106], [0xEF, 0xBE]);
+    assert_eq!(memory.read_word(0x0010, 0x0004), 0xBEEF);
+    assert_eq!(memory.read_byte(0x0000, 0x0105), 0xBE);
+}
+
+#[test]
+fn test_words_wrap_within_the_segment() {
+    let mut memory = Memory::new();
+    memory.write_word(0x2000, 0xFFFF, 0x1234);
+
+    assert_eq!(memory.bytes()[0x2FFFF], 0x34);
+    // the high byte lands at offset 0 of the same segment, not the next physical byte
+    assert_eq!(memory.bytes()[0x20000], 0x12);
+    assert_eq!(memory.bytes()[0x30000], 0x00);
+    assert_eq!(memory.read_word(0x2000, 0xFFFF), 0x1234);
+}
+
+#[test]
+fn test_accesses_wrap_at_one_megabyte() {
+    let mut memory = Memory::new();
+    memory.write_word(0xFFFF, 0x000F, 0xABCD);
+
+    assert_eq!(memory.bytes()[0xFFFFF], 0xCD);
+    assert_eq!(memory.bytes()[0x00000], 0xAB);
+}
+
+#[rstest]
+#[case::byte(Width::Byte, 0x1234, 0x0034)]
+#[case::word(Width::Word, 0x1234, 0x1234)]
+fn test_width_access(#[case] width: Width, #[case] value: u16, #[case] expected: u16) {
+    let mut memory = Memory::new();
+    memory.write(width, 0, 0x10, value);
+    assert_eq!(memory.read(width, 0, 0x10), expected);
+    assert_eq!(memory.read_byte(0, 0x11), (expected >> 8) as u8);
+}
+
+#[test]
+fn test_load_copies_into_the_segment() {
+    let mut memory = Memory::new();
+    memory.load(0x0100, 0xFFFE, &[1, 2, 3]);
+
+    assert_eq!(memory.bytes()[0x10FFE..0x11000], [1, 2]);
+    assert_eq!(memory.bytes()[0x1000], 3);
+}
+
+#[test]
+fn test_journal_keeps_stores_while_on() {
+    let mut memory = Memory::new();
+    memory.write_byte(0, 0x10, 1);
+    memory.start_journal();
+    memory.write_word(0x0001, 0x0000, 0x0203);
+    memory.store(0x10, 1);
+
+    assert_eq!(
+        memory.take_journal(),
+        [
+            ByteWrite {
+                address: 0x10,
+                old: 1,
+                new: 3
+            },
+            ByteWrite {
+                address: 0x11,
+                old: 0,
+                new: 2
+            },
+            ByteWrite {
+                address: 0x10,
+                old: 3,
+                new: 1
+            },
+        ]
+    );
+    assert_eq!(memory.take_journal(), []);
+
+    memory.stop_journal();
+    memory.write_byte(0, 0, 9);
+    assert_eq!(memory.take_journal(), []);
+    assert_eq!(memory, {
+        let mut expected = Memory::new();
+        expected.load(0, 0, &[9]);
+        expected.load(0, 0x10, &[1, 2]);
+        expected
+    });
+}
+
+#[test]
+fn test_generations_count_stores_per_line() {
+    let mut memory = Memory::new();
+    memory.write_word(0, LINE as u16 - 1, 0x1234);
+    memory.write_byte(0, 0, 0);
+
+    assert_eq!(memory.generation(0), 2);
+    assert_eq!(memory.generation(LINE - 1), 2);
+    assert_eq!(memory.generation(LINE), 1);
+    assert_eq!(memory.generation(2 * LINE), 0);
+}
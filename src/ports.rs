@@ -0,0 +1,304 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+#[cfg(test)]
+mod ports_tests;
+
+/// What a read from a port nothing answers on gives, the data lines float high.
+pub const OPEN_BUS: u8 = 0xFF;
+
+/// Something on the I/O bus that `in` and `out` talk to a byte at a time. `now` is the cycle
+/// count of the CPU, which every instruction moves on by the clocks it took.
+pub trait Device {
+    fn read(&mut self, port: u16, now: u64) -> u8;
+    fn write(&mut self, port: u16, value: u8, now: u64);
+
+    /// The interrupt the device is raising at `now`, asked after each instruction while IF is
+    /// set. A request stays pending until it's been returned once.
+    fn interrupt(&mut self, _now: u64) -> Option<u8> {
+        None
+    }
+}
+
+/// Where `in` and `out` go. A word goes to two byte ports, the low byte to `port` and the high
+/// byte to `port + 1`, as on the 8086's bus. Ports nothing is attached to read as [`OPEN_BUS`]
+/// and ignore writes.
+#[derive(Default)]
+pub struct PortBus {
+    devices: Vec<(RangeInclusive<u16>, Box<dyn Device>)>,
+}
+
+impl PortBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Puts `device` on `ports`, in front of anything attached there before.
+    pub fn attach(&mut self, ports: RangeInclusive<u16>, device: impl Device + 'static) {
+        self.devices.push((ports, Box::new(device)));
+    }
+
+    /// Takes whatever is on `port` off the bus, for every port it covered.
+    pub fn detach(&mut self, port: u16) {
+        if let Some(index) = self.find(port) {
+            self.devices.remove(index);
+        }
+    }
+
+    pub fn read_byte(&mut self, port: u16, now: u64) -> u8 {
+        match self.find(port) {
+            Some(index) => self.devices[index].1.read(port, now),
+            None => OPEN_BUS,
+        }
+    }
+
+    pub fn write_byte(&mut self, port: u16, value: u8, now: u64) {
+        if let Some(index) = self.find(port) {
+            self.devices[index].1.write(port, value, now);
+        }
+    }
+
+    pub fn read_word(&mut self, port: u16, now: u64) -> u16 {
+        let low = self.read_byte(port, now);
+        let high = self.read_byte(port.wrapping_add(1), now);
+        u16::from_le_bytes([low, high])
+    }
+
+    pub fn write_word(&mut self, port: u16, value: u16, now: u64) {
+        let [low, high] = value.to_le_bytes();
+        self.write_byte(port, low, now);
+        self.write_byte(port.wrapping_add(1), high, now);
+    }
+
+    /// The first interrupt any device is raising at `now`.
+    pub fn interrupt(&mut self, now: u64) -> Option<u8> {
+        self.devices
+            .iter_mut()
+            .find_map(|(_, device)| device.interrupt(now))
+    }
+
+    fn find(&self, port: u16) -> Option<usize> {
+        self.devices
+            .iter()
+            .rposition(|(ports, _)| ports.contains(&port))
+    }
+}
+
+impl fmt::Debug for PortBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ports: Vec<_> = self.devices.iter().map(|(ports, _)| ports).collect();
+        f.debug_struct("PortBus").field("ports", &ports).finish()
+    }
+}
+
+/// The 8253 timer's three counters then its mode register.
+pub const PIT_PORTS: RangeInclusive<u16> = 0x40..=0x43;
+/// The interrupt counter 0 raises each time it gets to zero, IRQ 0 on the PC.
+pub const TIMER_VECTOR: u8 = 8;
+/// CPU clocks per timer tick, the PC's 4.77 MHz clock over the timer's 1.19 MHz.
+pub const CLOCKS_PER_TICK: u64 = 4;
+
+/// The 8253 programmable interval timer's counters, each counting down from its reload value
+/// once every [`CLOCKS_PER_TICK`] and starting over when it gets to zero, whatever mode it's
+/// set to. Writing the mode register selects which bytes of the count the counter's port reads
+/// and writes, or latches the count for reading. It answers on the bottom two bits of the port,
+/// like the PC decodes it at [`PIT_PORTS`]. Once counter 0 has been loaded, it raises
+/// [`TIMER_VECTOR`] every time it gets to zero, which makes a timer interrupt every
+/// `reload * CLOCKS_PER_TICK` clocks.
+#[derive(Debug, Clone, Default)]
+pub struct Pit {
+    counters: [Counter; 3],
+    /// When counter 0 next gets to zero and raises [`TIMER_VECTOR`], once it's been loaded.
+    timer_due: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Counter {
+    /// 0 counts 65536 ticks.
+    reload: u16,
+    /// When the count started from `reload`.
+    loaded_at: u64,
+    /// Bits 4 and 5 of the mode, 1 is the low byte, 2 the high byte and 3 both in turn.
+    access: u8,
+    /// Half of a reload value written a byte at a time.
+    pending_low: Option<u8>,
+    latched: Option<u16>,
+    /// Whether the next read of a 2-byte access is the high byte.
+    reading_high: bool,
+}
+
+impl Counter {
+    /// Ticks from the reload value to zero.
+    fn period(&self) -> u64 {
+        match self.reload {
+            0 => 0x10000,
+            reload => u64::from(reload),
+        }
+    }
+
+    fn count(&self, now: u64) -> u16 {
+        let period = self.period();
+        let elapsed = now.saturating_sub(self.loaded_at) / CLOCKS_PER_TICK;
+        ((period - elapsed % period) % 0x10000) as u16
+    }
+}
+
+impl Pit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counter `channel`'s count at `now`.
+    pub fn count(&self, channel: usize, now: u64) -> u16 {
+        self.counters[channel].count(now)
+    }
+}
+
+impl Device for Pit {
+    fn read(&mut self, port: u16, now: u64) -> u8 {
+        let Some(counter) = self.counters.get_mut(usize::from(port & 0b11)) else {
+            // the mode register can't be read
+            return OPEN_BUS;
+        };
+        let [low, high] = counter
+            .latched
+            .unwrap_or_else(|| counter.count(now))
+            .to_le_bytes();
+        match counter.access {
+            1 => low,
+            2 => high,
+            _ => {
+                counter.reading_high = !counter.reading_high;
+                if counter.reading_high {
+                    return low;
+                }
+                counter.latched = None;
+                high
+            }
+        }
+    }
+
+    fn write(&mut self, port: u16, value: u8, now: u64) {
+        if port & 0b11 == 0b11 {
+            // the 8254's read-back command, channel 3, isn't on the 8253
+            let Some(counter) = self.counters.get_mut(usize::from(value >> 6)) else {
+                return;
+            };
+            match (value >> 4) & 0b11 {
+                0 => counter.latched = Some(counter.count(now)),
+                access => {
+                    counter.access = access;
+                    counter.pending_low = None;
+                    counter.latched = None;
+                    counter.reading_high = false;
+                }
+            }
+            return;
+        }
+        let channel = usize::from(port & 0b11);
+        let counter = &mut self.counters[channel];
+        let reload = match (counter.access, counter.pending_low.take()) {
+            (1, _) => u16::from(value),
+            (2, _) => u16::from(value) << 8,
+            (_, Some(low)) => u16::from_le_bytes([low, value]),
+            (_, None) => {
+                counter.pending_low = Some(value);
+                return;
+            }
+        };
+        counter.reload = reload;
+        counter.loaded_at = now;
+        if channel == 0 {
+            self.timer_due = Some(now + counter.period() * CLOCKS_PER_TICK);
+        }
+    }
+
+    /// Counts that went by while interrupts were off only raise one.
+    fn interrupt(&mut self, now: u64) -> Option<u8> {
+        let due = self.timer_due.filter(|&due| due <= now)?;
+        let interval = self.counters[0].period() * CLOCKS_PER_TICK;
+        self.timer_due = Some(due + ((now - due) / interval + 1) * interval);
+        Some(TIMER_VECTOR)
+    }
+}
+
+/// The port Bochs and QEMU print bytes written to, used by test programs for output.
+pub const CONSOLE_PORT: u16 = 0xE9;
+
+/// Collects the bytes written to its port as text. Reading it gives the port number back, which
+/// is how programs check the port is there.
+#[derive(Debug, Clone, Default)]
+pub struct ConsolePort {
+    output: Rc<RefCell<Vec<u8>>>,
+}
+
+impl ConsolePort {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written so far, by this console or any clone of it.
+    pub fn output(&self) -> Vec<u8> {
+        self.output.borrow().clone()
+    }
+}
+
+impl Device for ConsolePort {
+    fn read(&mut self, port: u16, _: u64) -> u8 {
+        port as u8
+    }
+
+    fn write(&mut self, _: u16, value: u8, _: u64) {
+        self.output.borrow_mut().push(value);
+    }
+}
+
+/// One byte that went over the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read { port: u16, value: u8, now: u64 },
+    Write { port: u16, value: u8, now: u64 },
+}
+
+/// Logs every access for tests to check, and answers reads with the bytes queued for them,
+/// then [`OPEN_BUS`]. Clones share the log and the queue, so a test can keep one while the bus
+/// owns the other.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    log: Rc<RefCell<Vec<Access>>>,
+    replies: Rc<RefCell<VecDeque<u8>>>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds bytes for reads to return, in order.
+    pub fn reply(&self, values: &[u8]) {
+        self.replies.borrow_mut().extend(values);
+    }
+
+    pub fn accesses(&self) -> Vec<Access> {
+        self.log.borrow().clone()
+    }
+}
+
+impl Device for Recorder {
+    fn read(&mut self, port: u16, now: u64) -> u8 {
+        let value = self.replies.borrow_mut().pop_front().unwrap_or(OPEN_BUS);
+        self.log
+            .borrow_mut()
+            .push(Access::Read { port, value, now });
+        value
+    }
+
+    fn write(&mut self, port: u16, value: u8, now: u64) {
+        self.log
+            .borrow_mut()
+            .push(Access::Write { port, value, now });
+    }
+}
@@ -0,0 +1,181 @@
+use super::*;
+use rstest::rstest;
+
+#[test]
+fn test_unattached_ports_float_high() {
+    let mut ports = PortBus::new();
+    ports.write_word(0x300, 0x1234, 0);
+
+    assert_eq!(ports.read_byte(0x300, 0), OPEN_BUS);
+    assert_eq!(ports.read_word(0x300, 0), 0xFFFF);
+}
+
+#[test]
+fn test_words_are_two_byte_accesses() {
+    let recorder = Recorder::new();
+    recorder.reply(&[0x34, 0x12]);
+    let mut ports = PortBus::new();
+    ports.attach(0x60..=0x61, recorder.clone());
+
+    assert_eq!(ports.read_word(0x60, 7), 0x1234);
+    ports.write_word(0x60, 0xBEEF, 9);
+    // the high byte of the last word is past the recorder's ports
+    ports.write_word(0x61, 0xAA55, 10);
+
+    assert_eq!(
+        recorder.accesses(),
+        [
+            Access::Read {
+                port: 0x60,
+                value: 0x34,
+                now: 7,
+            },
+            Access::Read {
+                port: 0x61,
+                value: 0x12,
+                now: 7,
+            },
+            Access::Write {
+                port: 0x60,
+                value: 0xEF,
+                now: 9,
+            },
+            Access::Write {
+                port: 0x61,
+                value: 0xBE,
+                now: 9,
+            },
+            Access::Write {
+                port: 0x61,
+                value: 0x55,
+                now: 10,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_later_devices_shadow_earlier_ones() {
+    let (first, second) = (Recorder::new(), Recorder::new());
+    first.reply(&[1, 1]);
+    second.reply(&[2]);
+    let mut ports = PortBus::new();
+    ports.attach(0x10..=0x1F, first.clone());
+    ports.attach(0x18..=0x18, second.clone());
+
+    assert_eq!(ports.read_byte(0x18, 0), 2);
+    assert_eq!(ports.read_byte(0x17, 0), 1);
+    ports.detach(0x18);
+    assert_eq!(ports.read_byte(0x18, 0), 1);
+    ports.detach(0x10);
+    assert_eq!(ports.read_byte(0x10, 0), OPEN_BUS);
+    assert_eq!(format!("{ports:?}"), "PortBus { ports: [] }");
+}
+
+#[test]
+fn test_console_port_collects_writes() {
+    let console = ConsolePort::new();
+    let mut ports = PortBus::new();
+    ports.attach(CONSOLE_PORT..=CONSOLE_PORT, console.clone());
+
+    for &byte in b"hi\n" {
+        ports.write_byte(CONSOLE_PORT, byte, 0);
+    }
+    assert_eq!(console.output(), b"hi\n");
+    assert_eq!(ports.read_byte(CONSOLE_PORT, 0), 0xE9);
+}
+
+/// A timer with counter 0 set to `reload` at clock `at`, read low then high byte.
+fn pit(reload: u16, at: u64) -> PortBus {
+    let mut ports = PortBus::new();
+    ports.attach(PIT_PORTS, Pit::new());
+    ports.write_byte(0x43, 0b0011_0100, at);
+    ports.write_byte(0x40, reload as u8, at);
+    ports.write_byte(0x40, (reload >> 8) as u8, at);
+    ports
+}
+
+#[rstest]
+#[case::loaded(1000, 0, 1000)]
+#[case::under_a_tick(1000, 3, 1000)]
+#[case::one_tick(1000, 4, 999)]
+#[case::many_ticks(1000, 4 * 600, 400)]
+#[case::last_tick(1000, 4 * 999, 1)]
+#[case::wraps_round(1000, 4 * 1000, 1000)]
+#[case::wraps_round_again(1000, 4 * 2500, 500)]
+#[case::zero_is_65536(0, 4, 0xFFFF)]
+fn test_pit_counts_down(#[case] reload: u16, #[case] clocks: u64, #[case] expected: u16) {
+    let mut ports = pit(reload, 100);
+
+    let low = ports.read_byte(0x40, 100 + clocks);
+    let high = ports.read_byte(0x40, 100 + clocks);
+    assert_eq!(u16::from_le_bytes([low, high]), expected);
+}
+
+#[test]
+fn test_pit_latches_the_count() {
+    let mut ports = pit(1000, 0);
+    // latch counter 0 at 10 ticks
+    ports.write_byte(0x43, 0b0000_0000, 40);
+
+    assert_eq!(ports.read_byte(0x40, 400), 990u16.to_le_bytes()[0]);
+    assert_eq!(ports.read_byte(0x40, 800), 990u16.to_le_bytes()[1]);
+    // the latch goes once both bytes are read
+    assert_eq!(ports.read_byte(0x40, 400), 900u16.to_le_bytes()[0]);
+}
+
+#[rstest]
+#[case::low_byte(0b0001_0000, &[0x34], 0x34, 0x34)]
+#[case::high_byte(0b0010_0000, &[0x12], 0x1200, 0x12)]
+fn test_pit_single_byte_access(
+    #[case] mode: u8,
+    #[case] writes: &[u8],
+    #[case] reload: u16,
+    #[case] read: u8,
+) {
+    let mut pit = Pit::new();
+    // counter 2, which the PC's speaker uses
+    pit.write(0x43, 0b1000_0000 | mode, 0);
+    for &byte in writes {
+        pit.write(0x42, byte, 0);
+    }
+
+    assert_eq!(pit.count(2, 0), reload);
+    assert_eq!(pit.read(0x42, 0), read);
+    assert_eq!(pit.count(0, 0), 0);
+}
+
+#[test]
+fn test_pit_mode_register_reads_float() {
+    assert_eq!(pit(10, 0).read_byte(0x43, 0), OPEN_BUS);
+}
+
+#[test]
+fn test_pit_raises_the_timer_interrupt_each_period() {
+    let mut pit = Pit::new();
+    assert_eq!(pit.interrupt(1_000_000), None);
+    // counter 0 counting 10 ticks, 40 clocks, from clock 100
+    pit.write(0x43, 0b0011_0100, 100);
+    pit.write(0x40, 10, 100);
+    pit.write(0x40, 0, 100);
+
+    assert_eq!(pit.interrupt(139), None);
+    assert_eq!(pit.interrupt(140), Some(TIMER_VECTOR));
+    assert_eq!(pit.interrupt(150), None);
+    // periods missed while nothing asked only raise one
+    assert_eq!(pit.interrupt(300), Some(TIMER_VECTOR));
+    assert_eq!(pit.interrupt(300), None);
+    assert_eq!(pit.interrupt(340), Some(TIMER_VECTOR));
+}
+
+#[test]
+fn test_bus_passes_on_device_interrupts() {
+    let mut ports = PortBus::new();
+    ports.attach(0x60..=0x60, Recorder::new());
+    ports.attach(PIT_PORTS, Pit::new());
+    assert_eq!(ports.interrupt(0), None);
+
+    ports.write_byte(0x43, 0b0001_0000, 0);
+    ports.write_byte(0x40, 1, 0);
+    assert_eq!(ports.interrupt(4), Some(TIMER_VECTOR));
+}
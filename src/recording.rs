@@ -0,0 +1,366 @@
+use crate::cpu::Cpu;
+use crate::decoder::{RegisterWordOp, SegmentRegister};
+use crate::flags::FlagSet;
+use crate::memory::{self, ByteWrite, Memory};
+use std::fmt;
+use std::io::{self, Read, Write};
+
+#[cfg(test)]
+mod recording_tests;
+
+/// Start of every recording file.
+const MAGIC: &[u8; 8] = b"8086dlt\0";
+const VERSION: u8 = 2;
+
+/// Part of the CPU's state a [`Delta`] can change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Register(RegisterWordOp),
+    Segment(SegmentRegister),
+    Ip,
+    Flags,
+}
+
+impl Location {
+    /// Their order in the file, each has the bit of its index in a record's mask.
+    const ALL: [Self; 14] = [
+        Self::Register(RegisterWordOp::AX),
+        Self::Register(RegisterWordOp::CX),
+        Self::Register(RegisterWordOp::DX),
+        Self::Register(RegisterWordOp::BX),
+        Self::Register(RegisterWordOp::SP),
+        Self::Register(RegisterWordOp::BP),
+        Self::Register(RegisterWordOp::SI),
+        Self::Register(RegisterWordOp::DI),
+        Self::Segment(SegmentRegister::ES),
+        Self::Segment(SegmentRegister::CS),
+        Self::Segment(SegmentRegister::SS),
+        Self::Segment(SegmentRegister::DS),
+        Self::Ip,
+        Self::Flags,
+    ];
+
+    fn index(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|&location| location == self)
+            .unwrap()
+    }
+
+    pub fn get(self, cpu: &Cpu) -> u16 {
+        match self {
+            Self::Register(register) => cpu.word(register),
+            Self::Segment(segment) => cpu.segment(segment),
+            Self::Ip => cpu.ip,
+            Self::Flags => cpu.flags.bits(),
+        }
+    }
+
+    pub fn set(self, cpu: &mut Cpu, value: u16) {
+        match self {
+            Self::Register(register) => cpu.set_word(register, value),
+            Self::Segment(segment) => cpu.set_segment(segment, value),
+            Self::Ip => cpu.ip = value,
+            Self::Flags => cpu.flags = FlagSet::from_bits(value),
+        }
+    }
+}
+
+/// One location's value before and after an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub location: Location,
+    pub before: u16,
+    pub after: u16,
+}
+
+/// Everything one instruction changed, enough to redo it without decoding or undo it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Delta {
+    /// In [`Location`] file order.
+    pub changes: Vec<Change>,
+    /// In the order they were made.
+    pub writes: Vec<ByteWrite>,
+    /// Added to [`Cpu::cycles`], the clocks the instruction took.
+    pub clocks: u64,
+}
+
+/// A recorded run: what each instruction changed, and which state the first one ran from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    /// [`fingerprint`] of the machine before the first instruction.
+    pub start: u64,
+    pub deltas: Vec<Delta>,
+}
+
+/// A hash of the registers, flags and memory, which a recording only replays onto if they're
+/// the same as when it was made. Cycles aren't part of it, deltas only add to them.
+pub fn fingerprint(cpu: &Cpu, memory: &Memory) -> u64 {
+    // 64-bit FNV-1a, which unlike the standard library's hashers won't change between builds
+    let words = Location::ALL
+        .iter()
+        .flat_map(|location| location.get(cpu).to_le_bytes());
+    words
+        .chain(memory.bytes().iter().copied())
+        .fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01B3)
+        })
+}
+
+impl Delta {
+    /// What differs between the CPU states either side of an instruction, along with the memory
+    /// it wrote (from [`Memory::take_journal`]).
+    pub fn between(before: &Cpu, after: &Cpu, writes: Vec<ByteWrite>) -> Self {
+        let changes = Location::ALL
+            .into_iter()
+            .map(|location| Change {
+                location,
+                before: location.get(before),
+                after: location.get(after),
+            })
+            .filter(|change| change.before != change.after)
+            .collect();
+        Self {
+            changes,
+            writes,
+            clocks: after.cycles.wrapping_sub(before.cycles),
+        }
+    }
+
+    /// Puts the state back the way the instruction left it.
+    pub fn apply(&self, cpu: &mut Cpu, memory: &mut Memory) {
+        for change in &self.changes {
+            change.location.set(cpu, change.after);
+        }
+        for write in &self.writes {
+            memory.store(write.address, write.new);
+        }
+        cpu.cycles = cpu.cycles.wrapping_add(self.clocks);
+    }
+
+    /// Puts the state back the way the instruction found it.
+    pub fn revert(&self, cpu: &mut Cpu, memory: &mut Memory) {
+        for change in &self.changes {
+            change.location.set(cpu, change.before);
+        }
+        // backwards, so the oldest value of a byte written twice wins
+        for write in self.writes.iter().rev() {
+            memory.store(write.address, write.old);
+        }
+        cpu.cycles = cpu.cycles.wrapping_sub(self.clocks);
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    /// The file doesn't start with the recording header.
+    NotARecording,
+    UnsupportedVersion(u8),
+    /// It was made from another state than the one it's being replayed onto.
+    DifferentStart,
+    /// A record was cut short or holds something no instruction could have done.
+    Corrupt {
+        record: usize,
+        reason: &'static str,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotARecording => write!(f, "not an execution recording"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported recording version {version}")
+            }
+            Self::DifferentStart => write!(
+                f,
+                "the recording starts from a different machine state, load the program the \
+                 way the recorded run did"
+            ),
+            Self::Corrupt { record, reason } => {
+                write!(f, "record {record} of the recording is corrupt: {reason}")
+            }
+            Self::Io(_) => write!(f, "failed to read the recording"),
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RecordingError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Mask bit saying a record has a clock count after its changes.
+const CLOCKS_BIT: u16 = 1 << Location::ALL.len();
+
+/// Writes `deltas` after a header that ends in the [`fingerprint`] of the state they `start`
+/// from, a little-endian `u64`. Each delta is a little-endian `u16` mask of the [`Location`]s it
+/// changed followed by their before and after words, the clocks as a LEB128 number if they
+/// aren't zero, then the number of memory writes (also LEB128) and for each a 3-byte physical
+/// address and the old and new bytes.
+pub fn write_deltas(out: &mut impl Write, start: u64, deltas: &[Delta]) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&[VERSION])?;
+    out.write_all(&start.to_le_bytes())?;
+    for delta in deltas {
+        let mut mask = delta
+            .changes
+            .iter()
+            .fold(0, |mask, change| mask | 1 << change.location.index());
+        if delta.clocks != 0 {
+            mask |= CLOCKS_BIT;
+        }
+        out.write_all(&mask.to_le_bytes())?;
+
+        let mut changes = delta.changes.clone();
+        changes.sort_by_key(|change| change.location.index());
+        for change in changes {
+            out.write_all(&change.before.to_le_bytes())?;
+            out.write_all(&change.after.to_le_bytes())?;
+        }
+        if delta.clocks != 0 {
+            write_number(out, delta.clocks)?;
+        }
+
+        write_number(out, delta.writes.len() as u64)?;
+        for write in &delta.writes {
+            out.write_all(&(write.address as u32).to_le_bytes()[..3])?;
+            out.write_all(&[write.old, write.new])?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads what [`write_deltas`] wrote.
+pub fn read_deltas(input: &mut impl Read) -> Result<Recording, RecordingError> {
+    let mut header = [0; MAGIC.len() + 1];
+    match input.read_exact(&mut header) {
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+            return Err(RecordingError::NotARecording);
+        }
+        result => result?,
+    }
+    if header[..MAGIC.len()] != MAGIC[..] {
+        return Err(RecordingError::NotARecording);
+    }
+    if header[MAGIC.len()] != VERSION {
+        return Err(RecordingError::UnsupportedVersion(header[MAGIC.len()]));
+    }
+
+    let mut start = [0; 8];
+    match input.read_exact(&mut start) {
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+            return Err(RecordingError::NotARecording);
+        }
+        result => result?,
+    }
+    let start = u64::from_le_bytes(start);
+
+    let mut deltas = vec![];
+    loop {
+        let mut first = [0];
+        if input.read(&mut first)? == 0 {
+            return Ok(Recording { start, deltas });
+        }
+        let record = deltas.len();
+        let corrupt = |reason| RecordingError::Corrupt { record, reason };
+        let mut reader = RecordReader { input, record };
+
+        let mask = u16::from_le_bytes([first[0], reader.byte()?]);
+        if mask > CLOCKS_BIT | (CLOCKS_BIT - 1) {
+            return Err(corrupt("unknown locations in the mask"));
+        }
+        let mut delta = Delta::default();
+        for (index, location) in Location::ALL.into_iter().enumerate() {
+            if mask & 1 << index != 0 {
+                delta.changes.push(Change {
+                    location,
+                    before: reader.word()?,
+                    after: reader.word()?,
+                });
+            }
+        }
+        if mask & CLOCKS_BIT != 0 {
+            delta.clocks = reader.number()?;
+        }
+
+        let writes = reader.number()?;
+        for _ in 0..writes {
+            let address = u32::from_le_bytes([reader.byte()?, reader.byte()?, reader.byte()?, 0]);
+            let address = address as usize;
+            if address >= memory::SIZE {
+                return Err(corrupt("a write past the end of memory"));
+            }
+            delta.writes.push(ByteWrite {
+                address,
+                old: reader.byte()?,
+                new: reader.byte()?,
+            });
+        }
+        deltas.push(delta);
+    }
+}
+
+/// Reads the fields of a record, running out partway through one means it's corrupt.
+struct RecordReader<'a, R> {
+    input: &'a mut R,
+    record: usize,
+}
+
+impl<R: Read> RecordReader<'_, R> {
+    fn byte(&mut self) -> Result<u8, RecordingError> {
+        let mut byte = [0];
+        match self.input.read_exact(&mut byte) {
+            Ok(()) => Ok(byte[0]),
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                Err(RecordingError::Corrupt {
+                    record: self.record,
+                    reason: "it ends partway through",
+                })
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn word(&mut self) -> Result<u16, RecordingError> {
+        Ok(u16::from_le_bytes([self.byte()?, self.byte()?]))
+    }
+
+    /// Unsigned LEB128, 7 bits a byte with the top bit set on all but the last.
+    fn number(&mut self) -> Result<u64, RecordingError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(RecordingError::Corrupt {
+            record: self.record,
+            reason: "a number is too long",
+        })
+    }
+}
+
+fn write_number(out: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
@@ -0,0 +1,230 @@
+use super::*;
+use crate::flags::Flag;
+use crate::sim::{RunOptions, run};
+use crate::syntax::Nasm;
+use crate::timing::Processor;
+use rstest::rstest;
+
+/// mov bx, 0x100 / mov word [bx], 0x1234 / push bx / add bx, bx / hlt
+const PROGRAM: &[u8] = &[
+    0xBB, 0x00, 0x01, 0xC7, 0x07, 0x34, 0x12, 0x53, 0x01, 0xDB, 0xF4,
+];
+
+fn record(data: &[u8], timing: Option<Processor>) -> (Vec<Delta>, Cpu, Memory) {
+    let mut cpu = Cpu::new();
+    cpu.set_segment(SegmentRegister::SS, 0x1000);
+    let mut memory = Memory::new();
+    let mut deltas = vec![];
+    run(
+        data,
+        &mut cpu,
+        &mut memory,
+        RunOptions {
+            timing,
+            recording: Some(&mut deltas),
+            ..RunOptions::default()
+        },
+        &Nasm::default(),
+        &mut vec![],
+    )
+    .unwrap();
+    (deltas, cpu, memory)
+}
+
+#[test]
+fn test_records_what_each_instruction_changed() {
+    let (deltas, _, _) = record(PROGRAM, None);
+
+    assert_eq!(deltas.len(), 5);
+    assert_eq!(
+        deltas[0].changes,
+        [
+            Change {
+                location: Location::Register(RegisterWordOp::BX),
+                before: 0,
+                after: 0x100,
+            },
+            Change {
+                location: Location::Ip,
+                before: 0,
+                after: 3,
+            },
+        ]
+    );
+    assert_eq!(
+        deltas[1].writes,
+        [
+            ByteWrite {
+                address: 0x100,
+                old: 0,
+                new: 0x34,
+            },
+            ByteWrite {
+                address: 0x101,
+                old: 0x00,
+                new: 0x12,
+            },
+        ]
+    );
+    // the push wraps sp round to the top of the stack segment
+    assert_eq!(deltas[2].writes[0].address, 0x1FFFE);
+    assert_eq!(
+        deltas[3].changes.last(),
+        Some(&Change {
+            location: Location::Flags,
+            before: 0,
+            after: FlagSet::of(&[Flag::Parity]).bits(),
+        })
+    );
+    assert_eq!(deltas[4].changes.len(), 1);
+    // clocks are counted whether or not the trace shows them
+    assert!(deltas.iter().all(|delta| delta.clocks != 0));
+}
+
+#[test]
+fn test_reverting_everything_gets_back_to_the_start() {
+    let (deltas, mut cpu, mut memory) = record(PROGRAM, Some(Processor::I8086));
+    let (end_cpu, end_memory) = (cpu.clone(), memory.clone());
+
+    for delta in deltas.iter().rev() {
+        delta.revert(&mut cpu, &mut memory);
+    }
+    let mut start = Cpu::new();
+    start.set_segment(SegmentRegister::SS, 0x1000);
+    let mut loaded = Memory::new();
+    loaded.load(0, 0, PROGRAM);
+    assert_eq!(cpu, start);
+    assert_eq!(memory, loaded);
+
+    for delta in &deltas {
+        delta.apply(&mut cpu, &mut memory);
+    }
+    assert_eq!(cpu, end_cpu);
+    assert_eq!(memory, end_memory);
+    assert_ne!(cpu.cycles, 0);
+}
+
+#[test]
+fn test_bytes_written_twice_revert_to_the_oldest_value() {
+    let delta = Delta {
+        writes: vec![
+            ByteWrite {
+                address: 5,
+                old: 1,
+                new: 2,
+            },
+            ByteWrite {
+                address: 5,
+                old: 2,
+                new: 3,
+            },
+        ],
+        ..Delta::default()
+    };
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+
+    delta.apply(&mut cpu, &mut memory);
+    assert_eq!(memory.bytes()[5], 3);
+    delta.revert(&mut cpu, &mut memory);
+    assert_eq!(memory.bytes()[5], 1);
+}
+
+#[test]
+fn test_file_round_trip() {
+    let (deltas, _, _) = record(PROGRAM, Some(Processor::I8088));
+    let mut file = vec![];
+    write_deltas(&mut file, 0x1234, &deltas).unwrap();
+
+    assert_eq!(
+        read_deltas(&mut file.as_slice()).unwrap(),
+        Recording {
+            start: 0x1234,
+            deltas,
+        }
+    );
+}
+
+#[test]
+fn test_fingerprint_covers_registers_and_memory() {
+    let (mut cpu, mut memory) = (Cpu::new(), Memory::new());
+    let reset = fingerprint(&cpu, &memory);
+
+    cpu.cycles = 10;
+    assert_eq!(fingerprint(&cpu, &memory), reset);
+    cpu.set_word(RegisterWordOp::DI, 1);
+    let changed = fingerprint(&cpu, &memory);
+    assert_ne!(changed, reset);
+    memory.store(0xF_FFFF, 1);
+    assert_ne!(fingerprint(&cpu, &memory), changed);
+}
+
+#[test]
+fn test_file_layout() {
+    let delta = Delta {
+        changes: vec![Change {
+            location: Location::Ip,
+            before: 0,
+            after: 2,
+        }],
+        writes: vec![ByteWrite {
+            address: 0xF_1234,
+            old: 0xAA,
+            new: 0xBB,
+        }],
+        clocks: 200,
+    };
+    let mut file = vec![];
+    write_deltas(&mut file, 0x0102_0304_0506_0708, &[delta, Delta::default()]).unwrap();
+
+    assert_eq!(
+        file,
+        [
+            b"8086dlt\0".as_slice(),
+            &[VERSION],
+            // where it starts
+            &[8, 7, 6, 5, 4, 3, 2, 1],
+            // ip and clocks bits, ip 0 -> 2, 200 clocks
+            &[0x00, 0x50, 0, 0, 2, 0, 0xC8, 0x01],
+            // one write
+            &[1, 0x34, 0x12, 0x0F, 0xAA, 0xBB],
+            // nothing changed
+            &[0, 0, 0],
+        ]
+        .concat()
+    );
+}
+
+#[rstest]
+#[case::empty(b"", "not an execution recording")]
+#[case::wrong_magic(b"8086dlt!\x02", "not an execution recording")]
+#[case::older_version(b"8086dlt\0\x01", "unsupported recording version 1")]
+#[case::no_start(b"8086dlt\0\x02\0\0\0", "not an execution recording")]
+#[case::cut_short(
+    b"8086dlt\0\x02\0\0\0\0\0\0\0\0\x00\x00\x00\x00\x10",
+    "record 1 of the recording is corrupt: it ends partway through"
+)]
+#[case::unknown_mask_bits(
+    b"8086dlt\0\x02\0\0\0\0\0\0\0\0\x00\x80",
+    "record 0 of the recording is corrupt: unknown locations in the mask"
+)]
+#[case::write_past_memory(
+    b"8086dlt\0\x02\0\0\0\0\0\0\0\0\x00\x00\x01\x00\x00\x10\x00\x00",
+    "record 0 of the recording is corrupt: a write past the end of memory"
+)]
+fn test_rejects_bad_files(#[case] file: &[u8], #[case] expected: &str) {
+    let error = read_deltas(&mut &file[..]).unwrap_err();
+    assert_eq!(error.to_string(), expected);
+}
+
+#[test]
+fn test_header_only_is_an_empty_recording() {
+    let recording = read_deltas(&mut &b"8086dlt\0\x02\x01\0\0\0\0\0\0\0"[..]).unwrap();
+    assert_eq!(
+        recording,
+        Recording {
+            start: 1,
+            deltas: vec![],
+        }
+    );
+}
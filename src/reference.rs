@@ -0,0 +1,319 @@
+use crate::cpu::{Cpu, TRACE_ORDER};
+use crate::decoder::SegmentRegister;
+use crate::memory::Memory;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[cfg(test)]
+mod reference_tests;
+
+/// Instructions before a divergence shown with it.
+const CONTEXT: usize = 3;
+
+/// What a reference trace says a run does, in the format `--exec` prints: a trace line per
+/// instruction, then `Final registers:` with a line for each register that isn't zero and the
+/// flags. An optional `Final memory:` section after it lists bytes memory should end up with as
+/// `address: bytes` lines in hex. Headers, blank lines, trailing spaces and CRLF line ends, which
+/// the course's files have, don't matter.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Reference {
+    pub trace: Vec<String>,
+    /// Registers missing from the reference are zero.
+    pub registers: BTreeMap<String, u16>,
+    pub flags: String,
+    /// Runs of bytes by their physical address.
+    pub memory: Vec<(usize, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based.
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} of the reference: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Trace,
+    Registers,
+    Memory,
+}
+
+impl Reference {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut reference = Self::default();
+        let mut section = Section::Trace;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            let error = |reason| ParseError {
+                line: index + 1,
+                reason,
+            };
+            match (section, line.trim_start()) {
+                (_, "") => {}
+                (_, "Final registers:") => section = Section::Registers,
+                (_, "Final memory:") => section = Section::Memory,
+                (_, header) if header.starts_with("---") && header.ends_with("---") => {}
+                (Section::Trace, _) => reference.trace.push(line.to_string()),
+                (Section::Registers, register) => {
+                    let (name, value) = register
+                        .split_once(':')
+                        .ok_or(error("a register line has no `:`"))?;
+                    let value = value.trim();
+                    if name == "flags" {
+                        reference.flags = value.to_string();
+                        continue;
+                    }
+                    let hex = value.split_whitespace().next().unwrap_or_default();
+                    let value = hex
+                        .strip_prefix("0x")
+                        .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+                        .ok_or(error("a register value isn't a 0x-prefixed word"))?;
+                    reference.registers.insert(name.to_string(), value);
+                }
+                (Section::Memory, bytes) => {
+                    let (address, bytes) = bytes
+                        .split_once(':')
+                        .ok_or(error("a memory line has no `:`"))?;
+                    let address = usize::from_str_radix(address, 16)
+                        .map_err(|_| error("a memory address isn't hex"))?;
+                    let bytes = bytes
+                        .split_whitespace()
+                        .map(|byte| u8::from_str_radix(byte, 16))
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| error("a memory byte isn't hex"))?;
+                    reference.memory.push((address, bytes));
+                }
+            }
+        }
+        Ok(reference)
+    }
+
+    /// The first place `trace`, and the `cpu` and `memory` it finished with, differ from the
+    /// reference. Instructions are compared first, then registers, flags and memory.
+    pub fn compare(&self, trace: &str, cpu: &Cpu, memory: &Memory) -> Result<(), Divergence> {
+        let actual: Vec<&str> = trace
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let context = |index: usize| {
+            actual[index.saturating_sub(CONTEXT)..index]
+                .iter()
+                .map(|line| line.to_string())
+                .collect()
+        };
+        for index in 0..self.trace.len().max(actual.len()) {
+            let (expected, line) = (self.trace.get(index), actual.get(index));
+            if expected.map(String::as_str) != line.copied() {
+                return Err(Divergence::Instruction {
+                    index,
+                    context: context(index),
+                    expected: expected.cloned(),
+                    actual: line.map(|line| line.to_string()),
+                });
+            }
+        }
+
+        let registers = TRACE_ORDER
+            .iter()
+            .map(|&register| (register.name(), cpu.word(register)))
+            .chain(
+                SegmentRegister::ALL
+                    .iter()
+                    .map(|&segment| (segment.name(), cpu.segment(segment))),
+            )
+            .chain([("ip", cpu.ip)]);
+        for (name, value) in registers {
+            let expected = self.registers.get(name).copied().unwrap_or(0);
+            if value != expected {
+                return Err(Divergence::Register {
+                    name: name.to_string(),
+                    expected,
+                    actual: value,
+                });
+            }
+        }
+        if let Some(name) = self.registers.keys().find(|&name| {
+            !TRACE_ORDER.iter().any(|register| register.name() == name)
+                && !SegmentRegister::ALL
+                    .iter()
+                    .any(|segment| segment.name() == name)
+                && name != "ip"
+        }) {
+            return Err(Divergence::UnknownRegister(name.clone()));
+        }
+        let flags = cpu.flags.to_string();
+        if flags != self.flags {
+            return Err(Divergence::Flags {
+                expected: self.flags.clone(),
+                actual: flags,
+            });
+        }
+
+        for (start, bytes) in &self.memory {
+            for (address, &expected) in (*start..).zip(bytes) {
+                let actual = memory.bytes().get(address).copied();
+                if actual != Some(expected) {
+                    return Err(Divergence::Memory {
+                        address,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a run first went differently from its reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// The trace line of instruction `index` (from 0) differs, `None` past the end of either.
+    Instruction {
+        index: usize,
+        /// The trace lines before it, which both agree on.
+        context: Vec<String>,
+        expected: Option<String>,
+        actual: Option<String>,
+    },
+    Register {
+        name: String,
+        expected: u16,
+        actual: u16,
+    },
+    /// The reference has a final value for something that isn't a register.
+    UnknownRegister(String),
+    Flags {
+        expected: String,
+        actual: String,
+    },
+    /// `actual` is `None` for addresses past the end of memory.
+    Memory {
+        address: usize,
+        expected: u8,
+        actual: Option<u8>,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Instruction {
+                index,
+                context,
+                expected,
+                actual,
+            } => {
+                writeln!(f, "instruction {} differs from the reference", index + 1)?;
+                for (number, line) in (index + 1 - context.len()..).zip(context) {
+                    writeln!(f, "{number:>8}: {line}")?;
+                }
+                let missing = "(nothing, the run ended)";
+                let expected = expected
+                    .as_deref()
+                    .unwrap_or("(nothing, the reference ended)");
+                writeln!(f, "expected: {expected}")?;
+                write!(f, "  actual: {}", actual.as_deref().unwrap_or(missing))?;
+                if let (Some(expected), Some(actual)) = (self.fields(true), self.fields(false)) {
+                    let only = |these: &[String], those: &[String]| {
+                        these
+                            .iter()
+                            .filter(|field| !those.contains(field))
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    };
+                    write!(
+                        f,
+                        "\n    only: {} / {}",
+                        only(&expected, &actual),
+                        only(&actual, &expected)
+                    )?;
+                }
+                Ok(())
+            }
+            Self::Register {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "final {name} is {actual:#06x} ({actual}), the reference has {expected:#06x} \
+                 ({expected})"
+            ),
+            Self::UnknownRegister(name) => {
+                write!(
+                    f,
+                    "the reference has a final value for unknown register {name}"
+                )
+            }
+            Self::Flags { expected, actual } => {
+                write!(
+                    f,
+                    "final flags are {actual:?}, the reference has {expected:?}"
+                )
+            }
+            Self::Memory {
+                address,
+                expected,
+                actual: Some(actual),
+            } => write!(
+                f,
+                "final byte at {address:#07x} is {actual:#04x}, the reference has {expected:#04x}"
+            ),
+            Self::Memory { address, .. } => {
+                write!(
+                    f,
+                    "the reference has a byte at {address:#x}, past the end of memory"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+impl Divergence {
+    /// The changes after ` ; ` in the expected or actual trace line, the clocks summary as one.
+    /// `None` unless both lines have them and the instructions agree.
+    fn fields(&self, expected: bool) -> Option<Vec<String>> {
+        let Self::Instruction {
+            expected: Some(expected_line),
+            actual: Some(actual_line),
+            ..
+        } = self
+        else {
+            return None;
+        };
+        let (expected_text, expected_changes) = expected_line.split_once(" ; ")?;
+        let (actual_text, actual_changes) = actual_line.split_once(" ; ")?;
+        if expected_text != actual_text {
+            return None;
+        }
+        let changes = if expected {
+            expected_changes
+        } else {
+            actual_changes
+        };
+        let (clocks, changes) = match changes.split_once(" | ") {
+            Some((clocks, changes)) => (Some(clocks.to_string()), changes),
+            None => (None, changes),
+        };
+        Some(
+            clocks
+                .into_iter()
+                .chain(changes.split_whitespace().map(str::to_string))
+                .collect(),
+        )
+    }
+}
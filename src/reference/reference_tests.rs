@@ -0,0 +1,219 @@
+use super::*;
+use crate::sim::{RunOptions, run};
+use crate::syntax::Nasm;
+use crate::timing::Processor;
+use rstest::rstest;
+use std::fs;
+use std::path::Path;
+
+/// mov cx, 3 / add cx, 2 / mov [0x10], cx
+const PROGRAM: &[u8] = &[0xB9, 0x03, 0x00, 0x83, 0xC1, 0x02, 0x89, 0x0E, 0x10, 0x00];
+
+const REFERENCE: &str = "--- program execution ---\r
+mov cx, 3 ; cx:0x0->0x3 ip:0x0->0x3\r
+add cx, 2 ; cx:0x3->0x5 ip:0x3->0x6 flags:->P\r
+mov [16], cx ; ip:0x6->0xa\r
+\r
+Final registers:\r
+      cx: 0x0005 (5)\r
+      ip: 0x000a (10)\r
+   flags: P\r
+\r
+Final memory:\r
+00010: 05 00\r
+";
+
+fn execute(data: &[u8], timing: Option<Processor>) -> (String, Cpu, Memory) {
+    let (mut cpu, mut memory, mut trace) = (Cpu::new(), Memory::new(), vec![]);
+    run(
+        data,
+        &mut cpu,
+        &mut memory,
+        RunOptions {
+            timing,
+            ..RunOptions::default()
+        },
+        &Nasm::default(),
+        &mut trace,
+    )
+    .unwrap();
+    (String::from_utf8(trace).unwrap(), cpu, memory)
+}
+
+/// Runs every listing with a `.txt` reference next to it, timed on an 8086 when the reference
+/// counts clocks.
+#[test]
+fn test_listings_match_their_references() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut checked = 0;
+    for entry in fs::read_dir(root).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_string_lossy();
+        if !name.starts_with("listing_") || !name.ends_with(".txt") {
+            continue;
+        }
+        let text = fs::read_to_string(&path).unwrap();
+        let reference = Reference::parse(&text).unwrap_or_else(|error| panic!("{name}: {error}"));
+        let timing = text.contains("Clocks:").then_some(Processor::I8086);
+        let (trace, cpu, memory) = execute(&fs::read(path.with_extension("")).unwrap(), timing);
+
+        if let Err(divergence) = reference.compare(&trace, &cpu, &memory) {
+            panic!("{name}: {divergence}");
+        }
+        checked += 1;
+    }
+    assert!(checked >= 6, "only {checked} references");
+}
+
+#[test]
+fn test_parses_the_reference_format() {
+    let reference = Reference::parse(REFERENCE).unwrap();
+
+    assert_eq!(
+        reference,
+        Reference {
+            trace: vec![
+                "mov cx, 3 ; cx:0x0->0x3 ip:0x0->0x3".to_string(),
+                "add cx, 2 ; cx:0x3->0x5 ip:0x3->0x6 flags:->P".to_string(),
+                "mov [16], cx ; ip:0x6->0xa".to_string(),
+            ],
+            registers: BTreeMap::from([("cx".to_string(), 5), ("ip".to_string(), 10)]),
+            flags: "P".to_string(),
+            memory: vec![(0x10, vec![5, 0])],
+        }
+    );
+}
+
+#[rstest]
+#[case::register_without_colon("Final registers:\n  ax 0x0001 (1)\n", 2)]
+#[case::decimal_register("\nFinal registers:\n      ax: 1\n", 3)]
+#[case::register_too_wide("Final registers:\n      ax: 0x10000\n", 2)]
+#[case::memory_without_colon("Final memory:\n003e8 01\n", 2)]
+#[case::memory_address("Final memory:\nxyz: 01\n", 2)]
+#[case::memory_byte("Final memory:\n003e8: 01 100\n", 2)]
+fn test_reports_the_line_it_cannot_parse(#[case] text: &str, #[case] line: usize) {
+    assert_eq!(Reference::parse(text).unwrap_err().line, line);
+}
+
+#[test]
+fn test_a_matching_run_has_no_divergence() {
+    let (trace, cpu, memory) = execute(PROGRAM, None);
+
+    assert_eq!(
+        Reference::parse(REFERENCE)
+            .unwrap()
+            .compare(&trace, &cpu, &memory),
+        Ok(())
+    );
+}
+
+#[rstest]
+#[case::changes("flags:->P", "flags:->PZ", Divergence::Instruction {
+    index: 1,
+    context: vec!["mov cx, 3 ; cx:0x0->0x3 ip:0x0->0x3".to_string()],
+    expected: Some("add cx, 2 ; cx:0x3->0x5 ip:0x3->0x6 flags:->PZ".to_string()),
+    actual: Some("add cx, 2 ; cx:0x3->0x5 ip:0x3->0x6 flags:->P".to_string()),
+})]
+#[case::missing_instruction("mov [16], cx ; ip:0x6->0xa\r\n", "", Divergence::Instruction {
+    index: 2,
+    context: vec![
+        "mov cx, 3 ; cx:0x0->0x3 ip:0x0->0x3".to_string(),
+        "add cx, 2 ; cx:0x3->0x5 ip:0x3->0x6 flags:->P".to_string(),
+    ],
+    expected: None,
+    actual: Some("mov [16], cx ; ip:0x6->0xa".to_string()),
+})]
+#[case::register("cx: 0x0005", "cx: 0x0006", Divergence::Register {
+    name: "cx".to_string(),
+    expected: 6,
+    actual: 5,
+})]
+#[case::missing_register("      cx: 0x0005 (5)\r\n", "", Divergence::Register {
+    name: "cx".to_string(),
+    expected: 0,
+    actual: 5,
+})]
+#[case::unknown_register("   flags", "      xx: 0x0001 (1)\r\n   flags", Divergence::UnknownRegister(
+    "xx".to_string(),
+))]
+#[case::flags("flags: P", "flags: C", Divergence::Flags {
+    expected: "C".to_string(),
+    actual: "P".to_string(),
+})]
+#[case::memory("05 00", "05 01", Divergence::Memory {
+    address: 0x11,
+    expected: 1,
+    actual: Some(0),
+})]
+#[case::past_memory("00010:", "fffff: 00 00\r\n00010:", Divergence::Memory {
+    address: 0x100000,
+    expected: 0,
+    actual: None,
+})]
+fn test_reports_the_first_divergence(
+    #[case] from: &str,
+    #[case] to: &str,
+    #[case] expected: Divergence,
+) {
+    let (trace, cpu, memory) = execute(PROGRAM, None);
+    let reference = Reference::parse(&REFERENCE.replacen(from, to, 1)).unwrap();
+
+    assert_eq!(reference.compare(&trace, &cpu, &memory), Err(expected));
+}
+
+#[test]
+fn test_instruction_divergence_shows_where_it_is() {
+    let (trace, cpu, memory) = execute(PROGRAM, None);
+    let reference =
+        Reference::parse(&REFERENCE.replace("flags:->P", "cx:0x5->0x6 flags:->PZ")).unwrap();
+    let divergence = reference.compare(&trace, &cpu, &memory).unwrap_err();
+
+    assert_eq!(
+        divergence.to_string(),
+        "instruction 2 differs from the reference
+       1: mov cx, 3 ; cx:0x0->0x3 ip:0x0->0x3
+expected: add cx, 2 ; cx:0x3->0x5 ip:0x3->0x6 cx:0x5->0x6 flags:->PZ
+  actual: add cx, 2 ; cx:0x3->0x5 ip:0x3->0x6 flags:->P
+    only: cx:0x5->0x6 flags:->PZ / flags:->P"
+    );
+}
+
+#[rstest]
+#[case::clocks(
+    "add cx, 2 ; Clocks: +4 = 8 | cx:0x3->0x5",
+    "add cx, 2 ; Clocks: +3 = 7 | cx:0x3->0x5",
+    "Clocks: +4 = 8 / Clocks: +3 = 7"
+)]
+#[case::another_instruction("add cx, 2 ; cx:0x3->0x5", "sub cx, 2 ; cx:0x3->0x1", "")]
+fn test_fields_that_differ(#[case] expected: &str, #[case] actual: &str, #[case] only: &str) {
+    let divergence = Divergence::Instruction {
+        index: 0,
+        context: vec![],
+        expected: Some(expected.to_string()),
+        actual: Some(actual.to_string()),
+    };
+
+    let text = divergence.to_string();
+    match only {
+        "" => assert!(!text.contains("only:"), "{text}"),
+        only => assert!(text.ends_with(&format!("only: {only}")), "{text}"),
+    }
+}
+
+#[test]
+fn test_run_that_stops_early_says_so() {
+    let divergence = Divergence::Instruction {
+        index: 5,
+        context: vec!["hlt".to_string()],
+        expected: Some("nop ; ip:0x5->0x6".to_string()),
+        actual: None,
+    };
+
+    assert_eq!(
+        divergence.to_string(),
+        "instruction 6 differs from the reference
+       5: hlt
+expected: nop ; ip:0x5->0x6
+  actual: (nothing, the run ended)"
+    );
+}
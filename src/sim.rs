@@ -0,0 +1,677 @@
+use crate::alu;
+use crate::cpu::Cpu;
+use crate::decode_cache::DecodeCache;
+use crate::decoder::{
+    DecodeError, EffectiveAddress, Instruction, Mnemonic, Operand, Register, RegisterWordOp,
+    SegmentRegister, Width, decode_instruction,
+};
+use crate::flags::{Flag, FlagSet};
+use crate::interrupts::Interrupts;
+use crate::memory::{Memory, physical_address};
+use crate::ports::PortBus;
+use crate::recording::Delta;
+use crate::syntax::InstructionFormatter;
+use crate::timing::{Clocks, Execution, Processor, estimate};
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::Range;
+
+#[cfg(test)]
+mod sim_tests;
+
+#[derive(Debug)]
+pub enum SimError {
+    Decode(DecodeError),
+    /// The instruction decoded fine but the simulator has no implementation for it.
+    Unsupported {
+        offset: usize,
+        instruction: String,
+    },
+    /// An interrupt handler couldn't do what the program asked.
+    Service {
+        offset: usize,
+        number: u8,
+        message: String,
+    },
+    /// Writing the trace failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode(error) => error.fmt(f),
+            Self::Unsupported {
+                offset,
+                instruction,
+            } => write!(
+                f,
+                "unable to simulate `{instruction}` at offset {offset:#06x}"
+            ),
+            Self::Service {
+                offset,
+                number,
+                message,
+            } => write!(
+                f,
+                "interrupt {number:02X}h at offset {offset:#06x} failed: {message}"
+            ),
+            Self::Io(_) => write!(f, "failed to write the trace"),
+        }
+    }
+}
+
+impl std::error::Error for SimError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            // displayed as is, so it isn't repeated as the cause
+            Self::Decode(_) | Self::Unsupported { .. } | Self::Service { .. } => None,
+            Self::Io(error) => Some(error),
+        }
+    }
+}
+
+impl From<DecodeError> for SimError {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+impl From<io::Error> for SimError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+fn unsupported(instruction: &Instruction) -> SimError {
+    SimError::Unsupported {
+        offset: instruction.offset,
+        instruction: instruction.to_string(),
+    }
+}
+
+fn mask(width: Width, value: u16) -> u16 {
+    value & width.mask()
+}
+
+/// The segment value and offset a memory operand refers to.
+fn locate(cpu: &Cpu, instruction: &Instruction, address: &EffectiveAddress) -> (u16, u16) {
+    let segment = cpu.segment(instruction.segment_of(address));
+    (segment, cpu.offset_of(address))
+}
+
+fn read(
+    cpu: &Cpu,
+    memory: &Memory,
+    instruction: &Instruction,
+    operand: Operand,
+) -> Result<u16, SimError> {
+    match operand {
+        Operand::Register(register) => Ok(cpu.register(register)),
+        Operand::Segment(segment) => Ok(cpu.segment(segment)),
+        Operand::Immediate(value) => Ok(mask(instruction.width, value)),
+        Operand::Memory(address) => {
+            let (segment, offset) = locate(cpu, instruction, &address);
+            Ok(memory.read(instruction.width, segment, offset))
+        }
+        Operand::Relative(_) | Operand::Far { .. } => Err(unsupported(instruction)),
+    }
+}
+
+fn write(
+    cpu: &mut Cpu,
+    memory: &mut Memory,
+    instruction: &Instruction,
+    operand: Operand,
+    value: u16,
+) -> Result<(), SimError> {
+    match operand {
+        Operand::Register(register) => cpu.set_register(register, value),
+        Operand::Segment(segment) => cpu.set_segment(segment, value),
+        Operand::Memory(address) => {
+            let (segment, offset) = locate(cpu, instruction, &address);
+            memory.write(instruction.width, segment, offset, value);
+        }
+        Operand::Immediate(_) | Operand::Relative(_) | Operand::Far { .. } => {
+            return Err(unsupported(instruction));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn push(cpu: &mut Cpu, memory: &mut Memory, value: u16) {
+    let sp = cpu.word(RegisterWordOp::SP).wrapping_sub(2);
+    cpu.set_word(RegisterWordOp::SP, sp);
+    memory.write_word(cpu.segment(SegmentRegister::SS), sp, value);
+}
+
+fn pop(cpu: &mut Cpu, memory: &Memory) -> u16 {
+    let sp = cpu.word(RegisterWordOp::SP);
+    cpu.set_word(RegisterWordOp::SP, sp.wrapping_add(2));
+    memory.read_word(cpu.segment(SegmentRegister::SS), sp)
+}
+
+/// Whether a conditional jump is taken, `None` for anything that isn't one.
+fn condition(flags: FlagSet, mnemonic: Mnemonic) -> Option<bool> {
+    let flag = |flag| flags.contains(flag);
+    let less = flag(Flag::Sign) != flag(Flag::Overflow);
+    Some(match mnemonic {
+        Mnemonic::Jo => flag(Flag::Overflow),
+        Mnemonic::Jno => !flag(Flag::Overflow),
+        Mnemonic::Jb => flag(Flag::Carry),
+        Mnemonic::Jnb => !flag(Flag::Carry),
+        Mnemonic::Je => flag(Flag::Zero),
+        Mnemonic::Jne => !flag(Flag::Zero),
+        Mnemonic::Jbe => flag(Flag::Carry) || flag(Flag::Zero),
+        Mnemonic::Ja => !flag(Flag::Carry) && !flag(Flag::Zero),
+        Mnemonic::Js => flag(Flag::Sign),
+        Mnemonic::Jns => !flag(Flag::Sign),
+        Mnemonic::Jp => flag(Flag::Parity),
+        Mnemonic::Jnp => !flag(Flag::Parity),
+        Mnemonic::Jl => less,
+        Mnemonic::Jnl => !less,
+        Mnemonic::Jle => less || flag(Flag::Zero),
+        Mnemonic::Jg => !less && !flag(Flag::Zero),
+        _ => return None,
+    })
+}
+
+/// Relative jumps, calls and loops. `cpu.ip` already points past the instruction, which is what
+/// the displacement is relative to.
+fn branch(
+    cpu: &mut Cpu,
+    memory: &mut Memory,
+    instruction: &Instruction,
+    displacement: i16,
+) -> Result<(), SimError> {
+    let mnemonic = instruction.mnemonic;
+    let taken = match mnemonic {
+        Mnemonic::Jmp => true,
+        Mnemonic::Call => {
+            push(cpu, memory, cpu.ip);
+            true
+        }
+        Mnemonic::Jcxz => cpu.word(RegisterWordOp::CX) == 0,
+        Mnemonic::Loop | Mnemonic::Loopz | Mnemonic::Loopnz => {
+            let cx = cpu.word(RegisterWordOp::CX).wrapping_sub(1);
+            cpu.set_word(RegisterWordOp::CX, cx);
+            let zero = cpu.flags.contains(Flag::Zero);
+            cx != 0
+                && match mnemonic {
+                    Mnemonic::Loopz => zero,
+                    Mnemonic::Loopnz => !zero,
+                    _ => true,
+                }
+        }
+        _ => condition(cpu.flags, mnemonic).ok_or_else(|| unsupported(instruction))?,
+    };
+    if taken {
+        cpu.ip = cpu.ip.wrapping_add(displacement as u16);
+    }
+    Ok(())
+}
+
+/// The `segment:offset` a far call or jump goes to, either written in the instruction or stored
+/// in memory as the offset followed by the segment.
+fn far_target(
+    cpu: &Cpu,
+    memory: &Memory,
+    instruction: &Instruction,
+    operand: Operand,
+) -> Result<(u16, u16), SimError> {
+    match operand {
+        Operand::Far { segment, offset } => Ok((segment, offset)),
+        Operand::Memory(address) => {
+            let (segment, offset) = locate(cpu, instruction, &address);
+            Ok((
+                memory.read_word(segment, offset.wrapping_add(2)),
+                memory.read_word(segment, offset),
+            ))
+        }
+        _ => Err(unsupported(instruction)),
+    }
+}
+
+/// Runs a single instruction against `cpu`. Control transfers expect `cpu.ip` to already point
+/// at the next instruction, the way [`step`] leaves it.
+pub fn execute(
+    cpu: &mut Cpu,
+    memory: &mut Memory,
+    instruction: &Instruction,
+) -> Result<(), SimError> {
+    let width = instruction.width;
+    match (
+        instruction.mnemonic,
+        instruction.destination,
+        instruction.source,
+    ) {
+        (Mnemonic::Mov, Some(destination), Some(source)) => {
+            let value = read(cpu, memory, instruction, source)?;
+            write(cpu, memory, instruction, destination, value)
+        }
+        // the 8086 pushes SP as it is after making room for it
+        (Mnemonic::Push, Some(Operand::Register(Register::Word(RegisterWordOp::SP))), None) => {
+            let sp = cpu.word(RegisterWordOp::SP).wrapping_sub(2);
+            push(cpu, memory, sp);
+            Ok(())
+        }
+        (Mnemonic::Push, Some(source), None) => {
+            let value = read(cpu, memory, instruction, source)?;
+            push(cpu, memory, value);
+            Ok(())
+        }
+        (Mnemonic::Pop, Some(destination), None) => {
+            let value = pop(cpu, memory);
+            write(cpu, memory, instruction, destination, value)
+        }
+        (Mnemonic::Pushf, None, None) => {
+            push(cpu, memory, cpu.flags.pushed());
+            Ok(())
+        }
+        (Mnemonic::Popf, None, None) => {
+            cpu.flags = FlagSet::from_bits(pop(cpu, memory));
+            Ok(())
+        }
+        (_, Some(Operand::Relative(displacement)), None) => {
+            branch(cpu, memory, instruction, displacement)
+        }
+        (Mnemonic::Jmp, Some(target), None) => {
+            cpu.ip = read(cpu, memory, instruction, target)?;
+            Ok(())
+        }
+        (Mnemonic::Call, Some(target), None) => {
+            let target = read(cpu, memory, instruction, target)?;
+            push(cpu, memory, cpu.ip);
+            cpu.ip = target;
+            Ok(())
+        }
+        (Mnemonic::JmpFar | Mnemonic::CallFar, Some(target), None) => {
+            let (segment, offset) = far_target(cpu, memory, instruction, target)?;
+            if instruction.mnemonic == Mnemonic::CallFar {
+                push(cpu, memory, cpu.segment(SegmentRegister::CS));
+                push(cpu, memory, cpu.ip);
+            }
+            cpu.set_segment(SegmentRegister::CS, segment);
+            cpu.ip = offset;
+            Ok(())
+        }
+        (Mnemonic::Iret, None, None) => {
+            cpu.ip = pop(cpu, memory);
+            let segment = pop(cpu, memory);
+            cpu.set_segment(SegmentRegister::CS, segment);
+            cpu.flags = FlagSet::from_bits(pop(cpu, memory));
+            Ok(())
+        }
+        (Mnemonic::Ret | Mnemonic::Retf, release, None) => {
+            cpu.ip = pop(cpu, memory);
+            if instruction.mnemonic == Mnemonic::Retf {
+                let segment = pop(cpu, memory);
+                cpu.set_segment(SegmentRegister::CS, segment);
+            }
+            // `ret 4` also drops the caller's arguments
+            if let Some(Operand::Immediate(bytes)) = release {
+                let sp = cpu.word(RegisterWordOp::SP).wrapping_add(bytes);
+                cpu.set_word(RegisterWordOp::SP, sp);
+            }
+            Ok(())
+        }
+        (Mnemonic::Clc | Mnemonic::Stc | Mnemonic::Cmc, None, None) => {
+            let carry = match instruction.mnemonic {
+                Mnemonic::Clc => false,
+                Mnemonic::Stc => true,
+                _ => !cpu.flags.contains(Flag::Carry),
+            };
+            cpu.flags.set(Flag::Carry, carry);
+            Ok(())
+        }
+        (Mnemonic::Cld | Mnemonic::Std, None, None) => {
+            cpu.flags
+                .set(Flag::Direction, instruction.mnemonic == Mnemonic::Std);
+            Ok(())
+        }
+        (Mnemonic::Cli | Mnemonic::Sti, None, None) => {
+            cpu.flags
+                .set(Flag::Interrupt, instruction.mnemonic == Mnemonic::Sti);
+            Ok(())
+        }
+        // `run` is the one that stops at `hlt`
+        (Mnemonic::Nop | Mnemonic::Hlt, None, None) => Ok(()),
+        (mnemonic, Some(destination), Some(source)) if mnemonic.is_shift() => {
+            let value = read(cpu, memory, instruction, destination)?;
+            let count = read(cpu, memory, instruction, source)? as u8;
+            let result = alu::shift(&mut cpu.flags, mnemonic, width, value, count)
+                .ok_or_else(|| unsupported(instruction))?;
+            write(cpu, memory, instruction, destination, result)
+        }
+        (mnemonic, Some(destination), Some(source)) => {
+            let a = read(cpu, memory, instruction, destination)?;
+            let b = read(cpu, memory, instruction, source)?;
+            let carry = cpu.flags.contains(Flag::Carry);
+            let flags = &mut cpu.flags;
+            let result = match mnemonic {
+                Mnemonic::Add => alu::add(flags, width, a, b, false),
+                Mnemonic::Adc => alu::add(flags, width, a, b, carry),
+                Mnemonic::Sub | Mnemonic::Cmp => alu::sub(flags, width, a, b, false),
+                Mnemonic::Sbb => alu::sub(flags, width, a, b, carry),
+                Mnemonic::And | Mnemonic::Test => alu::logic(flags, width, a & b),
+                Mnemonic::Or => alu::logic(flags, width, a | b),
+                Mnemonic::Xor => alu::logic(flags, width, a ^ b),
+                _ => return Err(unsupported(instruction)),
+            };
+            match mnemonic {
+                // Only the flags are kept.
+                Mnemonic::Cmp | Mnemonic::Test => Ok(()),
+                _ => write(cpu, memory, instruction, destination, result),
+            }
+        }
+        (mnemonic, Some(destination), None) => {
+            let value = read(cpu, memory, instruction, destination)?;
+            let flags = &mut cpu.flags;
+            let result = match mnemonic {
+                Mnemonic::Inc => alu::inc(flags, width, value),
+                Mnemonic::Dec => alu::dec(flags, width, value),
+                Mnemonic::Neg => alu::neg(flags, width, value),
+                Mnemonic::Not => mask(width, !value),
+                _ => return Err(unsupported(instruction)),
+            };
+            write(cpu, memory, instruction, destination, result)
+        }
+        _ => Err(unsupported(instruction)),
+    }
+}
+
+/// Longer than any instruction without a run of redundant prefixes.
+const FETCH_WINDOW: usize = 16;
+
+/// Decodes the instruction at `cs:ip`. Its offset is the IP it was fetched from.
+pub fn fetch(cpu: &Cpu, memory: &Memory) -> Result<Instruction, SimError> {
+    let cs = cpu.segment(SegmentRegister::CS);
+    let mut window = [0; FETCH_WINDOW];
+    for (offset, byte) in (0..).zip(&mut window) {
+        *byte = memory.read_byte(cs, cpu.ip.wrapping_add(offset));
+    }
+    let mut instruction =
+        decode_instruction(&window, 0).map_err(|error| error.at(usize::from(cpu.ip)))?;
+    instruction.offset = usize::from(cpu.ip);
+    Ok(instruction)
+}
+
+/// What [`step`] ran and how long it took.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stepped {
+    pub instruction: Instruction,
+    /// Estimated for the processor `step` was given, with entering any interrupt after it.
+    pub clocks: Clocks,
+}
+
+/// Fetches the instruction at `cs:ip` through `cache`, moves IP past it and executes it.
+/// Interrupts go to `interrupts` and `in` and `out` to `ports`. The clocks it took on
+/// `processor` are added to `cpu.cycles`, which is the time the devices on `ports` see.
+///
+/// Afterwards comes the single-step trap, `int 1`, if TF was set when the instruction started,
+/// then an interrupt a device on `ports` is raising if IF is set. What entering their handlers
+/// changed is part of the instruction's changes.
+pub fn step(
+    cpu: &mut Cpu,
+    memory: &mut Memory,
+    interrupts: &mut Interrupts,
+    ports: &mut PortBus,
+    cache: &mut DecodeCache,
+    processor: Processor,
+) -> Result<Stepped, SimError> {
+    let instruction = cache.fetch(cpu, memory)?;
+    let ip = cpu.ip;
+    let trap = cpu.flags.contains(Flag::Trap);
+    let mut execution = execution(cpu, &instruction);
+    let next = ip.wrapping_add(instruction.length as u16);
+    cpu.ip = next;
+    let result = match instruction.mnemonic {
+        Mnemonic::Int | Mnemonic::Int3 | Mnemonic::Into => {
+            interrupt(cpu, memory, interrupts, &instruction)
+        }
+        Mnemonic::In | Mnemonic::Out => port_io(cpu, memory, ports, &instruction),
+        _ => execute(cpu, memory, &instruction),
+    };
+    // leave IP on the instruction that couldn't run
+    result.inspect_err(|_| cpu.ip = ip)?;
+    execution.taken = cpu.ip != next;
+
+    if trap {
+        execution.trapped = external(cpu, memory, interrupts, &instruction, SINGLE_STEP)?;
+    }
+    // devices are asked at the time the instruction (and any trap) finished
+    let so_far = estimate(&instruction, processor, &execution).total();
+    if cpu.flags.contains(Flag::Interrupt)
+        && let Some(number) = ports.interrupt(cpu.cycles + u64::from(so_far))
+    {
+        execution.interrupted = external(cpu, memory, interrupts, &instruction, number)?;
+    }
+    let clocks = estimate(&instruction, processor, &execution);
+    cpu.cycles += u64::from(clocks.total());
+    Ok(Stepped {
+        instruction,
+        clocks,
+    })
+}
+
+/// The interrupt TF raises after each instruction.
+pub const SINGLE_STEP: u8 = 1;
+
+/// An interrupt the program didn't ask for, `false` if nothing handles it. Then it's dropped
+/// without taking any time, as if the processor had never been interrupted.
+fn external(
+    cpu: &mut Cpu,
+    memory: &mut Memory,
+    interrupts: &mut Interrupts,
+    instruction: &Instruction,
+    number: u8,
+) -> Result<bool, SimError> {
+    interrupts
+        .call(number, cpu, memory)
+        .map_err(|message| SimError::Service {
+            offset: instruction.offset,
+            number,
+            message,
+        })
+}
+
+fn interrupt(
+    cpu: &mut Cpu,
+    memory: &mut Memory,
+    interrupts: &mut Interrupts,
+    instruction: &Instruction,
+) -> Result<(), SimError> {
+    let number = match (instruction.mnemonic, instruction.destination) {
+        (Mnemonic::Int3, _) => 3,
+        (Mnemonic::Into, _) if !cpu.flags.contains(Flag::Overflow) => return Ok(()),
+        (Mnemonic::Into, _) => 4,
+        (_, Some(Operand::Immediate(number))) => number as u8,
+        _ => return Err(unsupported(instruction)),
+    };
+    match interrupts.call(number, cpu, memory) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(unsupported(instruction)),
+        Err(message) => Err(SimError::Service {
+            offset: instruction.offset,
+            number,
+            message,
+        }),
+    }
+}
+
+fn port_io(
+    cpu: &mut Cpu,
+    memory: &mut Memory,
+    ports: &mut PortBus,
+    instruction: &Instruction,
+) -> Result<(), SimError> {
+    let (Some(destination), Some(source)) = (instruction.destination, instruction.source) else {
+        return Err(unsupported(instruction));
+    };
+    let now = cpu.cycles;
+    if instruction.mnemonic == Mnemonic::In {
+        let port = read(cpu, memory, instruction, source)?;
+        let value = match instruction.width {
+            Width::Byte => u16::from(ports.read_byte(port, now)),
+            Width::Word => ports.read_word(port, now),
+        };
+        write(cpu, memory, instruction, destination, value)
+    } else {
+        let port = read(cpu, memory, instruction, destination)?;
+        let value = read(cpu, memory, instruction, source)?;
+        match instruction.width {
+            Width::Byte => ports.write_byte(port, value as u8, now),
+            Width::Word => ports.write_word(port, value, now),
+        }
+        Ok(())
+    }
+}
+
+/// The parts of an instruction's timing that depend on the state it starts in, [`step`] fills in
+/// the rest once it has run.
+fn execution(before: &Cpu, instruction: &Instruction) -> Execution {
+    let count = match (instruction.mnemonic, instruction.source) {
+        (mnemonic, Some(Operand::Register(register))) if mnemonic.is_shift() => {
+            before.register(register)
+        }
+        (mnemonic, _) if mnemonic.is_string() => before.word(RegisterWordOp::CX),
+        _ => 0,
+    };
+    Execution {
+        count,
+        address: instruction.operands().find_map(|operand| match operand {
+            Operand::Memory(address) => Some(before.offset_of(&address)),
+            _ => None,
+        }),
+        sp: before.word(RegisterWordOp::SP),
+        ..Execution::default()
+    }
+}
+
+/// Copies `data` into memory at `cs:ip`, returning the physical addresses it went to.
+pub fn load(data: &[u8], cpu: &Cpu, memory: &mut Memory) -> Range<usize> {
+    let cs = cpu.segment(SegmentRegister::CS);
+    memory.load(cs, cpu.ip, data);
+    let start = physical_address(cs, cpu.ip);
+    start..start + data.len()
+}
+
+/// Whether `cs:ip` points into `code`, running off the end of the program is how most of the
+/// course's listings finish.
+pub fn is_running(cpu: &Cpu, code: &Range<usize>) -> bool {
+    code.contains(&physical_address(cpu.segment(SegmentRegister::CS), cpu.ip))
+}
+
+/// Loads `data` at `cs:ip` and [`step`]s through it until IP leaves the loaded bytes or a `hlt`
+/// runs. Each instruction is written to `trace` followed by the registers it changed:
+///
+/// ```text
+/// mov cx, bx ; cx:0x0->0x1 ip:0x0->0x2
+/// sub cx, bx ; cx:0x1->0x0 ip:0x2->0x4 flags:->PZ
+/// ```
+///
+/// `cpu.cycles` always counts the clocks the instructions took, on an 8086 unless there's a
+/// `timing` processor. With one, they're also put in front of the changes along with the
+/// running total:
+///
+/// ```text
+/// mov word [bp], 1 ; Clocks: +19 = 62 (10 + 9ea) | ip:0x9->0xe
+/// ```
+pub fn run(
+    data: &[u8],
+    cpu: &mut Cpu,
+    memory: &mut Memory,
+    options: RunOptions,
+    formatter: &dyn InstructionFormatter,
+    trace: &mut impl Write,
+) -> Result<(), SimError> {
+    let code = load(data, cpu, memory);
+    run_loaded(&code, cpu, memory, options, formatter, trace)
+}
+
+/// What else [`run`] does besides executing, the default does nothing else.
+#[derive(Debug, Default)]
+pub struct RunOptions<'a> {
+    /// Count clocks for this processor rather than the 8086 and show them in the trace.
+    pub timing: Option<Processor>,
+    /// Push a [`Delta`] for each instruction onto this.
+    pub recording: Option<&'a mut Vec<Delta>>,
+    /// Where `int` instructions go, a fresh [`Interrupts`] when there isn't one. The run stops
+    /// once one of them terminates the program.
+    pub interrupts: Option<&'a mut Interrupts>,
+    /// Where `in` and `out` go, an empty bus when there isn't one.
+    pub ports: Option<&'a mut PortBus>,
+    /// Where decoded instructions are kept, a fresh [`DecodeCache::new`] when there isn't one.
+    pub cache: Option<&'a mut DecodeCache>,
+}
+
+/// Like [`run`] for a program that's already in memory at `code`, such as one from
+/// [`crate::dos::load`].
+pub fn run_loaded(
+    code: &Range<usize>,
+    cpu: &mut Cpu,
+    memory: &mut Memory,
+    mut options: RunOptions,
+    formatter: &dyn InstructionFormatter,
+    trace: &mut impl Write,
+) -> Result<(), SimError> {
+    if options.recording.is_some() {
+        memory.start_journal();
+    }
+    let result = run_until_done(code, cpu, memory, &mut options, formatter, trace);
+    memory.stop_journal();
+    result
+}
+
+fn run_until_done(
+    code: &Range<usize>,
+    cpu: &mut Cpu,
+    memory: &mut Memory,
+    options: &mut RunOptions,
+    formatter: &dyn InstructionFormatter,
+    trace: &mut impl Write,
+) -> Result<(), SimError> {
+    let mut fresh = Interrupts::new();
+    let interrupts = options.interrupts.as_deref_mut().unwrap_or(&mut fresh);
+    let mut empty = PortBus::new();
+    let ports = options.ports.as_deref_mut().unwrap_or(&mut empty);
+    let mut fresh_cache = DecodeCache::new();
+    let cache = options.cache.as_deref_mut().unwrap_or(&mut fresh_cache);
+    while is_running(cpu, code) && interrupts.exit_code().is_none() {
+        let before = cpu.clone();
+        let processor = options.timing.unwrap_or_default();
+        let Stepped {
+            instruction,
+            clocks,
+        } = step(cpu, memory, interrupts, ports, cache, processor)?;
+        let mut changes = cpu.changes_since(&before);
+        if options.timing.is_some() {
+            let mut summary = format!("Clocks: +{} = {}", clocks.total(), cpu.cycles);
+            if let Some(breakdown) = clocks.breakdown() {
+                summary = format!("{summary} {breakdown}");
+            }
+            changes.insert(0, summary + " |");
+        }
+        if let Some(recording) = &mut options.recording {
+            recording.push(Delta::between(&before, cpu, memory.take_journal()));
+        }
+        write_trace_line(trace, formatter, &instruction, &changes)?;
+        if instruction.mnemonic == Mnemonic::Hlt {
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn write_trace_line(
+    trace: &mut impl Write,
+    formatter: &dyn InstructionFormatter,
+    instruction: &Instruction,
+    changes: &[String],
+) -> io::Result<()> {
+    write!(trace, "{}", formatter.format_instruction(instruction))?;
+    if !changes.is_empty() {
+        write!(trace, " {} {}", formatter.comment(), changes.join(" "))?;
+    }
+    writeln!(trace)
+}
@@ -0,0 +1,796 @@
+use super::*;
+use crate::decode_cache::DecodeCache;
+use crate::decoder::{
+    Register, RegisterByteOp, RegisterWordOp, SegmentRegister, decode_instruction,
+};
+use crate::flags::FlagSet;
+use crate::interrupts::Interrupts;
+use crate::memory::Memory;
+use crate::ports::{Access, PIT_PORTS, Pit, PortBus, Recorder, TIMER_VECTOR};
+use crate::syntax::Nasm;
+use crate::timing::Processor;
+use rstest::rstest;
+use std::fs;
+use std::path::Path;
+
+fn trace(data: &[u8]) -> (String, Cpu, Memory) {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    let mut out = vec![];
+    run(
+        data,
+        &mut cpu,
+        &mut memory,
+        RunOptions::default(),
+        &Nasm::default(),
+        &mut out,
+    )
+    .unwrap();
+    (String::from_utf8(out).unwrap(), cpu, memory)
+}
+
+#[test]
+fn test_traces_register_movs() {
+    let data =
+        fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0044_register_movs")).unwrap();
+    let (trace, cpu, _) = trace(&data);
+
+    assert_eq!(
+        trace,
+        "\
+mov ax, 1 ; ax:0x0->0x1 ip:0x0->0x3
+mov bx, 2 ; bx:0x0->0x2 ip:0x3->0x6
+mov cx, 3 ; cx:0x0->0x3 ip:0x6->0x9
+mov dx, 4 ; dx:0x0->0x4 ip:0x9->0xc
+mov sp, ax ; sp:0x0->0x1 ip:0xc->0xe
+mov bp, bx ; bp:0x0->0x2 ip:0xe->0x10
+mov si, cx ; si:0x0->0x3 ip:0x10->0x12
+mov di, dx ; di:0x0->0x4 ip:0x12->0x14
+mov dx, sp ; dx:0x4->0x1 ip:0x14->0x16
+mov cx, bp ; cx:0x3->0x2 ip:0x16->0x18
+mov bx, si ; bx:0x2->0x3 ip:0x18->0x1a
+mov ax, di ; ax:0x1->0x4 ip:0x1a->0x1c
+"
+    );
+    assert_eq!(cpu.word(RegisterWordOp::AX), 4);
+    assert_eq!(cpu.word(RegisterWordOp::DX), 1);
+}
+
+#[test]
+fn test_traces_flags_of_add_sub_cmp() {
+    let data =
+        fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0046_add_sub_cmp")).unwrap();
+    let (trace, cpu, _) = trace(&data);
+
+    assert_eq!(
+        trace,
+        "\
+mov bx, -4093 ; bx:0x0->0xf003 ip:0x0->0x3
+mov cx, 3841 ; cx:0x0->0xf01 ip:0x3->0x6
+sub bx, cx ; bx:0xf003->0xe102 ip:0x6->0x8 flags:->S
+mov sp, 998 ; sp:0x0->0x3e6 ip:0x8->0xb
+mov bp, 999 ; bp:0x0->0x3e7 ip:0xb->0xe
+cmp bp, sp ; ip:0xe->0x10 flags:S->
+add bp, 1027 ; bp:0x3e7->0x7ea ip:0x10->0x14
+sub bp, 2026 ; bp:0x7ea->0x0 ip:0x14->0x18 flags:->PZ
+"
+    );
+    assert_eq!(cpu.flags.to_string(), "PZ");
+}
+
+#[test]
+fn test_byte_arithmetic_leaves_the_other_half_alone() {
+    // mov ax, 0x127f / add al, 1 / inc ah / mov cl, 4 / shl al, cl / neg ah / test ah, ah / not al
+    let data = [
+        0xB8, 0x7F, 0x12, 0x04, 0x01, 0xFE, 0xC4, 0xB1, 0x04, 0xD2, 0xE0, 0xF6, 0xDC, 0x84, 0xE4,
+        0xF6, 0xD0,
+    ];
+    let (trace, cpu, _) = trace(&data);
+
+    assert_eq!(
+        trace,
+        "\
+mov ax, 4735 ; ax:0x0->0x127f ip:0x0->0x3
+add al, 1 ; ax:0x127f->0x1280 ip:0x3->0x5 flags:->ASO
+inc ah ; ax:0x1280->0x1380 ip:0x5->0x7 flags:ASO->
+mov cl, 4 ; cx:0x0->0x4 ip:0x7->0x9
+shl al, cl ; ax:0x1380->0x1300 ip:0x9->0xb flags:->PZ
+neg ah ; ax:0x1300->0xed00 ip:0xb->0xd flags:PZ->CPAS
+test ah, ah ; ip:0xd->0xf flags:CPAS->PS
+not al ; ax:0xed00->0xedff ip:0xf->0x11
+"
+    );
+    assert_eq!(cpu.word(RegisterWordOp::AX), 0xEDFF);
+}
+
+#[test]
+fn test_traces_memory_movs() {
+    let data =
+        fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0051_memory_mov")).unwrap();
+    let (trace, _, memory) = trace(&data);
+
+    assert_eq!(
+        trace,
+        "\
+mov word [1000], 1 ; ip:0x0->0x6
+mov word [1002], 2 ; ip:0x6->0xc
+mov word [1004], 3 ; ip:0xc->0x12
+mov word [1006], 4 ; ip:0x12->0x18
+mov bx, 1000 ; bx:0x0->0x3e8 ip:0x18->0x1b
+mov word [bx + 4], 10 ; ip:0x1b->0x20
+mov bx, [1000] ; bx:0x3e8->0x1 ip:0x20->0x24
+mov cx, [1002] ; cx:0x0->0x2 ip:0x24->0x28
+mov dx, [1004] ; dx:0x0->0xa ip:0x28->0x2c
+mov bp, [1006] ; bp:0x0->0x4 ip:0x2c->0x30
+"
+    );
+    assert_eq!(&memory.bytes()[1000..1008], [1, 0, 2, 0, 10, 0, 4, 0]);
+}
+
+#[rstest]
+#[case::data_segment(&[0xA1, 0x10, 0x00], 0x2000)]
+#[case::bp_uses_stack_segment(&[0x8B, 0x46, 0x10], 0x4000)]
+#[case::segment_override(&[0x26, 0x8B, 0x46, 0x10], 0x6000)]
+fn test_memory_operand_segment(#[case] code: &[u8], #[case] expected: u16) {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    for (segment, value) in [
+        (SegmentRegister::DS, 0x0100),
+        (SegmentRegister::SS, 0x0200),
+        (SegmentRegister::ES, 0x0300),
+    ] {
+        cpu.set_segment(segment, value);
+        memory.write_word(value, 0x10, value * 0x20);
+    }
+
+    // mov ax, [16] / mov ax, [bp + 16] / mov ax, es:[bp + 16]
+    let instruction = decode_instruction(code, 0).unwrap();
+    execute(&mut cpu, &mut memory, &instruction).unwrap();
+    assert_eq!(cpu.word(RegisterWordOp::AX), expected);
+}
+
+#[test]
+fn test_arithmetic_on_memory() {
+    // mov bx, 0x100 / mov byte [bx], 0xff / add byte [bx], 1 / inc word [bx]
+    let (trace, cpu, memory) = trace(&[
+        0xBB, 0x00, 0x01, 0xC6, 0x07, 0xFF, 0x80, 0x07, 0x01, 0xFF, 0x07,
+    ]);
+
+    assert_eq!(
+        trace,
+        "\
+mov bx, 256 ; bx:0x0->0x100 ip:0x0->0x3
+mov byte [bx], -1 ; ip:0x3->0x6
+add byte [bx], 1 ; ip:0x6->0x9 flags:->CPAZ
+inc word [bx] ; ip:0x9->0xb flags:CPAZ->C
+"
+    );
+    assert_eq!(memory.read_word(0, 0x100), 1);
+    assert_eq!(cpu.flags.to_string(), "C");
+}
+
+#[test]
+fn test_traces_conditional_jumps() {
+    let data =
+        fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0049_conditional_jumps"))
+            .unwrap();
+    let (trace, cpu, _) = trace(&data);
+
+    assert_eq!(
+        trace,
+        "\
+mov cx, 3 ; cx:0x0->0x3 ip:0x0->0x3
+mov bx, 1000 ; bx:0x0->0x3e8 ip:0x3->0x6
+add bx, 10 ; bx:0x3e8->0x3f2 ip:0x6->0x9 flags:->A
+sub cx, 1 ; cx:0x3->0x2 ip:0x9->0xc flags:A->
+jne $-6 ; ip:0xc->0x6
+add bx, 10 ; bx:0x3f2->0x3fc ip:0x6->0x9 flags:->P
+sub cx, 1 ; cx:0x2->0x1 ip:0x9->0xc flags:P->
+jne $-6 ; ip:0xc->0x6
+add bx, 10 ; bx:0x3fc->0x406 ip:0x6->0x9 flags:->PA
+sub cx, 1 ; cx:0x1->0x0 ip:0x9->0xc flags:PA->PZ
+jne $-6 ; ip:0xc->0xe
+"
+    );
+    assert_eq!(cpu.ip, 0x0E);
+}
+
+#[test]
+fn test_calls_loops_and_stops_at_hlt() {
+    let data = [
+        0xBC, 0x00, 0x01, // mov sp, 256
+        0xB9, 0x02, 0x00, // mov cx, 2
+        0xE8, 0x05, 0x00, // call bump
+        0xE2, 0xFB, // loop $-3
+        0xE3, 0x03, // jcxz done
+        0xF4, // hlt
+        0x40, // bump: inc ax
+        0xC3, // ret
+        0xF4, // done: hlt
+        0xB8, 0x63, 0x00, // mov ax, 99
+    ];
+    let (trace, cpu, memory) = trace(&data);
+
+    assert_eq!(
+        trace,
+        "\
+mov sp, 256 ; sp:0x0->0x100 ip:0x0->0x3
+mov cx, 2 ; cx:0x0->0x2 ip:0x3->0x6
+call $+8 ; sp:0x100->0xfe ip:0x6->0xe
+inc ax ; ax:0x0->0x1 ip:0xe->0xf
+ret ; sp:0xfe->0x100 ip:0xf->0x9
+loop $-3 ; cx:0x2->0x1 ip:0x9->0x6
+call $+8 ; sp:0x100->0xfe ip:0x6->0xe
+inc ax ; ax:0x1->0x2 ip:0xe->0xf
+ret ; sp:0xfe->0x100 ip:0xf->0x9
+loop $-3 ; cx:0x1->0x0 ip:0x9->0xb
+jcxz $+5 ; ip:0xb->0x10
+hlt ; ip:0x10->0x11
+"
+    );
+    assert_eq!(cpu.word(RegisterWordOp::AX), 2);
+    // the return address is still on the stack
+    assert_eq!(memory.read_word(0, 0xFE), 0x09);
+}
+
+#[rstest]
+#[case::je_taken(0x74, &[Flag::Zero], true)]
+#[case::je_not_taken(0x74, &[], false)]
+#[case::jb_on_carry(0x72, &[Flag::Carry], true)]
+#[case::ja_needs_no_zero(0x77, &[Flag::Zero], false)]
+#[case::jl_sign_not_overflow(0x7C, &[Flag::Sign], true)]
+#[case::jl_sign_and_overflow(0x7C, &[Flag::Sign, Flag::Overflow], false)]
+#[case::jle_on_zero(0x7E, &[Flag::Zero], true)]
+#[case::jg(0x7F, &[Flag::Overflow, Flag::Sign], true)]
+#[case::jp(0x7A, &[Flag::Parity], true)]
+#[case::jno(0x71, &[Flag::Overflow], false)]
+fn test_conditional_jumps(#[case] opcode: u8, #[case] flags: &[Flag], #[case] taken: bool) {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    cpu.flags = FlagSet::of(flags);
+    memory.load(0, 0, &[opcode, 0x10]);
+
+    step(
+        &mut cpu,
+        &mut memory,
+        &mut Interrupts::new(),
+        &mut PortBus::new(),
+        &mut DecodeCache::new(),
+        Processor::I8086,
+    )
+    .unwrap();
+    assert_eq!(cpu.ip, if taken { 0x12 } else { 0x02 });
+}
+
+#[rstest]
+#[case::loopz_while_equal(0xE1, &[Flag::Zero], 2, 0x00)]
+#[case::loopz_stops_on_not_equal(0xE1, &[], 2, 0x02)]
+#[case::loopnz_stops_on_equal(0xE0, &[Flag::Zero], 2, 0x02)]
+#[case::loop_stops_at_zero(0xE2, &[], 1, 0x02)]
+#[case::loop_wraps_from_zero(0xE2, &[], 0, 0x00)]
+fn test_loops(
+    #[case] opcode: u8,
+    #[case] flags: &[Flag],
+    #[case] cx: u16,
+    #[case] expected_ip: u16,
+) {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    cpu.flags = FlagSet::of(flags);
+    cpu.set_word(RegisterWordOp::CX, cx);
+    memory.load(0, 0, &[opcode, 0xFE]);
+
+    step(
+        &mut cpu,
+        &mut memory,
+        &mut Interrupts::new(),
+        &mut PortBus::new(),
+        &mut DecodeCache::new(),
+        Processor::I8086,
+    )
+    .unwrap();
+    assert_eq!(cpu.ip, expected_ip);
+    assert_eq!(cpu.word(RegisterWordOp::CX), cx.wrapping_sub(1));
+}
+
+#[test]
+fn test_far_call_and_return() {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    cpu.set_segment(SegmentRegister::CS, 0x1000);
+    cpu.set_word(RegisterWordOp::SP, 0x100);
+    // call 0x2000:0x0010
+    memory.load(0x1000, 0, &[0x9A, 0x10, 0x00, 0x00, 0x20]);
+    // push ax / pop bx / retf 2
+    memory.load(0x2000, 0x10, &[0x50, 0x5B, 0xCA, 0x02, 0x00]);
+    cpu.set_word(RegisterWordOp::AX, 0x1234);
+
+    for _ in 0..4 {
+        step(
+            &mut cpu,
+            &mut memory,
+            &mut Interrupts::new(),
+            &mut PortBus::new(),
+            &mut DecodeCache::new(),
+            Processor::I8086,
+        )
+        .unwrap();
+    }
+    assert_eq!(cpu.segment(SegmentRegister::CS), 0x1000);
+    assert_eq!(cpu.ip, 0x05);
+    assert_eq!(cpu.word(RegisterWordOp::BX), 0x1234);
+    assert_eq!(cpu.word(RegisterWordOp::SP), 0x102);
+}
+
+#[test]
+fn test_push_sp_pushes_the_decremented_value() {
+    // mov sp, 0x100 / push sp / pop ax
+    let (_, cpu, memory) = trace(&[0xBC, 0x00, 0x01, 0x54, 0x58]);
+
+    assert_eq!(cpu.word(RegisterWordOp::AX), 0xFE);
+    assert_eq!(cpu.word(RegisterWordOp::SP), 0x100);
+    assert_eq!(memory.read_word(0, 0xFE), 0xFE);
+}
+
+#[test]
+fn test_pushf_sets_the_bits_that_arent_flags() {
+    // the 8086 check: pushf / pop ax / and ax, 0xf000 / cmp ax, 0xf000
+    let (_, cpu, _) = trace(&[0x9C, 0x58, 0x25, 0x00, 0xF0, 0x3D, 0x00, 0xF0]);
+
+    assert_eq!(cpu.word(RegisterWordOp::AX), 0xF000);
+    assert!(cpu.flags.contains(Flag::Zero));
+}
+
+#[test]
+fn test_fetches_from_the_code_segment() {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    cpu.set_segment(SegmentRegister::CS, 0x0100);
+    cpu.ip = 0x0020;
+    // mov ax, 1 / jmp past the end / hlt
+    let mut out = vec![];
+    run(
+        &[0xB8, 0x01, 0x00, 0xEB, 0x01, 0xF4],
+        &mut cpu,
+        &mut memory,
+        RunOptions::default(),
+        &Nasm::default(),
+        &mut out,
+    )
+    .unwrap();
+
+    assert_eq!(memory.bytes()[0x1020], 0xB8);
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "\
+mov ax, 1 ; ax:0x0->0x1 ip:0x20->0x23
+jmp $+3 ; ip:0x23->0x26
+"
+    );
+}
+
+fn timed(data: &[u8], processor: Processor) -> (String, Cpu) {
+    let mut cpu = Cpu::new();
+    let mut out = vec![];
+    run(
+        data,
+        &mut cpu,
+        &mut Memory::new(),
+        RunOptions {
+            timing: Some(processor),
+            ..RunOptions::default()
+        },
+        &Nasm::default(),
+        &mut out,
+    )
+    .unwrap();
+    (String::from_utf8(out).unwrap(), cpu)
+}
+
+#[test]
+fn test_traces_estimated_cycles() {
+    let data =
+        fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("listing_0056_estimating_cycles"))
+            .unwrap();
+    let (trace, cpu) = timed(&data, Processor::I8086);
+
+    assert_eq!(
+        trace,
+        "\
+mov bx, 1000 ; Clocks: +4 = 4 | bx:0x0->0x3e8 ip:0x0->0x3
+mov bp, 2000 ; Clocks: +4 = 8 | bp:0x0->0x7d0 ip:0x3->0x6
+mov si, 3000 ; Clocks: +4 = 12 | si:0x0->0xbb8 ip:0x6->0x9
+mov di, 4000 ; Clocks: +4 = 16 | di:0x0->0xfa0 ip:0x9->0xc
+mov cx, bx ; Clocks: +2 = 18 | cx:0x0->0x3e8 ip:0xc->0xe
+mov dx, 12 ; Clocks: +4 = 22 | dx:0x0->0xc ip:0xe->0x11
+mov dx, [1000] ; Clocks: +14 = 36 (8 + 6ea) | dx:0xc->0x0 ip:0x11->0x15
+mov cx, [bx] ; Clocks: +13 = 49 (8 + 5ea) | cx:0x3e8->0x0 ip:0x15->0x17
+mov cx, [bp] ; Clocks: +17 = 66 (8 + 9ea) | ip:0x17->0x1a
+mov [si], cx ; Clocks: +14 = 80 (9 + 5ea) | ip:0x1a->0x1c
+mov [di], cx ; Clocks: +14 = 94 (9 + 5ea) | ip:0x1c->0x1e
+mov cx, [bx + 1000] ; Clocks: +17 = 111 (8 + 9ea) | ip:0x1e->0x22
+mov cx, [bp + 1000] ; Clocks: +17 = 128 (8 + 9ea) | ip:0x22->0x26
+mov [si + 1000], cx ; Clocks: +18 = 146 (9 + 9ea) | ip:0x26->0x2a
+mov [di + 1000], cx ; Clocks: +18 = 164 (9 + 9ea) | ip:0x2a->0x2e
+add cx, dx ; Clocks: +3 = 167 | ip:0x2e->0x30 flags:->PZ
+add [di + 1000], cx ; Clocks: +25 = 192 (16 + 9ea) | ip:0x30->0x34
+add dx, 50 ; Clocks: +4 = 196 | dx:0x0->0x32 ip:0x34->0x37 flags:PZ->
+"
+    );
+    assert_eq!(cpu.cycles, 196);
+    // the 8088 pays 4 more for each of the 11 words moved through memory, two of them by the
+    // `add` that reads and writes back
+    assert_eq!(timed(&data, Processor::I8088).1.cycles, 196 + 4 * 11);
+}
+
+#[test]
+fn test_cycles_depend_on_what_ran() {
+    // mov bx, 0x101 / mov cl, 2 / shl word [bx], cl / jcxz $+2 / mov [bx], ax
+    let data = [
+        0xBB, 0x01, 0x01, 0xB1, 0x02, 0xD3, 0x27, 0xE3, 0x00, 0x89, 0x07,
+    ];
+    let (trace, cpu) = timed(&data, Processor::I8086);
+
+    assert_eq!(
+        trace,
+        "\
+mov bx, 257 ; Clocks: +4 = 4 | bx:0x0->0x101 ip:0x0->0x3
+mov cl, 2 ; Clocks: +4 = 8 | cx:0x0->0x2 ip:0x3->0x5
+shl word [bx], cl ; Clocks: +41 = 49 (28 + 5ea + 8p) | ip:0x5->0x7 flags:->PZ
+jcxz $+2 ; Clocks: +6 = 55 | ip:0x7->0x9
+mov [bx], ax ; Clocks: +18 = 73 (9 + 5ea + 4p) | ip:0x9->0xb
+"
+    );
+    assert_eq!(cpu.cycles, 73);
+}
+
+#[test]
+fn test_byte_and_segment_movs() {
+    // mov ax, 0x2222 / mov ss, ax / mov al, 0x11 / mov bh, 0x33 / mov ah, bh / mov sp, ss
+    let data = [
+        0xB8, 0x22, 0x22, 0x8E, 0xD0, 0xB0, 0x11, 0xB7, 0x33, 0x88, 0xFC, 0x8C, 0xD4,
+    ];
+    let (trace, cpu, _) = trace(&data);
+
+    assert_eq!(
+        trace,
+        "\
+mov ax, 8738 ; ax:0x0->0x2222 ip:0x0->0x3
+mov ss, ax ; ss:0x0->0x2222 ip:0x3->0x5
+mov al, 17 ; ax:0x2222->0x2211 ip:0x5->0x7
+mov bh, 51 ; bx:0x0->0x3300 ip:0x7->0x9
+mov ah, bh ; ax:0x2211->0x3311 ip:0x9->0xb
+mov sp, ss ; sp:0x0->0x2222 ip:0xb->0xd
+"
+    );
+    assert_eq!(cpu.register(Register::Byte(RegisterByteOp::AH)), 0x33);
+    assert_eq!(cpu.segment(SegmentRegister::SS), 0x2222);
+}
+
+#[test]
+fn test_unchanged_registers_only_move_ip() {
+    // mov cx, cx
+    assert_eq!(trace(&[0x89, 0xC9]).0, "mov cx, cx ; ip:0x0->0x2\n");
+}
+
+#[test]
+fn test_negative_byte_immediate_only_sets_the_low_byte() {
+    let mut cpu = Cpu::new();
+    // mov cl, -12
+    execute(
+        &mut cpu,
+        &mut Memory::new(),
+        &decode_instruction(&[0xB1, 0xF4], 0).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(cpu.word(RegisterWordOp::CX), 0x00F4);
+}
+
+#[test]
+fn test_stops_at_unsupported_instructions() {
+    let mut cpu = Cpu::new();
+    let mut out = vec![];
+    // mov ax, 1 / daa
+    let error = run(
+        &[0xB8, 0x01, 0x00, 0x27],
+        &mut cpu,
+        &mut Memory::new(),
+        RunOptions::default(),
+        &Nasm::default(),
+        &mut out,
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "unable to simulate `daa` at offset 0x0003"
+    );
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "mov ax, 1 ; ax:0x0->0x1 ip:0x0->0x3\n"
+    );
+    // left pointing at the instruction that failed
+    assert_eq!(cpu.ip, 3);
+}
+
+#[test]
+fn test_reports_decode_errors() {
+    let mut cpu = Cpu::new();
+    let error = run(
+        &[0x90, 0x0F],
+        &mut cpu,
+        &mut Memory::new(),
+        RunOptions::default(),
+        &Nasm::default(),
+        &mut vec![],
+    )
+    .unwrap_err();
+    assert!(matches!(error, SimError::Decode(_)));
+    // where it was fetched from, not where it is in the fetch window
+    assert_eq!(
+        error.to_string(),
+        "unable to determine instruction for byte 00001111 at offset 0x0001"
+    );
+}
+
+#[test]
+fn test_in_and_out_go_to_the_port_bus() {
+    let recorder = Recorder::new();
+    recorder.reply(&[0x12, 0x78, 0x56]);
+    let mut ports = PortBus::new();
+    ports.attach(0x0000..=0xFFFF, recorder.clone());
+    let mut cpu = Cpu::new();
+    let mut out = vec![];
+    // in al, 0x60 / mov dx, 0x3f8 / in ax, dx / out dx, al / out 0x61, ax
+    run(
+        &[0xE4, 0x60, 0xBA, 0xF8, 0x03, 0xED, 0xEE, 0xE7, 0x61],
+        &mut cpu,
+        &mut Memory::new(),
+        RunOptions {
+            timing: Some(Processor::I8086),
+            ports: Some(&mut ports),
+            ..RunOptions::default()
+        },
+        &Nasm::default(),
+        &mut out,
+    )
+    .unwrap();
+
+    assert_eq!(cpu.word(RegisterWordOp::AX), 0x5678);
+    let accesses: Vec<_> = recorder
+        .accesses()
+        .into_iter()
+        .map(|access| match access {
+            Access::Read { port, value, .. } => ('r', port, value),
+            Access::Write { port, value, .. } => ('w', port, value),
+        })
+        .collect();
+    assert_eq!(
+        accesses,
+        [
+            ('r', 0x60, 0x12),
+            ('r', 0x3F8, 0x78),
+            ('r', 0x3F9, 0x56),
+            ('w', 0x3F8, 0x78),
+            ('w', 0x61, 0x78),
+            ('w', 0x62, 0x56),
+        ]
+    );
+    // devices see the clock as it was before the instruction
+    let Access::Write { now, .. } = recorder.accesses()[3] else {
+        unreachable!()
+    };
+    assert_eq!(now, 10 + 4 + 8);
+}
+
+#[test]
+fn test_reads_the_timer_through_ports() {
+    let mut ports = PortBus::new();
+    ports.attach(PIT_PORTS, Pit::new());
+    let mut cpu = Cpu::new();
+    // mov al, 0x34 / out 0x43, al / mov al, 100 / out 0x40, al / xor al, al / out 0x40, al
+    // in al, 0x40 / mov bl, al / in al, 0x40 / mov bh, al
+    let code = [
+        0xB0, 0x34, 0xE6, 0x43, 0xB0, 0x64, 0xE6, 0x40, 0x30, 0xC0, 0xE6, 0x40, 0xE4, 0x40, 0x88,
+        0xC3, 0xE4, 0x40, 0x88, 0xC7,
+    ];
+    run(
+        &code,
+        &mut cpu,
+        &mut Memory::new(),
+        RunOptions {
+            timing: Some(Processor::I8086),
+            ports: Some(&mut ports),
+            ..RunOptions::default()
+        },
+        &Nasm::default(),
+        &mut vec![],
+    )
+    .unwrap();
+
+    // counting from 100 started with the last out, the low byte was read 10 clocks later and
+    // the high byte 22, 2 and 5 ticks in
+    assert_eq!(cpu.word(RegisterWordOp::BX), 98);
+}
+
+/// A machine running from 0100:0000, clear of the interrupt vector table, with a stack at
+/// 0200:0100 and `int number` going to `handler` in the code segment.
+fn with_vector(number: u8, handler: u16) -> (Cpu, Memory) {
+    let mut cpu = Cpu::new();
+    cpu.set_segment(SegmentRegister::CS, 0x100);
+    cpu.set_segment(SegmentRegister::SS, 0x200);
+    cpu.set_word(RegisterWordOp::SP, 0x100);
+    let mut memory = Memory::new();
+    memory.write_word(0, u16::from(number) * 4, handler);
+    memory.write_word(0, u16::from(number) * 4 + 2, 0x100);
+    (cpu, memory)
+}
+
+#[test]
+fn test_int_goes_through_the_vector_table_and_iret_comes_back() {
+    let (mut cpu, mut memory) = with_vector(0x40, 4);
+    let mut out = vec![];
+    // sti / int 0x40 / hlt / mov ax, 7 / iret
+    run(
+        &[0xFB, 0xCD, 0x40, 0xF4, 0xB8, 0x07, 0x00, 0xCF],
+        &mut cpu,
+        &mut memory,
+        RunOptions::default(),
+        &Nasm::default(),
+        &mut out,
+    )
+    .unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "\
+sti ; ip:0x0->0x1 flags:->I
+int 64 ; sp:0x100->0xfa ip:0x1->0x4 flags:I->
+mov ax, 7 ; ax:0x0->0x7 ip:0x4->0x7
+iret ; sp:0xfa->0x100 ip:0x7->0x3 flags:->I
+hlt ; ip:0x3->0x4
+"
+    );
+    assert_eq!(memory.read_word(0x200, 0xFC), 0x100);
+}
+
+#[test]
+fn test_trap_flag_single_steps() {
+    let (mut cpu, mut memory) = with_vector(SINGLE_STEP, 0x11);
+    let code = [
+        0x9C, 0x58, 0x0D, 0x00, 0x01, 0x50,
+        0x9D, // pushf / pop ax / or ax, 0x100 / push ax / popf
+        0x90, 0x90, // nop / nop
+        0x9C, 0x58, 0x25, 0xFF, 0xFE, 0x50,
+        0x9D, // pushf / pop ax / and ax, 0xfeff / push ax / popf
+        0xF4, // hlt
+        0x43, 0xCF, // inc bx / iret
+    ];
+    run(
+        &code,
+        &mut cpu,
+        &mut memory,
+        RunOptions::default(),
+        &Nasm::default(),
+        &mut vec![],
+    )
+    .unwrap();
+
+    // every instruction from the one after the first popf up to the second popf
+    assert_eq!(cpu.word(RegisterWordOp::BX), 7);
+    assert!(!cpu.flags.contains(Flag::Trap));
+    assert_eq!(cpu.ip, 0x11);
+}
+
+#[test]
+fn test_entering_a_trap_is_not_a_taken_branch() {
+    let (mut cpu, mut memory) = with_vector(SINGLE_STEP, 0x10);
+    cpu.flags = FlagSet::of(&[Flag::Trap]);
+    cpu.set_word(RegisterWordOp::CX, 1);
+    // jcxz $+4, which doesn't jump with CX set, and iret at the handler
+    memory.load(0x100, 0, &[0xE3, 0x02]);
+    memory.load(0x100, 0x10, &[0xCF]);
+
+    let stepped = step(
+        &mut cpu,
+        &mut memory,
+        &mut Interrupts::new(),
+        &mut PortBus::new(),
+        &mut DecodeCache::new(),
+        Processor::I8086,
+    )
+    .unwrap();
+
+    assert_eq!(cpu.ip, 0x10);
+    // 6 for the jcxz that fell through and 50 to enter the trap
+    assert_eq!(stepped.clocks.total(), 56);
+    assert_eq!(cpu.cycles, 56);
+}
+
+#[test]
+fn test_traps_nothing_handles_take_no_time() {
+    let (mut cpu, mut memory) = (Cpu::new(), Memory::new());
+    cpu.flags = FlagSet::of(&[Flag::Trap]);
+    // nop, with an empty vector table
+    memory.load(0, 0, &[0x90]);
+
+    let stepped = step(
+        &mut cpu,
+        &mut memory,
+        &mut Interrupts::new(),
+        &mut PortBus::new(),
+        &mut DecodeCache::new(),
+        Processor::I8086,
+    )
+    .unwrap();
+
+    assert_eq!(cpu.ip, 1);
+    assert_eq!(cpu.word(RegisterWordOp::SP), 0);
+    assert_eq!(stepped.clocks.total(), 3);
+    assert_eq!(cpu.cycles, 3);
+}
+
+/// Sets the timer to interrupt every 400 clocks, then counts down CX from 100 with `loop`, with
+/// the timer handler counting in BX.
+fn timer_program(sti: bool, timing: Option<Processor>) -> Cpu {
+    let (mut cpu, mut memory) = with_vector(TIMER_VECTOR, 0x15);
+    let mut ports = PortBus::new();
+    ports.attach(PIT_PORTS, Pit::new());
+    let code = [
+        0xB0,
+        0x34,
+        0xE6,
+        0x43, // mov al, 0x34 / out 0x43, al
+        0xB0,
+        0x64,
+        0xE6,
+        0x40,
+        0x30,
+        0xC0,
+        0xE6,
+        0x40,                          // reload 100
+        if sti { 0xFB } else { 0xFA }, // sti or cli
+        0xB9,
+        0x64,
+        0x00, // mov cx, 100
+        0xE2,
+        0xFE, // loop $
+        0xF4, // hlt
+        0x90,
+        0x90, // padding
+        0x43,
+        0xCF, // inc bx / iret
+    ];
+    run(
+        &code,
+        &mut cpu,
+        &mut memory,
+        RunOptions {
+            timing,
+            ports: Some(&mut ports),
+            ..RunOptions::default()
+        },
+        &Nasm::default(),
+        &mut vec![],
+    )
+    .unwrap();
+    cpu
+}
+
+#[rstest]
+// the loop takes 99 * 17 + 5 clocks and each handler 61 to enter and 3 + 24 to run, 5 periods
+// in all
+#[case::enabled(true, Some(Processor::I8086), 5)]
+#[case::disabled(false, Some(Processor::I8086), 0)]
+// clocks are counted without showing them too
+#[case::untimed(true, None, 5)]
+fn test_timer_interrupts_at_cycle_intervals(
+    #[case] sti: bool,
+    #[case] timing: Option<Processor>,
+    #[case] expected: u16,
+) {
+    let cpu = timer_program(sti, timing);
+
+    assert_eq!(cpu.word(RegisterWordOp::CX), 0);
+    assert_eq!(cpu.word(RegisterWordOp::BX), expected);
+}
@@ -0,0 +1,174 @@
+use crate::cpu::Cpu;
+use crate::decoder::{RegisterWordOp, SegmentRegister};
+use crate::flags::FlagSet;
+use crate::memory::{self, Memory};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+#[cfg(test)]
+mod snapshot_tests;
+
+/// Start of every snapshot file.
+const MAGIC: &[u8; 8] = b"8086snp\0";
+const VERSION: u8 = 1;
+
+/// Zero bytes between two runs of memory that are written as one run rather than two, a run's
+/// header is 8 bytes.
+const MERGE_GAP: usize = 8;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The file doesn't start with the snapshot header.
+    NotASnapshot,
+    UnsupportedVersion(u8),
+    /// The file was cut short or holds something no machine state could.
+    Corrupt(&'static str),
+    Io(io::Error),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotASnapshot => write!(f, "not a machine snapshot"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {version}")
+            }
+            Self::Corrupt(reason) => write!(f, "the snapshot is corrupt: {reason}"),
+            Self::Io(_) => write!(f, "failed to read the snapshot"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Writes the whole machine state. After the header come the general registers in encoding
+/// order, the segment registers (ES, CS, SS, DS), IP and the flags as little-endian words, then
+/// the cycle count as a little-endian `u64`. Memory follows as runs of bytes that aren't zero,
+/// each a little-endian `u32` physical address and `u32` length before the bytes, ending with a
+/// run of length zero.
+pub fn write_snapshot(out: &mut impl Write, cpu: &Cpu, memory: &Memory) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&[VERSION])?;
+    let registers = RegisterWordOp::ALL.map(|register| cpu.word(register));
+    let segments = SegmentRegister::ALL.map(|segment| cpu.segment(segment));
+    for word in registers
+        .into_iter()
+        .chain(segments)
+        .chain([cpu.ip, cpu.flags.bits()])
+    {
+        out.write_all(&word.to_le_bytes())?;
+    }
+    out.write_all(&cpu.cycles.to_le_bytes())?;
+
+    for run in runs(memory.bytes()) {
+        out.write_all(&(run.start as u32).to_le_bytes())?;
+        out.write_all(&(run.len() as u32).to_le_bytes())?;
+        out.write_all(&memory.bytes()[run])?;
+    }
+    out.write_all(&[0; 8])
+}
+
+/// The stretches of `bytes` that aren't zero, joined up when only a few zeros separate them.
+fn runs(bytes: &[u8]) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = vec![];
+    for (address, _) in bytes.iter().enumerate().filter(|&(_, &byte)| byte != 0) {
+        match runs.last_mut() {
+            Some(run) if address - run.end <= MERGE_GAP => run.end = address + 1,
+            _ => runs.push(address..address + 1),
+        }
+    }
+    runs
+}
+
+/// Reads what [`write_snapshot`] wrote.
+pub fn read_snapshot(input: &mut impl Read) -> Result<(Cpu, Memory), SnapshotError> {
+    let mut header = [0; MAGIC.len() + 1];
+    read_exact(input, &mut header).map_err(|error| match error {
+        SnapshotError::Corrupt(_) => SnapshotError::NotASnapshot,
+        error => error,
+    })?;
+    if header[..MAGIC.len()] != MAGIC[..] {
+        return Err(SnapshotError::NotASnapshot);
+    }
+    if header[MAGIC.len()] != VERSION {
+        return Err(SnapshotError::UnsupportedVersion(header[MAGIC.len()]));
+    }
+
+    let mut cpu = Cpu::new();
+    for register in RegisterWordOp::ALL {
+        cpu.set_word(register, read_word(input)?);
+    }
+    for segment in SegmentRegister::ALL {
+        cpu.set_segment(segment, read_word(input)?);
+    }
+    cpu.ip = read_word(input)?;
+    let flags = read_word(input)?;
+    cpu.flags = FlagSet::from_bits(flags);
+    if cpu.flags.bits() != flags {
+        return Err(SnapshotError::Corrupt("bits that aren't flags are set"));
+    }
+    let mut cycles = [0; 8];
+    read_exact(input, &mut cycles)?;
+    cpu.cycles = u64::from_le_bytes(cycles);
+
+    let mut memory = Memory::new();
+    loop {
+        let start = read_u32(input)? as usize;
+        let length = read_u32(input)? as usize;
+        if length == 0 {
+            break;
+        }
+        if start
+            .checked_add(length)
+            .is_none_or(|end| end > memory::SIZE)
+        {
+            return Err(SnapshotError::Corrupt("memory runs past the end of memory"));
+        }
+        let mut bytes = vec![0; length];
+        read_exact(input, &mut bytes)?;
+        for (address, byte) in (start..).zip(bytes) {
+            memory.store(address, byte);
+        }
+    }
+    if input.read(&mut [0])? != 0 {
+        return Err(SnapshotError::Corrupt(
+            "there's more after the end of memory",
+        ));
+    }
+    Ok((cpu, memory))
+}
+
+fn read_exact(input: &mut impl Read, buffer: &mut [u8]) -> Result<(), SnapshotError> {
+    input
+        .read_exact(buffer)
+        .map_err(|error| match error.kind() {
+            io::ErrorKind::UnexpectedEof => SnapshotError::Corrupt("it ends partway through"),
+            _ => error.into(),
+        })
+}
+
+fn read_word(input: &mut impl Read) -> Result<u16, SnapshotError> {
+    let mut word = [0; 2];
+    read_exact(input, &mut word)?;
+    Ok(u16::from_le_bytes(word))
+}
+
+fn read_u32(input: &mut impl Read) -> Result<u32, SnapshotError> {
+    let mut bytes = [0; 4];
+    read_exact(input, &mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
@@ -0,0 +1,105 @@
+use super::*;
+use crate::flags::Flag;
+use rstest::rstest;
+
+fn machine() -> (Cpu, Memory) {
+    let mut cpu = Cpu::new();
+    cpu.set_word(RegisterWordOp::AX, 0x1234);
+    cpu.set_word(RegisterWordOp::DI, 0xFFFF);
+    cpu.set_segment(SegmentRegister::SS, 0x9000);
+    cpu.ip = 0x0100;
+    cpu.flags = FlagSet::of(&[Flag::Zero, Flag::Interrupt]);
+    cpu.cycles = 1 << 40;
+
+    let mut memory = Memory::new();
+    memory.load(0, 0x100, &[0xB8, 0x01, 0x00, 0xF4]);
+    memory.store(0x10B, 7);
+    memory.store(0xF_FFFF, 0xFF);
+    (cpu, memory)
+}
+
+fn snapshot(cpu: &Cpu, memory: &Memory) -> Vec<u8> {
+    let mut file = vec![];
+    write_snapshot(&mut file, cpu, memory).unwrap();
+    file
+}
+
+#[test]
+fn test_round_trip() {
+    let (cpu, memory) = machine();
+    let file = snapshot(&cpu, &memory);
+
+    let (restored_cpu, restored_memory) = read_snapshot(&mut file.as_slice()).unwrap();
+    assert_eq!(restored_cpu, cpu);
+    assert_eq!(restored_memory, memory);
+}
+
+#[test]
+fn test_file_layout() {
+    let (cpu, memory) = machine();
+    let file = snapshot(&cpu, &memory);
+
+    let registers: [u8; 36] = [
+        0x34, 0x12, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, // ax..di
+        0, 0, 0, 0, 0, 0x90, 0, 0, // es, cs, ss, ds
+        0x00, 0x01, 0x40, 0x02, // ip, flags
+        0, 0, 0, 0, 0, 1, 0, 0, // cycles
+    ];
+    let memory_runs: &[u8] = &[
+        // the program and the 7 after it are close enough to be one run
+        0x00, 0x01, 0, 0, 12, 0, 0, 0, 0xB8, 0x01, 0x00, 0xF4, 0, 0, 0, 0, 0, 0, 0, 7, 0xFF, 0xFF,
+        0x0F, 0, 1, 0, 0, 0, 0xFF, // the last byte
+        0, 0, 0, 0, 0, 0, 0, 0, // end
+    ];
+    assert_eq!(
+        file,
+        [b"8086snp\0\x01".as_slice(), &registers, memory_runs].concat()
+    );
+}
+
+#[test]
+fn test_reset_state_is_small() {
+    let file = snapshot(&Cpu::new(), &Memory::new());
+    assert_eq!(file.len(), 9 + 36 + 8);
+    assert_eq!(
+        read_snapshot(&mut file.as_slice()).unwrap(),
+        (Cpu::new(), Memory::new())
+    );
+}
+
+#[rstest]
+#[case::empty(0, None, "not a machine snapshot")]
+#[case::wrong_magic(3, Some(b'!'), "not a machine snapshot")]
+#[case::newer_version(8, Some(2), "unsupported snapshot version 2")]
+#[case::not_a_flag(9 + 27, Some(0x80), "the snapshot is corrupt: bits that aren't flags are set")]
+#[case::run_past_memory(
+    9 + 36 + 2,
+    Some(0x10),
+    "the snapshot is corrupt: memory runs past the end of memory"
+)]
+fn test_rejects_bad_files(
+    #[case] index: usize,
+    #[case] replacement: Option<u8>,
+    #[case] expected: &str,
+) {
+    let (cpu, memory) = machine();
+    let mut file = snapshot(&cpu, &memory);
+    match replacement {
+        Some(byte) => file[index] = byte,
+        None => file.truncate(index),
+    }
+
+    let error = read_snapshot(&mut file.as_slice()).unwrap_err();
+    assert_eq!(error.to_string(), expected);
+}
+
+#[rstest]
+#[case::cut_short(-1, "the snapshot is corrupt: it ends partway through")]
+#[case::trailing_bytes(1, "the snapshot is corrupt: there's more after the end of memory")]
+fn test_rejects_the_wrong_length(#[case] change: isize, #[case] expected: &str) {
+    let mut file = snapshot(&Cpu::new(), &Memory::new());
+    file.resize(file.len().checked_add_signed(change).unwrap(), 0);
+
+    let error = read_snapshot(&mut file.as_slice()).unwrap_err();
+    assert_eq!(error.to_string(), expected);
+}
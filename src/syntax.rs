@@ -0,0 +1,489 @@
+use crate::decoder::{EffectiveAddress, Flow, Instruction, Operand, Width};
+use std::fmt::{self, Write};
+
+#[cfg(test)]
+mod syntax_tests;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LetterCase {
+    #[default]
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberBase {
+    #[default]
+    Decimal,
+    Hex,
+}
+
+/// Knobs shared by every syntax.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Case of mnemonics, registers, size keywords and hex digits.
+    pub case: LetterCase,
+    /// Decimal immediates are signed for the operand width, hex ones are the raw bits.
+    pub immediates: NumberBase,
+    /// Applies to both signed displacements and direct addresses.
+    pub displacements: NumberBase,
+}
+
+/// How a syntax marks hex numbers, `0x1f` or `1fh`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HexStyle {
+    Prefix,
+    Suffix,
+}
+
+impl FormatOptions {
+    fn keyword(&self, word: &str) -> String {
+        match self.case {
+            LetterCase::Lower => word.to_owned(),
+            LetterCase::Upper => word.to_uppercase(),
+        }
+    }
+
+    fn number(&self, value: u16, base: NumberBase, style: HexStyle) -> String {
+        if base == NumberBase::Decimal {
+            return value.to_string();
+        }
+
+        let digits = match self.case {
+            LetterCase::Lower => format!("{value:x}"),
+            LetterCase::Upper => format!("{value:X}"),
+        };
+        match style {
+            HexStyle::Prefix => format!("0x{digits}"),
+            // MASM needs a leading digit so `0ffh` isn't read as a symbol
+            HexStyle::Suffix if digits.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                format!("0{digits}{}", self.keyword("h"))
+            }
+            HexStyle::Suffix => format!("{digits}{}", self.keyword("h")),
+        }
+    }
+
+    fn immediate(&self, instruction: &Instruction, value: u16, style: HexStyle) -> String {
+        if instruction.mnemonic.has_unsigned_immediate() {
+            return self.number(value, self.immediates, style);
+        }
+
+        match (self.immediates, instruction.width) {
+            (NumberBase::Decimal, Width::Byte) => (value as u8 as i8).to_string(),
+            (NumberBase::Decimal, Width::Word) => (value as i16).to_string(),
+            (NumberBase::Hex, Width::Byte) => self.number(value & 0xFF, NumberBase::Hex, style),
+            (NumberBase::Hex, Width::Word) => self.number(value, NumberBase::Hex, style),
+        }
+    }
+
+    /// A relative jump target as a distance from the start of the instruction, e.g. `$-4`, so
+    /// the text reassembles to the same bytes without needing labels.
+    fn relative(
+        &self,
+        instruction: &Instruction,
+        displacement: i16,
+        here: &str,
+        style: HexStyle,
+    ) -> String {
+        let distance = i32::from(displacement) + instruction.length as i32;
+        let sign = if distance < 0 { '-' } else { '+' };
+        let magnitude = self.number(distance.unsigned_abs() as u16, self.displacements, style);
+        format!("{here}{sign}{magnitude}")
+    }
+
+    fn far_pointer(&self, segment: u16, offset: u16, style: HexStyle) -> (String, String) {
+        (
+            self.number(segment, self.displacements, style),
+            self.number(offset, self.displacements, style),
+        )
+    }
+
+    /// Sign and magnitude of a displacement, `None` when there is nothing to add.
+    fn displacement(&self, displacement: i16, style: HexStyle) -> Option<(char, String)> {
+        let sign = if displacement < 0 { '-' } else { '+' };
+        (displacement != 0).then(|| {
+            (
+                sign,
+                self.number(displacement.unsigned_abs(), self.displacements, style),
+            )
+        })
+    }
+
+    /// `name` with the `lock`/`rep` prefixes in front and, for string instructions, the operand
+    /// size after it, e.g. `rep movsb`.
+    fn mnemonic(&self, instruction: &Instruction, name: &str) -> String {
+        let mut text = String::new();
+        // an override with no memory operand to attach to, e.g. `es movsb` or `cs xlat`
+        let has_memory = instruction
+            .operands()
+            .any(|operand| matches!(operand, Operand::Memory(_)));
+        if let Some(segment) = instruction.prefixes.segment.filter(|_| !has_memory) {
+            text += segment.name();
+            text += " ";
+        }
+        if instruction.prefixes.lock {
+            text += "lock ";
+        }
+        if let Some(repeat) = instruction.prefixes.repeat {
+            text += repeat.name();
+            text += " ";
+        }
+        text += name;
+        if instruction.mnemonic.is_string() {
+            text += match instruction.width {
+                Width::Byte => "b",
+                Width::Word => "w",
+            };
+        }
+        self.keyword(&text)
+    }
+
+    fn address(&self, address: u16, style: HexStyle) -> String {
+        self.number(address, self.displacements, style)
+    }
+
+    /// Names of the registers making up the base of an effective address.
+    fn base_registers(&self, address: &EffectiveAddress) -> Vec<String> {
+        address.base.map_or_else(Vec::new, |base| {
+            let (first, second) = base.registers();
+            [Some(first), second]
+                .into_iter()
+                .flatten()
+                .map(|register| self.keyword(register.name()))
+                .collect()
+        })
+    }
+}
+
+/// Renders decoded instructions as assembly text in a particular syntax.
+pub trait InstructionFormatter {
+    /// Directive that starts a source file for a 16-bit target, e.g. `bits 16`.
+    fn header(&self) -> String;
+
+    /// Starts a line comment.
+    fn comment(&self) -> &'static str {
+        ";"
+    }
+
+    fn write_instruction(&self, out: &mut dyn Write, instruction: &Instruction) -> fmt::Result;
+
+    fn format_instruction(&self, instruction: &Instruction) -> String {
+        let mut text = String::new();
+        self.write_instruction(&mut text, instruction)
+            .expect("writing to a String can't fail");
+        text
+    }
+
+    /// Bytes that aren't code, e.g. `db 0x12, 0x34`.
+    fn format_data(&self, bytes: &[u8]) -> String;
+}
+
+fn data_directive(
+    options: &FormatOptions,
+    directive: &str,
+    bytes: &[u8],
+    style: HexStyle,
+) -> String {
+    let values: Vec<_> = bytes
+        .iter()
+        .map(|&byte| options.number(u16::from(byte), options.immediates, style))
+        .collect();
+    format!("{} {}", options.keyword(directive), values.join(", "))
+}
+
+fn write_operands(
+    out: &mut dyn Write,
+    operands: impl Iterator<Item = String>,
+    separator: &str,
+) -> fmt::Result {
+    for (index, operand) in operands.enumerate() {
+        out.write_str(if index == 0 { " " } else { separator })?;
+        out.write_str(&operand)?;
+    }
+    Ok(())
+}
+
+/// NASM syntax, e.g. `mov word [bp + di + 4], 7`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Nasm {
+    pub options: FormatOptions,
+}
+
+impl Nasm {
+    fn operand(&self, instruction: &Instruction, operand: Operand) -> String {
+        let options = &self.options;
+        match operand {
+            Operand::Register(register) => options.keyword(register.name()),
+            Operand::Segment(segment) => options.keyword(segment.name()),
+            Operand::Immediate(value) => options.immediate(instruction, value, HexStyle::Prefix),
+            Operand::Relative(displacement) => {
+                options.relative(instruction, displacement, "$", HexStyle::Prefix)
+            }
+            Operand::Far { segment, offset } => {
+                let (segment, offset) = options.far_pointer(segment, offset, HexStyle::Prefix);
+                format!("{segment}:{offset}")
+            }
+            Operand::Memory(address) => {
+                let mut text = String::new();
+                if instruction.mnemonic.is_far() {
+                    text += &options.keyword("far ");
+                } else if instruction.needs_width_hint() {
+                    text += &options.keyword(instruction.width.name());
+                    text += " ";
+                }
+                text += "[";
+                if let Some(segment) = instruction.prefixes.segment {
+                    text += &options.keyword(segment.name());
+                    text += ":";
+                }
+                match address.base {
+                    None => text += &options.address(address.displacement as u16, HexStyle::Prefix),
+                    Some(_) => {
+                        text += &options.base_registers(&address).join(" + ");
+                        if let Some((sign, magnitude)) =
+                            options.displacement(address.displacement, HexStyle::Prefix)
+                        {
+                            text += &format!(" {sign} {magnitude}");
+                        }
+                    }
+                }
+                text + "]"
+            }
+        }
+    }
+}
+
+impl InstructionFormatter for Nasm {
+    fn header(&self) -> String {
+        self.options.keyword("bits 16")
+    }
+
+    fn format_data(&self, bytes: &[u8]) -> String {
+        data_directive(&self.options, "db", bytes, HexStyle::Prefix)
+    }
+
+    fn write_instruction(&self, out: &mut dyn Write, instruction: &Instruction) -> fmt::Result {
+        out.write_str(
+            &self
+                .options
+                .mnemonic(instruction, instruction.mnemonic.name()),
+        )?;
+        write_operands(
+            out,
+            instruction
+                .operands()
+                .map(|operand| self.operand(instruction, operand)),
+            ", ",
+        )
+    }
+}
+
+/// MASM syntax, e.g. `mov word ptr [bp+di+4], 7`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Masm {
+    pub options: FormatOptions,
+}
+
+impl Masm {
+    fn operand(&self, instruction: &Instruction, operand: Operand) -> String {
+        let options = &self.options;
+        match operand {
+            Operand::Register(register) => options.keyword(register.name()),
+            Operand::Segment(segment) => options.keyword(segment.name()),
+            Operand::Immediate(value) => options.immediate(instruction, value, HexStyle::Suffix),
+            Operand::Relative(displacement) => {
+                options.relative(instruction, displacement, "$", HexStyle::Suffix)
+            }
+            Operand::Far { segment, offset } => {
+                let (segment, offset) = options.far_pointer(segment, offset, HexStyle::Suffix);
+                format!("{segment}:{offset}")
+            }
+            Operand::Memory(address) => {
+                let mut text = String::new();
+                if instruction.mnemonic.is_far() {
+                    text += &options.keyword("dword ptr ");
+                } else if instruction.needs_width_hint() {
+                    text += &options.keyword(&format!("{} ptr ", instruction.width.name()));
+                }
+                match (instruction.prefixes.segment, address.base) {
+                    (Some(segment), _) => text += &options.keyword(&format!("{}:", segment.name())),
+                    // a bare `[1234]` is an immediate to MASM, direct addresses need a segment
+                    (None, None) => text += &options.keyword("ds:"),
+                    (None, Some(_)) => {}
+                }
+                text += "[";
+                match address.base {
+                    None => text += &options.address(address.displacement as u16, HexStyle::Suffix),
+                    Some(_) => {
+                        text += &options.base_registers(&address).join("+");
+                        if let Some((sign, magnitude)) =
+                            options.displacement(address.displacement, HexStyle::Suffix)
+                        {
+                            text += &format!("{sign}{magnitude}");
+                        }
+                    }
+                }
+                text + "]"
+            }
+        }
+    }
+}
+
+impl InstructionFormatter for Masm {
+    fn header(&self) -> String {
+        self.options.keyword(".8086")
+    }
+
+    fn format_data(&self, bytes: &[u8]) -> String {
+        data_directive(&self.options, "db", bytes, HexStyle::Suffix)
+    }
+
+    fn write_instruction(&self, out: &mut dyn Write, instruction: &Instruction) -> fmt::Result {
+        out.write_str(
+            &self
+                .options
+                .mnemonic(instruction, instruction.mnemonic.name()),
+        )?;
+        write_operands(
+            out,
+            instruction
+                .operands()
+                .map(|operand| self.operand(instruction, operand)),
+            ", ",
+        )
+    }
+}
+
+/// AT&T syntax, e.g. `movw $7, 4(%bp,%di)`, with the source operand first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Att {
+    pub options: FormatOptions,
+}
+
+impl Att {
+    fn register(&self, name: &str) -> String {
+        format!("%{}", self.options.keyword(name))
+    }
+
+    fn operand(&self, instruction: &Instruction, operand: Operand) -> String {
+        let options = &self.options;
+        match operand {
+            Operand::Register(register) => self.register(register.name()),
+            Operand::Segment(segment) => self.register(segment.name()),
+            Operand::Immediate(value) => {
+                format!(
+                    "${}",
+                    options.immediate(instruction, value, HexStyle::Prefix)
+                )
+            }
+            Operand::Relative(displacement) => {
+                options.relative(instruction, displacement, ".", HexStyle::Prefix)
+            }
+            Operand::Far { segment, offset } => {
+                let (segment, offset) = options.far_pointer(segment, offset, HexStyle::Prefix);
+                format!("${segment}, ${offset}")
+            }
+            Operand::Memory(address) => {
+                let mut text = String::new();
+                if let Some(segment) = instruction.prefixes.segment {
+                    text += &self.register(segment.name());
+                    text += ":";
+                }
+                match address.base {
+                    None => text += &options.address(address.displacement as u16, HexStyle::Prefix),
+                    Some(_) => {
+                        if let Some((sign, magnitude)) =
+                            options.displacement(address.displacement, HexStyle::Prefix)
+                        {
+                            if sign == '-' {
+                                text.push(sign);
+                            }
+                            text += &magnitude;
+                        }
+                        let registers: Vec<_> = options
+                            .base_registers(&address)
+                            .iter()
+                            .map(|register| format!("%{register}"))
+                            .collect();
+                        text += &format!("({})", registers.join(","));
+                    }
+                }
+                text
+            }
+        }
+    }
+}
+
+impl InstructionFormatter for Att {
+    fn header(&self) -> String {
+        self.options.keyword(".code16")
+    }
+
+    fn comment(&self) -> &'static str {
+        "#"
+    }
+
+    fn format_data(&self, bytes: &[u8]) -> String {
+        data_directive(&self.options, ".byte", bytes, HexStyle::Prefix)
+    }
+
+    fn write_instruction(&self, out: &mut dyn Write, instruction: &Instruction) -> fmt::Result {
+        let mnemonic = instruction.mnemonic;
+        let flow = mnemonic.flow();
+
+        // far transfers are `ljmp`, `lcall` and `lret`
+        let name = match mnemonic.name() {
+            "retf" => "lret",
+            name if mnemonic.is_far() => &format!("l{name}"),
+            name => name,
+        };
+        out.write_str(&self.options.mnemonic(instruction, name))?;
+
+        // control transfers take their size from the mode, not a suffix
+        if flow == Flow::Sequential && instruction.operands().next().is_some() {
+            let suffix = match instruction.width {
+                Width::Byte => "b",
+                Width::Word => "w",
+            };
+            out.write_str(&self.options.keyword(suffix))?;
+        }
+
+        let indirect = matches!(flow, Flow::Jump | Flow::Call);
+        let mut operands: Vec<_> = instruction
+            .operands()
+            .map(|operand| match operand {
+                Operand::Register(_) | Operand::Memory(_) if indirect => {
+                    format!("*{}", self.operand(instruction, operand))
+                }
+                _ => self.operand(instruction, operand),
+            })
+            .collect();
+        operands.reverse();
+        write_operands(out, operands.into_iter(), ", ")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Syntax {
+    #[default]
+    Nasm,
+    Masm,
+    Att,
+}
+
+impl Syntax {
+    pub fn formatter(self, options: FormatOptions) -> Box<dyn InstructionFormatter> {
+        match self {
+            Self::Nasm => Box::new(Nasm { options }),
+            Self::Masm => Box::new(Masm { options }),
+            Self::Att => Box::new(Att { options }),
+        }
+    }
+}
+
+/// NASM syntax with the default options.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Nasm::default().write_instruction(f, self)
+    }
+}
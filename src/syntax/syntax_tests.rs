@@ -0,0 +1,171 @@
+use super::*;
+use crate::decoder::decode_instruction;
+use rstest::rstest;
+
+fn format(formatter: &dyn InstructionFormatter, bytes: &[u8]) -> String {
+    formatter.format_instruction(&decode_instruction(bytes, 0).unwrap())
+}
+
+const HEX: FormatOptions = FormatOptions {
+    case: LetterCase::Lower,
+    immediates: NumberBase::Hex,
+    displacements: NumberBase::Hex,
+};
+
+const UPPER: FormatOptions = FormatOptions {
+    case: LetterCase::Upper,
+    immediates: NumberBase::Decimal,
+    displacements: NumberBase::Decimal,
+};
+
+#[rstest]
+#[case::registers(&[0x89, 0xD9], "mov cx, bx")]
+#[case::negative_immediate(&[0xB5, 0xF4], "mov ch, -12")]
+#[case::memory(&[0x8B, 0x41, 0xDB], "mov ax, [bx + di - 37]")]
+#[case::width_hint(&[0xC6, 0x03, 0x07], "mov byte [bp + di], 7")]
+#[case::direct_address(&[0xA1, 0xFB, 0x09], "mov ax, [2555]")]
+#[case::segment_override(&[0x26, 0x8A, 0x07], "mov al, [es:bx]")]
+#[case::segment_register(&[0x8C, 0xDB], "mov bx, ds")]
+fn test_nasm(#[case] bytes: &[u8], #[case] expected: &str) {
+    assert_eq!(format(&Nasm::default(), bytes), expected);
+}
+
+#[rstest]
+#[case::registers(&[0x89, 0xD9], "mov cx, bx")]
+#[case::memory(&[0x8B, 0x41, 0xDB], "mov ax, [bx+di-37]")]
+#[case::width_hint(&[0xC7, 0x00, 0x07, 0x00], "mov word ptr [bx+si], 7")]
+#[case::direct_address(&[0xA1, 0xFB, 0x09], "mov ax, ds:[2555]")]
+#[case::segment_override(&[0x26, 0x8A, 0x07], "mov al, es:[bx]")]
+#[case::relative_jump(&[0x75, 0xFC], "jne $-2")]
+#[case::indirect_far_jump(&[0xFF, 0x2F], "jmp dword ptr [bx]")]
+#[case::far_jump(&[0xEA, 0x78, 0x56, 0x34, 0x12], "jmp 4660:22136")]
+fn test_masm(#[case] bytes: &[u8], #[case] expected: &str) {
+    assert_eq!(format(&Masm::default(), bytes), expected);
+}
+
+#[rstest]
+#[case::registers(&[0x89, 0xD9], "movw %bx, %cx")]
+#[case::byte_immediate(&[0xB1, 0x0C], "movb $12, %cl")]
+#[case::memory(&[0x8B, 0x41, 0xDB], "movw -37(%bx,%di), %ax")]
+#[case::no_displacement(&[0x8A, 0x00], "movb (%bx,%si), %al")]
+#[case::immediate_to_memory(&[0xC6, 0x03, 0x07], "movb $7, (%bp,%di)")]
+#[case::direct_address(&[0xA1, 0xFB, 0x09], "movw 2555, %ax")]
+#[case::segment_override(&[0x26, 0x8A, 0x07], "movb %es:(%bx), %al")]
+#[case::relative_jump(&[0x75, 0xFC], "jne .-2")]
+#[case::indirect_jump(&[0xFF, 0xE3], "jmp *%bx")]
+#[case::indirect_call(&[0xFF, 0x17], "call *(%bx)")]
+#[case::far_jump(&[0xEA, 0x78, 0x56, 0x34, 0x12], "ljmp $4660, $22136")]
+#[case::indirect_far_call(&[0xFF, 0x1F], "lcall *(%bx)")]
+#[case::far_return(&[0xCB], "lret")]
+#[case::interrupt(&[0xCD, 0x21], "int $33")]
+#[case::inc(&[0xFE, 0x07], "incb (%bx)")]
+fn test_att(#[case] bytes: &[u8], #[case] expected: &str) {
+    assert_eq!(format(&Att::default(), bytes), expected);
+}
+
+#[rstest]
+#[case::nasm(Syntax::Nasm, "mov word [bx + di - 0x25], 0xfff4")]
+#[case::masm(Syntax::Masm, "mov word ptr [bx+di-25h], 0fff4h")]
+#[case::att(Syntax::Att, "movw $0xfff4, -0x25(%bx,%di)")]
+fn test_hex_numbers(#[case] syntax: Syntax, #[case] expected: &str) {
+    let formatter = syntax.formatter(HEX);
+    assert_eq!(
+        format(formatter.as_ref(), &[0xC7, 0x41, 0xDB, 0xF4, 0xFF]),
+        expected
+    );
+}
+
+#[rstest]
+#[case::nasm(Syntax::Nasm, "MOV BYTE [ES:BP + SI + 4], 7")]
+#[case::masm(Syntax::Masm, "MOV BYTE PTR ES:[BP+SI+4], 7")]
+#[case::att(Syntax::Att, "MOVB $7, %ES:4(%BP,%SI)")]
+fn test_upper_case(#[case] syntax: Syntax, #[case] expected: &str) {
+    let formatter = syntax.formatter(UPPER);
+    assert_eq!(
+        format(formatter.as_ref(), &[0x26, 0xC6, 0x42, 0x04, 0x07]),
+        expected
+    );
+}
+
+#[test]
+fn test_upper_case_hex_digits() {
+    let formatter = Masm {
+        options: FormatOptions {
+            case: LetterCase::Upper,
+            ..HEX
+        },
+    };
+    assert_eq!(format(&formatter, &[0xA1, 0xFB, 0x09]), "MOV AX, DS:[9FBH]");
+}
+
+#[rstest]
+#[case::nasm(Syntax::Nasm, "bits 16", ";")]
+#[case::masm(Syntax::Masm, ".8086", ";")]
+#[case::att(Syntax::Att, ".code16", "#")]
+fn test_headers(#[case] syntax: Syntax, #[case] header: &str, #[case] comment: &str) {
+    let formatter = syntax.formatter(FormatOptions::default());
+    assert_eq!(formatter.header(), header);
+    assert_eq!(formatter.comment(), comment);
+}
+
+#[test]
+fn test_display_is_default_nasm() {
+    let instruction = decode_instruction(&[0x8A, 0x80, 0x87, 0x13], 0).unwrap();
+    assert_eq!(
+        instruction.to_string(),
+        Nasm::default().format_instruction(&instruction)
+    );
+}
+
+#[rstest]
+#[case::nasm(Syntax::Nasm, "int 0x21")]
+#[case::masm(Syntax::Masm, "int 21h")]
+#[case::att(Syntax::Att, "int $0x21")]
+fn test_unsigned_immediates(#[case] syntax: Syntax, #[case] expected: &str) {
+    let formatter = syntax.formatter(HEX);
+    assert_eq!(format(formatter.as_ref(), &[0xCD, 0x21]), expected);
+}
+
+#[rstest]
+#[case::nasm(Syntax::Nasm, "jmp 0x1234:0x5678", "jne $-0x2")]
+#[case::masm(Syntax::Masm, "jmp 1234h:5678h", "jne $-2h")]
+#[case::att(Syntax::Att, "ljmp $0x1234, $0x5678", "jne .-0x2")]
+fn test_hex_jumps(#[case] syntax: Syntax, #[case] far: &str, #[case] relative: &str) {
+    let formatter = syntax.formatter(HEX);
+    assert_eq!(
+        format(formatter.as_ref(), &[0xEA, 0x78, 0x56, 0x34, 0x12]),
+        far
+    );
+    assert_eq!(format(formatter.as_ref(), &[0x75, 0xFC]), relative);
+}
+
+#[rstest]
+#[case::nasm(Syntax::Nasm, FormatOptions::default(), "db 18, 255")]
+#[case::nasm_hex(Syntax::Nasm, HEX, "db 0x12, 0xff")]
+#[case::masm_hex(Syntax::Masm, HEX, "db 12h, 0ffh")]
+#[case::att(Syntax::Att, HEX, ".byte 0x12, 0xff")]
+#[case::upper(Syntax::Nasm, UPPER, "DB 18, 255")]
+fn test_data(#[case] syntax: Syntax, #[case] options: FormatOptions, #[case] expected: &str) {
+    assert_eq!(
+        syntax.formatter(options).format_data(&[0x12, 0xFF]),
+        expected
+    );
+}
+
+#[rstest]
+#[case::nasm(Syntax::Nasm, ["rep movsw", "es lodsb", "lock xchg ax, [bx]", "shl byte [bx], cl"])]
+#[case::masm(Syntax::Masm, ["rep movsw", "es lodsb", "lock xchg ax, [bx]", "shl byte ptr [bx], cl"])]
+#[case::att(Syntax::Att, ["rep movsw", "es lodsb", "lock xchgw (%bx), %ax", "shlb %cl, (%bx)"])]
+fn test_prefixes_and_string_instructions(#[case] syntax: Syntax, #[case] expected: [&str; 4]) {
+    let formatter = syntax.formatter(FormatOptions::default());
+    let programs: [&[u8]; 4] = [
+        &[0xF3, 0xA5],
+        &[0x26, 0xAC],
+        &[0xF0, 0x87, 0x07],
+        &[0xD2, 0x27],
+    ];
+
+    for (bytes, expected) in programs.into_iter().zip(expected) {
+        assert_eq!(format(formatter.as_ref(), bytes), expected);
+    }
+}
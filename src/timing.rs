@@ -0,0 +1,335 @@
+use crate::decoder::{
+    AddressBase, EffectiveAddress, Instruction, Mnemonic, Operand, Register, RegisterWordOp, Width,
+};
+use std::fmt;
+
+#[cfg(test)]
+mod timing_tests;
+
+/// Which chip the clocks are counted for. They run the same instructions in the same number of
+/// clocks, except the 8088 only has an 8-bit bus, so every word it moves costs an extra 4.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Processor {
+    #[default]
+    I8086,
+    I8088,
+}
+
+/// What happened when the instruction ran, the parts of its timing the encoding alone can't
+/// tell. The default is what a static estimate assumes: branches not taken, no repetitions and
+/// everything at even addresses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Execution {
+    /// The jump, loop or conditional return went to its target.
+    pub taken: bool,
+    /// Bits a shift or rotate by CL moved, or iterations of a `rep` string instruction.
+    pub count: u16,
+    /// Offset of the memory operand, odd ones cost the 8086 an extra bus cycle per word.
+    pub address: Option<u16>,
+    /// SP before the instruction, for the same penalty on pushes and pops.
+    pub sp: u16,
+    /// The single-step trap was entered after the instruction.
+    pub trapped: bool,
+    /// A device's interrupt was entered after the instruction.
+    pub interrupted: bool,
+}
+
+/// Clocks one instruction takes, split the way the manual's tables add them up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Clocks {
+    /// The figure from the instruction table, including any per-bit or per-repetition part.
+    pub base: u32,
+    /// Effective address calculation.
+    pub ea: u32,
+    /// 4 per word transferred at an odd address, or per word at all on the 8088.
+    pub penalty: u32,
+}
+
+impl Clocks {
+    pub fn total(&self) -> u32 {
+        self.base + self.ea + self.penalty
+    }
+
+    /// How the total adds up, the way the course's reference traces print it, e.g. `(10 + 9ea)`
+    /// or `(14 + 9ea + 4p)`. `None` when it's only the base.
+    pub fn breakdown(&self) -> Option<String> {
+        if self.ea == 0 && self.penalty == 0 {
+            return None;
+        }
+        let mut text = format!("({}", self.base);
+        if self.ea != 0 {
+            text += &format!(" + {}ea", self.ea);
+        }
+        if self.penalty != 0 {
+            text += &format!(" + {}p", self.penalty);
+        }
+        Some(text + ")")
+    }
+}
+
+/// The total followed by its [breakdown](Clocks::breakdown), e.g. `19 (10 + 9ea)`.
+impl fmt::Display for Clocks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.total())?;
+        match self.breakdown() {
+            Some(breakdown) => write!(f, " {breakdown}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Clocks to work out a memory operand's address, plus 2 for a segment override. A
+/// displacement that's encoded costs the same whatever it is, zero included.
+pub fn effective_address_clocks(address: &EffectiveAddress, segment_override: bool) -> u32 {
+    let displacement = address.has_displacement;
+    let clocks = match address.base {
+        None => 6,
+        Some(AddressBase::Bp) => 9,
+        Some(AddressBase::Bx | AddressBase::Si | AddressBase::Di) if displacement => 9,
+        Some(AddressBase::Bx | AddressBase::Si | AddressBase::Di) => 5,
+        Some(AddressBase::BpDi | AddressBase::BxSi) if displacement => 11,
+        Some(AddressBase::BpDi | AddressBase::BxSi) => 7,
+        Some(AddressBase::BpSi | AddressBase::BxDi) if displacement => 12,
+        Some(AddressBase::BpSi | AddressBase::BxDi) => 8,
+    };
+    clocks + if segment_override { 2 } else { 0 }
+}
+
+/// How an operand is reached, which is what the timing tables are organised by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Accumulator,
+    Register,
+    Segment,
+    Memory,
+    Immediate,
+    None,
+}
+
+fn kind(operand: Option<Operand>) -> Kind {
+    match operand {
+        Some(Operand::Register(register)) if register.word() == RegisterWordOp::AX => {
+            Kind::Accumulator
+        }
+        Some(Operand::Register(_)) => Kind::Register,
+        Some(Operand::Segment(_)) => Kind::Segment,
+        Some(Operand::Memory(_)) => Kind::Memory,
+        Some(Operand::Immediate(_) | Operand::Relative(_) | Operand::Far { .. }) => Kind::Immediate,
+        None => Kind::None,
+    }
+}
+
+/// The table entry for an instruction: its base clocks, how many times it reads or writes its
+/// memory operand and how many words it pushes or pops.
+struct Entry {
+    base: u32,
+    transfers: u32,
+    stack: u32,
+    /// Whether the memory operand's address has to be worked out, rather than being in the
+    /// instruction.
+    ea: bool,
+}
+
+fn entry(base: u32, transfers: u32) -> Entry {
+    stack(base, transfers, 0)
+}
+
+fn stack(base: u32, transfers: u32, stack: u32) -> Entry {
+    Entry {
+        base,
+        transfers,
+        stack,
+        ea: true,
+    }
+}
+
+/// Base clocks from the instruction timings in the 8086 family user's manual. Where the manual
+/// gives a range (multiplication and division depend on the operands) the lowest figure is used.
+fn table(instruction: &Instruction, execution: &Execution) -> Entry {
+    use Kind::{Accumulator as A, Immediate as I, Memory as M, Register as R};
+    use Mnemonic::*;
+    const DX: Operand = Operand::Register(Register::Word(RegisterWordOp::DX));
+
+    let destination = kind(instruction.destination);
+    let source = kind(instruction.source);
+    let register = !matches!(destination, M);
+    let byte = instruction.width == Width::Byte;
+    let count = u32::from(execution.count);
+    let taken = execution.taken;
+    let branch = |taken_clocks, not_taken| entry(if taken { taken_clocks } else { not_taken }, 0);
+    let direct = |operand: Option<Operand>| {
+        matches!(
+            operand,
+            Some(Operand::Memory(EffectiveAddress { base: None, .. }))
+        )
+    };
+
+    match (instruction.mnemonic, destination, source) {
+        // assemblers always pick the short accumulator encoding for direct addresses
+        (Mov, A, M) if direct(instruction.source) => Entry {
+            ea: false,
+            ..entry(10, 1)
+        },
+        (Mov, M, A) if direct(instruction.destination) => Entry {
+            ea: false,
+            ..entry(10, 1)
+        },
+        (Mov, M, I) => entry(10, 1),
+        (Mov, M, _) => entry(9, 1),
+        (Mov, _, M) => entry(8, 1),
+        (Mov, _, I) => entry(4, 0),
+        (Mov, _, _) => entry(2, 0),
+
+        (Push, M, _) => stack(16, 1, 1),
+        (Push, R | A, _) => stack(11, 0, 1),
+        (Push, _, _) | (Pushf, _, _) => stack(10, 0, 1),
+        (Pop, M, _) => stack(17, 1, 1),
+        (Pop, _, _) | (Popf, _, _) => stack(8, 0, 1),
+        (Xchg, M, _) | (Xchg, _, M) => entry(17, 2),
+        (Xchg, A, _) | (Xchg, _, A) => entry(3, 0),
+        (Xchg, _, _) => entry(4, 0),
+        // the port is in DX rather than the instruction
+        (In | Out, _, _) if instruction.operands().any(|operand| operand == DX) => entry(8, 0),
+        (In | Out, _, _) => entry(10, 0),
+        (Xlat, _, _) => entry(11, 1),
+        (Lea, _, _) => entry(2, 0),
+        (Lds | Les, _, _) => entry(16, 2),
+        (Lahf | Sahf, _, _) => entry(4, 0),
+
+        (Cmp, M, I) => entry(10, 1),
+        (Cmp, M, _) | (Cmp, _, M) => entry(9, 1),
+        (Test, M, I) => entry(11, 1),
+        (Test, M, _) | (Test, _, M) => entry(9, 1),
+        (Test, A, I) => entry(4, 0),
+        (Test, _, I) => entry(5, 0),
+        (Add | Adc | Sub | Sbb | And | Or | Xor, M, I) => entry(17, 2),
+        (Add | Adc | Sub | Sbb | And | Or | Xor, M, _) => entry(16, 2),
+        (Add | Adc | Sub | Sbb | And | Or | Xor, _, M) => entry(9, 1),
+        (Add | Adc | Sub | Sbb | And | Or | Xor | Cmp, _, I) => entry(4, 0),
+        (Add | Adc | Sub | Sbb | And | Or | Xor | Cmp | Test, _, _) => entry(3, 0),
+
+        (Inc | Dec, M, _) => entry(15, 2),
+        (Inc | Dec, _, _) if byte => entry(3, 0),
+        (Inc | Dec, _, _) => entry(2, 0),
+        (Neg | Not, M, _) => entry(16, 2),
+        (Neg | Not, _, _) => entry(3, 0),
+
+        (Aaa | Aas | Daa | Das, _, _) => entry(4, 0),
+        (Aad, _, _) => entry(60, 0),
+        (Aam, _, _) => entry(83, 0),
+        (Cbw, _, _) => entry(2, 0),
+        (Cwd, _, _) => entry(5, 0),
+        (Mul | Imul | Div | Idiv, _, _) => {
+            let (byte_clocks, word_clocks) = match instruction.mnemonic {
+                Mul => (70, 118),
+                Imul => (80, 128),
+                Div => (80, 144),
+                _ => (101, 165),
+            };
+            let clocks = if byte { byte_clocks } else { word_clocks };
+            match register {
+                true => entry(clocks, 0),
+                false => entry(clocks + 6, 1),
+            }
+        }
+
+        (mnemonic, _, I) if mnemonic.is_shift() => match register {
+            true => entry(2, 0),
+            false => entry(15, 2),
+        },
+        (mnemonic, _, _) if mnemonic.is_shift() => match register {
+            true => entry(8 + 4 * count, 0),
+            false => entry(20 + 4 * count, 2),
+        },
+
+        (mnemonic, _, _) if mnemonic.is_string() => {
+            let (single, repeated, transfers) = match mnemonic {
+                Movs => (18, 17, 2),
+                Cmps => (22, 22, 2),
+                Scas => (15, 15, 1),
+                Lods => (12, 13, 1),
+                _ => (11, 10, 1),
+            };
+            match instruction.prefixes.repeat {
+                Some(_) => entry(9 + repeated * count, transfers * count),
+                None => entry(single, transfers),
+            }
+        }
+
+        (Call, I, _) => stack(19, 0, 1),
+        (Call, M, _) => stack(21, 1, 1),
+        (Call, _, _) => stack(16, 0, 1),
+        (CallFar, M, _) => stack(37, 2, 2),
+        (CallFar, _, _) => stack(28, 0, 2),
+        (Jmp, M, _) => entry(18, 1),
+        (Jmp, I, _) => entry(15, 0),
+        (Jmp, _, _) => entry(11, 0),
+        (JmpFar, M, _) => entry(24, 2),
+        (JmpFar, _, _) => entry(15, 0),
+        (Ret, I, _) => stack(12, 0, 1),
+        (Ret, _, _) => stack(8, 0, 1),
+        (Retf, I, _) => stack(17, 0, 2),
+        (Retf, _, _) => stack(18, 0, 2),
+        (Jcxz, _, _) => branch(18, 6),
+        (Loop, _, _) => branch(17, 5),
+        (Loopz, _, _) => branch(18, 6),
+        (Loopnz, _, _) => branch(19, 5),
+        (
+            Jo | Jno | Jb | Jnb | Je | Jne | Jbe | Ja | Js | Jns | Jp | Jnp | Jl | Jnl | Jle | Jg,
+            _,
+            _,
+        ) => branch(16, 4),
+        (Int, _, _) => stack(51, 0, 3),
+        (Int3, _, _) => stack(52, 0, 3),
+        (Into, _, _) if taken => stack(53, 0, 3),
+        (Into, _, _) => entry(4, 0),
+        (Iret, _, _) => stack(24, 0, 3),
+
+        (Wait | Nop, _, _) => entry(3, 0),
+        // flag changes and `hlt`
+        _ => entry(2, 0),
+    }
+}
+
+/// Clocks the 8086 takes to enter the single-step trap and a maskable interrupt, which push the
+/// flags, CS and IP like `int` does.
+const TRAP_ENTRY: u32 = 50;
+const INTERRUPT_ENTRY: u32 = 61;
+
+/// Estimates how long `instruction` takes on `processor`, along with entering the interrupts
+/// that came after it.
+pub fn estimate(instruction: &Instruction, processor: Processor, execution: &Execution) -> Clocks {
+    let mut entry = table(instruction, execution);
+    for (entered, clocks) in [
+        (execution.trapped, TRAP_ENTRY),
+        (execution.interrupted, INTERRUPT_ENTRY),
+    ] {
+        if entered {
+            entry.base += clocks;
+            entry.stack += 3;
+        }
+    }
+    let memory = instruction.operands().find_map(|operand| match operand {
+        Operand::Memory(address) => Some(address),
+        _ => None,
+    });
+    let ea = memory.filter(|_| entry.ea).map_or(0, |address| {
+        effective_address_clocks(&address, instruction.prefixes.segment.is_some())
+    });
+
+    let word = instruction.width == Width::Word;
+    let operand_words = if word { entry.transfers } else { 0 };
+    let penalised = |words: u32, offset: Option<u16>| match processor {
+        Processor::I8088 => words,
+        Processor::I8086 if offset.is_some_and(|offset| offset % 2 == 1) => words,
+        Processor::I8086 => 0,
+    };
+    let penalty =
+        penalised(operand_words, execution.address) + penalised(entry.stack, Some(execution.sp));
+
+    Clocks {
+        base: entry.base,
+        ea,
+        penalty: 4 * penalty,
+    }
+}
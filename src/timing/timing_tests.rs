@@ -0,0 +1,108 @@
+use super::*;
+use crate::decoder::decode_instruction;
+use rstest::rstest;
+
+#[rstest]
+#[case::direct(None, Some(1000), 6)]
+#[case::base(Some(AddressBase::Bx), None, 5)]
+#[case::bp_always_has_a_displacement(Some(AddressBase::Bp), Some(0), 9)]
+#[case::base_and_displacement(Some(AddressBase::Si), Some(-4), 9)]
+#[case::base_and_zero_displacement(Some(AddressBase::Di), Some(0), 9)]
+#[case::fast_pair(Some(AddressBase::BpDi), None, 7)]
+#[case::slow_pair(Some(AddressBase::BxDi), None, 8)]
+#[case::fast_pair_and_displacement(Some(AddressBase::BxSi), Some(8), 11)]
+#[case::slow_pair_and_displacement(Some(AddressBase::BpSi), Some(8), 12)]
+fn test_effective_address_clocks(
+    #[case] base: Option<AddressBase>,
+    #[case] displacement: Option<i16>,
+    #[case] expected: u32,
+) {
+    let address = EffectiveAddress {
+        base,
+        displacement: displacement.unwrap_or(0),
+        has_displacement: displacement.is_some(),
+    };
+    assert_eq!(effective_address_clocks(&address, false), expected);
+    assert_eq!(effective_address_clocks(&address, true), expected + 2);
+}
+
+fn clocks(code: &[u8], processor: Processor, execution: Execution) -> String {
+    estimate(&decode_instruction(code, 0).unwrap(), processor, &execution).to_string()
+}
+
+#[rstest]
+// mov cx, bx
+#[case::register_move(&[0x89, 0xD9], "2")]
+// mov dx, 12
+#[case::immediate_move(&[0xBA, 0x0C, 0x00], "4")]
+// mov ax, [1000]
+#[case::accumulator_move(&[0xA1, 0xE8, 0x03], "10")]
+// mov [bp + 1000], cx
+#[case::store(&[0x89, 0x8E, 0xE8, 0x03], "18 (9 + 9ea)")]
+// add [di + 1000], cx
+#[case::read_modify_write(&[0x01, 0x8D, 0xE8, 0x03], "25 (16 + 9ea)")]
+// cmp [bx], 1
+#[case::compare_memory(&[0x83, 0x3F, 0x01], "15 (10 + 5ea)")]
+// mov cx, [bx + 0], with the zero encoded as a byte
+#[case::zero_displacement(&[0x8B, 0x4F, 0x00], "17 (8 + 9ea)")]
+// inc al
+#[case::byte_increment(&[0xFE, 0xC0], "3")]
+// es: mov cx, [bx]
+#[case::segment_override(&[0x26, 0x8B, 0x0F], "15 (8 + 7ea)")]
+// mul bl
+#[case::multiply(&[0xF6, 0xE3], "70")]
+// push ax
+#[case::push(&[0x50], "11")]
+// jne $+2
+#[case::branch_not_taken(&[0x75, 0x00], "4")]
+fn test_static_estimates(#[case] code: &[u8], #[case] expected: &str) {
+    assert_eq!(
+        clocks(code, Processor::I8086, Execution::default()),
+        expected
+    );
+}
+
+#[rstest]
+// jne $+2
+#[case::branch_taken(&[0x75, 0x00], Execution { taken: true, ..Execution::default() }, "16")]
+// loop $+2
+#[case::loop_taken(&[0xE2, 0x00], Execution { taken: true, ..Execution::default() }, "17")]
+// shl ax, cl
+#[case::shift_per_bit(&[0xD3, 0xE0], Execution { count: 3, ..Execution::default() }, "20")]
+// rep movsw
+#[case::repeated_string(&[0xF3, 0xA5], Execution { count: 4, ..Execution::default() }, "77")]
+// mov [bx], cx
+#[case::odd_word_address(&[0x89, 0x0F], Execution { address: Some(0x101), ..Execution::default() }, "18 (9 + 5ea + 4p)")]
+// mov [bx], cl
+#[case::odd_byte_address(&[0x88, 0x0F], Execution { address: Some(0x101), ..Execution::default() }, "14 (9 + 5ea)")]
+// add [bx], cx
+#[case::odd_read_and_write(&[0x01, 0x0F], Execution { address: Some(0x101), ..Execution::default() }, "29 (16 + 5ea + 8p)")]
+// push ax
+#[case::odd_stack(&[0x50], Execution { sp: 0x101, ..Execution::default() }, "15 (11 + 4p)")]
+// nop, then the single-step trap
+#[case::trapped(&[0x90], Execution { trapped: true, ..Execution::default() }, "53")]
+// push ax, then a timer interrupt with the stack still at an odd address
+#[case::interrupted(&[0x50], Execution { interrupted: true, sp: 0x101, ..Execution::default() }, "88 (72 + 16p)")]
+fn test_execution_dependent_estimates(
+    #[case] code: &[u8],
+    #[case] execution: Execution,
+    #[case] expected: &str,
+) {
+    assert_eq!(clocks(code, Processor::I8086, execution), expected);
+}
+
+#[rstest]
+// mov [bx], cx
+#[case::word_store(&[0x89, 0x0F], "18 (9 + 5ea + 4p)")]
+// mov [bx], cl
+#[case::byte_store(&[0x88, 0x0F], "14 (9 + 5ea)")]
+// call $+3
+#[case::call_pushes_a_word(&[0xE8, 0x00, 0x00], "23 (19 + 4p)")]
+// mov ax, bx
+#[case::no_memory(&[0x89, 0xD8], "2")]
+fn test_8088_pays_for_every_word(#[case] code: &[u8], #[case] expected: &str) {
+    assert_eq!(
+        clocks(code, Processor::I8088, Execution::default()),
+        expected
+    );
+}
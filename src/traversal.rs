@@ -0,0 +1,139 @@
+use crate::decoder::{DecodeError, Flow, Instruction, decode_instruction};
+use crate::syntax::InstructionFormatter;
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+mod traversal_tests;
+
+/// Most bytes a single `db` line holds.
+const DATA_CHUNK: usize = 8;
+
+/// Result of following control flow through an image instead of sweeping it linearly.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Traversal {
+    /// Offset the traversal was limited to, bytes before it are not part of the image.
+    pub start: usize,
+    /// Every reachable instruction, sorted by offset.
+    pub instructions: Vec<Instruction>,
+    /// Offsets of jumps and calls whose target isn't known statically (indirect or far).
+    pub unresolved: Vec<usize>,
+    /// Paths that ran into bytes that don't decode, those bytes are left as data.
+    pub errors: Vec<DecodeError>,
+}
+
+/// A piece of the image, either a reachable instruction or bytes nothing reaches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region<'a> {
+    Code(&'a Instruction),
+    Data { offset: usize, bytes: &'a [u8] },
+}
+
+impl Region<'_> {
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::Code(instruction) => instruction.offset,
+            Self::Data { offset, .. } => *offset,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Code(instruction) => instruction.length,
+            Self::Data { bytes, .. } => bytes.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn format(&self, formatter: &dyn InstructionFormatter) -> String {
+        match self {
+            Self::Code(instruction) => formatter.format_instruction(instruction),
+            Self::Data { bytes, .. } => formatter.format_data(bytes),
+        }
+    }
+}
+
+/// Decodes everything reachable from `entry_points` in `data[start..]`, following jumps, calls
+/// and conditional branches, and stopping a path at returns, `hlt`, unconditional jumps and
+/// anything that fails to decode or overlaps already decoded code.
+///
+/// Indirect and far transfers can't be followed, they are recorded in
+/// [`Traversal::unresolved`] and the path ends (or continues after a call) as usual.
+pub fn traverse(data: &[u8], start: usize, entry_points: &[usize]) -> Traversal {
+    let mut covered = vec![false; data.len()];
+    let mut instructions = BTreeMap::new();
+    let mut unresolved = vec![];
+    let mut errors = BTreeMap::new();
+    let mut pending: Vec<usize> = entry_points.iter().rev().copied().collect();
+
+    while let Some(mut offset) = pending.pop() {
+        while (start..data.len()).contains(&offset)
+            && !covered[offset]
+            && !errors.contains_key(&offset)
+        {
+            let instruction = match decode_instruction(data, offset) {
+                Ok(instruction) => instruction,
+                Err(error) => {
+                    errors.insert(offset, error);
+                    break;
+                }
+            };
+
+            let bytes = offset..offset + instruction.length;
+            if covered[bytes.clone()].iter().any(|&byte| byte) {
+                break;
+            }
+            covered[bytes].fill(true);
+            instructions.insert(offset, instruction);
+
+            let flow = instruction.mnemonic.flow();
+            match instruction.branch_target() {
+                Some(target) => pending.push(target),
+                None if matches!(flow, Flow::Jump | Flow::Call) => unresolved.push(offset),
+                None => {}
+            }
+
+            match flow {
+                Flow::Jump | Flow::Return | Flow::Halt => break,
+                Flow::Sequential | Flow::ConditionalJump | Flow::Call | Flow::Interrupt => {
+                    offset += instruction.length;
+                }
+            }
+        }
+    }
+
+    unresolved.sort_unstable();
+    Traversal {
+        start,
+        instructions: instructions.into_values().collect(),
+        unresolved,
+        errors: errors.into_values().collect(),
+    }
+}
+
+impl Traversal {
+    /// Walks `data[start..]` in order, splitting the bytes no instruction covers into chunks.
+    pub fn regions<'a>(&'a self, data: &'a [u8]) -> Vec<Region<'a>> {
+        let mut regions = vec![];
+        let mut offset = self.start;
+
+        for instruction in &self.instructions {
+            push_data(&mut regions, data, offset..instruction.offset);
+            regions.push(Region::Code(instruction));
+            offset = instruction.offset + instruction.length;
+        }
+        push_data(&mut regions, data, offset..data.len());
+
+        regions
+    }
+}
+
+fn push_data<'a>(regions: &mut Vec<Region<'a>>, data: &'a [u8], range: std::ops::Range<usize>) {
+    let mut offset = range.start;
+    for bytes in data[range].chunks(DATA_CHUNK) {
+        regions.push(Region::Data { offset, bytes });
+        offset += bytes.len();
+    }
+}
@@ -0,0 +1,123 @@
+use super::*;
+use crate::syntax::Nasm;
+use rstest::rstest;
+
+fn offsets(traversal: &Traversal) -> Vec<usize> {
+    traversal
+        .instructions
+        .iter()
+        .map(|instruction| instruction.offset)
+        .collect()
+}
+
+#[rstest]
+// jmp $+5 skips three bytes that never run
+#[case::skips_bytes_after_jump(&[0xEB, 0x03, 0xFF, 0xFF, 0xFF, 0x89, 0xD9], &[0], &[0x0, 0x5])]
+// jne falls through and branches, both sides are code
+#[case::follows_both_sides_of_branch(&[0x75, 0x01, 0xF4, 0xC3], &[0], &[0x0, 0x2, 0x3])]
+// call $+4, then hlt, the callee is a ret past a data byte
+#[case::follows_call_and_return(&[0xE8, 0x02, 0x00, 0xF4, 0xFF, 0xC3], &[0], &[0x0, 0x3, 0x5])]
+// hlt stops the path, the second entry point picks up after the data
+#[case::multiple_entry_points(&[0xF4, 0xFF, 0x89, 0xD9], &[0, 2], &[0x0, 0x2])]
+// a jump into the middle of an instruction doesn't decode it twice
+#[case::overlapping_target(&[0xB9, 0xEB, 0xFE, 0xEB, 0xFC], &[0], &[0x0, 0x3])]
+// targets outside the image are ignored
+#[case::target_out_of_range(&[0xEB, 0x10], &[0], &[0x0])]
+fn test_reachable_instructions(
+    #[case] data: &[u8],
+    #[case] entry_points: &[usize],
+    #[case] expected: &[usize],
+) {
+    let traversal = traverse(data, 0, entry_points);
+    assert_eq!(offsets(&traversal), expected);
+    assert!(traversal.errors.is_empty());
+}
+
+#[test]
+fn test_records_indirect_transfers_as_unresolved() {
+    // call bx, jmp [bx]
+    let traversal = traverse(&[0xFF, 0xD3, 0xFF, 0x27], 0, &[0]);
+
+    assert_eq!(offsets(&traversal), [0x0, 0x2]);
+    assert_eq!(traversal.unresolved, [0x0, 0x2]);
+}
+
+#[test]
+fn test_records_far_transfers_as_unresolved() {
+    // jmp 0x1234:0x5678
+    let traversal = traverse(&[0xEA, 0x78, 0x56, 0x34, 0x12], 0, &[0]);
+
+    assert_eq!(offsets(&traversal), [0x0]);
+    assert_eq!(traversal.unresolved, [0x0]);
+}
+
+#[test]
+fn test_decode_errors_end_the_path() {
+    // jne into an unknown opcode, the fall through still decodes
+    let traversal = traverse(&[0x75, 0x01, 0xF4, 0x0F], 0, &[0]);
+
+    assert_eq!(offsets(&traversal), [0x0, 0x2]);
+    assert_eq!(
+        traversal.errors,
+        [DecodeError::UnknownOpcode {
+            offset: 3,
+            opcode: 0x0F
+        }]
+    );
+}
+
+#[test]
+fn test_respects_start_offset() {
+    // the jump lands before the start, which isn't part of the image
+    let traversal = traverse(&[0xFF, 0xF4, 0xEB, 0xFC], 2, &[2]);
+
+    assert_eq!(offsets(&traversal), [0x2]);
+    let regions = traversal.regions(&[0xFF, 0xF4, 0xEB, 0xFC]);
+    assert_eq!(regions.len(), 1);
+    assert_eq!(regions[0].offset(), 2);
+}
+
+#[test]
+fn test_regions_fill_gaps_with_data() {
+    let data = [0xEB, 0x03, 0xFF, 0xFF, 0xFF, 0x89, 0xD9, 0xC3, 0x01, 0x02];
+    let traversal = traverse(&data, 0, &[0]);
+
+    let formatter = Nasm::default();
+    let lines: Vec<_> = traversal
+        .regions(&data)
+        .iter()
+        .map(|region| (region.offset(), region.len(), region.format(&formatter)))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            (0x0, 2, "jmp $+5".to_string()),
+            (0x2, 3, "db 255, 255, 255".to_string()),
+            (0x5, 2, "mov cx, bx".to_string()),
+            (0x7, 1, "ret".to_string()),
+            (0x8, 2, "db 1, 2".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_regions_split_long_data() {
+    let mut data = vec![0xF4];
+    data.extend([0xFF; 20]);
+    let traversal = traverse(&data, 0, &[0]);
+
+    let sizes: Vec<_> = traversal
+        .regions(&data)
+        .iter()
+        .map(|region| (region.offset(), region.len()))
+        .collect();
+    assert_eq!(sizes, [(0x0, 1), (0x1, 8), (0x9, 8), (0x11, 4)]);
+}
+
+#[test]
+fn test_empty_image() {
+    let traversal = traverse(&[], 0, &[0]);
+
+    assert_eq!(traversal, Traversal::default());
+    assert!(traversal.regions(&[]).is_empty());
+}